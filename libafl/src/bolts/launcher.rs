@@ -7,6 +7,10 @@
 //! To connect multiple nodes together via TCP, we can use the `remote_broker_addr`.
 //! (this requires the `llmp_bind_public` compile-time feature for `LibAFL`).
 //!
+//! To survive a crash of the broker, set a `broker_wal`: the broker persists all testcases to this file,
+//! and replays them to the clients once it is launched again. Clients that outlived the broker catch up
+//! if `reconnect_to_broker` is set, see [`crate::events::LlmpEventManager::watch_broker`].
+//!
 //! On `Unix` systems, the [`Launcher`] will use `fork` if the `fork` feature is used for `LibAFL`.
 //! Else, it will start subsequent nodes with the same commandline, and will set special `env` variables accordingly.
 
@@ -15,12 +19,12 @@ use alloc::string::ToString;
 use core::fmt::{self, Debug, Formatter};
 #[cfg(feature = "std")]
 use core::marker::PhantomData;
-#[cfg(feature = "std")]
-use std::net::SocketAddr;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use std::process::Stdio;
 #[cfg(all(unix, feature = "std", feature = "fork"))]
 use std::{fs::File, os::unix::io::AsRawFd};
+#[cfg(feature = "std")]
+use std::{net::SocketAddr, path::PathBuf};
 
 #[cfg(feature = "std")]
 use serde::de::DeserializeOwned;
@@ -78,6 +82,14 @@ where
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The write-ahead log the broker persists all testcases to.
+    /// If it exists when the broker starts, e.g. after a crash, the testcases in it are replayed to the clients.
+    #[builder(default = None)]
+    broker_wal: Option<PathBuf>,
+    /// If the clients should reconnect to the broker once it got restarted, e.g. after a crash.
+    /// They check for a restarted broker once a second.
+    #[builder(default = false)]
+    reconnect_to_broker: bool,
     /// If this launcher should spawn a new `broker` on `[Self::broker_port]` (default).
     /// The reason you may not want this is, if you already have a [`Launcher`]
     /// with a different configuration (for the same target) running on this machine.
@@ -102,6 +114,8 @@ where
             .field("core", &self.cores)
             .field("spawn_broker", &self.spawn_broker)
             .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("broker_wal", &self.broker_wal)
+            .field("reconnect_to_broker", &self.reconnect_to_broker)
             .field("stdout_file", &self.stdout_file)
             .finish_non_exhaustive()
     }
@@ -173,6 +187,7 @@ where
                         let (state, mgr) = RestartingMgr::<MT, S, SP>::builder()
                             .shmem_provider(self.shmem_provider.clone())
                             .broker_port(self.broker_port)
+                            .reconnect_to_broker(self.reconnect_to_broker)
                             .kind(ManagerKind::Client {
                                 cpu_core: Some(*bind_to),
                             })
@@ -197,6 +212,7 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .broker_wal(self.broker_wal.clone())
                 .configuration(self.configuration)
                 .build()
                 .launch()?;
@@ -241,6 +257,7 @@ where
                 let (state, mgr) = RestartingMgr::<MT, S, SP>::builder()
                     .shmem_provider(self.shmem_provider.clone())
                     .broker_port(self.broker_port)
+                    .reconnect_to_broker(self.reconnect_to_broker)
                     .kind(ManagerKind::Client {
                         cpu_core: Some(CoreId { id: core_id }),
                     })
@@ -295,6 +312,7 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
+                .broker_wal(self.broker_wal.clone())
                 .configuration(self.configuration)
                 .build()
                 .launch()?;
@@ -79,10 +79,13 @@ use std::os::unix::io::AsRawFd;
 #[cfg(feature = "std")]
 use std::{
     env,
-    io::{ErrorKind, Read, Write},
+    fs::{self, File, OpenOptions},
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
     net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
     sync::mpsc::channel,
     thread,
+    time::Instant,
 };
 
 #[cfg(all(debug_assertions, feature = "llmp_debug", feature = "std"))]
@@ -157,6 +160,19 @@ const _NULL_ENV_STR: &str = "_NULL";
 /// Magic indicating that a got initialized correctly
 const PAGE_INITIALIZED_MAGIC: u64 = 0x1A1A1A1A1A1A1AF1;
 
+/// The magic bytes at the start of each [`LlmpWal`] file
+#[cfg(feature = "std")]
+const LLMP_WAL_MAGIC: &[u8; 8] = b"LLMPWAL1";
+/// The size of the header before each record in an [`LlmpWal`]: payload len, tag, flags, and checksum
+#[cfg(feature = "std")]
+const LLMP_WAL_RECORD_HEADER_LEN: usize = 4 + 4 + 4 + 8;
+/// By default, an [`LlmpWal`] is synced to disk at most once per second
+#[cfg(feature = "std")]
+const LLMP_WAL_DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+/// By default, an [`LlmpWal`] is rotated once it grows beyond 1 GiB
+#[cfg(feature = "std")]
+const LLMP_WAL_DEFAULT_MAX_SIZE: u64 = 1 << 30;
+
 /// Size of a new page message, header, payload, and alignment
 const EOP_MSG_SIZE: usize =
     llmp_align(size_of::<LlmpMsg>() + size_of::<LlmpPayloadSharedMapInfo>());
@@ -199,6 +215,8 @@ pub enum TcpRequest {
         /// The hostname of our broker, trying to connect.
         hostname: String,
     },
+    /// We only check that the broker is alive, see [`LlmpClient::broker_status`].
+    Probe,
 }
 
 impl TryFrom<&Vec<u8>> for TcpRequest {
@@ -2011,6 +2029,14 @@ where
         self.llmp_out.send_buf_with_flags(tag, flags, buf)
     }
 
+    /// Broadcasts all messages in the given write-ahead log to the clients, e.g. after a broker restart.
+    /// As clients start reading at the first page of the broker, clients connecting later receive them, too.
+    /// Returns the amount of messages replayed.
+    #[cfg(feature = "std")]
+    pub fn replay_wal(&mut self, wal: &LlmpWal) -> Result<usize, Error> {
+        wal.replay(|tag, flags, buf| self.send_buf_with_flags(tag, flags, buf))
+    }
+
     /// Launches a thread using a tcp listener socket, on which new clients may connect to this broker.
     /// Does so on the given port.
     #[cfg(feature = "std")]
@@ -2218,6 +2244,7 @@ where
                     *current_client_id += 1;
                 }
             }
+            TcpRequest::Probe => (),
         };
     }
 
@@ -2423,6 +2450,21 @@ pub struct LlmpClientDescription {
     sender: LlmpDescription,
     /// Description of the receiver
     receiver: LlmpDescription,
+    /// Description of the initial map of the broker
+    #[serde(default)]
+    broker: Option<ShMemDescription>,
+}
+
+/// The status of the broker a [`LlmpClient`] attached to, see [`LlmpClient::broker_status`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmpBrokerStatus {
+    /// The broker is up
+    Alive,
+    /// No broker listens on the port, it may have crashed
+    Down,
+    /// Another broker listens on the port, the previous one got restarted
+    Restarted,
 }
 
 /// Client side of LLMP
@@ -2435,6 +2477,9 @@ where
     pub sender: LlmpSender<SP>,
     /// Incoming (broker) broadcast map
     pub receiver: LlmpReceiver<SP>,
+    /// The initial map of the broker, if we attached to it via tcp.
+    /// A restarted broker announces a different one.
+    pub broker_shmem_description: Option<ShMemDescription>,
 }
 
 /// `n` clients connect to a broker. They share an outgoing map with the broker,
@@ -2465,6 +2510,7 @@ where
                 current_broker_shmem,
                 last_msg_recvd_offset,
             )?,
+            broker_shmem_description: None,
         })
     }
 
@@ -2480,6 +2526,16 @@ where
                 shmem_provider,
                 &format!("{env_name}_RECEIVER"),
             )?,
+            broker_shmem_description: match (
+                env::var(format!("{env_name}_BROKER")),
+                env::var(format!("{env_name}_BROKER_SIZE")),
+            ) {
+                (Ok(id), Ok(size)) => Some(ShMemDescription::from_string_and_size(
+                    &id,
+                    str::parse::<usize>(&size)?,
+                )),
+                _ => None,
+            },
         })
     }
 
@@ -2488,7 +2544,15 @@ where
     #[cfg(feature = "std")]
     pub fn to_env(&self, env_name: &str) -> Result<(), Error> {
         self.sender.to_env(&format!("{env_name}_SENDER"))?;
-        self.receiver.to_env(&format!("{env_name}_RECEIVER"))
+        self.receiver.to_env(&format!("{env_name}_RECEIVER"))?;
+        if let Some(broker) = &self.broker_shmem_description {
+            env::set_var(format!("{env_name}_BROKER"), broker.id.to_string());
+            env::set_var(
+                format!("{env_name}_BROKER_SIZE"),
+                format!("{}", broker.size),
+            );
+        }
+        Ok(())
     }
 
     /// Describe this client in a way that it can be recreated, for example after crash
@@ -2496,6 +2560,7 @@ where
         Ok(LlmpClientDescription {
             sender: self.sender.describe()?,
             receiver: self.receiver.describe()?,
+            broker: self.broker_shmem_description,
         })
     }
 
@@ -2513,6 +2578,7 @@ where
                 shmem_provider,
                 &description.receiver,
             )?,
+            broker_shmem_description: description.broker,
         })
     }
 
@@ -2563,6 +2629,7 @@ where
                 shmem_provider,
                 highest_msg_id: 0,
             },
+            broker_shmem_description: None,
         })
     }
 
@@ -2657,6 +2724,50 @@ where
         Self::new(shmem_provider, map, client_id)
     }
 
+    /// Connects this client to a broker restarted on `port`, e.g. after the previous broker crashed,
+    /// waiting for it to come up. The client receives the messages of the new broker from its first page on,
+    /// including the ones it replayed from its [`LlmpWal`].
+    /// Messages sent to the previous broker, but not yet read by it, are lost.
+    #[cfg(feature = "std")]
+    pub fn reconnect_tcp(&mut self, port: u16) -> Result<(), Error> {
+        *self = Self::create_attach_to_tcp(self.sender.shmem_provider.clone(), port)?;
+        Ok(())
+    }
+
+    /// Checks if the broker this client attached to still listens on `port`.
+    /// Without a connection via tcp, a client can't tell a restarted broker, so it is always considered [`LlmpBrokerStatus::Alive`] then.
+    #[cfg(feature = "std")]
+    pub fn broker_status(&self, port: u16) -> Result<LlmpBrokerStatus, Error> {
+        let mut stream = match TcpStream::connect((_LLMP_CONNECT_ADDR, port)) {
+            Ok(stream) => stream,
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                return Ok(LlmpBrokerStatus::Down)
+            }
+            Err(e) => return Err(Error::illegal_state(e.to_string())),
+        };
+
+        let broker_shmem_description = if let TcpResponse::BrokerConnectHello {
+            broker_shmem_description,
+            hostname: _,
+        } = recv_tcp_msg(&mut stream)?.try_into()?
+        {
+            broker_shmem_description
+        } else {
+            return Err(Error::illegal_state(
+                "Received unexpected Broker Hello".to_string(),
+            ));
+        };
+        // The broker may not be interested in our probe, if it's shutting down.
+        drop(send_tcp_msg(&mut stream, &TcpRequest::Probe));
+
+        Ok(match &self.broker_shmem_description {
+            Some(attached) if attached.id != broker_shmem_description.id => {
+                LlmpBrokerStatus::Restarted
+            }
+            _ => LlmpBrokerStatus::Alive,
+        })
+    }
+
     #[cfg(feature = "std")]
     /// Create a [`LlmpClient`], getting the ID from a given port
     pub fn create_attach_to_tcp(mut shmem_provider: SP, port: u16) -> Result<Self, Error> {
@@ -2699,6 +2810,7 @@ where
 
         // We'll set `sender_id` later
         let mut ret = Self::new(shmem_provider, map, 0)?;
+        ret.broker_shmem_description = Some(broker_shmem_description);
 
         let client_hello_req = TcpRequest::LocalClientHello {
             shmem_description: ret.sender.out_shmems.first().unwrap().shmem.description(),
@@ -2728,19 +2840,263 @@ where
     }
 }
 
+/// A write-ahead log of llmp messages on disk.
+/// The broker appends the messages it wants to keep, and replays them after a restart,
+/// so that clients connecting to the new broker catch up on the messages sent before.
+///
+/// Each record is written with a single `write` and carries a checksum,
+/// so a record torn by a crash, or by a reboot before the next sync, is detected and dropped on [`LlmpWal::open`].
+///
+/// Once the log grows beyond its maximum size, see [`LlmpWal::with_max_size`], it is moved to `<path>.1`,
+/// replacing the one moved there before, and a new log is started. Both are replayed, the older one first.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct LlmpWal {
+    path: PathBuf,
+    file: File,
+    /// The length of the current log file
+    len: u64,
+    /// The records in the current log file
+    records: usize,
+    /// The records in the rotated log file
+    rotated_records: usize,
+    max_size: u64,
+    sync_interval: Duration,
+    last_sync: Instant,
+}
+
+#[cfg(feature = "std")]
+impl LlmpWal {
+    /// Opens the write-ahead log at `path`, creating it if it doesn't exist.
+    /// A torn record at the end of an existing log is truncated.
+    pub fn open<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let file_len = file.metadata()?.len();
+        let (valid_len, records) = if file_len == 0 {
+            file.write_all(LLMP_WAL_MAGIC)?;
+            file.sync_all()?;
+            (LLMP_WAL_MAGIC.len() as u64, 0)
+        } else {
+            Self::read_records(&mut BufReader::new(&file), &path, |_, _, _| Ok(()))?
+        };
+        let rotated_path = Self::rotated_path(&path);
+        let rotated_records = if rotated_path.exists() {
+            let rotated = File::open(&rotated_path)?;
+            Self::read_records(
+                &mut BufReader::new(rotated),
+                &rotated_path,
+                |_, _, _| Ok(()),
+            )?
+            .1
+        } else {
+            0
+        };
+        if valid_len < file_len {
+            println!(
+                "LLMP WAL: Dropping {} bytes of a torn record at the end of {path:?}",
+                file_len - valid_len
+            );
+            file.set_len(valid_len)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            path,
+            file,
+            len: valid_len,
+            records,
+            rotated_records,
+            max_size: LLMP_WAL_DEFAULT_MAX_SIZE,
+            sync_interval: LLMP_WAL_DEFAULT_SYNC_INTERVAL,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// Sets the size in bytes beyond which the log is rotated. Defaults to 1 GiB.
+    /// At most twice this size is kept on disk, older records are dropped.
+    #[must_use]
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets how often the log is synced to disk, at most. Defaults to one second.
+    /// Records appended since the last sync survive a crash of the broker, but may be lost if the host goes down.
+    #[must_use]
+    pub fn with_sync_interval(mut self, sync_interval: Duration) -> Self {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    /// The path of this log
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The amount of records in this log, including the ones in the rotated log
+    #[must_use]
+    pub fn records(&self) -> usize {
+        self.rotated_records + self.records
+    }
+
+    /// Appends a message to the log
+    pub fn append(&mut self, tag: Tag, flags: Flags, buf: &[u8]) -> Result<(), Error> {
+        let buf_len: u32 = buf
+            .len()
+            .try_into()
+            .map_err(|_| Error::illegal_argument("LLMP WAL: Message too large"))?;
+        let mut record = Vec::with_capacity(LLMP_WAL_RECORD_HEADER_LEN + buf.len());
+        record.extend_from_slice(&buf_len.to_le_bytes());
+        record.extend_from_slice(&tag.to_le_bytes());
+        record.extend_from_slice(&flags.to_le_bytes());
+        record.extend_from_slice(&Self::checksum(tag, flags, buf).to_le_bytes());
+        record.extend_from_slice(buf);
+        if self.records > 0 && self.len + record.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(&record)?;
+        self.len += record.len() as u64;
+        self.records += 1;
+
+        if self.last_sync.elapsed() >= self.sync_interval {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Syncs all appended records to disk
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.file.sync_data()?;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Calls `on_record` for each record in this log, in order, starting with the rotated log.
+    /// Returns the amount of records replayed.
+    pub fn replay<F>(&self, mut on_record: F) -> Result<usize, Error>
+    where
+        F: FnMut(Tag, Flags, &[u8]) -> Result<(), Error>,
+    {
+        let mut records = 0;
+        let rotated_path = Self::rotated_path(&self.path);
+        if rotated_path.exists() {
+            let file = File::open(&rotated_path)?;
+            records +=
+                Self::read_records(&mut BufReader::new(file), &rotated_path, &mut on_record)?.1;
+        }
+        let file = File::open(&self.path)?;
+        records += Self::read_records(&mut BufReader::new(file), &self.path, &mut on_record)?.1;
+        Ok(records)
+    }
+
+    /// Moves the current log to the rotated log, and starts a new one
+    fn rotate(&mut self) -> Result<(), Error> {
+        self.file.sync_all()?;
+        fs::rename(&self.path, Self::rotated_path(&self.path))?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&self.path)?;
+        file.write_all(LLMP_WAL_MAGIC)?;
+        file.sync_all()?;
+
+        self.file = file;
+        self.len = LLMP_WAL_MAGIC.len() as u64;
+        self.rotated_records = self.records;
+        self.records = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// The path the log at `path` is moved to once it grew too large
+    fn rotated_path(path: &Path) -> PathBuf {
+        let mut rotated = path.as_os_str().to_os_string();
+        rotated.push(".1");
+        rotated.into()
+    }
+
+    fn checksum(tag: Tag, flags: Flags, buf: &[u8]) -> u64 {
+        xxhash_rust::xxh3::xxh3_64_with_seed(buf, (u64::from(tag) << 32) | u64::from(flags))
+    }
+
+    /// Reads all valid records, returns the length of the valid part of the log, and the amount of records in it
+    fn read_records<R, F>(
+        reader: &mut R,
+        path: &Path,
+        mut on_record: F,
+    ) -> Result<(u64, usize), Error>
+    where
+        R: Read,
+        F: FnMut(Tag, Flags, &[u8]) -> Result<(), Error>,
+    {
+        let mut magic = [0_u8; LLMP_WAL_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != LLMP_WAL_MAGIC {
+            return Err(Error::illegal_argument(format!(
+                "{path:?} is not an LLMP write-ahead log"
+            )));
+        }
+
+        let mut valid_len = LLMP_WAL_MAGIC.len() as u64;
+        let mut records = 0;
+        let mut header = [0_u8; LLMP_WAL_RECORD_HEADER_LEN];
+        let mut buf = vec![];
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let buf_len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+            let tag = Tag::from_le_bytes(header[4..8].try_into().unwrap());
+            let flags = Flags::from_le_bytes(header[8..12].try_into().unwrap());
+            let checksum = u64::from_le_bytes(header[12..20].try_into().unwrap());
+
+            buf.resize(buf_len, 0);
+            match reader.read_exact(&mut buf) {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            if Self::checksum(tag, flags, &buf) != checksum {
+                break;
+            }
+
+            on_record(tag, flags, &buf)?;
+            valid_len += (LLMP_WAL_RECORD_HEADER_LEN + buf_len) as u64;
+            records += 1;
+        }
+        Ok((valid_len, records))
+    }
+}
+
 #[cfg(test)]
 #[cfg(all(unix, feature = "std"))]
 mod tests {
 
-    use std::{thread::sleep, time::Duration};
+    use std::{net::TcpListener, thread::sleep, time::Duration};
 
     use serial_test::serial;
 
     use super::{
-        LlmpClient,
+        LlmpBroker, LlmpBrokerStatus, LlmpClient,
         LlmpConnection::{self, IsBroker, IsClient},
         LlmpMsgHookResult::ForwardToClients,
-        Tag,
+        LlmpWal, Tag,
     };
     use crate::bolts::shmem::{ShMemProvider, StdShMemProvider};
 
@@ -2795,4 +3151,146 @@ mod tests {
         // We want at least the tcp and sender clients.
         assert_eq!(broker.llmp_clients.len(), 2);
     }
+
+    /// A port no one listens on right now
+    fn free_port() -> u16 {
+        TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[test]
+    pub fn test_llmp_wal() {
+        let path = std::env::temp_dir().join(format!("llmp_wal_test_{}", std::process::id()));
+        drop(std::fs::remove_file(&path));
+
+        let mut wal = LlmpWal::open(&path).unwrap();
+        wal.append(0x1337, 0, b"first").unwrap();
+        wal.append(0x1338, 1, b"second").unwrap();
+        wal.sync().unwrap();
+        drop(wal);
+
+        // Simulate a record torn by a crash
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, &[6, 0, 0, 0, 0x37, 0x13]).unwrap();
+        drop(file);
+
+        let mut wal = LlmpWal::open(&path).unwrap();
+        assert_eq!(wal.records(), 2);
+        wal.append(0x1339, 0, b"third").unwrap();
+
+        let mut replayed = vec![];
+        let count = wal
+            .replay(|tag, flags, buf| {
+                replayed.push((tag, flags, buf.to_vec()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            replayed,
+            vec![
+                (0x1337, 0, b"first".to_vec()),
+                (0x1338, 1, b"second".to_vec()),
+                (0x1339, 0, b"third".to_vec()),
+            ]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_llmp_wal_rotation() {
+        let path = std::env::temp_dir().join(format!("llmp_wal_rotation_{}", std::process::id()));
+        let rotated_path =
+            path.with_file_name(format!("{}.1", path.file_name().unwrap().to_string_lossy()));
+        drop(std::fs::remove_file(&path));
+        drop(std::fs::remove_file(&rotated_path));
+
+        // Room for the magic and two records of 5 bytes each
+        let mut wal = LlmpWal::open(&path).unwrap().with_max_size(8 + 2 * 25);
+        for i in 0..5_u8 {
+            wal.append(u32::from(i), 0, &[i; 5]).unwrap();
+        }
+        assert_eq!(wal.records(), 3);
+        drop(wal);
+
+        let wal = LlmpWal::open(&path).unwrap();
+        assert_eq!(wal.records(), 3);
+        let mut replayed = vec![];
+        let count = wal
+            .replay(|tag, _flags, buf| {
+                assert_eq!(buf, [tag as u8; 5]);
+                replayed.push(tag);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(replayed, vec![2, 3, 4]);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rotated_path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    pub fn test_llmp_wal_reconnect() {
+        let path = std::env::temp_dir().join(format!("llmp_wal_reconnect_{}", std::process::id()));
+        drop(std::fs::remove_file(&path));
+
+        let (port, new_port) = (free_port(), free_port());
+        let shmem_provider = StdShMemProvider::new().unwrap();
+        let mut broker = LlmpBroker::create_attach_to_tcp(shmem_provider.clone(), port).unwrap();
+        let mut client = LlmpClient::create_attach_to_tcp(shmem_provider.clone(), port).unwrap();
+        assert_eq!(client.broker_status(port).unwrap(), LlmpBrokerStatus::Alive);
+        sleep(Duration::from_millis(100));
+        broker
+            .once(&mut |_sender_id, _tag, _flags, _msg| Ok(ForwardToClients))
+            .unwrap();
+
+        let mut wal = LlmpWal::open(&path).unwrap();
+        client.send_buf(0x1337, b"testcase").unwrap();
+        broker
+            .once(&mut |_sender_id, tag, flags, msg| {
+                wal.append(tag, flags, msg)?;
+                Ok(ForwardToClients)
+            })
+            .unwrap();
+        assert_eq!(client.recv_buf_blocking().unwrap().2, b"testcase");
+        wal.sync().unwrap();
+
+        // The broker goes down, and a new one replays the log
+        drop(broker);
+        drop(wal);
+        assert_eq!(
+            client.broker_status(free_port()).unwrap(),
+            LlmpBrokerStatus::Down
+        );
+        let mut broker = LlmpBroker::create_attach_to_tcp(shmem_provider, new_port).unwrap();
+        assert_eq!(
+            broker.replay_wal(&LlmpWal::open(&path).unwrap()).unwrap(),
+            1
+        );
+        assert_eq!(
+            client.broker_status(new_port).unwrap(),
+            LlmpBrokerStatus::Restarted
+        );
+
+        // The client, connected to the previous broker, catches up
+        client.reconnect_tcp(new_port).unwrap();
+        assert_eq!(
+            client.broker_status(new_port).unwrap(),
+            LlmpBrokerStatus::Alive
+        );
+        let (_sender_id, tag, buf) = client.recv_buf_blocking().unwrap();
+        assert_eq!(tag, 0x1337);
+        assert_eq!(buf, b"testcase");
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
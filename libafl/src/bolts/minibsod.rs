@@ -538,6 +538,45 @@ fn write_crash<W: Write>(
     Ok(())
 }
 
+/// The address of the faulting instruction in the given context, if known on this platform
+#[must_use]
+#[allow(clippy::unnecessary_cast, clippy::cast_sign_loss)]
+pub fn crash_pc(ucontext: &ucontext_t) -> Option<usize> {
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    return Some(ucontext.uc_mcontext.gregs[libc::REG_RIP as usize] as usize);
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        target_arch = "aarch64"
+    ))]
+    return Some(ucontext.uc_mcontext.pc as usize);
+    #[cfg(all(target_os = "linux", target_arch = "arm"))]
+    return Some(ucontext.uc_mcontext.arm_pc as usize);
+    #[cfg(all(target_vendor = "apple", target_arch = "aarch64"))]
+    return Some(unsafe { (*ucontext.uc_mcontext).__ss.__pc } as usize);
+    #[cfg(all(target_vendor = "apple", target_arch = "x86_64"))]
+    return Some(unsafe { (*ucontext.uc_mcontext).__ss.__rip } as usize);
+    #[cfg(all(target_os = "freebsd", target_arch = "x86_64"))]
+    return Some(ucontext.uc_mcontext.mc_rip as usize);
+    #[cfg(all(target_os = "freebsd", target_arch = "aarch64"))]
+    return Some(ucontext.uc_mcontext.mc_gpregs.gp_elr as usize);
+    #[allow(unreachable_code)]
+    {
+        let _ = ucontext;
+        None
+    }
+}
+
+/// The faulting address reported in the given [`siginfo_t`]
+#[must_use]
+pub fn fault_address(info: &siginfo_t) -> usize {
+    #[cfg(target_os = "android")]
+    return ((info._pad[0] as i64) | ((info._pad[1] as i64) << 32)) as usize;
+    #[cfg(not(target_os = "android"))]
+    unsafe {
+        info.si_addr() as usize
+    }
+}
+
 /// Generates a mini-BSOD given a signal and context.
 #[cfg(unix)]
 #[allow(clippy::non_ascii_literal)]
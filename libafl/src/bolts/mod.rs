@@ -79,6 +79,12 @@ pub trait HasLen {
     }
 }
 
+/// Can be truncated to a smaller length
+pub trait Truncate {
+    /// Reduce the length to `new_len`, if it is smaller than the current length
+    fn truncate(&mut self, new_len: usize);
+}
+
 /// Has a ref count
 pub trait HasRefCnt {
     /// The ref count
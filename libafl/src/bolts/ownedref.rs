@@ -10,7 +10,7 @@ use core::{clone::Clone, fmt::Debug, slice};
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::bolts::{AsMutSlice, AsSlice};
+use crate::bolts::{AsMutSlice, AsSlice, Truncate};
 
 /// Trait to convert into an Owned type
 pub trait IntoOwned {
@@ -439,6 +439,23 @@ impl<'a, T: Sized> AsMutSlice<T> for OwnedSliceMut<'a, T> {
     }
 }
 
+impl<'a, T: 'a + Sized> Truncate for OwnedSliceMut<'a, T> {
+    /// Truncate the slice, keeping the first `new_len` elements
+    fn truncate(&mut self, new_len: usize) {
+        match &mut self.inner {
+            OwnedSliceMutInner::RefRaw(_, len) => {
+                *len = new_len.min(*len);
+            }
+            OwnedSliceMutInner::Ref(r) => {
+                let slice = core::mem::take(r);
+                let len = new_len.min(slice.len());
+                *r = &mut slice[..len];
+            }
+            OwnedSliceMutInner::Owned(v) => v.truncate(new_len),
+        }
+    }
+}
+
 impl<'a, T> IntoOwned for OwnedSliceMut<'a, T>
 where
     T: Sized + Clone,
@@ -1,5 +1,7 @@
 //! Whole corpus minimizers, for reducing the number of samples/the total size/the average runtime
 //! of your corpus.
+//!
+//! The [`GreedyCorpusMinimizer`] works like `afl-cmin`, the optimal [`MapCorpusMinimizer`] needs the `cmin` feature.
 
 use alloc::{
     string::{String, ToString},
@@ -8,18 +10,23 @@ use alloc::{
 use core::{hash::Hash, marker::PhantomData};
 
 use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "cmin")]
 use num_traits::ToPrimitive;
+#[cfg(feature = "cmin")]
 use z3::{ast::Bool, Config, Context, Optimize};
 
 use crate::{
     bolts::{
+        current_time,
         tuples::{MatchName, Named},
-        AsIter,
+        AsIter, HasLen,
     },
     corpus::Corpus,
-    executors::{Executor, HasObservers},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::UsesInput,
     observers::{MapObserver, ObserversTuple},
     schedulers::{LenTimeMulTestcaseScore, Scheduler, TestcaseScore},
+    stages::calibrate::UnstableEntriesMetadata,
     state::{HasCorpus, HasMetadata, UsesState},
     Error, HasScheduler,
 };
@@ -49,6 +56,7 @@ where
 /// Minimizes a corpus according to coverage maps, weighting by the specified `TestcaseScore`.
 ///
 /// Algorithm based on WMOPT: <https://hexhive.epfl.ch/publications/files/21ISSTA2.pdf>
+#[cfg(feature = "cmin")]
 #[derive(Debug)]
 pub struct MapCorpusMinimizer<E, O, T, TS>
 where
@@ -61,9 +69,11 @@ where
 }
 
 /// Standard corpus minimizer, which weights inputs by length and time.
+#[cfg(feature = "cmin")]
 pub type StdCorpusMinimizer<E, O, T> =
     MapCorpusMinimizer<E, O, T, LenTimeMulTestcaseScore<<E as UsesState>::State>>;
 
+#[cfg(feature = "cmin")]
 impl<E, O, T, TS> MapCorpusMinimizer<E, O, T, TS>
 where
     E: UsesState,
@@ -83,6 +93,7 @@ where
     }
 }
 
+#[cfg(feature = "cmin")]
 impl<E, O, T, TS> CorpusMinimizer<E> for MapCorpusMinimizer<E, O, T, TS>
 where
     E: UsesState,
@@ -111,6 +122,13 @@ where
         let mut seed_exprs = HashMap::new();
         let mut cov_map = HashMap::new();
 
+        // Entries the calibration found to be unstable must not keep seeds alive
+        let unstable = state
+            .metadata()
+            .get::<UnstableEntriesMetadata>()
+            .map(|meta| meta.unstable_entries().clone())
+            .unwrap_or_default();
+
         for idx in 0..state.corpus().count() {
             let (weight, input) = {
                 let mut testcase = state.corpus().get(idx)?.borrow_mut();
@@ -141,6 +159,9 @@ where
             // Store coverage, mapping coverage map indices to hit counts (if present) and the
             // associated seeds for the map indices with those hit counts.
             for (i, e) in obs.as_iter().copied().enumerate() {
+                if unstable.contains(&i) {
+                    continue;
+                }
                 cov_map
                     .entry(i)
                     .or_insert_with(HashMap::new)
@@ -185,15 +206,7 @@ where
                     removed.push(idx);
                 }
             }
-            // reverse order; if indexes are stored in a vec, we need to remove from back to front
-            removed.sort_unstable_by(|idx1, idx2| idx2.cmp(idx1));
-            for idx in removed {
-                let removed = state.corpus_mut().remove(idx)?;
-                // scheduler needs to know we've removed the input, or it will continue to try
-                // to use now-missing inputs
-                fuzzer.scheduler_mut().on_remove(state, idx, &removed)?;
-            }
-            Ok(())
+            remove_testcases(fuzzer, state, removed)
         } else {
             Err(Error::unknown("Corpus minimization failed; unsat."))
         };
@@ -201,3 +214,285 @@ where
         res
     }
 }
+
+/// Minimizes a corpus according to coverage maps, like `afl-cmin` does: for each hit count of each map entry,
+/// the testcase with the lowest `TestcaseScore` hitting it is chosen, and these testcases are kept greedily,
+/// starting with the rarest entries, until every entry and hit count of the corpus is covered.
+///
+/// Like `afl-cmin`, testcases that do not run to completion, i.e. crash or time out, are dropped.
+///
+/// This keeps the coverage of the [`MapCorpusMinimizer`], but the result isn't guaranteed to be minimal.
+/// On the other hand, it doesn't need `z3` and scales to corpora with hundreds of thousands of testcases.
+/// Inputs are loaded on demand, so it works with any [`Corpus`].
+#[derive(Debug)]
+pub struct GreedyCorpusMinimizer<E, O, T, TS>
+where
+    E: UsesState,
+    E::State: HasCorpus + HasMetadata,
+    TS: TestcaseScore<E::State>,
+{
+    obs_name: String,
+    phantom: PhantomData<(E, O, T, TS)>,
+}
+
+/// Standard greedy corpus minimizer, which weights inputs by length and time.
+pub type StdGreedyCorpusMinimizer<E, O, T> =
+    GreedyCorpusMinimizer<E, O, T, LenTimeMulTestcaseScore<<E as UsesState>::State>>;
+
+impl<E, O, T, TS> GreedyCorpusMinimizer<E, O, T, TS>
+where
+    E: UsesState,
+    E::State: HasCorpus + HasMetadata,
+    TS: TestcaseScore<E::State>,
+{
+    /// Constructs a new `GreedyCorpusMinimizer` from a provided observer. This observer will be used
+    /// in the future to get observed maps from an executed input.
+    pub fn new(obs: &O) -> Self
+    where
+        O: Named,
+    {
+        Self {
+            obs_name: obs.name().to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, O, T, TS> CorpusMinimizer<E> for GreedyCorpusMinimizer<E, O, T, TS>
+where
+    E: UsesState,
+    for<'a> O: MapObserver<Entry = T> + AsIter<'a, Item = T>,
+    E::State: HasMetadata + HasCorpus,
+    <E::State as UsesInput>::Input: HasLen,
+    T: Copy + Hash + Eq,
+    TS: TestcaseScore<E::State>,
+{
+    fn minimize<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+    ) -> Result<(), Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        CS: Scheduler<State = E::State>,
+        EM: UsesState<State = E::State>,
+        Z: HasScheduler<CS, State = E::State>,
+    {
+        // Entries the calibration found to be unstable must not keep seeds alive
+        let unstable = state
+            .metadata()
+            .get::<UnstableEntriesMetadata>()
+            .map(|meta| meta.unstable_entries().clone())
+            .unwrap_or_default();
+
+        let count = state.corpus().count();
+        // The covered map entries and hit counts of each testcase
+        let mut coverage: Vec<Vec<(usize, T)>> = Vec::with_capacity(count);
+        // For each covered map entry and hit count, the best testcase hitting it
+        let mut best: HashMap<(usize, T), BestTestcase> = HashMap::new();
+        // All covered map entries and hit counts, in the order they were found
+        let mut found = Vec::new();
+
+        for idx in 0..count {
+            let input = state.corpus().get(idx)?.borrow_mut().load_input()?.clone();
+
+            // Execute the input; we cannot rely on the metadata already being present.
+            let start = current_time();
+            executor.observers_mut().pre_exec_all(state, &input)?;
+            let kind = executor.run_target(fuzzer, state, manager, &input)?;
+            executor
+                .observers_mut()
+                .post_exec_all(state, &input, &kind)?;
+            let exec_time = current_time().saturating_sub(start);
+
+            // A testcase that crashes or times out covers nothing, so it's never kept
+            if kind != ExitKind::Ok {
+                coverage.push(Vec::new());
+                continue;
+            }
+
+            // Weight by the score, and by the length if the scores are equal, e.g. for fast targets
+            let weight = {
+                let mut testcase = state.corpus().get(idx)?.borrow_mut();
+                testcase.set_exec_time(exec_time);
+                (TS::compute(&mut *testcase, state)?, testcase.cached_len()?)
+            };
+
+            let obs: &O = executor
+                .observers()
+                .match_name::<O>(&self.obs_name)
+                .expect("Observer must be present.");
+            let initial = obs.initial();
+            let tuples: Vec<(usize, T)> = obs
+                .as_iter()
+                .copied()
+                .enumerate()
+                .filter(|(i, e)| *e != initial && !unstable.contains(i))
+                .collect();
+
+            for tuple in &tuples {
+                let entry = best.entry(*tuple).or_insert_with(|| {
+                    found.push(*tuple);
+                    BestTestcase {
+                        hits: 0,
+                        idx,
+                        weight,
+                    }
+                });
+                entry.hits += 1;
+                if weight < entry.weight {
+                    entry.idx = idx;
+                    entry.weight = weight;
+                }
+            }
+            coverage.push(tuples);
+        }
+
+        // Start with the rarest entries, they leave the least choice
+        found.sort_by_key(|tuple| best[tuple].hits);
+
+        let mut kept = vec![false; count];
+        let mut covered = HashSet::with_capacity(found.len());
+        for tuple in found {
+            if covered.contains(&tuple) {
+                continue;
+            }
+            let idx = best[&tuple].idx;
+            kept[idx] = true;
+            covered.extend(coverage[idx].iter().copied());
+        }
+
+        let removed = (0..count).filter(|idx| !kept[*idx]).collect();
+        remove_testcases(fuzzer, state, removed)
+    }
+}
+
+/// The best testcase hitting an entry and hit count of the map, for the [`GreedyCorpusMinimizer`]
+#[derive(Debug, Clone, Copy)]
+struct BestTestcase {
+    /// The number of testcases hitting the entry
+    hits: usize,
+    /// The index of the best testcase
+    idx: usize,
+    /// The score and length of the best testcase
+    weight: (f64, usize),
+}
+
+/// Remove the testcases with the given indices from the corpus, and tell the scheduler
+fn remove_testcases<CS, S, Z>(
+    fuzzer: &mut Z,
+    state: &mut S,
+    mut removed: Vec<usize>,
+) -> Result<(), Error>
+where
+    CS: Scheduler<State = S>,
+    S: HasCorpus,
+    Z: HasScheduler<CS, State = S>,
+{
+    // reverse order; if indexes are stored in a vec, we need to remove from back to front
+    removed.sort_unstable_by(|idx1, idx2| idx2.cmp(idx1));
+    for idx in removed {
+        let removed = state.corpus_mut().remove(idx)?;
+        // scheduler needs to know we've removed the input, or it will continue to try
+        // to use now-missing inputs
+        fuzzer.scheduler_mut().on_remove(state, idx, &removed)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use serial_test::serial;
+
+    use super::{CorpusMinimizer, StdGreedyCorpusMinimizer};
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list, AsSlice},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasTargetBytes},
+        observers::StdMapObserver,
+        schedulers::QueueScheduler,
+        state::{HasCorpus, StdState},
+        StdFuzzer,
+    };
+
+    static mut MAP: [u8; 16] = [0; 16];
+
+    #[test]
+    #[serial]
+    fn test_greedy_corpus_minimizer() {
+        let observer = StdMapObserver::new("map", unsafe { &mut MAP });
+        let minimizer = StdGreedyCorpusMinimizer::new(&observer);
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        for bytes in [
+            vec![0],
+            vec![0, 1],
+            vec![1, 2, 3],
+            vec![3],
+            vec![2, 3, 0, 1],
+            vec![4],
+            vec![],
+        ] {
+            state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(bytes)))
+                .unwrap();
+        }
+
+        let mut fuzzer = StdFuzzer::<_, _, _, ()>::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+        let mut harness = |input: &BytesInput| {
+            for byte in input.target_bytes().as_slice() {
+                unsafe { MAP[*byte as usize] = 1 };
+            }
+            // The only input covering entry 4 crashes
+            if input.target_bytes().as_slice() == [4] {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            }
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        minimizer
+            .minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)
+            .unwrap();
+
+        // Entry 2 is the rarest, and [1, 2, 3] its shortest input. It leaves entry 0 to [0].
+        let kept: Vec<Vec<u8>> = (0..state.corpus().count())
+            .map(|idx| {
+                let mut testcase = state.corpus().get(idx).unwrap().borrow_mut();
+                testcase
+                    .load_input()
+                    .unwrap()
+                    .target_bytes()
+                    .as_slice()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(kept, vec![vec![0], vec![1, 2, 3]]);
+    }
+}
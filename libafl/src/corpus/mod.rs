@@ -1,7 +1,7 @@
 //! Corpuses contain the testcases, either in memory, on disk, or somewhere else.
 
 pub mod testcase;
-pub use testcase::{SchedulerTestcaseMetaData, Testcase};
+pub use testcase::{ParentIdMetadata, SchedulerTestcaseMetaData, Testcase};
 
 pub mod inmemory;
 pub use inmemory::InMemoryCorpus;
@@ -9,19 +9,19 @@ pub use inmemory::InMemoryCorpus;
 #[cfg(feature = "std")]
 pub mod ondisk;
 #[cfg(feature = "std")]
-pub use ondisk::OnDiskCorpus;
+pub use ondisk::{AflNameMetadata, OnDiskCorpus};
 
 #[cfg(feature = "std")]
 pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
-#[cfg(feature = "cmin")]
 pub mod minimizer;
 use core::cell::RefCell;
 
+pub use minimizer::{CorpusMinimizer, GreedyCorpusMinimizer, StdGreedyCorpusMinimizer};
 #[cfg(feature = "cmin")]
-pub use minimizer::*;
+pub use minimizer::{MapCorpusMinimizer, StdCorpusMinimizer};
 
 use crate::{inputs::UsesInput, Error};
 
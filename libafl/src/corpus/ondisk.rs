@@ -1,7 +1,7 @@
 //! The ondisk corpus stores unused testcases to disk.
 
-use alloc::vec::Vec;
-use core::{cell::RefCell, time::Duration};
+use alloc::{string::String, vec::Vec};
+use core::{cell::RefCell, fmt::Write as _, time::Duration};
 #[cfg(feature = "std")]
 use std::{fs, fs::File, io::Write};
 use std::{
@@ -12,8 +12,9 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    bolts::serdeany::SerdeAnyMap,
-    corpus::{Corpus, Testcase},
+    bolts::{current_time, serdeany::SerdeAnyMap},
+    corpus::{Corpus, ParentIdMetadata, Testcase},
+    executors::ExitKind,
     inputs::{Input, UsesInput},
     state::HasMetadata,
     Error,
@@ -31,6 +32,35 @@ pub enum OnDiskMetadataFormat {
     JsonPretty,
 }
 
+/// The parts of the AFL-style name of a testcase, see [`OnDiskCorpus::new_afl_style`].
+/// Whoever knows a part adds it before the testcase is stored, e.g. the [`crate::feedbacks::CrashTriageFeedback`] the `sig`.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AflNameMetadata {
+    /// The signal the target crashed with
+    pub sig: Option<i32>,
+    /// The stage the testcase was found in, e.g. `havoc`
+    pub op: Option<String>,
+    /// The number of stacked mutations the testcase was found with
+    pub rep: Option<usize>,
+}
+
+crate::impl_serdeany!(AflNameMetadata);
+
+#[cfg(feature = "std")]
+impl AflNameMetadata {
+    /// The [`AflNameMetadata`] of the `testcase`, added if it has none yet
+    pub fn of_mut<I>(testcase: &mut Testcase<I>) -> &mut Self
+    where
+        I: Input,
+    {
+        if !testcase.has_metadata::<Self>() {
+            testcase.add_metadata(Self::default());
+        }
+        testcase.metadata_mut().get_mut::<Self>().unwrap()
+    }
+}
+
 /// A corpus able to store testcases to disk, and load them from disk, when they are being used.
 #[cfg(feature = "std")]
 #[derive(Debug, Serialize)]
@@ -52,6 +82,27 @@ where
     current: Option<usize>,
     dir_path: PathBuf,
     meta_format: Option<OnDiskMetadataFormat>,
+    /// Name the testcases like AFL does, see [`OnDiskCorpus::new_afl_style`]
+    #[serde(default)]
+    afl_style: bool,
+    /// The id of the next testcase, for AFL-style names
+    #[serde(default)]
+    next_id: usize,
+    /// The directory timeouts are stored in, see [`OnDiskCorpus::with_afl_hangs_dir`]
+    #[serde(default)]
+    hangs_dir: Option<PathBuf>,
+    /// The id of the next testcase in the `hangs_dir`
+    #[serde(default)]
+    next_hang_id: usize,
+    /// The directory out-of-memory inputs are stored in, see [`OnDiskCorpus::with_oom_dir`]
+    #[serde(default)]
+    oom_dir: Option<PathBuf>,
+    /// The id of the next AFL-style testcase in the `oom_dir`
+    #[serde(default)]
+    next_oom_id: usize,
+    /// The time this corpus was created, for AFL-style names
+    #[serde(default)]
+    start_time: Duration,
 }
 
 impl<I> UsesInput for OnDiskCorpus<I>
@@ -139,6 +190,13 @@ where
                 current: None,
                 dir_path,
                 meta_format: None,
+                afl_style: false,
+                next_id: 0,
+                hangs_dir: None,
+                next_hang_id: 0,
+                oom_dir: None,
+                next_oom_id: 0,
+                start_time: current_time(),
             })
         }
         new(dir_path.as_ref().to_path_buf())
@@ -156,17 +214,123 @@ where
             current: None,
             dir_path,
             meta_format,
+            afl_style: false,
+            next_id: 0,
+            hangs_dir: None,
+            next_hang_id: 0,
+            oom_dir: None,
+            next_oom_id: 0,
+            start_time: current_time(),
         })
     }
 
+    /// Creates an [`OnDiskCorpus`] naming the testcases like AFL does, e.g. `id:000042,sig:11,src:000023,time:1337,execs:4242,op:havoc,rep:4`,
+    /// so that scripts working on the `queue` and `crashes` directories of AFL keep working.
+    /// The `src` is the corpus index of the parent, taken from the [`ParentIdMetadata`] the fuzzer adds to new testcases,
+    /// the `time` is in milliseconds since the corpus was created.
+    /// The `sig`, `op` and `rep` are taken from the [`AflNameMetadata`] of the testcase, each is left out if it is unknown.
+    /// The ids continue after the highest one already in `dir_path`, e.g. from a previous run.
+    /// Will error, if [`std::fs::create_dir_all()`] failed for `dir_path`.
+    pub fn new_afl_style<P>(dir_path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut corpus = Self::new(dir_path)?;
+        corpus.afl_style = true;
+        corpus.next_id = first_free_afl_id(&corpus.dir_path)?;
+        Ok(corpus)
+    }
+
+    /// Stores the testcases carrying an [`ExitKind::Timeout`] metadata in `hangs_dir` instead, like the `hangs` directory of AFL.
+    /// Use this for an AFL-style objective corpus in `crashes`, with `hangs` next to it.
+    /// Will error, if [`std::fs::create_dir_all()`] failed for `hangs_dir`.
+    pub fn with_afl_hangs_dir<P>(mut self, hangs_dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let hangs_dir = hangs_dir.as_ref().to_path_buf();
+        fs::create_dir_all(&hangs_dir)?;
+        self.next_hang_id = first_free_afl_id(&hangs_dir)?;
+        self.hangs_dir = Some(hangs_dir);
+        Ok(self)
+    }
+
+    /// Stores the testcases carrying an [`ExitKind::Oom`] metadata in `oom_dir` instead,
+    /// so that inputs running out of memory, see [`crate::feedbacks::OomFeedback`], are kept apart from the crashes.
+    /// Will error, if [`std::fs::create_dir_all()`] failed for `oom_dir`.
+    pub fn with_oom_dir<P>(mut self, oom_dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let oom_dir = oom_dir.as_ref().to_path_buf();
+        fs::create_dir_all(&oom_dir)?;
+        if self.afl_style {
+            self.next_oom_id = first_free_afl_id(&oom_dir)?;
+        }
+        self.oom_dir = Some(oom_dir);
+        Ok(self)
+    }
+
+    /// The directory for a new testcase, and its AFL-style name
+    fn afl_name(&mut self, testcase: &Testcase<I>) -> (PathBuf, String) {
+        let exit_kind = testcase.metadata().get::<ExitKind>();
+        let (dir, id) = match (exit_kind, &self.hangs_dir, &self.oom_dir) {
+            (Some(ExitKind::Timeout), Some(hangs_dir), _) => {
+                self.next_hang_id += 1;
+                (hangs_dir.clone(), self.next_hang_id - 1)
+            }
+            (Some(ExitKind::Oom), _, Some(oom_dir)) => {
+                self.next_oom_id += 1;
+                (oom_dir.clone(), self.next_oom_id - 1)
+            }
+            _ => {
+                self.next_id += 1;
+                (self.dir_path.clone(), self.next_id - 1)
+            }
+        };
+
+        let parts = testcase.metadata().get::<AflNameMetadata>();
+        let mut name = format!("id:{id:06}");
+        if let Some(sig) = parts.and_then(|parts| parts.sig) {
+            write!(name, ",sig:{sig:02}").unwrap();
+        }
+        if let Some(parent) = testcase.metadata().get::<ParentIdMetadata>() {
+            write!(name, ",src:{:06}", parent.id).unwrap();
+        }
+        write!(
+            name,
+            ",time:{},execs:{}",
+            current_time().saturating_sub(self.start_time).as_millis(),
+            testcase.executions()
+        )
+        .unwrap();
+        if let Some(op) = parts.and_then(|parts| parts.op.as_ref()) {
+            write!(name, ",op:{op}").unwrap();
+        }
+        if let Some(rep) = parts.and_then(|parts| parts.rep) {
+            write!(name, ",rep:{rep}").unwrap();
+        }
+        (dir, name)
+    }
+
     fn save_testcase(&mut self, testcase: &mut Testcase<I>) -> Result<(), Error> {
         if testcase.filename().is_none() {
-            // TODO walk entry metadata to ask for pieces of filename (e.g. :havoc in AFL)
-            let file_orig = testcase
-                .input()
-                .as_ref()
-                .unwrap()
-                .generate_name(self.entries.len());
+            let (dir, file_orig) = if self.afl_style {
+                self.afl_name(testcase)
+            } else {
+                let dir = match (testcase.metadata().get::<ExitKind>(), &self.oom_dir) {
+                    (Some(ExitKind::Oom), Some(oom_dir)) => oom_dir.clone(),
+                    _ => self.dir_path.clone(),
+                };
+                (
+                    dir,
+                    testcase
+                        .input()
+                        .as_ref()
+                        .unwrap()
+                        .generate_name(self.entries.len()),
+                )
+            };
             let mut file = file_orig.clone();
 
             let mut ctr = 2;
@@ -177,10 +341,10 @@ where
                 if OpenOptions::new()
                     .write(true)
                     .create_new(true)
-                    .open(self.dir_path.join(lockfile))
+                    .open(dir.join(lockfile))
                     .is_ok()
                 {
-                    break self.dir_path.join(file);
+                    break dir.join(file);
                 }
 
                 file = format!("{}-{ctr}", &file_orig);
@@ -239,6 +403,219 @@ where
         Ok(())
     }
 }
+
+/// The AFL id in the name of a testcase, e.g. `42` from `id:000042,src:000023`
+fn afl_id(file_name: &str) -> Option<usize> {
+    file_name
+        .strip_prefix("id:")?
+        .split(',')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// The id following the highest one of the AFL-style testcases in `dir`
+fn first_free_afl_id(dir: &Path) -> Result<usize, Error> {
+    let mut next_id = 0;
+    for entry in fs::read_dir(dir)? {
+        if let Some(id) = afl_id(&entry?.file_name().to_string_lossy()) {
+            next_id = next_id.max(id + 1);
+        }
+    }
+    Ok(next_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+    use std::fs;
+
+    use super::{AflNameMetadata, OnDiskCorpus};
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{Corpus, InMemoryCorpus, ParentIdMetadata, Testcase},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedback_or,
+        feedbacks::{ConstFeedback, CrashFeedback, OomFeedback},
+        fuzzer::{Evaluator, ExecutionProcessor, StdFuzzer},
+        inputs::{BytesInput, HasBytesVec},
+        schedulers::QueueScheduler,
+        state::{HasCorpus, HasMetadata, HasSolutions, StdState},
+    };
+
+    /// The sorted names of the AFL-style testcases in `dir`, without their `time`
+    fn names(dir: &std::path::Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("id:"))
+            .map(|name| {
+                name.split(',')
+                    .filter(|part| !part.starts_with("time:"))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_afl_style_names() {
+        let out_dir = std::env::temp_dir().join(format!("afl_names_test_{}", std::process::id()));
+        let crashes = out_dir.join("crashes");
+        let hangs = out_dir.join("hangs");
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let solutions = OnDiskCorpus::<BytesInput>::new_afl_style(&crashes)
+            .unwrap()
+            .with_afl_hangs_dir(&hangs)
+            .unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(true);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            solutions,
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        // Without a scheduled testcase, a solution has no parent
+        fuzzer
+            .process_execution(
+                &mut state,
+                &mut mgr,
+                BytesInput::new(vec![0]),
+                &(),
+                &ExitKind::Crash,
+                false,
+            )
+            .unwrap();
+
+        // The testcase scheduled from the queue is the parent of the solutions found meanwhile
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![1])))
+            .unwrap();
+        *state.corpus_mut().current_mut() = Some(0);
+        fuzzer
+            .process_execution(
+                &mut state,
+                &mut mgr,
+                BytesInput::new(vec![2]),
+                &(),
+                &ExitKind::Timeout,
+                false,
+            )
+            .unwrap();
+
+        let mut crash = Testcase::with_executions(BytesInput::new(vec![3]), 2);
+        crash.add_metadata(ExitKind::Crash);
+        crash.add_metadata(ParentIdMetadata::new(0));
+        crash.add_metadata(AflNameMetadata {
+            sig: Some(11),
+            op: Some("havoc".into()),
+            rep: Some(2),
+        });
+        state.solutions_mut().add(crash).unwrap();
+
+        assert_eq!(
+            names(&crashes),
+            [
+                "id:000000,execs:0",
+                "id:000001,sig:11,src:000000,execs:2,op:havoc,rep:2"
+            ]
+        );
+        assert_eq!(names(&hangs), ["id:000000,src:000000,execs:0"]);
+
+        // A corpus resuming in the same directories continues after the existing ids
+        let mut corpus = OnDiskCorpus::<BytesInput>::new_afl_style(&crashes)
+            .unwrap()
+            .with_afl_hangs_dir(&hangs)
+            .unwrap();
+        corpus
+            .add(Testcase::with_executions(BytesInput::new(vec![3]), 4))
+            .unwrap();
+        let mut hang = Testcase::with_executions(BytesInput::new(vec![4]), 5);
+        hang.add_metadata(ExitKind::Timeout);
+        corpus.add(hang).unwrap();
+
+        assert_eq!(names(&crashes)[2], "id:000002,execs:4");
+        assert_eq!(names(&hangs)[1], "id:000001,execs:5");
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_oom_dir() {
+        let out_dir = std::env::temp_dir().join(format!("oom_dir_test_{}", std::process::id()));
+        let crashes = out_dir.join("crashes");
+        let oom = out_dir.join("oom");
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let solutions = OnDiskCorpus::<BytesInput>::new(&crashes)
+            .unwrap()
+            .with_oom_dir(&oom)
+            .unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = feedback_or!(CrashFeedback::new(), OomFeedback::new());
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            solutions,
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        let mut harness = |input: &BytesInput| match input.bytes() {
+            [0] => ExitKind::Crash,
+            [1] => ExitKind::Oom,
+            _ => ExitKind::Ok,
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+        for byte in 0..3 {
+            fuzzer
+                .evaluate_input(
+                    &mut state,
+                    &mut executor,
+                    &mut mgr,
+                    BytesInput::new(vec![byte]),
+                )
+                .unwrap();
+        }
+        assert_eq!(state.solutions().count(), 2);
+
+        // Only the crash is among the crashes
+        let contents = |dir: &std::path::Path| -> Vec<Vec<u8>> {
+            fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| !path.file_name().unwrap().to_string_lossy().starts_with('.'))
+                .map(|path| fs::read(path).unwrap())
+                .collect()
+        };
+        assert_eq!(contents(&crashes), [vec![0]]);
+        assert_eq!(contents(&oom), [vec![1]]);
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
+
 #[cfg(feature = "python")]
 /// `OnDiskCorpus` Python bindings
 pub mod pybind {
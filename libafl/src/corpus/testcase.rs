@@ -344,6 +344,23 @@ impl SchedulerTestcaseMetaData {
 
 crate::impl_serdeany!(SchedulerTestcaseMetaData);
 
+/// The corpus index of the testcase that was scheduled when a new testcase was found, i.e. its parent
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParentIdMetadata {
+    /// The index of the parent in the corpus
+    pub id: usize,
+}
+
+impl ParentIdMetadata {
+    /// Create new [`struct@ParentIdMetadata`]
+    #[must_use]
+    pub fn new(id: usize) -> Self {
+        Self { id }
+    }
+}
+
+crate::impl_serdeany!(ParentIdMetadata);
+
 #[cfg(feature = "python")]
 #[allow(missing_docs)]
 /// `Testcase` Python bindings
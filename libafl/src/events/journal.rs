@@ -0,0 +1,295 @@
+//! A journal to record a fuzzing session and replay it deterministically.
+//!
+//! The journal records the seed of the [`crate::bolts::rands::Rand`], every decision of the scheduler,
+//! and every testcase imported from other clients, in the order they happened.
+//! To record, wrap the scheduler in a [`crate::schedulers::JournalScheduler`] and pass the journal to the
+//! [`super::LlmpEventManager`] using `set_journal`.
+//! To replay, create a single-client [`super::SimpleEventManager`], seed the `Rand` with [`Journal::seed`],
+//! wrap the scheduler the same way, and append a [`crate::stages::JournalReplayStage`] to the stages,
+//! that evaluates the imported testcases where the event manager originally did.
+//!
+//! Executions that depend on time, such as calibration or timeouts, and restarts of the fuzzer
+//! cannot be reproduced. The [`crate::schedulers::JournalScheduler`] reports the first decision that diverges.
+
+use alloc::{collections::VecDeque, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, inputs::Input, Error};
+
+/// A [`Journal`] shared between the scheduler, the event manager and the stages
+pub type SharedJournal<I> = Rc<RefCell<Journal<I>>>;
+
+/// An entry of the [`Journal`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "I: serde::de::DeserializeOwned")]
+pub enum JournalEntry<I>
+where
+    I: Input,
+{
+    /// The seed of the `Rand`, the first entry
+    Seed(u64),
+    /// The scheduler chose the corpus entry with this index
+    Scheduled(usize),
+    /// A testcase was imported from another client
+    NewTestcase {
+        /// The input of the testcase
+        input: I,
+        /// The exit kind the other client reported
+        exit_kind: ExitKind,
+        /// The serialized observers, if they were used instead of running the input
+        observers_buf: Option<Vec<u8>>,
+    },
+}
+
+/// A testcase imported from another client, to replay
+pub type JournalTestcase<I> = (I, ExitKind, Option<Vec<u8>>);
+
+/// A journal of a fuzzing session, either recording to, or replaying from a file.
+/// Each entry is stored as its length, as little-endian `u32`, followed by the [`postcard`] serialized [`JournalEntry`].
+#[derive(Debug)]
+pub struct Journal<I>
+where
+    I: Input,
+{
+    path: PathBuf,
+    seed: u64,
+    /// The file to record to, `None` when replaying
+    writer: Option<BufWriter<File>>,
+    /// The entries left to replay
+    entries: VecDeque<JournalEntry<I>>,
+    /// The number of scheduler decisions recorded or replayed so far
+    decisions: usize,
+}
+
+impl<I> Journal<I>
+where
+    I: Input,
+{
+    /// Start recording a new journal to `path`, for a `Rand` seeded with `seed`.
+    /// An existing journal at `path` is overwritten.
+    pub fn record<P>(path: P, seed: u64) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut journal = Self {
+            path: path.as_ref().to_path_buf(),
+            seed,
+            writer: Some(BufWriter::new(File::create(path)?)),
+            entries: VecDeque::new(),
+            decisions: 0,
+        };
+        journal.write_entry(&JournalEntry::Seed(seed))?;
+        Ok(journal)
+    }
+
+    /// Load the journal recorded at `path` to replay it
+    pub fn replay<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let data = fs::read(path.as_ref())?;
+        let mut entries = VecDeque::new();
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let len = rest
+                .get(..4)
+                .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize);
+            match len {
+                Some(len) if rest.len() >= 4 + len => {
+                    entries.push_back(postcard::from_bytes(&rest[4..4 + len])?);
+                    rest = &rest[4 + len..];
+                }
+                _ => {
+                    // The fuzzer died while writing the last entry
+                    println!(
+                        "Dropping the torn last {} bytes of the journal {}",
+                        rest.len(),
+                        path.as_ref().display()
+                    );
+                    break;
+                }
+            }
+        }
+
+        let seed = match entries.pop_front() {
+            Some(JournalEntry::Seed(seed)) => seed,
+            _ => {
+                return Err(Error::illegal_argument(format!(
+                    "{} is not a journal, it does not start with a seed",
+                    path.as_ref().display()
+                )))
+            }
+        };
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            seed,
+            writer: None,
+            entries,
+            decisions: 0,
+        })
+    }
+
+    /// Share this journal between the scheduler, the event manager and the stages
+    #[must_use]
+    pub fn into_shared(self) -> SharedJournal<I> {
+        Rc::new(RefCell::new(self))
+    }
+
+    /// The seed the `Rand` of the recorded session was created with
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The path of the journal file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// If this journal replays a recorded session
+    #[must_use]
+    pub fn is_replaying(&self) -> bool {
+        self.writer.is_none()
+    }
+
+    /// The number of scheduler decisions recorded or replayed so far
+    #[must_use]
+    pub fn decisions(&self) -> usize {
+        self.decisions
+    }
+
+    /// Record that the scheduler chose the corpus entry `idx`
+    pub fn record_scheduled(&mut self, idx: usize) -> Result<(), Error> {
+        self.decisions += 1;
+        self.write_entry(&JournalEntry::Scheduled(idx))
+    }
+
+    /// Record a testcase imported from another client
+    pub fn record_new_testcase(
+        &mut self,
+        input: &I,
+        exit_kind: &ExitKind,
+        observers_buf: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        self.write_entry(&JournalEntry::NewTestcase {
+            input: input.clone(),
+            exit_kind: *exit_kind,
+            observers_buf: observers_buf.map(<[u8]>::to_vec),
+        })
+    }
+
+    /// The next recorded scheduler decision.
+    /// Returns [`Error::ShuttingDown`] once the whole journal has been replayed.
+    pub fn replay_scheduled(&mut self) -> Result<usize, Error> {
+        let pos = self
+            .entries
+            .iter()
+            .position(|entry| matches!(entry, JournalEntry::Scheduled(_)));
+        match pos.and_then(|pos| self.entries.remove(pos)) {
+            Some(JournalEntry::Scheduled(idx)) => {
+                self.decisions += 1;
+                Ok(idx)
+            }
+            _ => Err(Error::shutting_down()),
+        }
+    }
+
+    /// The testcases imported before the next recorded scheduler decision
+    pub fn replay_new_testcases(&mut self) -> Vec<JournalTestcase<I>> {
+        let mut testcases = vec![];
+        while let Some(JournalEntry::NewTestcase { .. }) = self.entries.front() {
+            if let Some(JournalEntry::NewTestcase {
+                input,
+                exit_kind,
+                observers_buf,
+            }) = self.entries.pop_front()
+            {
+                testcases.push((input, exit_kind, observers_buf));
+            }
+        }
+        testcases
+    }
+
+    fn write_entry(&mut self, entry: &JournalEntry<I>) -> Result<(), Error> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| Error::illegal_state("Cannot record to a journal that is replayed"))?;
+        let buf = postcard::to_allocvec(entry)?;
+        writer.write_all(&(buf.len() as u32).to_le_bytes())?;
+        writer.write_all(&buf)?;
+        // Keep the journal complete if the fuzzer dies
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Journal;
+    use crate::{executors::ExitKind, inputs::BytesInput, Error};
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let path = std::env::temp_dir().join("libafl_test_journal");
+        {
+            let mut journal = Journal::<BytesInput>::record(&path, 1337).unwrap();
+            journal.record_scheduled(0).unwrap();
+            journal
+                .record_new_testcase(&BytesInput::new(vec![1, 2]), &ExitKind::Ok, None)
+                .unwrap();
+            journal.record_scheduled(1).unwrap();
+            journal.record_scheduled(1).unwrap();
+        }
+
+        let mut journal = Journal::<BytesInput>::replay(&path).unwrap();
+        assert_eq!(journal.seed(), 1337);
+        assert!(journal.is_replaying());
+        assert!(journal.replay_new_testcases().is_empty());
+        assert_eq!(journal.replay_scheduled().unwrap(), 0);
+        let testcases = journal.replay_new_testcases();
+        assert_eq!(testcases.len(), 1);
+        assert_eq!(testcases[0].0, BytesInput::new(vec![1, 2]));
+        assert_eq!(journal.replay_scheduled().unwrap(), 1);
+        assert_eq!(journal.replay_scheduled().unwrap(), 1);
+        assert!(matches!(
+            journal.replay_scheduled(),
+            Err(Error::ShuttingDown)
+        ));
+        assert!(journal.record_scheduled(2).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_torn_tail() {
+        let path = std::env::temp_dir().join(format!("libafl_torn_journal_{}", std::process::id()));
+        {
+            let mut journal = Journal::<BytesInput>::record(&path, 1337).unwrap();
+            journal.record_scheduled(0).unwrap();
+            journal
+                .record_new_testcase(&BytesInput::new(vec![1, 2]), &ExitKind::Ok, None)
+                .unwrap();
+        }
+        let data = std::fs::read(&path).unwrap();
+
+        // Cut mid-body of the last entry, and mid-length prefix of an entry following it
+        for torn in [&data[..data.len() - 1], &[&data[..], &[5, 0]].concat()] {
+            std::fs::write(&path, torn).unwrap();
+            let mut journal = Journal::<BytesInput>::replay(&path).unwrap();
+            assert_eq!(journal.replay_scheduled().unwrap(), 0);
+            let testcases = journal.replay_new_testcases();
+            assert_eq!(testcases.len(), usize::from(torn.len() > data.len()));
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -9,7 +9,10 @@ use alloc::{
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::{marker::PhantomData, time::Duration};
 #[cfg(feature = "std")]
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+};
 
 use serde::Deserialize;
 #[cfg(feature = "std")]
@@ -30,7 +33,14 @@ use crate::bolts::{
     llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
 };
 #[cfg(feature = "std")]
-use crate::bolts::{llmp::LlmpConnection, shmem::StdShMemProvider, staterestore::StateRestorer};
+use crate::bolts::{
+    current_time,
+    llmp::{LlmpBrokerStatus, LlmpConnection, LlmpWal},
+    shmem::StdShMemProvider,
+    staterestore::StateRestorer,
+};
+#[cfg(feature = "std")]
+use crate::events::SharedJournal;
 use crate::{
     bolts::{
         llmp::{self, Flags, LlmpClient, LlmpClientDescription, Tag},
@@ -58,6 +68,10 @@ const LLMP_TAG_EVENT_TO_BOTH: Tag = 0x2B0741;
 const _LLMP_TAG_RESTART: Tag = 0x8357A87;
 const _LLMP_TAG_NO_RESTART: Tag = 0x57A7EE71;
 
+/// How often a client checks if the broker it watches got restarted, see [`LlmpEventManager::watch_broker`]
+#[cfg(feature = "std")]
+const BROKER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 /// The minimum buffer size at which to compress LLMP IPC messages.
 #[cfg(feature = "llmp_compression")]
 const COMPRESS_THRESHOLD: usize = 1024;
@@ -73,6 +87,9 @@ where
 {
     monitor: MT,
     llmp: llmp::LlmpBroker<SP>,
+    /// The write-ahead log of broadcast testcases, if any
+    #[cfg(feature = "std")]
+    wal: Option<LlmpWal>,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
     phantom: PhantomData<I>,
@@ -89,6 +106,8 @@ where
         Ok(Self {
             monitor,
             llmp,
+            #[cfg(feature = "std")]
+            wal: None,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             phantom: PhantomData,
@@ -102,6 +121,7 @@ where
         Ok(Self {
             monitor,
             llmp: llmp::LlmpBroker::create_attach_to_tcp(shmem_provider, port)?,
+            wal: None,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             phantom: PhantomData,
@@ -117,9 +137,28 @@ where
         self.llmp.connect_b2b(addr)
     }
 
+    /// Persist all testcases broadcast by this broker to the write-ahead log at `path`.
+    /// If the log already exists, e.g. after the broker crashed or the host rebooted,
+    /// the testcases in it are broadcast again first, so that clients connecting to this broker catch up on them.
+    #[cfg(feature = "std")]
+    pub fn set_wal<P>(&mut self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let wal = LlmpWal::open(path)?;
+        let replayed = self.llmp.replay_wal(&wal)?;
+        if replayed > 0 {
+            println!("Replayed {replayed} testcases from {:?}", wal.path());
+        }
+        self.wal = Some(wal);
+        Ok(())
+    }
+
     /// Run forever in the broker
     pub fn broker_loop(&mut self) -> Result<(), Error> {
         let monitor = &mut self.monitor;
+        #[cfg(feature = "std")]
+        let wal = &mut self.wal;
         #[cfg(feature = "llmp_compression")]
         let compressor = &self.compressor;
         self.llmp.loop_forever(
@@ -138,7 +177,14 @@ where
                     };
                     let event: Event<I> = postcard::from_bytes(event_bytes)?;
                     match Self::handle_in_broker(monitor, client_id, &event)? {
-                        BrokerEventResult::Forward => Ok(llmp::LlmpMsgHookResult::ForwardToClients),
+                        BrokerEventResult::Forward => {
+                            // Log the testcase as it was sent, so it can be replayed as is
+                            #[cfg(feature = "std")]
+                            if let (Some(wal), Event::NewTestcase { .. }) = (wal.as_mut(), &event) {
+                                wal.append(tag, _flags, msg)?;
+                            }
+                            Ok(llmp::LlmpMsgHookResult::ForwardToClients)
+                        }
                         BrokerEventResult::Handled => Ok(llmp::LlmpMsgHookResult::Handled),
                     }
                 } else {
@@ -148,6 +194,11 @@ where
             Some(Duration::from_millis(5)),
         );
 
+        #[cfg(feature = "std")]
+        if let Some(wal) = self.wal.as_mut() {
+            wal.sync()?;
+        }
+
         Ok(())
     }
 
@@ -255,6 +306,15 @@ where
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
     configuration: EventConfig,
+    /// The journal recording the testcases imported from other clients, if any
+    #[cfg(feature = "std")]
+    journal: Option<SharedJournal<S::Input>>,
+    /// The port of the broker to reconnect to once it got restarted, if any
+    #[cfg(feature = "std")]
+    watched_broker_port: Option<u16>,
+    /// When we last checked the watched broker
+    #[cfg(feature = "std")]
+    last_broker_check: Duration,
     phantom: PhantomData<S>,
 }
 
@@ -269,6 +329,10 @@ where
         //.field("custom_buf_handlers", &self.custom_buf_handlers)
         #[cfg(feature = "llmp_compression")]
         let debug = debug.field("compressor", &self.compressor);
+        #[cfg(feature = "std")]
+        let debug = debug
+            .field("journal", &self.journal)
+            .field("watched_broker_port", &self.watched_broker_port);
         debug
             .field("configuration", &self.configuration)
             .field("phantom", &self.phantom)
@@ -299,6 +363,12 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            #[cfg(feature = "std")]
+            journal: None,
+            #[cfg(feature = "std")]
+            watched_broker_port: None,
+            #[cfg(feature = "std")]
+            last_broker_check: Duration::ZERO,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -318,6 +388,12 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            #[cfg(feature = "std")]
+            journal: None,
+            #[cfg(feature = "std")]
+            watched_broker_port: None,
+            #[cfg(feature = "std")]
+            last_broker_check: Duration::ZERO,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -335,11 +411,55 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            #[cfg(feature = "std")]
+            journal: None,
+            #[cfg(feature = "std")]
+            watched_broker_port: None,
+            #[cfg(feature = "std")]
+            last_broker_check: Duration::ZERO,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
     }
 
+    /// Reconnects to a broker restarted on `port`, e.g. after the previous broker crashed.
+    /// If the new broker replays its write-ahead log, see [`LlmpEventBroker::set_wal`],
+    /// the testcases shared before the crash are received again.
+    #[cfg(feature = "std")]
+    pub fn reconnect_to_broker(&mut self, port: u16) -> Result<(), Error> {
+        self.llmp.reconnect_tcp(port)
+    }
+
+    /// Watch the broker on `port` while processing events, and reconnect to it once it got restarted,
+    /// see [`LlmpEventManager::reconnect_if_broker_restarted`].
+    #[cfg(feature = "std")]
+    pub fn watch_broker(&mut self, port: u16) {
+        self.watched_broker_port = Some(port);
+    }
+
+    /// Reconnects to the watched broker, if another broker listens on its port by now.
+    /// Returns `true` if we reconnected.
+    #[cfg(feature = "std")]
+    pub fn reconnect_if_broker_restarted(&mut self) -> Result<bool, Error> {
+        let port = match self.watched_broker_port {
+            Some(port) => port,
+            None => return Ok(false),
+        };
+        self.last_broker_check = current_time();
+        match self.llmp.broker_status(port)? {
+            LlmpBrokerStatus::Alive => Ok(false),
+            LlmpBrokerStatus::Down => {
+                println!("The broker on port {port} is down, waiting for it to restart");
+                Ok(false)
+            }
+            LlmpBrokerStatus::Restarted => {
+                println!("The broker on port {port} got restarted, reconnecting");
+                self.reconnect_to_broker(port)?;
+                Ok(true)
+            }
+        }
+    }
+
     /// Describe the client event mgr's llmp parts in a restorable fashion
     pub fn describe(&self) -> Result<LlmpClientDescription, Error> {
         self.llmp.describe()
@@ -356,6 +476,12 @@ where
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::new(COMPRESS_THRESHOLD),
             configuration,
+            #[cfg(feature = "std")]
+            journal: None,
+            #[cfg(feature = "std")]
+            watched_broker_port: None,
+            #[cfg(feature = "std")]
+            last_broker_check: Duration::ZERO,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
         })
@@ -367,6 +493,13 @@ where
         self.llmp.to_env(env_name).unwrap();
     }
 
+    /// Record the testcases imported from other clients to the `journal`,
+    /// to replay this session later, see [`crate::events::journal`].
+    #[cfg(feature = "std")]
+    pub fn set_journal(&mut self, journal: SharedJournal<S::Input>) {
+        self.journal = Some(journal);
+    }
+
     // Handle arriving events in the client
     #[allow(clippy::unused_self)]
     fn handle_in_client<E, Z>(
@@ -398,9 +531,18 @@ where
                     _client_id, client_config
                 );
 
-                let _res = if client_config.match_with(&self.configuration)
-                    && observers_buf.is_some()
-                {
+                let use_observers =
+                    client_config.match_with(&self.configuration) && observers_buf.is_some();
+                #[cfg(feature = "std")]
+                if let Some(journal) = &self.journal {
+                    journal.borrow_mut().record_new_testcase(
+                        &input,
+                        &exit_kind,
+                        observers_buf.as_deref().filter(|_| use_observers),
+                    )?;
+                }
+
+                let _res = if use_observers {
                     let observers: E::Observers =
                         postcard::from_bytes(observers_buf.as_ref().unwrap())?;
                     fuzzer.process_execution(state, self, input, &observers, &exit_kind, false)?
@@ -511,6 +653,13 @@ where
         state: &mut Self::State,
         executor: &mut E,
     ) -> Result<usize, Error> {
+        #[cfg(feature = "std")]
+        if self.watched_broker_port.is_some()
+            && current_time().saturating_sub(self.last_broker_check) >= BROKER_CHECK_INTERVAL
+        {
+            self.reconnect_if_broker_restarted()?;
+        }
+
         // TODO: Get around local event copy by moving handle_in_client
         let self_id = self.llmp.sender.id;
         let mut count = 0;
@@ -727,6 +876,12 @@ where
     pub fn staterestorer_mut(&mut self) -> &mut StateRestorer<SP> {
         &mut self.staterestorer
     }
+
+    /// Record the testcases imported from other clients to the `journal`,
+    /// to replay this session later, see [`crate::events::journal`].
+    pub fn set_journal(&mut self, journal: SharedJournal<S::Input>) {
+        self.llmp_mgr.journal = Some(journal);
+    }
 }
 
 /// The kind of manager we're creating right now
@@ -794,6 +949,13 @@ where
     /// The address to connect to
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The write-ahead log the broker persists all testcases to, and replays them from after a restart
+    #[builder(default = None)]
+    broker_wal: Option<PathBuf>,
+    /// If the client should reconnect to the broker on `broker_port` once it got restarted, e.g. after a crash,
+    /// see [`LlmpEventManager::watch_broker`]
+    #[builder(default = false)]
+    reconnect_to_broker: bool,
     /// The type of manager to build
     #[builder(default = ManagerKind::Any)]
     kind: ManagerKind,
@@ -816,7 +978,12 @@ where
             .is_err()
         {
             let broker_things = |mut broker: LlmpEventBroker<S::Input, MT, SP>,
-                                 remote_broker_addr| {
+                                 remote_broker_addr,
+                                 broker_wal: Option<&Path>| {
+                if let Some(broker_wal) = broker_wal {
+                    broker.set_wal(broker_wal)?;
+                };
+
                 if let Some(remote_broker_addr) = remote_broker_addr {
                     println!("B2b: Connecting to {:?}", &remote_broker_addr);
                     broker.connect_b2b(remote_broker_addr)?;
@@ -842,7 +1009,11 @@ where
                                 "Doing broker things. Run this tool again to start fuzzing in a client."
                             );
 
-                            broker_things(event_broker, self.remote_broker_addr)?;
+                            broker_things(
+                                event_broker,
+                                self.remote_broker_addr,
+                                self.broker_wal.as_deref(),
+                            )?;
 
                             return Err(Error::shutting_down());
                         }
@@ -859,7 +1030,11 @@ where
                         self.broker_port,
                     )?;
 
-                    broker_things(event_broker, self.remote_broker_addr)?;
+                    broker_things(
+                        event_broker,
+                        self.remote_broker_addr,
+                        self.broker_wal.as_deref(),
+                    )?;
 
                     return Err(Error::shutting_down());
                 }
@@ -977,6 +1152,10 @@ where
         // We reset the staterestorer, the next staterestorer and receiver (after crash) will reuse the page from the initial message.
         mgr.staterestorer.reset();
 
+        if self.reconnect_to_broker {
+            mgr.llmp_mgr.watch_broker(self.broker_port);
+        }
+
         /* TODO: Not sure if this is needed
         // We commit an empty NO_RESTART message to this buf, against infinite loops,
         // in case something crashes in the fuzzer.
@@ -991,9 +1170,21 @@ where
 #[cfg(feature = "std")]
 mod tests {
     use core::sync::atomic::{compiler_fence, Ordering};
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    use core::time::Duration;
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    use std::{net::TcpListener, path::Path, thread::sleep};
 
     use serial_test::serial;
 
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    use crate::{
+        bolts::{
+            llmp::{LlmpBroker, LlmpMsgHookResult::ForwardToClients, LlmpWal},
+            os::{fork, ChildHandle, ForkResult},
+        },
+        Error,
+    };
     use crate::{
         bolts::{
             llmp::{LlmpClient, LlmpSharedMap},
@@ -1106,4 +1297,99 @@ mod tests {
                 .unwrap();
         }
     }
+
+    /// A broker running in a child process, killed on drop
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    struct BrokerProcess(ChildHandle);
+
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    impl BrokerProcess {
+        /// Fork a broker on `port`, replaying and logging all messages to the write-ahead log at `path`
+        fn spawn(port: u16, path: &Path) -> Self {
+            fn run_broker(port: u16, path: &Path) -> Result<(), Error> {
+                let mut broker = LlmpBroker::create_attach_to_tcp(StdShMemProvider::new()?, port)?;
+                let mut wal = LlmpWal::open(path)?;
+                broker.replay_wal(&wal)?;
+                loop {
+                    broker.once(&mut |_sender_id, tag, flags, msg| {
+                        wal.append(tag, flags, msg)?;
+                        wal.sync()?;
+                        Ok(ForwardToClients)
+                    })?;
+                    sleep(Duration::from_millis(1));
+                }
+            }
+
+            match unsafe { fork() }.unwrap() {
+                ForkResult::Parent(handle) => Self(handle),
+                ForkResult::Child => {
+                    drop(run_broker(port, path));
+                    unsafe { libc::_exit(1) }
+                }
+            }
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    impl Drop for BrokerProcess {
+        fn drop(&mut self) {
+            unsafe {
+                libc::kill(self.0.pid, libc::SIGKILL);
+            }
+            let _status = self.0.status();
+        }
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    fn test_mgr_reconnect_to_restarted_broker() {
+        type TestState =
+            StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+        let port = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let path = std::env::temp_dir().join(format!("llmp_mgr_reconnect_{}", std::process::id()));
+        drop(std::fs::remove_file(&path));
+
+        let broker = BrokerProcess::spawn(port, &path);
+        let mut llmp_mgr = LlmpEventManager::<TestState, _>::new_on_port(
+            StdShMemProvider::new().unwrap(),
+            port,
+            "fuzzer".into(),
+        )
+        .unwrap();
+        llmp_mgr.watch_broker(port);
+        assert!(!llmp_mgr.reconnect_if_broker_restarted().unwrap());
+
+        // Once the broker forwarded the testcase, it's in the log
+        llmp_mgr.llmp.send_buf(0x1337, b"testcase").unwrap();
+        assert_eq!(llmp_mgr.llmp.recv_buf_blocking().unwrap().2, b"testcase");
+
+        // The broker crashes, the client keeps going
+        drop(broker);
+        assert!(!llmp_mgr.reconnect_if_broker_restarted().unwrap());
+
+        // A restarted broker replays the log to the client, once it reconnected
+        let _broker = BrokerProcess::spawn(port, &path);
+        let mut reconnected = false;
+        for _ in 0..1000 {
+            if llmp_mgr.reconnect_if_broker_restarted().unwrap() {
+                reconnected = true;
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        assert!(reconnected);
+        let (_sender_id, tag, buf) = llmp_mgr.llmp.recv_buf_blocking().unwrap();
+        assert_eq!(tag, 0x1337);
+        assert_eq!(buf, b"testcase");
+
+        // The client waits for the broker to map its pages
+        drop(llmp_mgr);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -2,6 +2,10 @@
 
 pub mod simple;
 pub use simple::*;
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "std")]
+pub use journal::{Journal, JournalEntry, JournalTestcase, SharedJournal};
 pub mod llmp;
 use alloc::{
     boxed::Box,
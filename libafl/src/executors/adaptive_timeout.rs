@@ -0,0 +1,386 @@
+//! The [`AdaptiveTimeoutExecutor`] derives the timeout of an executor from the execution times measured in calibration,
+//! like AFL picks its timeout from the execution times of the seed corpus.
+
+use core::{fmt::Debug, time::Duration};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout, ReturnsTimeouts},
+    observers::{ObserversTuple, UsesObservers},
+    schedulers::powersched::SchedulerMetadata,
+    state::{HasMetadata, UsesState},
+    Error,
+};
+
+/// The default factor the average execution time is multiplied with
+pub const DEFAULT_TIMEOUT_MULTIPLIER: f64 = 5.0;
+/// The default lower bound of the timeout, AFL's `EXEC_TM_ROUND`
+pub const DEFAULT_TIMEOUT_FLOOR: Duration = Duration::from_millis(20);
+/// The default upper bound of the timeout, AFL's `EXEC_TIMEOUT`
+pub const DEFAULT_TIMEOUT_CEILING: Duration = Duration::from_secs(1);
+
+/// Wraps an executor with a [`HasTimeout`], such as a [`crate::executors::TimeoutExecutor`] or a
+/// `TimeoutForkserverExecutor`, to adjust its timeout to the target.
+///
+/// Whenever the [`crate::stages::CalibrationStage`] calibrated new testcases, the timeout is set to the average
+/// execution time in the [`SchedulerMetadata`], multiplied with the `multiplier`, and kept between `floor` and `ceiling`.
+/// Until then, the timeout the wrapped executor was created with is used.
+///
+/// With a [`ReturnsTimeouts`] executor, such as the `TimeoutForkserverExecutor`, runs that time out can be re-run once
+/// with a longer [`AdaptiveTimeoutExecutor::hang_timeout`] before they are reported as [`ExitKind::Timeout`],
+/// to filter out false positives on loaded machines.
+/// The in-process executors handle timeouts in their signal handlers, so they only get the adapted timeout.
+#[derive(Debug)]
+pub struct AdaptiveTimeoutExecutor<E> {
+    executor: E,
+    multiplier: f64,
+    floor: Duration,
+    ceiling: Duration,
+    hang_timeout: Option<Duration>,
+    /// The calibration cycles the timeout was last derived from
+    calibrated_cycles: u64,
+}
+
+impl<E> AdaptiveTimeoutExecutor<E>
+where
+    E: HasTimeout,
+{
+    /// Wrap `executor`, using AFL's defaults: five times the average execution time, between 20ms and 1s.
+    /// Runs that time out are reported right away, see [`AdaptiveTimeoutExecutor::hang_timeout`].
+    pub fn new(executor: E) -> Self {
+        Self {
+            executor,
+            multiplier: DEFAULT_TIMEOUT_MULTIPLIER,
+            floor: DEFAULT_TIMEOUT_FLOOR,
+            ceiling: DEFAULT_TIMEOUT_CEILING,
+            hang_timeout: None,
+            calibrated_cycles: 0,
+        }
+    }
+
+    /// The factor the average execution time is multiplied with
+    #[must_use]
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// The lower bound of the timeout
+    #[must_use]
+    pub fn floor(mut self, floor: Duration) -> Self {
+        self.floor = floor;
+        self
+    }
+
+    /// The upper bound of the timeout
+    #[must_use]
+    pub fn ceiling(mut self, ceiling: Duration) -> Self {
+        self.ceiling = ceiling;
+        self
+    }
+
+    /// The timeout currently used
+    pub fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    /// The wrapped executor
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    /// Derive the timeout from the calibration, if new testcases were calibrated since the last update
+    fn update_timeout<S>(&mut self, state: &S)
+    where
+        S: HasMetadata,
+    {
+        let psmeta = match state.metadata().get::<SchedulerMetadata>() {
+            Some(psmeta) => psmeta,
+            None => return,
+        };
+        if psmeta.cycles() == self.calibrated_cycles || psmeta.cycles() == 0 {
+            return;
+        }
+        self.calibrated_cycles = psmeta.cycles();
+
+        let cycles = u32::try_from(psmeta.cycles()).unwrap_or(u32::MAX);
+        let timeout = (psmeta.exec_time() / cycles)
+            .mul_f64(self.multiplier)
+            .clamp(self.floor, self.ceiling);
+        self.executor.set_timeout(timeout);
+    }
+}
+
+impl<E> AdaptiveTimeoutExecutor<E>
+where
+    E: ReturnsTimeouts,
+{
+    /// The timeout to re-run runs that timed out with, or `None` to report them right away, the default.
+    /// Runs are only re-run if this is longer than the current timeout. AFL uses 1s.
+    #[must_use]
+    pub fn hang_timeout(mut self, hang_timeout: Option<Duration>) -> Self {
+        self.hang_timeout = hang_timeout;
+        self
+    }
+}
+
+impl<E, EM, Z> Executor<EM, Z> for AdaptiveTimeoutExecutor<E>
+where
+    E: Executor<EM, Z> + HasObservers + HasTimeout + Debug,
+    E::State: HasMetadata,
+    EM: UsesState<State = E::State>,
+    Z: UsesState<State = E::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        self.update_timeout(state);
+
+        let exit_kind = self.executor.run_target(fuzzer, state, mgr, input)?;
+        match self.hang_timeout {
+            Some(hang_timeout)
+                if exit_kind == ExitKind::Timeout && hang_timeout > self.executor.timeout() =>
+            {
+                // Reset the observers, the fuzzer only looks at the second run
+                self.executor.observers_mut().pre_exec_all(state, input)?;
+
+                let timeout = self.executor.timeout();
+                self.executor.set_timeout(hang_timeout);
+                let exit_kind = self.executor.run_target(fuzzer, state, mgr, input);
+                self.executor.set_timeout(timeout);
+                exit_kind
+            }
+            _ => Ok(exit_kind),
+        }
+    }
+}
+
+impl<E> UsesState for AdaptiveTimeoutExecutor<E>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E> UsesObservers for AdaptiveTimeoutExecutor<E>
+where
+    E: UsesObservers,
+{
+    type Observers = E::Observers;
+}
+
+impl<E> HasObservers for AdaptiveTimeoutExecutor<E>
+where
+    E: HasObservers,
+{
+    #[inline]
+    fn observers(&self) -> &Self::Observers {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut Self::Observers {
+        self.executor.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{marker::PhantomData, time::Duration};
+
+    use super::AdaptiveTimeoutExecutor;
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers, HasTimeout, ReturnsTimeouts},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasBytesVec},
+        observers::UsesObservers,
+        schedulers::{powersched::SchedulerMetadata, QueueScheduler},
+        state::{HasMetadata, StdState, UsesState},
+        Error, StdFuzzer,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// Pretends to run for as many milliseconds as the first byte of the input, times ten
+    #[derive(Debug)]
+    struct SleepExecutor {
+        timeout: Duration,
+        runs: usize,
+        observers: (),
+        phantom: PhantomData<TestState>,
+    }
+
+    impl SleepExecutor {
+        fn new(timeout: Duration) -> Self {
+            Self {
+                timeout,
+                runs: 0,
+                observers: (),
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    impl UsesState for SleepExecutor {
+        type State = TestState;
+    }
+
+    impl UsesObservers for SleepExecutor {
+        type Observers = ();
+    }
+
+    impl HasObservers for SleepExecutor {
+        fn observers(&self) -> &() {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut () {
+            &mut self.observers
+        }
+    }
+
+    impl HasTimeout for SleepExecutor {
+        fn timeout(&self) -> Duration {
+            self.timeout
+        }
+
+        fn set_timeout(&mut self, exec_tmout: Duration) {
+            self.timeout = exec_tmout;
+        }
+    }
+
+    impl ReturnsTimeouts for SleepExecutor {}
+
+    impl<EM, Z> Executor<EM, Z> for SleepExecutor
+    where
+        EM: UsesState<State = TestState>,
+        Z: UsesState<State = TestState>,
+    {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut TestState,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            self.runs += 1;
+            let run_time = Duration::from_millis(u64::from(input.bytes()[0]) * 10);
+            if run_time > self.timeout {
+                Ok(ExitKind::Timeout)
+            } else {
+                Ok(ExitKind::Ok)
+            }
+        }
+    }
+
+    fn test_state() -> TestState {
+        StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_hang_timeout() {
+        let mut state = test_state();
+        let mut fuzzer: StdFuzzer<_, _, _, ()> = StdFuzzer::new(
+            QueueScheduler::new(),
+            ConstFeedback::new(false),
+            ConstFeedback::new(false),
+        );
+        let mut mgr = NopEventManager::<TestState>::new();
+        let input = BytesInput::new(vec![15]);
+
+        // Reported right away by default
+        let mut executor =
+            AdaptiveTimeoutExecutor::new(SleepExecutor::new(Duration::from_millis(100)));
+        assert_eq!(
+            executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap(),
+            ExitKind::Timeout
+        );
+        assert_eq!(executor.inner().runs, 1);
+
+        // Re-run once with the hang timeout, which is restored afterwards
+        let mut executor =
+            AdaptiveTimeoutExecutor::new(SleepExecutor::new(Duration::from_millis(100)))
+                .hang_timeout(Some(Duration::from_millis(200)));
+        assert_eq!(
+            executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap(),
+            ExitKind::Ok
+        );
+        assert_eq!(executor.inner().runs, 2);
+        assert_eq!(executor.timeout(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_adaptive_timeout() {
+        let mut state = test_state();
+        let mut executor = AdaptiveTimeoutExecutor::new(SleepExecutor::new(Duration::from_secs(2)))
+            .hang_timeout(Some(Duration::from_millis(500)));
+        let mut fuzzer: StdFuzzer<_, _, _, ()> = StdFuzzer::new(
+            QueueScheduler::new(),
+            ConstFeedback::new(false),
+            ConstFeedback::new(false),
+        );
+        let mut mgr = NopEventManager::<TestState>::new();
+        let mut run = |executor: &mut AdaptiveTimeoutExecutor<SleepExecutor>,
+                       state: &mut TestState,
+                       run_time: u8| {
+            executor
+                .run_target(
+                    &mut fuzzer,
+                    state,
+                    &mut mgr,
+                    &BytesInput::new(vec![run_time]),
+                )
+                .unwrap()
+        };
+
+        // Not calibrated yet
+        assert_eq!(run(&mut executor, &mut state, 15), ExitKind::Ok);
+        assert_eq!(executor.timeout(), Duration::from_secs(2));
+
+        // 10 runs of 2ms on average
+        let mut psmeta = SchedulerMetadata::new(None);
+        psmeta.set_exec_time(Duration::from_millis(20));
+        psmeta.set_cycles(10);
+        state.add_metadata(psmeta);
+        assert_eq!(run(&mut executor, &mut state, 1), ExitKind::Ok);
+        assert_eq!(executor.timeout(), Duration::from_millis(20));
+
+        // Finishes with the hang timeout
+        assert_eq!(run(&mut executor, &mut state, 15), ExitKind::Ok);
+        assert_eq!(executor.timeout(), Duration::from_millis(20));
+        // Hangs for real
+        assert_eq!(run(&mut executor, &mut state, 60), ExitKind::Timeout);
+
+        // Calibrated a slow testcase, 11 runs of 100ms on average
+        state
+            .metadata_mut()
+            .get_mut::<SchedulerMetadata>()
+            .unwrap()
+            .set_exec_time(Duration::from_millis(1100));
+        state
+            .metadata_mut()
+            .get_mut::<SchedulerMetadata>()
+            .unwrap()
+            .set_cycles(11);
+        assert_eq!(run(&mut executor, &mut state, 1), ExitKind::Ok);
+        assert_eq!(executor.timeout(), Duration::from_millis(500));
+    }
+}
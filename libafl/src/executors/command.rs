@@ -19,7 +19,9 @@ use std::{
 
 use super::HasObservers;
 #[cfg(all(feature = "std", unix))]
-use crate::executors::{Executor, ExitKind};
+use crate::executors::{
+    memlimit::signal_exit_kind, Executor, ExitKind, MemoryLimit, MemoryLimiter,
+};
 use crate::{
     bolts::{
         fs::{InputFile, INPUTFILE_STD},
@@ -84,6 +86,8 @@ pub struct StdCommandConfigurator {
     input_location: InputLocation,
     /// The Command to execute
     command: Command,
+    /// The memory limit of the child
+    memory_limiter: Option<MemoryLimiter>,
 }
 
 impl CommandConfigurator for StdCommandConfigurator {
@@ -129,6 +133,9 @@ impl CommandConfigurator for StdCommandConfigurator {
                 if let Some(cwd) = self.command.get_current_dir() {
                     cmd.current_dir(cwd);
                 }
+                if let Some(limiter) = &self.memory_limiter {
+                    limiter.apply(&mut cmd);
+                }
                 Ok(cmd.spawn()?)
             }
             InputLocation::StdIn => {
@@ -146,6 +153,10 @@ impl CommandConfigurator for StdCommandConfigurator {
             }
         }
     }
+
+    fn memory_limiter(&mut self) -> Option<&mut MemoryLimiter> {
+        self.memory_limiter.as_mut()
+    }
 }
 
 /// A `CommandExecutor` is a wrapper around [`std::process::Command`] to execute a target as a child process.
@@ -244,6 +255,7 @@ where
                 debug_child,
                 has_stdout_observer,
                 has_stderr_observer,
+                memory_limiter: None,
             },
             phantom: PhantomData,
         })
@@ -320,9 +332,7 @@ where
             .expect("waiting on child failed")
             .map(|status| status.signal())
         {
-            // for reference: https://www.man7.org/linux/man-pages/man7/signal.7.html
-            Some(Some(9)) => Ok(ExitKind::Oom),
-            Some(Some(_)) => Ok(ExitKind::Crash),
+            Some(Some(signal)) => signal_exit_kind(signal, self.configurer.memory_limiter()),
             Some(None) => Ok(ExitKind::Ok),
             None => {
                 // if this fails, there is not much we can do. let's hope it failed because the process finished
@@ -396,6 +406,7 @@ pub struct CommandExecutorBuilder {
     input_location: InputLocation,
     cwd: Option<PathBuf>,
     envs: Vec<(OsString, OsString)>,
+    memory_limit: Option<MemoryLimit>,
 }
 
 impl Default for CommandExecutorBuilder {
@@ -415,6 +426,7 @@ impl CommandExecutorBuilder {
             cwd: None,
             envs: vec![],
             debug_child: false,
+            memory_limit: None,
         }
     }
 
@@ -526,6 +538,14 @@ impl CommandExecutorBuilder {
         self
     }
 
+    /// Limit the memory of the child process.
+    /// Children killed by the kernel because they exceed a [`MemoryLimit::Cgroup`] are reported as [`ExitKind::Oom`].
+    /// Defaults to no limit.
+    pub fn memory_limit(&mut self, memory_limit: MemoryLimit) -> &mut CommandExecutorBuilder {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
     /// Builds the `ComandExecutor`
     pub fn build<EM, OT, S, Z>(
         &self,
@@ -560,6 +580,14 @@ impl CommandExecutorBuilder {
         if let Some(cwd) = &self.cwd {
             command.current_dir(cwd);
         }
+        let memory_limiter = self
+            .memory_limit
+            .as_ref()
+            .map(MemoryLimit::setup)
+            .transpose()?;
+        if let Some(limiter) = &memory_limiter {
+            limiter.apply(&mut command);
+        }
         if !self.debug_child {
             command.stdout(Stdio::null());
             command.stderr(Stdio::null());
@@ -578,6 +606,7 @@ impl CommandExecutorBuilder {
             has_stderr_observer: observers.observes_stderr(),
             input_location: self.input_location.clone(),
             command,
+            memory_limiter,
         };
         Ok(configurator.into_executor::<EM, OT, S, Z>(observers))
     }
@@ -628,6 +657,12 @@ pub trait CommandConfigurator: Sized + Debug {
     where
         I: Input + HasTargetBytes;
 
+    /// The memory limit the children are spawned with, if any.
+    /// Used to tell if a child killed with `SIGKILL` ran out of memory.
+    fn memory_limiter(&mut self) -> Option<&mut MemoryLimiter> {
+        None
+    }
+
     /// Create an `Executor` from this `CommandConfigurator`.
     fn into_executor<EM, OT, S, Z>(self, observers: OT) -> CommandExecutor<EM, OT, S, Self, Z>
     where
@@ -647,7 +682,7 @@ mod tests {
         events::SimpleEventManager,
         executors::{
             command::{CommandExecutor, InputLocation},
-            Executor,
+            Executor, ExitKind, MemoryLimit,
         },
         inputs::BytesInput,
         monitors::SimpleMonitor,
@@ -679,6 +714,37 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_memory_limit() {
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|status| {
+            println!("{status}");
+        }));
+
+        let mut executor = CommandExecutor::builder();
+        executor
+            .program("sh")
+            .arg("-c")
+            .arg_input_arg()
+            .memory_limit(MemoryLimit::Rlimit(512));
+        let mut executor = executor.build(()).unwrap();
+
+        let mut run = |script: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut NopState::new(),
+                    &mut mgr,
+                    &BytesInput::new(script.to_vec()),
+                )
+                .unwrap()
+        };
+        assert_eq!(run(b"exit 0"), ExitKind::Ok);
+        // Only a cgroup can tell an OOM kill from any other `SIGKILL`
+        assert_eq!(run(b"kill -9 $$"), ExitKind::Crash);
+        assert_eq!(run(b"kill -11 $$"), ExitKind::Crash);
+    }
+
     #[test]
     #[cfg(unix)]
     fn test_parse_afl_cmdline() {
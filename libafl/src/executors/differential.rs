@@ -161,6 +161,18 @@ where
         self.primary.as_mut().observe_stderr(stderr);
         self.secondary.as_mut().observe_stderr(stderr);
     }
+
+    /// Returns true if a response observer was added to the list
+    #[inline]
+    fn observes_responses(&self) -> bool {
+        self.primary.as_ref().observes_responses() || self.secondary.as_ref().observes_responses()
+    }
+
+    /// Runs `observe_response` for all response observers in the list
+    fn observe_response(&mut self, response: &[u8]) {
+        self.primary.as_mut().observe_response(response);
+        self.secondary.as_mut().observe_response(response);
+    }
 }
 
 impl<A, B> MatchName for ProxyObserversTuple<A, B>
@@ -28,13 +28,17 @@ use crate::{
         fs::{InputFile, INPUTFILE_STD},
         os::{dup2, pipes::Pipe},
         shmem::{ShMem, ShMemProvider, StdShMemProvider},
-        AsMutSlice, AsSlice,
+        AsMutSlice, AsSlice, Truncate,
+    },
+    executors::{
+        memlimit::{set_rlimit, signal_exit_kind},
+        Executor, ExitKind, HasObservers, HasTimeout, MemoryLimit, MemoryLimiter, ReturnsTimeouts,
     },
-    executors::{Executor, ExitKind, HasObservers},
     inputs::{HasTargetBytes, Input, UsesInput},
     mutators::Tokens,
     observers::{
-        get_asan_runtime_flags_with_log_path, AsanBacktraceObserver, ObserversTuple, UsesObservers,
+        get_asan_runtime_flags_with_log_path, AsanBacktraceObserver, MapObserver, Observer,
+        ObserversTuple, UsesObservers,
     },
     state::UsesState,
     Error,
@@ -47,15 +51,54 @@ const FS_OPT_ENABLED: i32 = 0x80000001_u32 as i32;
 const FS_OPT_SHDMEM_FUZZ: i32 = 0x01000000_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_OPT_AUTODICT: i32 = 0x10000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_MAPSIZE: i32 = 0x40000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_NEWCMPLOG: i32 = 0x02000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_ERROR: i32 = 0xf800008f_u32 as i32;
+const FS_ERROR_MAP_SIZE: i32 = 1;
+const FS_ERROR_MAP_ADDR: i32 = 2;
+const FS_ERROR_SHM_OPEN: i32 = 4;
+const FS_ERROR_SHMAT: i32 = 8;
+const FS_ERROR_MMAP: i32 = 16;
+const FS_ERROR_OLD_CMPLOG: i32 = 32;
+const FS_ERROR_OLD_CMPLOG_QEMU: i32 = 64;
+/// The largest coverage map size a target can report in the forkserver handshake
+pub const FS_OPT_MAX_MAPSIZE: usize = (0x00fffffe >> 1) + 1;
 /// The length of header bytes which tells shmem size
 const SHMEM_FUZZ_HDR_SIZE: usize = 4;
 const MAX_FILE: usize = 1024 * 1024;
 
+/// The map size a target reports in the handshake, rounded up to a multiple of 64 like AFL++ does
+#[allow(clippy::cast_sign_loss)]
+fn fs_opt_get_mapsize(status: i32) -> usize {
+    let map_size = (((status as u32 & 0x00fffffe) >> 1) + 1) as usize;
+    (map_size + 63) & !63
+}
+
+/// The error a target reports instead of the handshake, e.g. if it can't map the shared memory
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+fn fs_opt_get_error(status: i32) -> Error {
+    let error = (status as u32 & 0x00ffff00) >> 8;
+    let msg = match error as i32 {
+        FS_ERROR_MAP_SIZE => "the coverage map of the target is larger than the shared map, set AFL_MAP_SIZE to a larger value",
+        FS_ERROR_MAP_ADDR => "the target could not map the shared map at the fixed address it was compiled with",
+        FS_ERROR_SHM_OPEN => "shm_open() failed in the target",
+        FS_ERROR_SHMAT => "shmat() failed in the target",
+        FS_ERROR_MMAP => "mmap() failed in the target",
+        FS_ERROR_OLD_CMPLOG => "the target was compiled with an outdated cmplog, recompile it",
+        FS_ERROR_OLD_CMPLOG_QEMU => "the target uses an outdated cmplog in QEMU mode, update qemuafl",
+        _ => "unknown error",
+    };
+    Error::illegal_state(format!("The forkserver reported error {error}: {msg}"))
+}
+
 /// Configure the target, `limit`, `setsid`, `pipe_stdin`, the code was borrowed from the [`Angora`](https://github.com/AngoraFuzzer/Angora) fuzzer
 pub trait ConfigTarget {
     /// Sets the sid
     fn setsid(&mut self) -> &mut Self;
-    /// Sets a mem limit
+    /// Sets a mem limit, in MiB
     fn setlimit(&mut self, memlimit: u64) -> &mut Self;
     /// Sets the stdin
     fn setstdin(&mut self, fd: RawFd, use_stdin: bool) -> &mut Self;
@@ -129,36 +172,9 @@ impl ConfigTarget for Command {
         }
     }
 
-    #[allow(trivial_numeric_casts, clippy::cast_possible_wrap)]
     fn setlimit(&mut self, memlimit: u64) -> &mut Self {
-        if memlimit == 0 {
-            return self;
-        }
-        let func = move || {
-            let memlimit: libc::rlim_t = (memlimit as libc::rlim_t) << 20;
-            let r = libc::rlimit {
-                rlim_cur: memlimit,
-                rlim_max: memlimit,
-            };
-            let r0 = libc::rlimit {
-                rlim_cur: 0,
-                rlim_max: 0,
-            };
-
-            #[cfg(target_os = "openbsd")]
-            let mut ret = unsafe { libc::setrlimit(libc::RLIMIT_RSS, &r) };
-            #[cfg(not(target_os = "openbsd"))]
-            let mut ret = unsafe { libc::setrlimit(libc::RLIMIT_AS, &r) };
-            if ret < 0 {
-                return Err(io::Error::last_os_error());
-            }
-            ret = unsafe { libc::setrlimit(libc::RLIMIT_CORE, &r0) };
-            if ret < 0 {
-                return Err(io::Error::last_os_error());
-            }
-            Ok(())
-        };
-        unsafe { self.pre_exec(func) }
+        set_rlimit(self, memlimit);
+        self
     }
 }
 
@@ -171,6 +187,8 @@ pub struct Forkserver {
     child_pid: Pid,
     status: i32,
     last_run_timed_out: i32,
+    /// The memory limit of the forkserver and its children
+    memory_limiter: Option<MemoryLimiter>,
 }
 
 #[allow(clippy::fn_params_excessive_bools)]
@@ -183,7 +201,7 @@ impl Forkserver {
         envs: Vec<(OsString, OsString)>,
         input_filefd: RawFd,
         use_stdin: bool,
-        memlimit: u64,
+        memory_limit: Option<&MemoryLimit>,
         is_persistent: bool,
         is_deferred_frksrv: bool,
         debug_output: bool,
@@ -215,11 +233,15 @@ impl Forkserver {
             command.env("__AFL_DEFER_FORKSRV", "1");
         }
 
+        let memory_limiter = memory_limit.map(MemoryLimit::setup).transpose()?;
+        if let Some(limiter) = &memory_limiter {
+            limiter.apply(&mut command);
+        }
+
         match command
             .env("LD_BIND_NOW", "1")
             .env("ASAN_OPTIONS", get_asan_runtime_flags_with_log_path())
             .envs(envs)
-            .setlimit(memlimit)
             .setsid()
             .setstdin(input_filefd, use_stdin)
             .setpipe(
@@ -249,6 +271,7 @@ impl Forkserver {
             child_pid: Pid::from_raw(0),
             status: 0,
             last_run_timed_out: 0,
+            memory_limiter,
         })
     }
 
@@ -274,6 +297,16 @@ impl Forkserver {
         self.status = status;
     }
 
+    /// The [`ExitKind`] of the last run, derived from its status.
+    /// Children killed by the kernel because they ran out of memory are reported as [`ExitKind::Oom`].
+    pub fn last_run_exit_kind(&mut self) -> Result<ExitKind, Error> {
+        if libc::WIFSIGNALED(self.status) {
+            signal_exit_kind(libc::WTERMSIG(self.status), self.memory_limiter.as_mut())
+        } else {
+            Ok(ExitKind::Ok)
+        }
+    }
+
     /// The child pid
     #[must_use]
     pub fn child_pid(&self) -> Pid {
@@ -398,6 +431,19 @@ impl<E> TimeoutForkserverExecutor<E> {
     }
 }
 
+impl<E> HasTimeout for TimeoutForkserverExecutor<E> {
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.num_milliseconds() as u64)
+    }
+
+    fn set_timeout(&mut self, exec_tmout: Duration) {
+        self.timeout = TimeSpec::milliseconds(exec_tmout.as_millis() as i64);
+    }
+}
+
+impl<E> ReturnsTimeouts for TimeoutForkserverExecutor<E> {}
+
 impl<E, EM, Z> Executor<EM, Z> for TimeoutForkserverExecutor<E>
 where
     E: Executor<EM, Z> + HasForkserver + Debug,
@@ -413,8 +459,6 @@ where
         _mgr: &mut EM,
         input: &Self::Input,
     ) -> Result<ExitKind, Error> {
-        let mut exit_kind = ExitKind::Ok;
-
         let last_run_timed_out = self.executor.forkserver().last_run_timed_out();
 
         match &mut self.executor.shmem_mut() {
@@ -464,15 +508,13 @@ where
             .forkserver_mut()
             .set_child_pid(Pid::from_raw(pid));
 
-        if let Some(status) = self
+        let exit_kind = if let Some(status) = self
             .executor
             .forkserver_mut()
             .read_st_timed(&self.timeout)?
         {
             self.executor.forkserver_mut().set_status(status);
-            if libc::WIFSIGNALED(self.executor.forkserver().status()) {
-                exit_kind = ExitKind::Crash;
-            }
+            self.executor.forkserver_mut().last_run_exit_kind()?
         } else {
             self.executor.forkserver_mut().set_last_run_timed_out(1);
 
@@ -482,8 +524,8 @@ where
             if recv_status_len != 4 {
                 return Err(Error::unknown("Could not kill timed-out child".to_string()));
             }
-            exit_kind = ExitKind::Timeout;
-        }
+            ExitKind::Timeout
+        };
 
         self.executor
             .forkserver_mut()
@@ -506,6 +548,8 @@ where
     forkserver: Forkserver,
     observers: OT,
     map: Option<SP::ShMem>,
+    /// The size of the coverage map the target reported in the handshake, if any
+    map_size: Option<usize>,
     phantom: PhantomData<S>,
     /// Cache that indicates if we have a `ASan` observer registered.
     has_asan_observer: Option<bool>,
@@ -524,6 +568,7 @@ where
             .field("forkserver", &self.forkserver)
             .field("observers", &self.observers)
             .field("map", &self.map)
+            .field("map_size", &self.map_size)
             .finish()
     }
 }
@@ -561,6 +606,12 @@ where
     pub fn input_file(&self) -> &InputFile {
         &self.input_file
     }
+
+    /// The size of the coverage map the target reported in the forkserver handshake, if any
+    #[must_use]
+    pub fn coverage_map_size(&self) -> Option<usize> {
+        self.map_size
+    }
 }
 
 /// The builder for `ForkserverExecutor`
@@ -577,17 +628,88 @@ pub struct ForkserverExecutorBuilder<'a, SP> {
     autotokens: Option<&'a mut Tokens>,
     input_filename: Option<OsString>,
     shmem_provider: Option<&'a mut SP>,
+    map_size: Option<usize>,
+    is_cmplog: bool,
+    memory_limit: Option<MemoryLimit>,
 }
 
 impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
     /// Builds `ForkserverExecutor`.
-    #[allow(clippy::pedantic)]
     pub fn build<OT, S>(&mut self, observers: OT) -> Result<ForkserverExecutor<OT, S, SP>, Error>
     where
         OT: ObserversTuple<S>,
         S: UsesInput,
         S::Input: Input + HasTargetBytes,
         SP: ShMemProvider,
+    {
+        let (target, forkserver, input_file, map) = self.build_helper()?;
+
+        Ok(ForkserverExecutor {
+            target,
+            args: self.arguments.clone(),
+            input_file,
+            forkserver,
+            observers,
+            map,
+            map_size: self.map_size,
+            phantom: PhantomData,
+            has_asan_observer: None, // initialized on first use
+        })
+    }
+
+    /// Builds `ForkserverExecutor`, sizing the coverage map to the target.
+    /// The `map_observer` has to observe a shared map of the largest size the target may use,
+    /// which is told to the target in the `AFL_MAP_SIZE` env var. After the handshake,
+    /// the observer is truncated to the size of the coverage map the target reports, as AFL++ does for `afl-clang-lto` binaries.
+    pub fn build_dynamic_map<MO, OT, S>(
+        &mut self,
+        mut map_observer: MO,
+        other_observers: OT,
+    ) -> Result<ForkserverExecutor<(MO, OT), S, SP>, Error>
+    where
+        MO: Observer<S> + MapObserver + Truncate,
+        OT: ObserversTuple<S>,
+        S: UsesInput,
+        S::Input: Input + HasTargetBytes,
+        SP: ShMemProvider,
+    {
+        if !self.envs.iter().any(|(key, _)| key == "AFL_MAP_SIZE") {
+            self.envs
+                .push(("AFL_MAP_SIZE".into(), map_observer.len().to_string().into()));
+        }
+
+        let (target, forkserver, input_file, map) = self.build_helper()?;
+
+        if let Some(map_size) = self.map_size {
+            if map_size > map_observer.len() {
+                return Err(Error::illegal_argument(format!(
+                    "The coverage map of the target has {map_size} entries, more than the {} of the map observer",
+                    map_observer.len()
+                )));
+            }
+            map_observer.truncate(map_size);
+        }
+
+        Ok(ForkserverExecutor {
+            target,
+            args: self.arguments.clone(),
+            input_file,
+            forkserver,
+            observers: (map_observer, other_observers),
+            map,
+            map_size: self.map_size,
+            phantom: PhantomData,
+            has_asan_observer: None, // initialized on first use
+        })
+    }
+
+    /// Spawn the forkserver and do the handshake
+    #[allow(clippy::pedantic, clippy::type_complexity)]
+    fn build_helper(
+        &mut self,
+    ) -> Result<(OsString, Forkserver, InputFile, Option<SP::ShMem>), Error>
+    where
+        SP: ShMemProvider,
     {
         let input_filename = match &self.input_filename {
             Some(name) => name.clone(),
@@ -617,7 +739,7 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                     self.envs.clone(),
                     input_file.as_raw_fd(),
                     self.use_stdin,
-                    0,
+                    self.memory_limit.as_ref(),
                     self.is_persistent,
                     self.is_deferred_frksrv,
                     self.debug_child,
@@ -637,7 +759,25 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         if rlen != 4 {
             return Err(Error::unknown("Failed to start a forkserver".to_string()));
         }
+        if status & FS_OPT_ERROR == FS_OPT_ERROR {
+            return Err(fs_opt_get_error(status));
+        }
         println!("All right - fork server is up.");
+
+        if status & FS_OPT_ENABLED == FS_OPT_ENABLED && status & FS_OPT_MAPSIZE == FS_OPT_MAPSIZE {
+            let map_size = fs_opt_get_mapsize(status);
+            println!("Target map size: {map_size}");
+            self.map_size = Some(map_size);
+        }
+
+        if self.is_cmplog
+            && status & (FS_OPT_ENABLED | FS_OPT_NEWCMPLOG) != (FS_OPT_ENABLED | FS_OPT_NEWCMPLOG)
+        {
+            return Err(Error::illegal_state(
+                "The cmplog target was compiled with an outdated AFL++ cmplog, recompile it"
+                    .to_string(),
+            ));
+        }
         // If forkserver is responding, we then check if there's any option enabled.
         // We'll send 4-bytes message back to the forkserver to tell which features to use
         // The forkserver is listening to our response if either shmem fuzzing is enabled or auto dict is enabled
@@ -700,16 +840,7 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             self.use_stdin
         );
 
-        Ok(ForkserverExecutor {
-            target,
-            args: self.arguments.clone(),
-            input_file,
-            forkserver,
-            observers,
-            map,
-            phantom: PhantomData,
-            has_asan_observer: None, // initialized on first use
-        })
+        Ok((target, forkserver, input_file, map))
     }
 
     /// Use autodict?
@@ -770,6 +901,9 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
             autotokens: None,
             input_filename: None,
             shmem_provider: None,
+            map_size: None,
+            is_cmplog: false,
+            memory_limit: None,
         }
     }
 
@@ -871,6 +1005,27 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
         self
     }
 
+    #[must_use]
+    /// Limit the memory of the forkserver and the children it forks; default is no limit.
+    /// Children killed by the kernel for exceeding a [`MemoryLimit::Cgroup`] are reported as [`ExitKind::Oom`].
+    pub fn memory_limit(mut self, memory_limit: MemoryLimit) -> Self {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
+    #[must_use]
+    /// Run an AFL++ cmplog binary, the companion of the coverage binary built with `AFL_LLVM_CMPLOG=1`.
+    /// It logs its compares to the given shared memory, which an
+    /// [`crate::observers::AFLppCmpLogObserver`] reads using [`crate::observers::AFLppCmpMap::from_shmem`].
+    /// Use the resulting executor in a [`crate::stages::TracingStage`].
+    pub fn cmplog_shmem<SHM>(mut self, shmem: &SHM) -> Self
+    where
+        SHM: ShMem,
+    {
+        self.is_cmplog = true;
+        self.env("__AFL_CMPLOG_SHM_ID", shmem.id().to_string())
+    }
+
     /// Shmem provider for forkserver's shared memory testcase feature.
     pub fn shmem_provider<SP: ShMemProvider>(
         self,
@@ -887,6 +1042,9 @@ impl<'a> ForkserverExecutorBuilder<'a, StdShMemProvider> {
             autotokens: self.autotokens,
             input_filename: self.input_filename,
             shmem_provider: Some(shmem_provider),
+            map_size: self.map_size,
+            is_cmplog: self.is_cmplog,
+            memory_limit: self.memory_limit,
         }
     }
 }
@@ -914,8 +1072,6 @@ where
         _mgr: &mut EM,
         input: &Self::Input,
     ) -> Result<ExitKind, Error> {
-        let mut exit_kind = ExitKind::Ok;
-
         // Write to testcase
         match &mut self.map {
             Some(map) => {
@@ -966,8 +1122,8 @@ where
 
         self.forkserver.set_status(status);
 
-        if libc::WIFSIGNALED(self.forkserver.status()) {
-            exit_kind = ExitKind::Crash;
+        let exit_kind = self.forkserver.last_run_exit_kind()?;
+        if exit_kind == ExitKind::Crash {
             if self.has_asan_observer.is_none() {
                 self.has_asan_observer = Some(
                     self.observers()
@@ -1102,10 +1258,12 @@ mod tests {
         bolts::{
             shmem::{ShMem, ShMemProvider, StdShMemProvider},
             tuples::tuple_list,
-            AsMutSlice,
+            AsMutSlice, HasLen, Truncate,
+        },
+        executors::forkserver::{
+            fs_opt_get_mapsize, ForkserverExecutorBuilder, FS_OPT_ENABLED, FS_OPT_MAPSIZE,
         },
-        executors::forkserver::ForkserverExecutorBuilder,
-        observers::{ConstMapObserver, HitcountsMapObserver},
+        observers::{ConstMapObserver, HitcountsMapObserver, MapObserver, StdMapObserver},
         Error,
     };
 
@@ -1144,4 +1302,20 @@ mod tests {
         };
         assert!(result);
     }
+
+    #[test]
+    fn test_forkserver_map_size() {
+        // What `FS_OPT_SET_MAPSIZE` of the target sends for a map of 3000 entries
+        let status = FS_OPT_ENABLED | FS_OPT_MAPSIZE | ((3000 - 1) << 1);
+        let map_size = fs_opt_get_mapsize(status);
+        assert_eq!(map_size, 3008);
+
+        let mut map = vec![0_u8; 65536];
+        let mut observer = HitcountsMapObserver::new(StdMapObserver::new("map", &mut map));
+        observer.truncate(map_size);
+        assert_eq!(observer.len(), 3008);
+        assert_eq!(observer.usable_count(), 3008);
+        observer.truncate(65536);
+        assert_eq!(observer.len(), 3008);
+    }
 }
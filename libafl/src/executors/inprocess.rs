@@ -645,10 +645,18 @@ mod unix_signal_handler {
                         .objective_mut()
                         .append_metadata(state, &mut new_testcase)
                         .expect("Failed adding metadata");
-                    state
+                    let idx = state
                         .solutions_mut()
                         .add(new_testcase)
                         .expect("In timeout handler solutions failure.");
+                    fuzzer
+                        .objective_mut()
+                        .testcase_added(
+                            state,
+                            idx,
+                            &mut state.solutions().get(idx).unwrap().borrow_mut(),
+                        )
+                        .expect("Failed adding metadata");
                     event_mgr
                         .fire(
                             state,
@@ -724,10 +732,18 @@ mod unix_signal_handler {
                 .objective_mut()
                 .append_metadata(state, &mut new_testcase)
                 .expect("Failed adding metadata");
-            state
+            let idx = state
                 .solutions_mut()
                 .add(new_testcase)
                 .expect("In timeout handler solutions failure.");
+            fuzzer
+                .objective_mut()
+                .testcase_added(
+                    state,
+                    idx,
+                    &mut state.solutions().get(idx).unwrap().borrow_mut(),
+                )
+                .expect("Failed adding metadata");
             event_mgr
                 .fire(
                     state,
@@ -784,6 +800,14 @@ mod unix_signal_handler {
 
             let input = data.take_current_input::<<E::State as UsesInput>::Input>();
 
+            // Let the observers collect the backtrace from the faulting frame
+            #[cfg(feature = "std")]
+            crate::observers::set_crash_context(crate::observers::CrashContext {
+                signal: signal as i32,
+                fault_address: crate::bolts::minibsod::fault_address(&_info) as u64,
+                pc: crate::bolts::minibsod::crash_pc(_context).map(|pc| pc as u64),
+            });
+
             observers
                 .post_exec_all(state, input, &ExitKind::Crash)
                 .expect("Observers post_exec_all failed");
@@ -813,10 +837,18 @@ mod unix_signal_handler {
                     .objective_mut()
                     .append_metadata(state, &mut new_testcase)
                     .expect("Failed adding metadata");
-                state
+                let idx = state
                     .solutions_mut()
                     .add(new_testcase)
                     .expect("In crash handler solutions failure.");
+                fuzzer
+                    .objective_mut()
+                    .testcase_added(
+                        state,
+                        idx,
+                        &mut state.solutions().get(idx).unwrap().borrow_mut(),
+                    )
+                    .expect("Failed adding metadata");
                 event_mgr
                     .fire(
                         state,
@@ -998,10 +1030,18 @@ mod windows_exception_handler {
                         .objective_mut()
                         .append_metadata(state, &mut new_testcase)
                         .expect("Failed adding metadata");
-                    state
+                    let idx = state
                         .solutions_mut()
                         .add(new_testcase)
                         .expect("In timeout handler solutions failure.");
+                    fuzzer
+                        .objective_mut()
+                        .testcase_added(
+                            state,
+                            idx,
+                            &mut state.solutions().get(idx).unwrap().borrow_mut(),
+                        )
+                        .expect("Failed adding metadata");
                     event_mgr
                         .fire(
                             state,
@@ -1088,10 +1128,18 @@ mod windows_exception_handler {
                         .objective_mut()
                         .append_metadata(state, &mut new_testcase)
                         .expect("Failed adding metadata");
-                    state
+                    let idx = state
                         .solutions_mut()
                         .add(new_testcase)
                         .expect("In timeout handler solutions failure.");
+                    fuzzer
+                        .objective_mut()
+                        .testcase_added(
+                            state,
+                            idx,
+                            &mut state.solutions().get(idx).unwrap().borrow_mut(),
+                        )
+                        .expect("Failed adding metadata");
                     event_mgr
                         .fire(
                             state,
@@ -1237,10 +1285,18 @@ mod windows_exception_handler {
                     .objective_mut()
                     .append_metadata(state, &mut new_testcase)
                     .expect("Failed adding metadata");
-                state
+                let idx = state
                     .solutions_mut()
                     .add(new_testcase)
                     .expect("In crash handler solutions failure.");
+                fuzzer
+                    .objective_mut()
+                    .testcase_added(
+                        state,
+                        idx,
+                        &mut state.solutions().get(idx).unwrap().borrow_mut(),
+                    )
+                    .expect("Failed adding metadata");
                 event_mgr
                     .fire(
                         state,
@@ -1908,6 +1964,11 @@ pub mod child_signal_handlers {
             let observers = executor.observers_mut();
             let state = data.state_mut::<E::State>();
             let input = data.take_current_input::<<E::State as UsesInput>::Input>();
+            crate::observers::set_crash_context(crate::observers::CrashContext {
+                signal: _signal as i32,
+                fault_address: crate::bolts::minibsod::fault_address(&_info) as u64,
+                pc: crate::bolts::minibsod::crash_pc(_context).map(|pc| pc as u64),
+            });
             observers
                 .post_exec_child_all(state, input, &ExitKind::Crash)
                 .expect("Failed to run post_exec on observers");
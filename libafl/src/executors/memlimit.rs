@@ -0,0 +1,273 @@
+//! Memory limits for the child processes of out-of-process executors, such as the
+//! [`crate::executors::CommandExecutor`] and the `ForkserverExecutor`.
+//!
+//! A limit is either enforced with an rlimit, or by running the children in a child group of a cgroup v2 hierarchy.
+//! An rlimit makes allocations beyond the limit fail, and the target handles that however it wants to, usually by aborting.
+//! A cgroup lets the kernel kill the target with `SIGKILL` once it uses more memory than allowed,
+//! which the executors can reliably report as [`ExitKind::Oom`].
+
+use alloc::string::ToString;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    os::unix::{io::AsRawFd, process::CommandExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{executors::ExitKind, Error};
+
+/// Counts the cgroups created by this process, to give them unique names
+static CGROUP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A memory limit for the child processes of an executor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryLimit {
+    /// Limit the address space of each child to this many MiB, with `RLIMIT_AS` (`RLIMIT_RSS` on `OpenBSD`)
+    Rlimit(u64),
+    /// Limit the memory of the children to `limit_mb` MiB, in a new child group of the cgroup v2 group `parent`.
+    /// The `parent` has to be writable, and needs the `memory` controller enabled for its children
+    /// in its `cgroup.subtree_control`. Linux only.
+    Cgroup {
+        /// The cgroup to create the child group in, e.g. `/sys/fs/cgroup/fuzzing`
+        parent: PathBuf,
+        /// The limit, in MiB
+        limit_mb: u64,
+    },
+}
+
+impl MemoryLimit {
+    /// Set up this limit, creating the child cgroup for [`MemoryLimit::Cgroup`]
+    pub fn setup(&self) -> Result<MemoryLimiter, Error> {
+        match self {
+            MemoryLimit::Rlimit(limit_mb) => Ok(MemoryLimiter::Rlimit(*limit_mb)),
+            MemoryLimit::Cgroup { parent, limit_mb } => {
+                Ok(MemoryLimiter::Cgroup(MemoryCgroup::new(parent, *limit_mb)?))
+            }
+        }
+    }
+}
+
+/// A [`MemoryLimit`] set up for the child processes of an executor
+#[derive(Debug)]
+pub enum MemoryLimiter {
+    /// Limit the address space of each child to this many MiB
+    Rlimit(u64),
+    /// Run the children in this cgroup
+    Cgroup(MemoryCgroup),
+}
+
+impl MemoryLimiter {
+    /// Apply this limit to the processes spawned from `command`
+    pub fn apply(&self, command: &mut Command) {
+        match self {
+            MemoryLimiter::Rlimit(limit_mb) => set_rlimit(command, *limit_mb),
+            MemoryLimiter::Cgroup(cgroup) => cgroup.add_on_spawn(command),
+        }
+    }
+
+    /// The [`ExitKind`] of a child with this limit that was terminated by `signal`
+    fn signal_exit_kind(&mut self, signal: i32) -> Result<ExitKind, Error> {
+        match self {
+            MemoryLimiter::Cgroup(cgroup) => {
+                // Always check, so that an OOM kill is not attributed to a later run
+                let oom_killed = cgroup.check_oom_kill()?;
+                Ok(if oom_killed && signal == libc::SIGKILL {
+                    ExitKind::Oom
+                } else {
+                    ExitKind::Crash
+                })
+            }
+            // Allocations beyond an rlimit fail, there is no way to tell what the target made of that
+            MemoryLimiter::Rlimit(_) => Ok(ExitKind::Crash),
+        }
+    }
+}
+
+/// The [`ExitKind`] of a child that was terminated by `signal`.
+/// It is only reported as [`ExitKind::Oom`] if the child ran in a [`MemoryCgroup`] that counted an OOM kill,
+/// as a `SIGKILL` alone could have been sent by anyone.
+pub fn signal_exit_kind(
+    signal: i32,
+    limiter: Option<&mut MemoryLimiter>,
+) -> Result<ExitKind, Error> {
+    match limiter {
+        Some(limiter) => limiter.signal_exit_kind(signal),
+        None => Ok(ExitKind::Crash),
+    }
+}
+
+/// Limit the address space of the processes spawned from `command` to `limit_mb` MiB, and disable core dumps.
+/// A limit of `0` disables the limit.
+#[allow(trivial_numeric_casts, clippy::cast_possible_wrap)]
+pub fn set_rlimit(command: &mut Command, limit_mb: u64) {
+    if limit_mb == 0 {
+        return;
+    }
+    let func = move || {
+        let memlimit: libc::rlim_t = (limit_mb as libc::rlim_t) << 20;
+        let r = libc::rlimit {
+            rlim_cur: memlimit,
+            rlim_max: memlimit,
+        };
+        let r0 = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+
+        #[cfg(target_os = "openbsd")]
+        let mut ret = unsafe { libc::setrlimit(libc::RLIMIT_RSS, &r) };
+        #[cfg(not(target_os = "openbsd"))]
+        let mut ret = unsafe { libc::setrlimit(libc::RLIMIT_AS, &r) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        ret = unsafe { libc::setrlimit(libc::RLIMIT_CORE, &r0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    };
+    unsafe {
+        command.pre_exec(func);
+    }
+}
+
+/// A child group of a cgroup v2 hierarchy with a memory limit, removed again when dropped.
+/// Processes spawned in it, and all their children, share the limit.
+#[derive(Debug)]
+pub struct MemoryCgroup {
+    path: PathBuf,
+    /// The `cgroup.procs` file, kept open to add the spawned processes
+    procs: File,
+    /// The OOM kills counted so far
+    oom_kills: u64,
+}
+
+impl MemoryCgroup {
+    /// Create a new child group in the cgroup `parent`, limited to `limit_mb` MiB of memory without swap
+    pub fn new<P>(parent: P, limit_mb: u64) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = parent.as_ref().join(format!(
+            "libafl-{}-{}",
+            std::process::id(),
+            CGROUP_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path).map_err(|e| {
+            Error::illegal_argument(format!("Could not create cgroup {}: {e}", path.display()))
+        })?;
+        let procs = match OpenOptions::new()
+            .write(true)
+            .open(path.join("cgroup.procs"))
+        {
+            Ok(procs) => procs,
+            Err(e) => {
+                drop(fs::remove_dir(&path));
+                return Err(e.into());
+            }
+        };
+        let mut cgroup = Self {
+            path,
+            procs,
+            oom_kills: 0,
+        };
+
+        fs::write(cgroup.path.join("memory.max"), (limit_mb << 20).to_string()).map_err(|e| {
+            Error::illegal_argument(format!(
+                "Could not set the memory limit of cgroup {}, is the memory controller enabled in {}? {e}",
+                cgroup.path.display(),
+                parent.as_ref().join("cgroup.subtree_control").display()
+            ))
+        })?;
+        // Without swap accounting, the file does not exist, and there is nothing to disable
+        drop(fs::write(cgroup.path.join("memory.swap.max"), "0"));
+
+        cgroup.oom_kills = cgroup.oom_kills()?;
+        Ok(cgroup)
+    }
+
+    /// The path of this cgroup
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add the processes spawned from `command` to this cgroup, before they execute the target
+    pub fn add_on_spawn(&self, command: &mut Command) {
+        let procs = self.procs.as_raw_fd();
+        let func = move || {
+            // Writing `0` moves the writing process
+            let ret = unsafe { libc::write(procs, b"0".as_ptr().cast(), 1) };
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        };
+        unsafe {
+            command.pre_exec(func);
+        }
+    }
+
+    /// The number of processes the kernel killed in this cgroup because they ran out of memory
+    pub fn oom_kills(&self) -> Result<u64, Error> {
+        Ok(parse_oom_kills(&fs::read_to_string(
+            self.path.join("memory.events"),
+        )?))
+    }
+
+    /// If a process was killed because it ran out of memory since the last call
+    pub fn check_oom_kill(&mut self) -> Result<bool, Error> {
+        let oom_kills = self.oom_kills()?;
+        let killed = oom_kills > self.oom_kills;
+        self.oom_kills = oom_kills;
+        Ok(killed)
+    }
+}
+
+impl Drop for MemoryCgroup {
+    fn drop(&mut self) {
+        // Fails if processes are still running in the cgroup, nothing we can do about that.
+        drop(fs::remove_dir(&self.path));
+    }
+}
+
+/// The `oom_kill` counter of a `memory.events` file
+fn parse_oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_oom_kills, signal_exit_kind, MemoryLimiter};
+    use crate::executors::ExitKind;
+
+    #[test]
+    fn test_parse_oom_kills() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), 2);
+        assert_eq!(parse_oom_kills("low 0\n"), 0);
+    }
+
+    #[test]
+    fn test_signal_exit_kind() {
+        assert_eq!(
+            signal_exit_kind(libc::SIGKILL, None).unwrap(),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            signal_exit_kind(libc::SIGKILL, Some(&mut MemoryLimiter::Rlimit(100))).unwrap(),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            signal_exit_kind(libc::SIGSEGV, Some(&mut MemoryLimiter::Rlimit(100))).unwrap(),
+            ExitKind::Crash
+        );
+    }
+}
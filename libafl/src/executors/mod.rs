@@ -13,13 +13,33 @@ pub use differential::DiffExecutor;
 #[cfg(any(unix, feature = "std"))]
 pub mod timeout;
 #[cfg(any(unix, feature = "std"))]
-pub use timeout::TimeoutExecutor;
+pub use timeout::{HasTimeout, ReturnsTimeouts, TimeoutExecutor};
+
+#[cfg(any(unix, feature = "std"))]
+pub mod adaptive_timeout;
+#[cfg(any(unix, feature = "std"))]
+pub use adaptive_timeout::AdaptiveTimeoutExecutor;
 
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub mod forkserver;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use forkserver::{Forkserver, ForkserverExecutor, TimeoutForkserverExecutor};
 
+#[cfg(all(feature = "std", target_os = "linux", target_arch = "x86_64"))]
+pub mod ptrace;
+#[cfg(all(feature = "std", target_os = "linux", target_arch = "x86_64"))]
+pub use ptrace::PtraceSnapshotExecutor;
+
+#[cfg(all(feature = "std", unix))]
+pub mod memlimit;
+#[cfg(all(feature = "std", unix))]
+pub use memlimit::{MemoryCgroup, MemoryLimit, MemoryLimiter};
+
+#[cfg(all(feature = "std", unix))]
+pub mod network;
+#[cfg(all(feature = "std", unix))]
+pub use network::{NetworkExecutor, NetworkTransport};
+
 pub mod combined;
 pub use combined::CombinedExecutor;
 
@@ -0,0 +1,689 @@
+//! The network executor spawns a server for each run and sends it the messages of a [`MultiMessageInput`]
+//! over TCP, UDP or a Unix socket, in the spirit of [`AFLNet`](https://github.com/aflnet/aflnet).
+//!
+//! After each message, the response of the server is handed to the observers that observe responses,
+//! such as the [`crate::observers::ResponseCodeObserver`].
+
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream, UdpSocket},
+    os::unix::{net::UnixStream, process::ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use wait_timeout::ChildExt;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{MultiMessageInput, UsesInput},
+    observers::{ObserversTuple, UsesObservers},
+    state::UsesState,
+    Error,
+};
+
+/// The default timeout of a whole run
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The default time to wait for the response to a message
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_millis(10);
+/// The default time to wait for the server to accept connections
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(1);
+/// The delay between two attempts to connect to the server
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(1);
+/// The maximum size of a response kept for the observers
+const MAX_RESPONSE_SIZE: usize = 1 << 16;
+
+/// How to deliver the messages to the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkTransport {
+    /// Connect to a TCP server at this address
+    Tcp(SocketAddr),
+    /// Send datagrams to a UDP server at this address
+    Udp(SocketAddr),
+    /// Connect to a Unix stream socket at this path
+    Unix(PathBuf),
+}
+
+/// An open connection to the server
+#[derive(Debug)]
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    /// Connect to the server, `Ok(None)` if it does not accept connections (yet)
+    fn connect(transport: &NetworkTransport) -> Result<Option<Self>, Error> {
+        let res = match transport {
+            NetworkTransport::Tcp(addr) => TcpStream::connect(addr).map(|stream| {
+                // Send each message as soon as possible
+                drop(stream.set_nodelay(true));
+                Connection::Tcp(stream)
+            }),
+            NetworkTransport::Udp(addr) => {
+                // Datagrams sent before the server bound its socket would be lost
+                if !udp_port_bound(addr.port())? {
+                    return Ok(None);
+                }
+                let bind_addr: SocketAddr = if addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0_u16; 8], 0).into()
+                };
+                let socket = UdpSocket::bind(bind_addr)?;
+                socket.connect(addr).map(|()| Connection::Udp(socket))
+            }
+            NetworkTransport::Unix(path) => UnixStream::connect(path).map(Connection::Unix),
+        };
+        match res {
+            Ok(connection) => Ok(Some(connection)),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::ConnectionRefused
+                        | ErrorKind::NotFound
+                        | ErrorKind::AddrNotAvailable
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.write_all(message),
+            Connection::Udp(socket) => socket.send(message).map(|_| ()),
+            Connection::Unix(stream) => stream.write_all(message),
+        }
+    }
+
+    /// Receive whatever the server answers within `timeout`
+    fn receive(&mut self, timeout: Duration, response: &mut Vec<u8>) -> io::Result<()> {
+        response.clear();
+        let mut buf = [0_u8; 4096];
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if now >= deadline || response.len() >= MAX_RESPONSE_SIZE {
+                return Ok(());
+            }
+            let read = match self {
+                Connection::Tcp(stream) => stream
+                    .set_read_timeout(Some(deadline - now))
+                    .and_then(|()| stream.read(&mut buf)),
+                Connection::Udp(socket) => socket
+                    .set_read_timeout(Some(deadline - now))
+                    .and_then(|()| socket.recv(&mut buf)),
+                Connection::Unix(stream) => stream
+                    .set_read_timeout(Some(deadline - now))
+                    .and_then(|()| stream.read(&mut buf)),
+            };
+            match read {
+                // The server closed the connection
+                Ok(0) => return Ok(()),
+                Ok(len) => response.extend_from_slice(&buf[..len]),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(())
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Check if any UDP socket is bound to `port`, as listed in `/proc/net/udp` and `/proc/net/udp6`
+#[cfg(target_os = "linux")]
+fn udp_port_bound(port: u16) -> io::Result<bool> {
+    let port = format!(":{port:04X}");
+    for table in ["/proc/net/udp", "/proc/net/udp6"] {
+        let sockets = match std::fs::read_to_string(table) {
+            Ok(sockets) => sockets,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        // Skip the header, the second column is the local address
+        if sockets
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .any(|local| local.ends_with(&port))
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Without `/proc`, the [`NetworkExecutor`] waits for the startup timeout before sending datagrams
+#[cfg(not(target_os = "linux"))]
+#[allow(clippy::unnecessary_wraps)]
+fn udp_port_bound(_port: u16) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// An [`Executor`] spawning a network server for each run, and sending it the messages of a [`MultiMessageInput`].
+/// A run ends once all messages are sent, the server closed the connection, crashed, or the timeout passed.
+pub struct NetworkExecutor<OT, S> {
+    program: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    debug_child: bool,
+    transport: NetworkTransport,
+    timeout: Duration,
+    response_timeout: Duration,
+    startup_timeout: Duration,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for NetworkExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkExecutor")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("transport", &self.transport)
+            .field("timeout", &self.timeout)
+            .field("response_timeout", &self.response_timeout)
+            .field("startup_timeout", &self.startup_timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NetworkExecutor<(), ()> {
+    /// Builder for [`NetworkExecutor`]
+    #[must_use]
+    pub fn builder() -> NetworkExecutorBuilder {
+        NetworkExecutorBuilder::new()
+    }
+}
+
+impl<OT, S> NetworkExecutor<OT, S> {
+    /// The server `program` that's going to run.
+    pub fn program(&self) -> &OsString {
+        &self.program
+    }
+
+    /// The `args` used for the server.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// How the messages are delivered to the server.
+    pub fn transport(&self) -> &NetworkTransport {
+        &self.transport
+    }
+
+    fn spawn_server(&self) -> Result<Child, Error> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null());
+        if !self.debug_child {
+            command.stdout(Stdio::null());
+            command.stderr(Stdio::null());
+        }
+        Ok(command.spawn()?)
+    }
+
+    /// Connect to the freshly spawned server, waiting for it to accept connections
+    fn connect(&self, server: &mut Child) -> Result<Result<Connection, ExitKind>, Error> {
+        let deadline = Instant::now() + self.startup_timeout;
+        #[cfg(not(target_os = "linux"))]
+        if matches!(self.transport, NetworkTransport::Udp(_)) {
+            thread::sleep(self.startup_timeout);
+        }
+        loop {
+            if let Some(connection) = Connection::connect(&self.transport)? {
+                return Ok(Ok(connection));
+            }
+            if let Some(status) = server.try_wait()? {
+                return if status.signal().is_some() {
+                    Ok(Err(ExitKind::Crash))
+                } else {
+                    Err(Error::illegal_state(format!(
+                        "The server exited with {status} before accepting connections"
+                    )))
+                };
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::illegal_state(format!(
+                    "The server did not accept connections on {:?} within {:?}",
+                    self.transport, self.startup_timeout
+                )));
+            }
+            thread::sleep(CONNECT_RETRY_DELAY);
+        }
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for NetworkExecutor<OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: UsesInput<Input = MultiMessageInput>,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        _state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        let start = Instant::now();
+        let mut server = self.spawn_server()?;
+        let mut connection = match self.connect(&mut server) {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(exit_kind)) => return Ok(exit_kind),
+            Err(err) => {
+                drop(server.kill());
+                drop(server.wait());
+                return Err(err);
+            }
+        };
+
+        let observes_responses = self.observers.observes_responses();
+        let mut response = vec![];
+        // Many servers greet first, don't mistake the greeting for the response to the first message
+        if connection
+            .receive(self.response_timeout, &mut response)
+            .is_ok()
+            && observes_responses
+            && !response.is_empty()
+        {
+            self.observers.observe_response(&response);
+        }
+
+        let mut timed_out = false;
+        for message in input.messages() {
+            if start.elapsed() >= self.timeout {
+                timed_out = true;
+                break;
+            }
+            // A failing send means the server closed the connection, or crashed
+            if connection.send(message).is_err() {
+                break;
+            }
+            if connection
+                .receive(self.response_timeout, &mut response)
+                .is_err()
+            {
+                break;
+            }
+            if observes_responses && !response.is_empty() {
+                self.observers.observe_response(&response);
+            }
+        }
+        drop(connection);
+
+        // Give the server the chance to crash on the last message
+        let status = match server.wait_timeout(self.response_timeout)? {
+            Some(status) => status,
+            None => {
+                drop(server.kill());
+                let status = server.wait()?;
+                // The server may have crashed right before we killed it
+                if status
+                    .signal()
+                    .map_or(true, |signal| signal == libc::SIGKILL)
+                {
+                    return Ok(if timed_out {
+                        ExitKind::Timeout
+                    } else {
+                        ExitKind::Ok
+                    });
+                }
+                status
+            }
+        };
+        Ok(if status.signal().is_some() {
+            ExitKind::Crash
+        } else {
+            ExitKind::Ok
+        })
+    }
+}
+
+impl<OT, S> UsesState for NetworkExecutor<OT, S>
+where
+    S: UsesInput,
+{
+    type State = S;
+}
+
+impl<OT, S> UsesObservers for NetworkExecutor<OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: UsesInput,
+{
+    type Observers = OT;
+}
+
+impl<OT, S> HasObservers for NetworkExecutor<OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: UsesInput,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        &self.observers
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        &mut self.observers
+    }
+}
+
+/// The builder for [`NetworkExecutor`]
+#[derive(Debug, Clone, Default)]
+pub struct NetworkExecutorBuilder {
+    program: Option<OsString>,
+    arguments: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    debug_child: bool,
+    transport: Option<NetworkTransport>,
+    timeout: Option<Duration>,
+    response_timeout: Option<Duration>,
+    startup_timeout: Option<Duration>,
+}
+
+impl NetworkExecutorBuilder {
+    /// Creates a new builder for a [`NetworkExecutor`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The server to spawn
+    #[must_use]
+    pub fn program<O>(mut self, program: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument to the server's commandline
+    #[must_use]
+    pub fn arg<O>(mut self, arg: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.arguments.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds arguments to the server's commandline
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arguments.push(arg.as_ref().to_owned());
+        }
+        self
+    }
+
+    /// Adds an environmental var to the server's environment
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// Connect to the server over TCP
+    #[must_use]
+    pub fn tcp(mut self, addr: SocketAddr) -> Self {
+        self.transport = Some(NetworkTransport::Tcp(addr));
+        self
+    }
+
+    /// Send datagrams to the server over UDP.
+    /// The first datagram is sent once a socket is bound to the port, as listed in `/proc/net/udp`.
+    /// Where that is not available, the executor waits for the startup timeout instead.
+    #[must_use]
+    pub fn udp(mut self, addr: SocketAddr) -> Self {
+        self.transport = Some(NetworkTransport::Udp(addr));
+        self
+    }
+
+    /// Connect to the server over a Unix stream socket
+    #[must_use]
+    pub fn unix<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.transport = Some(NetworkTransport::Unix(path.as_ref().to_owned()));
+        self
+    }
+
+    /// The timeout of a whole run, defaults to 5 seconds
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The time to wait for the response to each message, defaults to 10 milliseconds.
+    /// After the last message, the server gets this long to crash before it is killed.
+    #[must_use]
+    pub fn response_timeout(mut self, response_timeout: Duration) -> Self {
+        self.response_timeout = Some(response_timeout);
+        self
+    }
+
+    /// The time to wait for the server to accept connections after spawning it, defaults to one second
+    #[must_use]
+    pub fn startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = Some(startup_timeout);
+        self
+    }
+
+    /// If `debug_child` is set, the server will print to `stdout`/`stderr`.
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// Builds the [`NetworkExecutor`]
+    pub fn build<OT, S>(self, observers: OT) -> Result<NetworkExecutor<OT, S>, Error>
+    where
+        OT: ObserversTuple<S>,
+        S: UsesInput,
+    {
+        let program = self
+            .program
+            .ok_or_else(|| Error::illegal_argument("NetworkExecutor::builder: no program set!"))?;
+        let transport = self.transport.ok_or_else(|| {
+            Error::illegal_argument("NetworkExecutor::builder: no transport set!")
+        })?;
+
+        Ok(NetworkExecutor {
+            program,
+            args: self.arguments,
+            envs: self.envs,
+            debug_child: self.debug_child,
+            transport,
+            timeout: self.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            response_timeout: self.response_timeout.unwrap_or(DEFAULT_RESPONSE_TIMEOUT),
+            startup_timeout: self.startup_timeout.unwrap_or(DEFAULT_STARTUP_TIMEOUT),
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+    use core::time::Duration;
+    use std::{
+        env,
+        net::{SocketAddr, TcpListener, UdpSocket},
+        process::Command,
+    };
+
+    use super::NetworkExecutor;
+    use crate::{
+        bolts::tuples::tuple_list,
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::MultiMessageInput,
+        observers::{ResponseCodeObserver, StdMapObserver},
+        state::NopState,
+        NopFuzzer,
+    };
+
+    /// Serves a single client, greets stream clients, echoes each message and crashes on `crash`
+    const SERVER: &str = r#"
+import os, signal, socket, sys
+kind, addr = sys.argv[1], sys.argv[2]
+if kind == "unix":
+    s = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    s.bind(addr)
+else:
+    host, port = addr.rsplit(":", 1)
+    s = socket.socket(socket.AF_INET, socket.SOCK_DGRAM if kind == "udp" else socket.SOCK_STREAM)
+    s.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+    s.bind((host, int(port)))
+
+def answer(msg):
+    if msg.startswith(b"crash"):
+        os.kill(os.getpid(), signal.SIGSEGV)
+    return msg
+
+if kind == "udp":
+    while True:
+        msg, peer = s.recvfrom(4096)
+        s.sendto(answer(msg), peer)
+s.listen(1)
+c, _ = s.accept()
+c.sendall(b"220 ready\n")
+while True:
+    msg = c.recv(4096)
+    if not msg:
+        break
+    c.sendall(answer(msg))
+"#;
+
+    fn has_python() -> bool {
+        matches!(
+            Command::new("python3").arg("--version").output(),
+            Ok(output) if output.status.success()
+        )
+    }
+
+    fn free_addr(udp: bool) -> SocketAddr {
+        if udp {
+            UdpSocket::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+        } else {
+            TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+        }
+    }
+
+    /// Run the server with the given messages, returning the exit kind and the observed response codes
+    fn run(kind: &str, addr: &str, messages: &[&[u8]]) -> (ExitKind, Vec<u32>) {
+        let builder = NetworkExecutor::builder()
+            .program("python3")
+            .args(["-c", SERVER, kind, addr])
+            .response_timeout(Duration::from_millis(200))
+            .startup_timeout(Duration::from_secs(5));
+        let builder = match kind {
+            "tcp" => builder.tcp(addr.parse().unwrap()),
+            "udp" => builder.udp(addr.parse().unwrap()),
+            _ => builder.unix(addr),
+        };
+        let observer =
+            ResponseCodeObserver::new(StdMapObserver::new_owned("states", vec![0_u8; 64]));
+        let mut executor = builder
+            .build::<_, NopState<MultiMessageInput>>(tuple_list!(observer))
+            .unwrap();
+        let input = MultiMessageInput::new(messages.iter().map(|m| m.to_vec()).collect());
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::new(),
+                &mut NopEventManager::new(),
+                &input,
+            )
+            .unwrap();
+        (exit_kind, executor.observers().0.codes().to_vec())
+    }
+
+    #[test]
+    fn test_network_executor_tcp() {
+        if !has_python() {
+            println!("No python3 found, skipping");
+            return;
+        }
+        let addr = free_addr(false).to_string();
+        assert_eq!(
+            run("tcp", &addr, &[b"200 hello", b"404 nope"]),
+            (ExitKind::Ok, vec![220, 200, 404])
+        );
+        let addr = free_addr(false).to_string();
+        assert_eq!(
+            run("tcp", &addr, &[b"200 hello", b"crash"]).0,
+            ExitKind::Crash
+        );
+    }
+
+    #[test]
+    fn test_network_executor_udp() {
+        if !has_python() {
+            println!("No python3 found, skipping");
+            return;
+        }
+        // The server binds late, no datagram may be lost
+        let addr = free_addr(true).to_string();
+        assert_eq!(
+            run("udp", &addr, &[b"200 hello", b"404 nope"]),
+            (ExitKind::Ok, vec![200, 404])
+        );
+    }
+
+    #[test]
+    fn test_network_executor_unix() {
+        if !has_python() {
+            println!("No python3 found, skipping");
+            return;
+        }
+        let path = env::temp_dir().join(format!("libafl_network_{}.sock", std::process::id()));
+        let addr = path.to_str().unwrap();
+        assert_eq!(
+            run("unix", addr, &[b"200 hello", b"500 error"]),
+            (ExitKind::Ok, vec![220, 200, 500])
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,1068 @@
+//! An executor for uninstrumented binaries, running them persistently from a memory snapshot.
+//!
+//! The target is started once under `ptrace` and stopped at a configurable entry address,
+//! where its registers and writable memory are snapshotted.
+//! Each run continues the target from the entry address, until it reaches the (optional) exit address,
+//! crashes, or times out. Afterwards, the registers and all pages dirtied during the run are restored.
+//! Dirty pages are tracked with the kernel's soft-dirty bits (`/proc/<pid>/clear_refs` and `/proc/<pid>/pagemap`);
+//! if they are not available, all writable memory is restored.
+//!
+//! Writable memory unmapped or shrunk during a run, e.g. by `munmap` or a heap trimmed with `brk`, is mapped again.
+//! The snapshot does not cover other kernel state: file descriptors opened, or memory mapped after the entry address
+//! are not reverted. The input is delivered as a file, which the target should (re-)open after the entry address.
+
+use alloc::{borrow::ToOwned, sync::Arc, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File, OpenOptions},
+    io,
+    os::unix::{fs::FileExt, process::CommandExt},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{Condvar, Mutex},
+    thread,
+    time::Instant,
+};
+
+use nix::{
+    sys::{
+        ptrace,
+        signal::{kill, Signal},
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::Pid,
+};
+
+use crate::{
+    bolts::{
+        fs::{InputFile, INPUTFILE_STD},
+        AsSlice,
+    },
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{HasTargetBytes, UsesInput},
+    observers::{ObserversTuple, UsesObservers},
+    state::UsesState,
+    Error,
+};
+
+/// The default timeout of a single run
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+/// The soft-dirty bit of a `/proc/<pid>/pagemap` entry
+const PAGEMAP_SOFT_DIRTY: u64 = 1 << 55;
+/// Writing this to `/proc/<pid>/clear_refs` clears the soft-dirty bits of all pages
+const CLEAR_REFS_SOFT_DIRTY: &[u8] = b"4";
+/// The `int3` instruction
+const BREAKPOINT: u8 = 0xcc;
+/// The `syscall` instruction
+const SYSCALL: [u8; 2] = [0x0f, 0x05];
+
+/// A writable memory region of the target, as listed in `/proc/<pid>/maps`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappedRegion {
+    /// The start address of the region
+    pub start: usize,
+    /// The end address of the region, exclusive
+    pub end: usize,
+    /// If this is the program break (`[heap]`), which is grown with `brk` instead of `mmap`
+    pub heap: bool,
+}
+
+/// Parse the writable regions from the contents of `/proc/<pid>/maps`.
+/// Kernel-provided regions, which can't be written through `/proc/<pid>/mem`, are skipped.
+#[must_use]
+pub fn parse_writable_regions(maps: &str) -> Vec<MappedRegion> {
+    maps.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let range = fields.next()?;
+            let perms = fields.next()?;
+            let name = fields.nth(3).unwrap_or("");
+            if !perms.contains('w') || name == "[vvar]" || name == "[vsyscall]" {
+                return None;
+            }
+            let (start, end) = range.split_once('-')?;
+            Some(MappedRegion {
+                start: usize::from_str_radix(start, 16).ok()?,
+                end: usize::from_str_radix(end, 16).ok()?,
+                heap: name == "[heap]",
+            })
+        })
+        .collect()
+}
+
+/// The parts of a snapshotted `region` which are no longer covered by the `current` writable regions,
+/// i.e. which were unmapped, shrunk, or made read-only since the snapshot.
+#[must_use]
+pub fn unmapped_ranges(region: &MappedRegion, current: &[MappedRegion]) -> Vec<MappedRegion> {
+    let mut covering: Vec<_> = current
+        .iter()
+        .filter(|mapped| mapped.start < region.end && mapped.end > region.start)
+        .collect();
+    covering.sort_by_key(|mapped| mapped.start);
+
+    let mut unmapped = vec![];
+    let mut start = region.start;
+    for mapped in covering {
+        if mapped.start > start {
+            unmapped.push(MappedRegion {
+                start,
+                end: mapped.start,
+                heap: region.heap,
+            });
+        }
+        start = start.max(mapped.end);
+    }
+    if start < region.end {
+        unmapped.push(MappedRegion {
+            start,
+            end: region.end,
+            heap: region.heap,
+        });
+    }
+    unmapped
+}
+
+/// The contents of a [`MappedRegion`] at snapshot time
+#[derive(Debug, Clone)]
+struct RegionSnapshot {
+    region: MappedRegion,
+    data: Vec<u8>,
+}
+
+/// How a single run of the snapshotted target ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunEnd {
+    /// The target is stopped and can be restored to the snapshot
+    Stopped(ExitKind),
+    /// The target exited or was killed, it has to be respawned
+    Exited(ExitKind),
+}
+
+/// A target process under `ptrace`, stopped at its entry address and snapshotted
+struct SnapshotProcess {
+    pid: Pid,
+    exit_addr: Option<usize>,
+    regs: libc::user_regs_struct,
+    fpregs: libc::user_fpregs_struct,
+    regions: Vec<RegionSnapshot>,
+    mem: File,
+    pagemap: File,
+    page_size: usize,
+    /// If the kernel tracks soft-dirty pages, otherwise all writable memory is restored
+    soft_dirty: bool,
+    /// The number of open file descriptors at snapshot time
+    fd_count: usize,
+    /// The watchdog sent a `SIGSTOP` the target did not receive yet
+    stop_pending: bool,
+}
+
+impl Debug for SnapshotProcess {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotProcess")
+            .field("pid", &self.pid)
+            .field("exit_addr", &self.exit_addr)
+            .field("regions", &self.regions.len())
+            .field("soft_dirty", &self.soft_dirty)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SnapshotProcess {
+    /// Spawn the command under `ptrace`, run it to `entry_addr` and take the snapshot
+    fn spawn(
+        command: &mut Command,
+        entry_addr: usize,
+        exit_addr: Option<usize>,
+    ) -> Result<Self, Error> {
+        unsafe {
+            command.pre_exec(|| {
+                // Keep the entry and exit addresses stable across respawns
+                libc::personality(libc::ADDR_NO_RANDOMIZE as libc::c_ulong);
+                ptrace::traceme().map_err(|errno| io::Error::from_raw_os_error(errno as i32))
+            });
+        }
+        let child = command.spawn()?;
+        #[allow(clippy::cast_possible_wrap)]
+        let pid = Pid::from_raw(child.id() as i32);
+
+        // The child stops with a `SIGTRAP` after `execve`
+        match waitpid(pid, None)? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => (),
+            status => {
+                return Err(Error::illegal_state(format!(
+                    "Unexpected state {status:?} of the target after exec"
+                )))
+            }
+        }
+        ptrace::setoptions(pid, ptrace::Options::PTRACE_O_EXITKILL)?;
+        let mem = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!("/proc/{pid}/mem"))?;
+
+        let entry_byte = set_breakpoint(&mem, entry_addr)?;
+        ptrace::cont(pid, None)?;
+        let mut regs = match waitpid(pid, None)? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => ptrace::getregs(pid)?,
+            status => {
+                let _ = kill(pid, Signal::SIGKILL);
+                return Err(Error::illegal_state(format!(
+                    "The target did not reach the entry address {entry_addr:#x}, but {status:?}"
+                )));
+            }
+        };
+        if regs.rip as usize != entry_addr + 1 {
+            let _ = kill(pid, Signal::SIGKILL);
+            return Err(Error::illegal_state(format!(
+                "The target stopped at {:#x} instead of the entry address {entry_addr:#x}",
+                regs.rip
+            )));
+        }
+        mem.write_all_at(&[entry_byte], entry_addr as u64)?;
+        regs.rip = entry_addr as u64;
+        ptrace::setregs(pid, regs)?;
+        if let Some(exit_addr) = exit_addr {
+            set_breakpoint(&mem, exit_addr)?;
+        }
+
+        let mut process = Self {
+            pid,
+            exit_addr,
+            regs,
+            fpregs: get_fpregs(pid)?,
+            regions: vec![],
+            mem,
+            pagemap: File::open(format!("/proc/{pid}/pagemap"))?,
+            page_size: page_size(),
+            soft_dirty: false,
+            fd_count: 0,
+            stop_pending: false,
+        };
+        process.take_snapshot()?;
+        Ok(process)
+    }
+
+    /// Snapshot all writable memory, and reset the soft-dirty bits
+    fn take_snapshot(&mut self) -> Result<(), Error> {
+        let maps = fs::read_to_string(format!("/proc/{}/maps", self.pid))?;
+        self.regions.clear();
+        for region in parse_writable_regions(&maps) {
+            let mut data = vec![0; region.end - region.start];
+            self.mem.read_exact_at(&mut data, region.start as u64)?;
+            self.regions.push(RegionSnapshot { region, data });
+        }
+        self.soft_dirty = self.probe_soft_dirty();
+        self.fd_count = self.open_fds()?;
+        Ok(())
+    }
+
+    /// Check if the kernel tracks soft-dirty pages (`CONFIG_MEM_SOFT_DIRTY`), and reset the soft-dirty bits if so
+    fn probe_soft_dirty(&self) -> bool {
+        let snapshot = match self.regions.first() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        if self.clear_soft_dirty().is_err() {
+            return false;
+        }
+        // Rewriting a byte marks its page soft-dirty, unless the kernel silently ignores the bits
+        let addr = snapshot.region.start;
+        let mut entry = [0; 8];
+        if self
+            .mem
+            .write_all_at(&snapshot.data[..1], addr as u64)
+            .and_then(|()| {
+                self.pagemap
+                    .read_exact_at(&mut entry, (addr / self.page_size * 8) as u64)
+            })
+            .is_err()
+        {
+            return false;
+        }
+        u64::from_ne_bytes(entry) & PAGEMAP_SOFT_DIRTY != 0 && self.clear_soft_dirty().is_ok()
+    }
+
+    /// Restore the registers and all memory written since the snapshot.
+    /// Snapshotted memory which is no longer mapped is mapped again and restored as a whole.
+    fn restore(&mut self) -> Result<(), Error> {
+        let maps = fs::read_to_string(format!("/proc/{}/maps", self.pid))?;
+        let current = parse_writable_regions(&maps);
+
+        let unmapped: Vec<_> = self
+            .regions
+            .iter()
+            .map(|snapshot| unmapped_ranges(&snapshot.region, &current))
+            .collect();
+        for range in unmapped.iter().flatten() {
+            self.remap(range)?;
+        }
+
+        let mut entries = vec![];
+        for (snapshot, unmapped) in self.regions.iter().zip(&unmapped) {
+            if !self.soft_dirty || !unmapped.is_empty() {
+                self.mem
+                    .write_all_at(&snapshot.data, snapshot.region.start as u64)?;
+                continue;
+            }
+            let pages = snapshot.data.len() / self.page_size;
+            entries.resize(pages * 8, 0);
+            self.pagemap.read_exact_at(
+                &mut entries,
+                (snapshot.region.start / self.page_size * 8) as u64,
+            )?;
+            for (page, entry) in entries.chunks_exact(8).enumerate() {
+                if u64::from_ne_bytes(entry.try_into().unwrap()) & PAGEMAP_SOFT_DIRTY != 0 {
+                    let offset = page * self.page_size;
+                    self.mem.write_all_at(
+                        &snapshot.data[offset..offset + self.page_size],
+                        (snapshot.region.start + offset) as u64,
+                    )?;
+                }
+            }
+        }
+        if self.soft_dirty {
+            self.clear_soft_dirty()?;
+        }
+
+        ptrace::setregs(self.pid, self.regs)?;
+        set_fpregs(self.pid, &self.fpregs)?;
+        Ok(())
+    }
+
+    /// Map a range of snapshotted memory again, which the target unmapped during the run.
+    /// The heap is grown back with `brk`, so that the target's allocator can keep using it.
+    fn remap(&mut self, range: &MappedRegion) -> Result<(), Error> {
+        let res = if range.heap {
+            self.inject_syscall(libc::SYS_brk, [range.end as u64, 0, 0, 0, 0, 0])?
+        } else {
+            #[allow(clippy::cast_sign_loss)]
+            self.inject_syscall(
+                libc::SYS_mmap,
+                [
+                    range.start as u64,
+                    (range.end - range.start) as u64,
+                    (libc::PROT_READ | libc::PROT_WRITE) as u64,
+                    (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED) as u64,
+                    u64::MAX,
+                    0,
+                ],
+            )?
+        };
+        let mapped = if range.heap {
+            res as usize >= range.end
+        } else {
+            res as usize == range.start
+        };
+        if mapped {
+            Ok(())
+        } else {
+            Err(Error::illegal_state(format!(
+                "Could not map {:#x}-{:#x} of the target again, got {res:#x}",
+                range.start, range.end
+            )))
+        }
+    }
+
+    /// Let the stopped target execute a single syscall at the entry address, returning its result.
+    /// The registers and the code at the entry address are clobbered, and have to be restored afterwards.
+    fn inject_syscall(&mut self, nr: libc::c_long, args: [u64; 6]) -> Result<u64, Error> {
+        let addr = self.regs.rip;
+        let mut code = [0; SYSCALL.len()];
+        self.mem.read_exact_at(&mut code, addr)?;
+        self.mem.write_all_at(&SYSCALL, addr)?;
+
+        let mut regs = self.regs;
+        #[allow(clippy::cast_sign_loss)]
+        {
+            regs.rax = nr as u64;
+        }
+        // Don't let the kernel restart a syscall interrupted by the stop
+        regs.orig_rax = u64::MAX;
+        regs.rdi = args[0];
+        regs.rsi = args[1];
+        regs.rdx = args[2];
+        regs.r10 = args[3];
+        regs.r8 = args[4];
+        regs.r9 = args[5];
+        ptrace::setregs(self.pid, regs)?;
+
+        let result = loop {
+            ptrace::step(self.pid, None)?;
+            match waitpid(self.pid, None)? {
+                WaitStatus::Stopped(_, Signal::SIGTRAP) => break Ok(ptrace::getregs(self.pid)?.rax),
+                WaitStatus::Stopped(_, Signal::SIGSTOP) if self.stop_pending => {
+                    // The late stop of the watchdog, before the syscall ran
+                    self.stop_pending = false;
+                }
+                status => {
+                    break Err(Error::illegal_state(format!(
+                        "Unexpected state {status:?} of the target during a syscall"
+                    )))
+                }
+            }
+        };
+        self.mem.write_all_at(&code, addr)?;
+        result
+    }
+
+    /// Continue the target from the snapshot until it stops, arming the watchdog for the timeout
+    fn run(&mut self, watchdog: &Watchdog, timeout: Duration) -> Result<RunEnd, Error> {
+        ptrace::cont(self.pid, None)?;
+        watchdog.arm(self.pid, timeout);
+        let res = loop {
+            let status = match waitpid(self.pid, None) {
+                Ok(status) => status,
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(err) => break Err(err.into()),
+            };
+            match status {
+                WaitStatus::Stopped(_, Signal::SIGTRAP) => {
+                    let rip = ptrace::getregs(self.pid)?.rip as usize;
+                    if self.exit_addr.map(|exit_addr| exit_addr + 1) == Some(rip) {
+                        break Ok(RunEnd::Stopped(ExitKind::Ok));
+                    }
+                    break Ok(RunEnd::Stopped(ExitKind::Crash));
+                }
+                WaitStatus::Stopped(_, Signal::SIGSTOP) => {
+                    if self.stop_pending {
+                        // The late stop of the previous run, not of this one
+                        self.stop_pending = false;
+                        ptrace::cont(self.pid, None)?;
+                        continue;
+                    }
+                    break Ok(RunEnd::Stopped(ExitKind::Timeout));
+                }
+                WaitStatus::Stopped(
+                    _,
+                    Signal::SIGSEGV
+                    | Signal::SIGBUS
+                    | Signal::SIGILL
+                    | Signal::SIGFPE
+                    | Signal::SIGABRT
+                    | Signal::SIGSYS,
+                ) => break Ok(RunEnd::Stopped(ExitKind::Crash)),
+                WaitStatus::Stopped(_, signal) => {
+                    // Forward all other signals to the target
+                    ptrace::cont(self.pid, signal)?;
+                }
+                WaitStatus::Exited(..) => break Ok(RunEnd::Exited(ExitKind::Ok)),
+                WaitStatus::Signaled(..) => break Ok(RunEnd::Exited(ExitKind::Crash)),
+                _ => ptrace::cont(self.pid, None)?,
+            }
+        };
+        if watchdog.disarm() && res.as_ref().ok() != Some(&RunEnd::Stopped(ExitKind::Timeout)) {
+            self.stop_pending = true;
+        }
+        res
+    }
+
+    /// Check if the target leaked file descriptors since the snapshot, e.g. by crashing before closing the input
+    fn leaked_fds(&self) -> Result<bool, Error> {
+        Ok(self.open_fds()? > self.fd_count)
+    }
+
+    fn open_fds(&self) -> Result<usize, Error> {
+        Ok(fs::read_dir(format!("/proc/{}/fd", self.pid))?.count())
+    }
+
+    fn clear_soft_dirty(&self) -> Result<(), Error> {
+        fs::write(
+            format!("/proc/{}/clear_refs", self.pid),
+            CLEAR_REFS_SOFT_DIRTY,
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for SnapshotProcess {
+    fn drop(&mut self) {
+        let _ = kill(self.pid, Signal::SIGKILL);
+        let _ = waitpid(self.pid, None);
+    }
+}
+
+/// Place an `int3` at `addr` through the target's `/proc/<pid>/mem`, returning the original byte
+fn set_breakpoint(mem: &File, addr: usize) -> Result<u8, Error> {
+    let mut byte = [0];
+    mem.read_exact_at(&mut byte, addr as u64)?;
+    mem.write_all_at(&[BREAKPOINT], addr as u64)?;
+    Ok(byte[0])
+}
+
+fn get_fpregs(pid: Pid) -> Result<libc::user_fpregs_struct, Error> {
+    let mut fpregs = core::mem::MaybeUninit::<libc::user_fpregs_struct>::uninit();
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETFPREGS,
+            pid.as_raw(),
+            core::ptr::null_mut::<libc::c_void>(),
+            fpregs.as_mut_ptr(),
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(unsafe { fpregs.assume_init() })
+}
+
+fn set_fpregs(pid: Pid, fpregs: &libc::user_fpregs_struct) -> Result<(), Error> {
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETFPREGS,
+            pid.as_raw(),
+            core::ptr::null_mut::<libc::c_void>(),
+            core::ptr::addr_of!(*fpregs),
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+fn page_size() -> usize {
+    #[allow(clippy::cast_sign_loss)]
+    unsafe {
+        libc::sysconf(libc::_SC_PAGESIZE) as usize
+    }
+}
+
+/// The state shared with the watchdog thread
+#[derive(Debug, Default)]
+struct WatchdogState {
+    deadline: Option<(Instant, Pid)>,
+    fired: bool,
+    shutdown: bool,
+}
+
+/// A thread stopping the target with `SIGSTOP` once the deadline of the current run passed
+#[derive(Debug)]
+struct Watchdog {
+    shared: Arc<(Mutex<WatchdogState>, Condvar)>,
+}
+
+impl Watchdog {
+    fn new() -> Self {
+        let shared = Arc::new((Mutex::new(WatchdogState::default()), Condvar::new()));
+        let watched = shared.clone();
+        thread::spawn(move || {
+            let (lock, cvar) = &*watched;
+            let mut state = lock.lock().unwrap();
+            while !state.shutdown {
+                match state.deadline {
+                    Some((deadline, pid)) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            let _ = kill(pid, Signal::SIGSTOP);
+                            state.fired = true;
+                            state.deadline = None;
+                        } else {
+                            state = cvar.wait_timeout(state, deadline - now).unwrap().0;
+                        }
+                    }
+                    None => state = cvar.wait(state).unwrap(),
+                }
+            }
+        });
+        Self { shared }
+    }
+
+    fn arm(&self, pid: Pid, timeout: Duration) {
+        let (lock, cvar) = &*self.shared;
+        let mut state = lock.lock().unwrap();
+        state.deadline = Some((Instant::now() + timeout, pid));
+        state.fired = false;
+        cvar.notify_one();
+    }
+
+    /// Disarm the watchdog, returns if it fired
+    fn disarm(&self) -> bool {
+        let (lock, _) = &*self.shared;
+        let mut state = lock.lock().unwrap();
+        state.deadline = None;
+        state.fired
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*self.shared;
+        lock.lock().unwrap().shutdown = true;
+        cvar.notify_one();
+    }
+}
+
+/// An [`Executor`] running uninstrumented binaries persistently, by restoring a snapshot of the target
+/// taken at an entry address after each run. See the [module documentation](self) for the details and limitations.
+pub struct PtraceSnapshotExecutor<OT, S> {
+    program: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    debug_child: bool,
+    entry_addr: usize,
+    exit_addr: Option<usize>,
+    timeout: Duration,
+    input_file: InputFile,
+    process: Option<SnapshotProcess>,
+    watchdog: Watchdog,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for PtraceSnapshotExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtraceSnapshotExecutor")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("entry_addr", &self.entry_addr)
+            .field("exit_addr", &self.exit_addr)
+            .field("timeout", &self.timeout)
+            .field("input_file", &self.input_file)
+            .field("process", &self.process)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PtraceSnapshotExecutor<(), ()> {
+    /// Builder for [`PtraceSnapshotExecutor`]
+    #[must_use]
+    pub fn builder() -> PtraceSnapshotExecutorBuilder {
+        PtraceSnapshotExecutorBuilder::new()
+    }
+}
+
+impl<OT, S> PtraceSnapshotExecutor<OT, S> {
+    /// The `program` that's going to run.
+    pub fn program(&self) -> &OsString {
+        &self.program
+    }
+
+    /// The `args` used for the binary.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The [`InputFile`] used by this [`Executor`].
+    pub fn input_file(&self) -> &InputFile {
+        &self.input_file
+    }
+
+    /// The pid of the snapshotted target, if it's currently running
+    pub fn pid(&self) -> Option<Pid> {
+        self.process.as_ref().map(|process| process.pid)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null());
+        if !self.debug_child {
+            command.stdout(Stdio::null());
+            command.stderr(Stdio::null());
+        }
+        command
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for PtraceSnapshotExecutor<OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: UsesInput,
+    S::Input: HasTargetBytes,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        _state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+
+        if self.process.is_none() {
+            let mut command = self.command();
+            self.process = Some(SnapshotProcess::spawn(
+                &mut command,
+                self.entry_addr,
+                self.exit_addr,
+            )?);
+        }
+        let process = self.process.as_mut().unwrap();
+
+        match process.run(&self.watchdog, self.timeout)? {
+            RunEnd::Stopped(exit_kind) => {
+                if exit_kind != ExitKind::Ok && process.leaked_fds()? {
+                    // Start over, instead of running out of file descriptors eventually
+                    self.process = None;
+                } else {
+                    process.restore()?;
+                }
+                Ok(exit_kind)
+            }
+            RunEnd::Exited(exit_kind) => {
+                self.process = None;
+                Ok(exit_kind)
+            }
+        }
+    }
+}
+
+impl<OT, S> UsesState for PtraceSnapshotExecutor<OT, S>
+where
+    S: UsesInput,
+{
+    type State = S;
+}
+
+impl<OT, S> UsesObservers for PtraceSnapshotExecutor<OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: UsesInput,
+{
+    type Observers = OT;
+}
+
+impl<OT, S> HasObservers for PtraceSnapshotExecutor<OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: UsesInput,
+{
+    #[inline]
+    fn observers(&self) -> &OT {
+        &self.observers
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> &mut OT {
+        &mut self.observers
+    }
+}
+
+/// The builder for [`PtraceSnapshotExecutor`]
+#[derive(Debug, Clone, Default)]
+pub struct PtraceSnapshotExecutorBuilder {
+    program: Option<OsString>,
+    arguments: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    debug_child: bool,
+    entry_addr: Option<usize>,
+    exit_addr: Option<usize>,
+    timeout: Option<Duration>,
+    input_filename: Option<OsString>,
+}
+
+impl PtraceSnapshotExecutorBuilder {
+    /// Creates a new builder for a [`PtraceSnapshotExecutor`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The binary to execute
+    #[must_use]
+    pub fn program<O>(mut self, program: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument to the binary's commandline
+    #[must_use]
+    pub fn arg<O>(mut self, arg: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.arguments.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds arguments to the binary's commandline
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arguments.push(arg.as_ref().to_owned());
+        }
+        self
+    }
+
+    /// Adds an environmental var to the binary's environment
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// Place the input at this position and set the filename for the input.
+    #[must_use]
+    pub fn arg_input_file<P: AsRef<Path>>(self, path: P) -> Self {
+        let mut moved = self.arg(path.as_ref());
+        moved.input_filename = Some(path.as_ref().as_os_str().to_os_string());
+        moved
+    }
+
+    /// Place the input at this position and set the default filename for the input.
+    #[must_use]
+    pub fn arg_input_file_std(self) -> Self {
+        self.arg_input_file(INPUTFILE_STD)
+    }
+
+    /// The address to stop the target at and take the snapshot.
+    /// As ASLR is disabled for the target, this is the address as shown by the disassembler for non-PIE
+    /// binaries, and the address relative to `0x555555554000` for PIE binaries on `x86_64`.
+    /// This option is required.
+    #[must_use]
+    pub fn entry_addr(mut self, entry_addr: usize) -> Self {
+        self.entry_addr = Some(entry_addr);
+        self
+    }
+
+    /// The address at which a run is over and the snapshot is restored, e.g. the end of the loop body.
+    /// Without it, each run only ends once the target exits, and the target is respawned.
+    #[must_use]
+    pub fn exit_addr(mut self, exit_addr: usize) -> Self {
+        self.exit_addr = Some(exit_addr);
+        self
+    }
+
+    /// The timeout of a single run, defaults to one second
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// If `debug_child` is set, the child will print to `stdout`/`stderr`.
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// Builds the [`PtraceSnapshotExecutor`].
+    /// The target is spawned and snapshotted lazily, on the first run.
+    pub fn build<OT, S>(self, observers: OT) -> Result<PtraceSnapshotExecutor<OT, S>, Error>
+    where
+        OT: ObserversTuple<S>,
+        S: UsesInput,
+    {
+        let program = self.program.ok_or_else(|| {
+            Error::illegal_argument("PtraceSnapshotExecutor::builder: no program set!")
+        })?;
+        let entry_addr = self.entry_addr.ok_or_else(|| {
+            Error::illegal_argument("PtraceSnapshotExecutor::builder: no entry address set!")
+        })?;
+        let input_filename = self
+            .input_filename
+            .unwrap_or_else(|| OsString::from(".cur_input"));
+
+        Ok(PtraceSnapshotExecutor {
+            program,
+            args: self.arguments,
+            envs: self.envs,
+            debug_child: self.debug_child,
+            entry_addr,
+            exit_addr: self.exit_addr,
+            timeout: self.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            input_file: InputFile::create(input_filename)?,
+            process: None,
+            watchdog: Watchdog::new(),
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use std::{env, fs, process::Command};
+
+    use super::{parse_writable_regions, unmapped_ranges, MappedRegion, PtraceSnapshotExecutor};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        inputs::BytesInput,
+        state::NopState,
+        NopFuzzer,
+    };
+
+    /// Checks that the snapshot is intact, then unmaps, shrinks and trims parts of it
+    const TARGET: &str = r#"
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+static char *region, *shrunk, *heap;
+static int runs;
+
+__attribute__((noinline)) void run(void) {
+    long page = sysconf(_SC_PAGESIZE);
+    if (runs++ != 0 || region[0] != 'A' || shrunk[page] != 'B' || heap[2 * page - 1] != 'C') abort();
+    region[0] = 'X';
+    munmap(region, page);
+    munmap(shrunk + page, page);
+    sbrk(-page);
+}
+
+__attribute__((noinline)) void done(void) { __asm__ volatile(""); }
+
+int main(void) {
+    long page = sysconf(_SC_PAGESIZE);
+    region = mmap(NULL, page, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    shrunk = mmap(NULL, 2 * page, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    memset(region, 'A', page);
+    memset(shrunk, 'B', 2 * page);
+    heap = sbrk(2 * page);
+    memset(heap, 'C', 2 * page);
+    for (;;) {
+        run();
+        done();
+    }
+}
+"#;
+
+    #[test]
+    fn test_parse_writable_regions() {
+        let maps = "555555554000-555555556000 r--p 00000000 08:01 1234    /usr/bin/target
+555555558000-555555559000 rw-p 00003000 08:01 1234    /usr/bin/target
+555555559000-55555557a000 rw-p 00000000 00:00 0       [heap]
+7ffff7fc1000-7ffff7fc5000 r--p 00000000 00:00 0       [vvar]
+7ffff7ffe000-7ffff7fff000 rw-p 00000000 00:00 0
+7ffffffde000-7ffffffff000 rw-p 00000000 00:00 0       [stack]
+ffffffffff600000-ffffffffff601000 --xp 00000000 00:00 0 [vsyscall]
+";
+        assert_eq!(
+            parse_writable_regions(maps),
+            [
+                MappedRegion {
+                    start: 0x5555_5555_8000,
+                    end: 0x5555_5555_9000,
+                    heap: false,
+                },
+                MappedRegion {
+                    start: 0x5555_5555_9000,
+                    end: 0x5555_5557_a000,
+                    heap: true,
+                },
+                MappedRegion {
+                    start: 0x7fff_f7ff_e000,
+                    end: 0x7fff_f7ff_f000,
+                    heap: false,
+                },
+                MappedRegion {
+                    start: 0x7fff_fffd_e000,
+                    end: 0x7fff_ffff_f000,
+                    heap: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmapped_ranges() {
+        let region = |start, end| MappedRegion {
+            start,
+            end,
+            heap: false,
+        };
+        let snapshot = region(0x1000, 0x5000);
+
+        // Still mapped, possibly split up by `mprotect`
+        assert!(unmapped_ranges(&snapshot, &[region(0, 0x8000)]).is_empty());
+        assert!(
+            unmapped_ranges(&snapshot, &[region(0x3000, 0x5000), region(0x1000, 0x3000)])
+                .is_empty()
+        );
+        // Vanished
+        assert_eq!(
+            unmapped_ranges(&snapshot, &[region(0x6000, 0x7000)]),
+            [snapshot]
+        );
+        // Shrunk at the end, and with a hole
+        assert_eq!(
+            unmapped_ranges(&snapshot, &[region(0x1000, 0x2000)]),
+            [region(0x2000, 0x5000)]
+        );
+        assert_eq!(
+            unmapped_ranges(&snapshot, &[region(0x1000, 0x2000), region(0x3000, 0x5000)]),
+            [region(0x2000, 0x3000)]
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn test_restore_unmapped() {
+        let dir = env::temp_dir().join(format!("libafl_ptrace_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("target.c");
+        let binary = dir.join("target");
+        fs::write(&source, TARGET).unwrap();
+        let compiled = Command::new("cc")
+            .args(["-O0", "-no-pie", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .status();
+        if !compiled.map_or(false, |status| status.success()) {
+            println!("No C compiler found, skipping");
+            return;
+        }
+        let symbols = Command::new("nm").arg(&binary).output().unwrap().stdout;
+        let symbol = |name: &str| {
+            String::from_utf8_lossy(&symbols)
+                .lines()
+                .find_map(|line| {
+                    let (addr, rest) = line.split_once(' ')?;
+                    (rest.split_whitespace().nth(1)? == name)
+                        .then(|| usize::from_str_radix(addr, 16).unwrap())
+                })
+                .unwrap()
+        };
+
+        let mut executor = PtraceSnapshotExecutor::builder()
+            .program(&binary)
+            .entry_addr(symbol("run"))
+            .exit_addr(symbol("done"))
+            .arg_input_file(dir.join(".cur_input"))
+            .build::<_, NopState<BytesInput>>(())
+            .unwrap();
+        for _ in 0..3 {
+            let exit_kind = executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut NopState::new(),
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(vec![]),
+                )
+                .unwrap();
+            // The target aborts if it did not start from an intact snapshot
+            assert_eq!(exit_kind, ExitKind::Ok);
+        }
+        drop(executor);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
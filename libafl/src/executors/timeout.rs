@@ -1,14 +1,12 @@
 //! A `TimeoutExecutor` sets a timeout before each target run
 
+#[cfg(any(windows, unix))]
+use core::fmt::{self, Debug, Formatter};
 #[cfg(target_os = "linux")]
 use core::ptr::{addr_of, addr_of_mut};
+use core::time::Duration;
 #[cfg(all(windows, feature = "std"))]
 use core::{ffi::c_void, ptr::write_volatile};
-#[cfg(any(windows, unix))]
-use core::{
-    fmt::{self, Debug, Formatter},
-    time::Duration,
-};
 #[cfg(unix)]
 use core::{mem::zeroed, ptr::null_mut};
 #[cfg(windows)]
@@ -75,10 +73,26 @@ extern "C" {
 #[cfg(all(unix, not(target_os = "linux")))]
 const ITIMER_REAL: c_int = 0;
 
+/// An [`Executor`] with a timeout that can be changed while fuzzing
+pub trait HasTimeout {
+    /// The timeout of a run
+    fn timeout(&self) -> Duration;
+
+    /// Sets the timeout of a run
+    fn set_timeout(&mut self, exec_tmout: Duration);
+}
+
+/// An executor with a [`HasTimeout`] that returns [`crate::executors::ExitKind::Timeout`] for runs that time out.
+/// The [`TimeoutExecutor`] does not: the in-process executors handle timeouts in their signal handlers,
+/// which run the objective and restart the fuzzer.
+pub trait ReturnsTimeouts: HasTimeout {}
+
 /// The timeout executor is a wrapper that sets a timeout before each run
 pub struct TimeoutExecutor<E> {
     /// The wrapped [`Executor`]
     executor: E,
+    /// The timeout of a run
+    exec_tmout: Duration,
     #[cfg(target_os = "linux")]
     itimerspec: libc::itimerspec,
     #[cfg(target_os = "linux")]
@@ -156,6 +170,7 @@ impl<E> TimeoutExecutor<E> {
         }
         Self {
             executor,
+            exec_tmout,
             itimerspec,
             timerid,
         }
@@ -177,6 +192,7 @@ impl<E> TimeoutExecutor<E> {
             it_value,
         };
         self.itimerspec = itimerspec;
+        self.exec_tmout = exec_tmout;
     }
 }
 
@@ -200,6 +216,7 @@ impl<E> TimeoutExecutor<E> {
         };
         Self {
             executor,
+            exec_tmout,
             itimerval,
         }
     }
@@ -220,6 +237,7 @@ impl<E> TimeoutExecutor<E> {
             it_value,
         };
         self.itimerval = itimerval;
+        self.exec_tmout = exec_tmout;
     }
 }
 
@@ -245,6 +263,7 @@ impl<E: HasInProcessHandlers> TimeoutExecutor<E> {
 
         Self {
             executor,
+            exec_tmout,
             milli_sec,
             tp_timer,
             critical,
@@ -255,6 +274,7 @@ impl<E: HasInProcessHandlers> TimeoutExecutor<E> {
     #[cfg(windows)]
     pub fn set_timeout(&mut self, exec_tmout: Duration) {
         self.milli_sec = exec_tmout.as_millis() as i64;
+        self.exec_tmout = exec_tmout;
     }
 
     /// Retrieve the inner `Executor` that is wrapped by this `TimeoutExecutor`.
@@ -263,6 +283,28 @@ impl<E: HasInProcessHandlers> TimeoutExecutor<E> {
     }
 }
 
+#[cfg(unix)]
+impl<E> HasTimeout for TimeoutExecutor<E> {
+    fn timeout(&self) -> Duration {
+        self.exec_tmout
+    }
+
+    fn set_timeout(&mut self, exec_tmout: Duration) {
+        TimeoutExecutor::set_timeout(self, exec_tmout);
+    }
+}
+
+#[cfg(windows)]
+impl<E: HasInProcessHandlers> HasTimeout for TimeoutExecutor<E> {
+    fn timeout(&self) -> Duration {
+        self.exec_tmout
+    }
+
+    fn set_timeout(&mut self, exec_tmout: Duration) {
+        TimeoutExecutor::set_timeout(self, exec_tmout);
+    }
+}
+
 #[cfg(windows)]
 impl<E, EM, Z> Executor<EM, Z> for TimeoutExecutor<E>
 where
@@ -24,6 +24,7 @@ use crate::{
     inputs::UsesInput,
     monitors::UserStats,
     observers::{MapObserver, ObserversTuple},
+    stages::calibrate::UnstableEntriesMetadata,
     state::{HasClientPerfMonitor, HasMetadata, HasNamedMetadata},
     Error,
 };
@@ -352,7 +353,7 @@ where
     N: IsNovel<T> + Debug,
     O: MapObserver<Entry = T> + for<'it> AsIter<'it, Item = T> + Debug,
     R: Reducer<T> + Debug,
-    S: UsesInput + HasClientPerfMonitor + HasNamedMetadata + Debug,
+    S: UsesInput + HasClientPerfMonitor + HasNamedMetadata + HasMetadata + Debug,
     T: Default + Copy + Serialize + for<'de> Deserialize<'de> + PartialEq + Debug + 'static,
 {
     fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
@@ -432,10 +433,9 @@ impl<O, S> Feedback<S> for MapFeedback<DifferentIsNovel, O, MaxReducer, S, u8>
 where
     O: MapObserver<Entry = u8> + AsSlice<u8>,
     for<'it> O: AsIter<'it, Item = u8>,
-    S: UsesInput + HasNamedMetadata + HasClientPerfMonitor + Debug,
+    S: UsesInput + HasNamedMetadata + HasMetadata + HasClientPerfMonitor + Debug,
 {
     #[allow(clippy::wrong_self_convention)]
-    #[allow(clippy::needless_range_loop)]
     fn is_interesting<EM, OT>(
         &mut self,
         state: &mut S,
@@ -451,7 +451,6 @@ where
         // 128 bits vectors
         type VectorType = core::simd::u8x16;
 
-        let mut interesting = false;
         // TODO Replace with match_name_type when stable
         let observer = observers.match_name::<O>(&self.observer_name).unwrap();
 
@@ -476,13 +475,11 @@ where
             let reduced = MaxReducer::reduce(*history, item);
             if DifferentIsNovel::is_novel(*history, reduced) {
                 *history = reduced;
-                interesting = true;
-                if self.novelties.is_some() {
-                    self.novelties.as_mut().unwrap().push(i);
-                }
+                novelties.push(i);
             }
         }*/
 
+        let mut novelties = vec![];
         let steps = size / VectorType::LANES;
         let left = size % VectorType::LANES;
 
@@ -492,15 +489,12 @@ where
             let items = VectorType::from_slice(&map[i..]);
 
             if items.simd_max(history) != history {
-                interesting = true;
                 unsafe {
                     for j in i..(i + VectorType::LANES) {
                         let item = *map.get_unchecked(j);
                         if item > *history_map.get_unchecked(j) {
                             *history_map.get_unchecked_mut(j) = item;
-                            if self.novelties.is_some() {
-                                self.novelties.as_mut().unwrap().push(j);
-                            }
+                            novelties.push(j);
                         }
                     }
                 }
@@ -511,35 +505,16 @@ where
             unsafe {
                 let item = *map.get_unchecked(j);
                 if item > *history_map.get_unchecked(j) {
-                    interesting = true;
                     *history_map.get_unchecked_mut(j) = item;
-                    if self.novelties.is_some() {
-                        self.novelties.as_mut().unwrap().push(j);
-                    }
+                    novelties.push(j);
                 }
             }
         }
 
         let initial = observer.initial();
+        let interesting = self.retain_stable_novelties(state, novelties);
         if interesting {
-            let len = history_map.len();
-            let mut filled = 0;
-            for i in 0..len {
-                if history_map[i] != initial {
-                    filled += 1;
-                    if self.indexes.is_some() {
-                        self.indexes.as_mut().unwrap().push(i);
-                    }
-                }
-            }
-            manager.fire(
-                state,
-                Event::UpdateUserStats {
-                    name: self.stats_name.to_string(),
-                    value: UserStats::Ratio(filled, len as u64),
-                    phantom: PhantomData,
-                },
-            )?;
+            self.fire_filled_entries(state, manager, initial)?;
         }
 
         Ok(interesting)
@@ -652,9 +627,18 @@ where
             phantom: PhantomData,
         }
     }
+}
 
+impl<N, O, R, S, T> MapFeedback<N, O, R, S, T>
+where
+    T: PartialEq + Default + Copy + 'static + Serialize + DeserializeOwned + Debug,
+    R: Reducer<T>,
+    O: MapObserver<Entry = T>,
+    for<'it> O: AsIter<'it, Item = T>,
+    N: IsNovel<T>,
+    S: UsesInput + HasNamedMetadata + HasMetadata + HasClientPerfMonitor + Debug,
+{
     #[allow(clippy::wrong_self_convention)]
-    #[allow(clippy::needless_range_loop)]
     #[allow(clippy::trivially_copy_pass_by_ref)]
     fn is_interesting_default<EM, OT>(
         &mut self,
@@ -668,7 +652,6 @@ where
         EM: EventFirer<State = S>,
         OT: ObserversTuple<S>,
     {
-        let mut interesting = false;
         // TODO Replace with match_name_type when stable
         let observer = observers.match_name::<O>(&self.observer_name).unwrap();
 
@@ -683,41 +666,72 @@ where
 
         let history_map = map_state.history_map.as_mut_slice();
 
+        let mut novelties = vec![];
         for (i, (item, history)) in observer.as_iter().zip(history_map.iter_mut()).enumerate() {
             let reduced = R::reduce(*history, *item);
             if N::is_novel(*history, reduced) {
                 *history = reduced;
-                interesting = true;
-                if self.novelties.is_some() {
-                    self.novelties.as_mut().unwrap().push(i);
-                }
+                novelties.push(i);
             }
         }
 
         let initial = observer.initial();
+        let interesting = self.retain_stable_novelties(state, novelties);
         if interesting {
-            let len = history_map.len();
-            let mut filled = 0;
-            for i in 0..len {
-                if history_map[i] != initial {
-                    filled += 1;
-                    if self.indexes.is_some() {
-                        self.indexes.as_mut().unwrap().push(i);
-                    }
-                }
-            }
-            manager.fire(
-                state,
-                Event::UpdateUserStats {
-                    name: self.stats_name.to_string(),
-                    value: UserStats::Ratio(filled, len as u64),
-                    phantom: PhantomData,
-                },
-            )?;
+            self.fire_filled_entries(state, manager, initial)?;
         }
 
         Ok(interesting)
     }
+
+    /// Drop the novelties in entries the calibration found to be unstable, they would flood the corpus.
+    /// Returns if any novelty is left.
+    fn retain_stable_novelties(&mut self, state: &S, mut novelties: Vec<usize>) -> bool {
+        if let Some(meta) = state.metadata().get::<UnstableEntriesMetadata>() {
+            novelties.retain(|idx| !meta.is_unstable(*idx));
+        }
+        let interesting = !novelties.is_empty();
+        if let Some(v) = self.novelties.as_mut() {
+            v.append(&mut novelties);
+        }
+        interesting
+    }
+
+    /// Track the indexes of the filled, stable entries, and report their number
+    fn fire_filled_entries<EM>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        initial: T,
+    ) -> Result<(), Error>
+    where
+        EM: EventFirer<State = S>,
+    {
+        let history_map = &state
+            .named_metadata()
+            .get::<MapFeedbackMetadata<T>>(&self.name)
+            .unwrap()
+            .history_map;
+        let unstable = state.metadata().get::<UnstableEntriesMetadata>();
+        let len = history_map.len();
+        let mut filled = 0;
+        for (i, history) in history_map.iter().enumerate() {
+            if *history != initial && !unstable.map_or(false, |meta| meta.is_unstable(i)) {
+                filled += 1;
+                if let Some(indexes) = self.indexes.as_mut() {
+                    indexes.push(i);
+                }
+            }
+        }
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: self.stats_name.clone(),
+                value: UserStats::Ratio(filled, len as u64),
+                phantom: PhantomData,
+            },
+        )
+    }
 }
 
 /// A [`ReachabilityFeedback`] reports if a target has been reached.
@@ -825,7 +839,22 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::feedbacks::{AllIsNovel, IsNovel, NextPow2IsNovel};
+    use hashbrown::HashSet;
+
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list},
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            AllIsNovel, ConstFeedback, Feedback, IsNovel, MapIndexesMetadata, MaxMapFeedback,
+            NextPow2IsNovel,
+        },
+        inputs::BytesInput,
+        observers::{MapObserver, StdMapObserver},
+        stages::calibrate::UnstableEntriesMetadata,
+        state::{HasMetadata, StdState},
+    };
 
     #[test]
     fn test_map_is_novel() {
@@ -846,6 +875,42 @@ mod tests {
         assert!(NextPow2IsNovel::is_novel(254_u8, 255));
         assert!(!NextPow2IsNovel::is_novel(255_u8, 255));
     }
+
+    #[test]
+    fn test_map_feedback_unstable_entries() {
+        let mut map = [0_u8; 64];
+        let observer = StdMapObserver::new("map", &mut map);
+        let mut feedback = MaxMapFeedback::new_tracking(&observer, true, false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        state.add_metadata(UnstableEntriesMetadata::new(HashSet::from_iter([3]), 64));
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![]);
+        let mut observers = tuple_list!(observer);
+
+        // Only a flaky entry changed
+        *observers.0.get_mut(3) = 1;
+        assert!(!feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        feedback.discard_metadata(&mut state, &input).unwrap();
+
+        *observers.0.get_mut(40) = 1;
+        assert!(feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        let mut testcase = Testcase::new(input);
+        feedback.append_metadata(&mut state, &mut testcase).unwrap();
+        let indexes = testcase.metadata().get::<MapIndexesMetadata>().unwrap();
+        assert_eq!(indexes.list, vec![40]);
+    }
 }
 
 /// `MapFeedback` Python bindings
@@ -20,6 +20,11 @@ pub use new_hash_feedback::NewHashFeedback;
 #[cfg(feature = "std")]
 pub use new_hash_feedback::NewHashFeedbackMetadata;
 
+#[cfg(feature = "std")]
+pub mod triage;
+#[cfg(feature = "std")]
+pub use triage::{CrashRecord, CrashTriageFeedback, CrashTriageMetadata};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 use alloc::string::{String, ToString};
@@ -120,6 +125,17 @@ where
     fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
         Ok(())
     }
+
+    /// Called once the testcase is stored in its corpus at `idx`, i.e. after the corpus assigned its filename
+    #[inline]
+    fn testcase_added(
+        &mut self,
+        _state: &S,
+        _idx: usize,
+        _testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// Has an associated observer name (mostly used to retrieve the observer with `MatchName` from an `ObserverTuple`)
@@ -253,6 +269,17 @@ where
         self.first.discard_metadata(state, input)?;
         self.second.discard_metadata(state, input)
     }
+
+    #[inline]
+    fn testcase_added(
+        &mut self,
+        state: &S,
+        idx: usize,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error> {
+        self.first.testcase_added(state, idx, testcase)?;
+        self.second.testcase_added(state, idx, testcase)
+    }
 }
 
 /// Logical combination of two feedbacks
@@ -661,6 +688,16 @@ where
     fn discard_metadata(&mut self, state: &mut S, input: &S::Input) -> Result<(), Error> {
         self.first.discard_metadata(state, input)
     }
+
+    #[inline]
+    fn testcase_added(
+        &mut self,
+        state: &S,
+        idx: usize,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error> {
+        self.first.testcase_added(state, idx, testcase)
+    }
 }
 
 impl<A, S> Named for NotFeedback<A, S>
@@ -877,6 +914,58 @@ impl Default for TimeoutFeedback {
 /// A feedback factory for timeout feedbacks
 pub type TimeoutFeedbackFactory = DefaultFeedbackFactory<TimeoutFeedback>;
 
+/// An [`OomFeedback`] reports as interesting if the target ran out of memory, i.e. for [`ExitKind::Oom`].
+///
+/// In the objective, next to a [`CrashFeedback`], out-of-memory inputs end up in the solutions corpus with the crashes.
+/// To store them apart, use [`crate::corpus::OnDiskCorpus::with_oom_dir`] for the solutions corpus.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OomFeedback {}
+
+impl<S> Feedback<S> for OomFeedback
+where
+    S: UsesInput + HasClientPerfMonitor,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(*exit_kind == ExitKind::Oom)
+    }
+}
+
+impl Named for OomFeedback {
+    #[inline]
+    fn name(&self) -> &str {
+        "OomFeedback"
+    }
+}
+
+impl OomFeedback {
+    /// Creates a new [`OomFeedback`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for OomFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A feedback factory for out-of-memory feedbacks
+pub type OomFeedbackFactory = DefaultFeedbackFactory<OomFeedback>;
+
 /// Nop feedback that annotates execution time in the new testcase, if any
 /// for this Feedback, the testcase is never interesting (use with an OR).
 /// It decides, if the given [`TimeObserver`] value of a run is interesting.
@@ -1653,6 +1742,15 @@ pub mod pybind {
         ) -> Result<(), Error> {
             unwrap_me_mut!(self.wrapper, f, { f.discard_metadata(state, input) })
         }
+
+        fn testcase_added(
+            &mut self,
+            state: &PythonStdState,
+            idx: usize,
+            testcase: &mut Testcase<BytesInput>,
+        ) -> Result<(), Error> {
+            unwrap_me_mut!(self.wrapper, f, { f.testcase_added(state, idx, testcase) })
+        }
     }
 
     /// Register the classes to the python module
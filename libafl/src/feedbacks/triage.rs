@@ -0,0 +1,574 @@
+//! The ``CrashTriageFeedback`` buckets objectives by their [`CrashReport`], so that
+//! crashes with the same root cause can be deduplicated and triaged together.
+//!
+//! Each crash is saved as a [`CrashRecord`] in JSON next to the solutions, where it can be
+//! loaded again with [`load_records`] and bucketed with [`bucket_records`], e.g. by the
+//! `crash_triage` utility. The number of crashes per bucket, including the ones deduplicated away,
+//! is saved in the same directory, see [`load_bucket_counts`].
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{hash::Hasher, marker::PhantomData};
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ahash::AHasher;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::tuples::Named,
+    corpus::{AflNameMetadata, Testcase},
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverName},
+    inputs::{Input, UsesInput},
+    observers::{CrashReport, ObserverWithCrashReport, ObserversTuple},
+    state::{HasClientPerfMonitor, HasMetadata},
+    Error,
+};
+
+/// The prefix of the feedback names
+pub const CRASHTRIAGEFEEDBACK_PREFIX: &str = "crashtriagefeedback_";
+
+/// The default number of top frames used to bucket crashes
+pub const DEFAULT_BUCKET_DEPTH: usize = 5;
+
+/// The file in the record directory the [`CrashTriageMetadata`] is saved to
+pub const BUCKET_COUNTS_FILE: &str = ".crash_buckets";
+
+/// A crash, as reported by an [`ObserverWithCrashReport`], together with the name of its input
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CrashRecord {
+    /// The name of the crashing input
+    pub input_name: String,
+    /// The report of the crash
+    pub report: CrashReport,
+}
+
+crate::impl_serdeany!(CrashRecord);
+
+impl CrashRecord {
+    /// Create a new [`CrashRecord`]
+    #[must_use]
+    pub fn new(input_name: String, report: CrashReport) -> Self {
+        Self { input_name, report }
+    }
+
+    /// The bucket of this crash: a hash over the bug class and the top `depth` frames.
+    /// Frames are identified by their function name if symbolized, by their address otherwise.
+    #[must_use]
+    pub fn bucket_hash(&self, depth: usize) -> u64 {
+        bucket_hash(&self.report, depth)
+    }
+}
+
+/// Hash the bug class and the top `depth` frames of a [`CrashReport`]
+#[must_use]
+pub fn bucket_hash(report: &CrashReport, depth: usize) -> u64 {
+    let mut hasher = AHasher::new_with_keys(0, 0);
+    if let Some(bug_class) = &report.bug_class {
+        hasher.write(bug_class.as_bytes());
+    }
+    hasher.write_u8(0);
+    for frame in report.frames.iter().take(depth) {
+        match &frame.function {
+            Some(function) => hasher.write(function.as_bytes()),
+            None => hasher.write_u64(frame.address),
+        }
+        hasher.write_u8(0);
+    }
+    hasher.finish()
+}
+
+/// A set of crashes sharing the same [`CrashRecord::bucket_hash`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CrashBucket {
+    /// The hash identifying this bucket
+    pub hash: u64,
+    /// The number of crashes that fell in this bucket
+    pub count: usize,
+    /// The first crash that fell in this bucket
+    pub representative: CrashRecord,
+}
+
+/// Group the given records into [`CrashBucket`]`s`, sorted by decreasing count
+#[must_use]
+pub fn bucket_records<'a, I>(records: I, depth: usize) -> Vec<CrashBucket>
+where
+    I: IntoIterator<Item = &'a CrashRecord>,
+{
+    bucket_records_with_counts(records, depth, None)
+}
+
+/// Group the given records into [`CrashBucket`]`s`, sorted by decreasing count.
+/// The crashes counted in `counts` that have no record, because they were deduplicated away,
+/// are added to the bucket of the first record sharing their bucket in `counts`.
+#[must_use]
+pub fn bucket_records_with_counts<'a, I>(
+    records: I,
+    depth: usize,
+    counts: Option<&CrashTriageMetadata>,
+) -> Vec<CrashBucket>
+where
+    I: IntoIterator<Item = &'a CrashRecord>,
+{
+    let records: Vec<&CrashRecord> = records.into_iter().collect();
+    // The number of records per bucket of the counts
+    let mut recorded: HashMap<u64, usize> = HashMap::new();
+    if let Some(counts) = counts {
+        for record in &records {
+            *recorded
+                .entry(record.bucket_hash(counts.depth))
+                .or_insert(0) += 1;
+        }
+    }
+
+    let mut buckets: Vec<CrashBucket> = vec![];
+    let mut indexes: HashMap<u64, usize> = HashMap::new();
+    for record in records {
+        let mut count = 1;
+        if let Some(counts) = counts {
+            let counted_hash = record.bucket_hash(counts.depth);
+            // Only once per bucket of the counts
+            if let Some(records) = recorded.remove(&counted_hash) {
+                let counted = counts.buckets.get(&counted_hash).copied().unwrap_or(0);
+                count += counted.saturating_sub(records);
+            }
+        }
+        let hash = record.bucket_hash(depth);
+        if let Some(&idx) = indexes.get(&hash) {
+            buckets[idx].count += count;
+        } else {
+            indexes.insert(hash, buckets.len());
+            buckets.push(CrashBucket {
+                hash,
+                count,
+                representative: record.clone(),
+            });
+        }
+    }
+    buckets.sort_by_key(|bucket| core::cmp::Reverse(bucket.count));
+    buckets
+}
+
+/// Load all the [`CrashRecord`]`s` written by a [`CrashTriageFeedback`] to the given directory
+pub fn load_records<P>(dir: P) -> Result<Vec<CrashRecord>, Error>
+where
+    P: AsRef<Path>,
+{
+    let mut records = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "json") {
+            let record: CrashRecord = serde_json::from_slice(&fs::read(&path)?)?;
+            records.push(record);
+        }
+    }
+    records.sort_by(|a, b| a.input_name.cmp(&b.input_name));
+    Ok(records)
+}
+
+/// Load the [`CrashTriageMetadata`] a [`CrashTriageFeedback`] saved to the given record directory, if any
+pub fn load_bucket_counts<P>(dir: P) -> Result<Option<CrashTriageMetadata>, Error>
+where
+    P: AsRef<Path>,
+{
+    let path = dir.as_ref().join(BUCKET_COUNTS_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+}
+
+fn default_bucket_depth() -> usize {
+    DEFAULT_BUCKET_DEPTH
+}
+
+/// The buckets of the crashes found so far, stored in the state by the [`CrashTriageFeedback`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashTriageMetadata {
+    /// The number of crashes seen per bucket hash
+    pub buckets: HashMap<u64, usize>,
+    /// The number of top frames the buckets are hashed over
+    #[serde(default = "default_bucket_depth")]
+    pub depth: usize,
+}
+
+crate::impl_serdeany!(CrashTriageMetadata);
+
+impl Default for CrashTriageMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrashTriageMetadata {
+    /// Create a new [`CrashTriageMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_depth(DEFAULT_BUCKET_DEPTH)
+    }
+
+    /// Create a new [`CrashTriageMetadata`] for buckets over the top `depth` frames
+    #[must_use]
+    pub fn with_depth(depth: usize) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            depth,
+        }
+    }
+
+    /// The number of crashes seen so far
+    #[must_use]
+    pub fn crashes(&self) -> usize {
+        self.buckets.values().sum()
+    }
+}
+
+/// A [`CrashTriageFeedback`] collects the [`CrashReport`] of each objective, buckets it by
+/// its top frames and attaches a [`CrashRecord`] to the testcase.
+/// With deduplication enabled, only crashes falling in a new bucket are considered interesting.
+/// A crash is counted in its bucket once the objective accepted it, or when it was deduplicated away.
+/// Combine it with a [`crate::feedbacks::CrashFeedback`] using `feedback_and_fast!`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashTriageFeedback<O, S> {
+    name: String,
+    observer_name: String,
+    depth: usize,
+    deduplicate: bool,
+    record_dir: Option<PathBuf>,
+    /// The report of the last run, until its testcase is added or discarded
+    last_report: Option<CrashReport>,
+    /// The bucket of the last run, and if it was deduplicated away
+    last_bucket: Option<(u64, bool)>,
+    o_type: PhantomData<(O, S)>,
+}
+
+impl<O, S> Feedback<S> for CrashTriageFeedback<O, S>
+where
+    O: ObserverWithCrashReport + Named + Debug,
+    S: UsesInput + Debug + HasMetadata + HasClientPerfMonitor,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if !state.has_metadata::<CrashTriageMetadata>() {
+            state.add_metadata(CrashTriageMetadata::with_depth(self.depth));
+        }
+        if let Some(dir) = &self.record_dir {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &<S as UsesInput>::Input,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        let observer = observers
+            .match_name::<O>(&self.observer_name)
+            .expect("A CrashTriageFeedback needs an ObserverWithCrashReport");
+
+        self.last_report = observer.crash_report().cloned();
+        self.last_bucket = None;
+        let report = match &self.last_report {
+            Some(report) => report,
+            // No report, i.e. the run did not crash
+            None => return Ok(false),
+        };
+
+        // The crash is only counted once the rest of the objective decided
+        let hash = bucket_hash(report, self.depth);
+        let duplicate = self.deduplicate
+            && state
+                .metadata()
+                .get::<CrashTriageMetadata>()
+                .ok_or_else(|| Error::key_not_found("CrashTriageMetadata not found".to_string()))?
+                .buckets
+                .contains_key(&hash);
+        self.last_bucket = Some((hash, duplicate));
+        Ok(!duplicate)
+    }
+
+    /// Attach the signal before the solution is stored, so that the corpus can use it to name the file
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error> {
+        if let Some((hash, _)) = self.last_bucket.take() {
+            self.count_crash(state, hash)?;
+        }
+        if let Some(signal) = self.last_report.as_ref().and_then(|report| report.signal) {
+            AflNameMetadata::of_mut(testcase).sig = Some(signal);
+        }
+        Ok(())
+    }
+
+    /// Name the [`CrashRecord`] once the solution is stored, after the solution file
+    fn testcase_added(
+        &mut self,
+        _state: &S,
+        idx: usize,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error> {
+        let report = match self.last_report.take() {
+            Some(report) => report,
+            None => return Ok(()),
+        };
+
+        let input_name = match testcase.filename() {
+            Some(filename) => Path::new(filename)
+                .file_name()
+                .map_or_else(|| filename.clone(), |f| f.to_string_lossy().to_string()),
+            None => testcase
+                .input()
+                .as_ref()
+                .map_or_else(String::new, |input| input.generate_name(idx)),
+        };
+
+        let record = CrashRecord::new(input_name, report);
+        if let Some(dir) = &self.record_dir {
+            let path = dir.join(format!("{}.json", record.input_name));
+            fs::write(path, serde_json::to_vec_pretty(&record)?)?;
+        }
+        testcase.add_metadata(record);
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.last_report = None;
+        // Count the crashes deduplicated away, but not the ones the rest of the objective rejected
+        if let Some((hash, true)) = self.last_bucket.take() {
+            self.count_crash(state, hash)?;
+        }
+        Ok(())
+    }
+}
+
+impl<O, S> Named for CrashTriageFeedback<O, S> {
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<O, S> HasObserverName for CrashTriageFeedback<O, S> {
+    #[inline]
+    fn observer_name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+impl<O, S> CrashTriageFeedback<O, S>
+where
+    O: ObserverWithCrashReport + Named + Debug,
+{
+    /// Returns a new [`CrashTriageFeedback`], bucketing all crashes without deduplication.
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self {
+            name: CRASHTRIAGEFEEDBACK_PREFIX.to_string() + observer.name(),
+            observer_name: observer.name().to_string(),
+            depth: DEFAULT_BUCKET_DEPTH,
+            deduplicate: false,
+            record_dir: None,
+            last_report: None,
+            last_bucket: None,
+            o_type: PhantomData,
+        }
+    }
+
+    /// Bucket crashes by their top `depth` frames
+    #[must_use]
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Only consider crashes falling in a new bucket interesting
+    #[must_use]
+    pub fn with_deduplication(mut self) -> Self {
+        self.deduplicate = true;
+        self
+    }
+
+    /// Write each [`CrashRecord`] as `<solution file name>.json` to the given directory,
+    /// and the number of crashes per bucket to [`BUCKET_COUNTS_FILE`] in it
+    #[must_use]
+    pub fn with_record_dir<P>(mut self, dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.record_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Count a crash in its bucket, and save the counts to the record directory
+    fn count_crash<ST>(&self, state: &mut ST, hash: u64) -> Result<(), Error>
+    where
+        ST: HasMetadata,
+    {
+        let meta = state
+            .metadata_mut()
+            .get_mut::<CrashTriageMetadata>()
+            .ok_or_else(|| Error::key_not_found("CrashTriageMetadata not found".to_string()))?;
+        *meta.buckets.entry(hash).or_insert(0) += 1;
+        if let Some(dir) = &self.record_dir {
+            fs::write(dir.join(BUCKET_COUNTS_FILE), serde_json::to_vec(meta)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use std::{env, fs};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{
+        bucket_records, bucket_records_with_counts, load_bucket_counts, load_records, CrashRecord,
+        CrashTriageFeedback, DEFAULT_BUCKET_DEPTH,
+    };
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, Named},
+        },
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{ConstFeedback, Feedback},
+        inputs::{BytesInput, UsesInput},
+        observers::{CrashFrame, CrashReport, Observer, ObserverWithCrashReport},
+        state::StdState,
+    };
+
+    /// Reports a preset crash
+    #[derive(Serialize, Deserialize, Debug)]
+    struct ReportObserver {
+        report: Option<CrashReport>,
+    }
+
+    impl Named for ReportObserver {
+        fn name(&self) -> &str {
+            "report"
+        }
+    }
+
+    impl<S> Observer<S> for ReportObserver where S: UsesInput {}
+
+    impl ObserverWithCrashReport for ReportObserver {
+        fn crash_report(&self) -> Option<&CrashReport> {
+            self.report.as_ref()
+        }
+    }
+
+    fn report(bug_class: &str, functions: &[&str]) -> CrashReport {
+        CrashReport {
+            bug_class: Some(bug_class.to_string()),
+            frames: functions
+                .iter()
+                .enumerate()
+                .map(|(i, f)| CrashFrame {
+                    address: i as u64,
+                    function: Some((*f).to_string()),
+                    location: None,
+                })
+                .collect(),
+            ..CrashReport::default()
+        }
+    }
+
+    fn record(name: &str, bug_class: &str, functions: &[&str]) -> CrashRecord {
+        CrashRecord::new(name.to_string(), report(bug_class, functions))
+    }
+
+    #[test]
+    fn test_bucket_records() {
+        let records = [
+            record("a", "heap-buffer-overflow", &["parse", "main", "x"]),
+            record("b", "SEGV", &["parse", "main"]),
+            record("c", "heap-buffer-overflow", &["parse", "main", "y"]),
+            record("d", "heap-buffer-overflow", &["parse", "main", "z"]),
+        ];
+
+        let buckets = bucket_records(&records, 3);
+        assert_eq!(buckets.len(), 4);
+
+        let buckets = bucket_records(&records, 2);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].count, 3);
+        assert_eq!(buckets[0].representative.input_name, "a");
+        assert_eq!(buckets[1].count, 1);
+        assert_eq!(buckets[1].representative.input_name, "b");
+    }
+
+    #[test]
+    fn test_crash_triage_counts() {
+        let dir = env::temp_dir().join(format!("libafl_triage_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut observers = tuple_list!(ReportObserver { report: None });
+        let mut feedback = CrashTriageFeedback::new(&observers.0)
+            .with_deduplication()
+            .with_record_dir(&dir);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut feedback,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![]);
+
+        // The objective accepts the first crash, and then two of its duplicates
+        observers.0.report = Some(report("SEGV", &["parse", "main"]));
+        for run in 0..3 {
+            let interesting = feedback
+                .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Crash)
+                .unwrap();
+            assert_eq!(interesting, run == 0);
+            if interesting {
+                let mut testcase = Testcase::new(input.clone());
+                feedback.append_metadata(&mut state, &mut testcase).unwrap();
+                feedback.testcase_added(&state, 0, &mut testcase).unwrap();
+            } else {
+                feedback.discard_metadata(&mut state, &input).unwrap();
+            }
+        }
+        // A new crash the rest of the objective rejects is not counted
+        observers.0.report = Some(report("SEGV", &["other"]));
+        assert!(feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Crash)
+            .unwrap());
+        feedback.discard_metadata(&mut state, &input).unwrap();
+
+        let records = load_records(&dir).unwrap();
+        assert_eq!(records.len(), 1);
+        let counts = load_bucket_counts(&dir).unwrap().unwrap();
+        assert_eq!(counts.buckets.len(), 1);
+        let buckets = bucket_records_with_counts(&records, DEFAULT_BUCKET_DEPTH, Some(&counts));
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].count, 3);
+        assert_eq!(bucket_records(&records, DEFAULT_BUCKET_DEPTH)[0].count, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
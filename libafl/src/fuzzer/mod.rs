@@ -13,7 +13,7 @@ use crate::monitors::PerfFeature;
 use crate::state::NopState;
 use crate::{
     bolts::current_time,
-    corpus::{Corpus, Testcase},
+    corpus::{Corpus, ParentIdMetadata, Testcase},
     events::{Event, EventConfig, EventFirer, EventProcessor, ProgressReporter},
     executors::{Executor, ExitKind, HasObservers},
     feedbacks::Feedback,
@@ -374,8 +374,16 @@ where
 
                 // Add the input to the main corpus
                 let mut testcase = Testcase::with_executions(input.clone(), *state.executions());
+                if let Some(parent) = *state.corpus().current() {
+                    testcase.add_metadata(ParentIdMetadata::new(parent));
+                }
                 self.feedback_mut().append_metadata(state, &mut testcase)?;
                 let idx = state.corpus_mut().add(testcase)?;
+                self.feedback_mut().testcase_added(
+                    state,
+                    idx,
+                    &mut state.corpus().get(idx)?.borrow_mut(),
+                )?;
                 self.scheduler_mut().on_add(state, idx)?;
 
                 if send_events {
@@ -406,8 +414,17 @@ where
 
                 // The input is a solution, add it to the respective corpus
                 let mut testcase = Testcase::with_executions(input, *state.executions());
+                testcase.add_metadata(*exit_kind);
+                if let Some(parent) = *state.corpus().current() {
+                    testcase.add_metadata(ParentIdMetadata::new(parent));
+                }
                 self.objective_mut().append_metadata(state, &mut testcase)?;
-                state.solutions_mut().add(testcase)?;
+                let idx = state.solutions_mut().add(testcase)?;
+                self.objective_mut().testcase_added(
+                    state,
+                    idx,
+                    &mut state.solutions().get(idx)?.borrow_mut(),
+                )?;
 
                 if send_events {
                     manager.fire(
@@ -494,6 +511,11 @@ where
         let mut testcase = Testcase::with_executions(input.clone(), *state.executions());
         self.feedback_mut().append_metadata(state, &mut testcase)?;
         let idx = state.corpus_mut().add(testcase)?;
+        self.feedback_mut().testcase_added(
+            state,
+            idx,
+            &mut state.corpus().get(idx)?.borrow_mut(),
+        )?;
         self.scheduler_mut().on_add(state, idx)?;
 
         let observers_buf = if manager.configuration() == EventConfig::AlwaysUnique {
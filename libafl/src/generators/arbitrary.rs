@@ -0,0 +1,48 @@
+//! Generators for [`ArbitraryInput`]s, creating values from random bytes
+use alloc::vec::Vec;
+use core::{fmt::Debug, marker::PhantomData};
+
+use arbitrary::Arbitrary;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    bolts::rands::Rand, generators::Generator, inputs::ArbitraryInput, state::HasRand, Error,
+};
+
+/// Generates [`ArbitraryInput`]s from random bytes
+#[derive(Clone, Debug)]
+pub struct ArbitraryGenerator<T> {
+    max_size: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<S, T> Generator<ArbitraryInput<T>, S> for ArbitraryGenerator<T>
+where
+    S: HasRand,
+    T: for<'a> Arbitrary<'a> + Serialize + DeserializeOwned + Clone + Debug,
+{
+    fn generate(&mut self, state: &mut S) -> Result<ArbitraryInput<T>, Error> {
+        let size = state.rand_mut().below(self.max_size as u64 + 1);
+        let data: Vec<u8> = (0..size)
+            .map(|_| state.rand_mut().below(256) as u8)
+            .collect();
+        ArbitraryInput::from_arbitrary_bytes(&data)
+    }
+
+    /// Generates the value created from empty data
+    fn generate_dummy(&self, _state: &mut S) -> ArbitraryInput<T> {
+        ArbitraryInput::from_arbitrary_bytes(&[])
+            .expect("The type cannot be created from empty data")
+    }
+}
+
+impl<T> ArbitraryGenerator<T> {
+    /// Returns a new [`ArbitraryGenerator`], generating values from up to `max_size` random bytes.
+    #[must_use]
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            phantom: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,1383 @@
+//! Grammars loaded at runtime from `ANTLR4` (`.g4`) or `EBNF` files, and a generator of [`GrammarInput`]s.
+//!
+//! Unlike the `nautilus` feature, this needs neither external tooling to convert the grammar, nor AGPL-licensed code.
+//! Grammars are parsed directly and their `EBNF` operators (groups, optionals and repetitions) are lowered to
+//! plain rules, so that each node of a [`DerivationTree`] is the derivation of one rule.
+//!
+//! For `ANTLR4`, parser and lexer rules, fragments, character sets, ranges, negations and the wildcard are supported;
+//! actions, predicates, labels, options and lexer modes are ignored. If the grammar skips whitespace (`-> skip`),
+//! a space is emitted between the elements of parser rules.
+//! For `EBNF`, the ISO (`rule = a , [ b ] , { c } ;`), W3C (`rule ::= a b? c*`) and BNF (`<rule> ::= ...`) flavours
+//! are understood, with `[a-z]` or `[#x20-#x7E]` character sets.
+//!
+//! Negated character sets and the wildcard generate printable ASCII characters, tabs and newlines.
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cmp::max, marker::PhantomData};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::rands::Rand,
+    generators::Generator,
+    inputs::{DerivationNode, DerivationTree, GrammarInput},
+    state::HasRand,
+    Error,
+};
+
+/// The default maximum depth of generated derivation trees
+pub const DEFAULT_MAX_DEPTH: usize = 16;
+
+/// The default maximum number of rule nodes of generated derivation trees.
+/// Once reached, only the shortest derivations are chosen.
+pub const DEFAULT_MAX_NODES: usize = 512;
+
+/// The characters generated for negated character sets and the wildcard
+const UNIVERSE: &[(char, char)] = &[('\t', '\n'), ('\r', '\r'), (' ', '~')];
+
+/// A symbol of an alternative of a [`GrammarRule`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum GrammarSymbol {
+    /// Bytes emitted as they are
+    Literal(Vec<u8>),
+    /// A single character out of the given inclusive ranges, emitted as `UTF-8`
+    CharSet(Vec<(char, char)>),
+    /// The index of another rule
+    Rule(usize),
+}
+
+/// A rule of a [`Grammar`], with its alternatives
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GrammarRule {
+    /// The name of the rule. Rules introduced for groups, optionals and repetitions are named
+    /// after their rule, with a `#` and a counter appended.
+    pub name: String,
+    /// The alternatives, each a sequence of symbols
+    pub alternatives: Vec<Vec<GrammarSymbol>>,
+}
+
+/// A context-free grammar, loaded from an `ANTLR4` or `EBNF` grammar
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Grammar {
+    rules: Vec<GrammarRule>,
+    start: usize,
+    /// The depth of the shortest derivation of each rule
+    min_depths: Vec<usize>,
+    /// The depth of the shortest derivation of each alternative of each rule, `usize::MAX` if it never terminates
+    alternative_depths: Vec<Vec<usize>>,
+}
+
+impl Grammar {
+    /// Load a grammar from an `ANTLR4` grammar. The start rule is the first parser rule.
+    pub fn from_antlr4(source: &str) -> Result<Self, Error> {
+        Self::parse(Dialect::Antlr4, &[source])
+    }
+
+    /// Load a grammar from an `EBNF` grammar. The start rule is the first rule.
+    pub fn from_ebnf(source: &str) -> Result<Self, Error> {
+        Self::parse(Dialect::Ebnf, &[source])
+    }
+
+    /// Load a grammar from a file, as `ANTLR4` if it has the `.g4` extension, as `EBNF` otherwise
+    #[cfg(feature = "std")]
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_files(&[path])
+    }
+
+    /// Load a grammar split across several files, e.g. the parser and the lexer grammar of an `ANTLR4` grammar.
+    /// The dialect is taken from the extension of the first file, the start rule is the first one of the first file.
+    #[cfg(feature = "std")]
+    pub fn from_files<P>(paths: &[P]) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let dialect = match paths.first() {
+            Some(path) if path.as_ref().extension().map_or(false, |ext| ext == "g4") => {
+                Dialect::Antlr4
+            }
+            Some(_) => Dialect::Ebnf,
+            None => return Err(Error::illegal_argument("No grammar files given")),
+        };
+        let sources = paths
+            .iter()
+            .map(fs::read_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+        Self::parse(dialect, &sources)
+    }
+
+    /// Use the rule with the given name as start rule
+    pub fn with_start(mut self, name: &str) -> Result<Self, Error> {
+        self.start = self
+            .rule_index(name)
+            .ok_or_else(|| Error::key_not_found(format!("No rule named {name}")))?;
+        if self.min_depths[self.start] == usize::MAX {
+            return Err(Error::illegal_argument(format!(
+                "The rule {name} never terminates"
+            )));
+        }
+        Ok(self)
+    }
+
+    /// The rules of this grammar
+    #[must_use]
+    pub fn rules(&self) -> &[GrammarRule] {
+        &self.rules
+    }
+
+    /// The index of the start rule
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The index of the rule with the given name
+    #[must_use]
+    pub fn rule_index(&self, name: &str) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.name == name)
+    }
+
+    /// The depth of the shortest derivation of the given rule, `usize::MAX` if it never terminates
+    #[must_use]
+    pub fn min_depth(&self, rule: usize) -> usize {
+        self.min_depths[rule]
+    }
+
+    /// The depth of the shortest derivation of the given alternative of a rule, `usize::MAX` if it never terminates
+    #[must_use]
+    pub fn alternative_depth(&self, rule: usize, alternative: usize) -> usize {
+        self.alternative_depths[rule][alternative]
+    }
+
+    fn parse(dialect: Dialect, sources: &[&str]) -> Result<Self, Error> {
+        let mut parsed = vec![];
+        for source in sources {
+            let mut parser = Parser::new(dialect, source);
+            match dialect {
+                Dialect::Antlr4 => parser.antlr4_grammar(&mut parsed)?,
+                Dialect::Ebnf => parser.ebnf_grammar(&mut parsed)?,
+            }
+        }
+        Self::lower(&parsed)
+    }
+
+    /// Lower the parsed rules to plain alternatives, introducing rules for nested operators
+    fn lower(parsed: &[ParsedRule]) -> Result<Self, Error> {
+        let mut lowering = Lowering {
+            names: HashMap::default(),
+            rules: vec![],
+            separator: None,
+        };
+        for rule in parsed {
+            if !lowering.names.contains_key(&rule.name) {
+                lowering
+                    .names
+                    .insert(rule.name.clone(), lowering.rules.len());
+                lowering.rules.push(GrammarRule {
+                    name: rule.name.clone(),
+                    alternatives: vec![],
+                });
+            }
+            if rule.skipped {
+                lowering.separator = Some(b" ".to_vec());
+            }
+        }
+        let start = parsed
+            .iter()
+            .find(|rule| !rule.lexer)
+            .or_else(|| parsed.first())
+            .map(|rule| lowering.names[&rule.name])
+            .ok_or_else(|| Error::illegal_argument("The grammar has no rules"))?;
+
+        for rule in parsed {
+            let idx = lowering.names[&rule.name];
+            let separated = !rule.lexer;
+            let alternatives = lowering.alternatives(&rule.name, &rule.expr, separated)?;
+            lowering.rules[idx].alternatives.extend(alternatives);
+        }
+
+        let rules = lowering.rules;
+        for rule in &rules {
+            if rule.alternatives.is_empty() {
+                return Err(Error::illegal_argument(format!(
+                    "The rule {} has no alternatives",
+                    rule.name
+                )));
+            }
+        }
+
+        // The shortest derivations, as a fixpoint over all rules
+        let mut min_depths = vec![usize::MAX; rules.len()];
+        let alternative_depth = |min_depths: &[usize], alternative: &[GrammarSymbol]| {
+            let mut depth = 1;
+            for symbol in alternative {
+                if let GrammarSymbol::Rule(rule) = symbol {
+                    depth = max(depth, min_depths[*rule].saturating_add(1));
+                }
+            }
+            depth
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, rule) in rules.iter().enumerate() {
+                for alternative in &rule.alternatives {
+                    let depth = alternative_depth(&min_depths, alternative);
+                    if depth < min_depths[idx] {
+                        min_depths[idx] = depth;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        let alternative_depths = rules
+            .iter()
+            .map(|rule| {
+                rule.alternatives
+                    .iter()
+                    .map(|alternative| alternative_depth(&min_depths, alternative))
+                    .collect()
+            })
+            .collect();
+
+        if min_depths[start] == usize::MAX {
+            return Err(Error::illegal_argument(format!(
+                "The start rule {} never terminates",
+                rules[start].name
+            )));
+        }
+
+        Ok(Self {
+            rules,
+            start,
+            min_depths,
+            alternative_depths,
+        })
+    }
+}
+
+/// The grammar formats we can parse
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Dialect {
+    Antlr4,
+    Ebnf,
+}
+
+/// A grammar expression, before lowering
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Vec<u8>),
+    CharSet(Vec<(char, char)>),
+    Ref(String),
+    Seq(Vec<Expr>),
+    Alt(Vec<Expr>),
+    Opt(Box<Expr>),
+    Star(Box<Expr>),
+    Plus(Box<Expr>),
+}
+
+/// A rule as written in the grammar
+#[derive(Clone, Debug)]
+struct ParsedRule {
+    name: String,
+    expr: Expr,
+    /// An `ANTLR4` lexer rule, or any `EBNF` rule: its elements are not separated
+    lexer: bool,
+    /// A lexer rule skipped by the `ANTLR4` lexer, e.g. whitespace
+    skipped: bool,
+}
+
+/// A recursive descent parser for both dialects
+struct Parser {
+    dialect: Dialect,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(dialect: Dialect, source: &str) -> Self {
+        Self {
+            dialect,
+            chars: source.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.starts_with(s) {
+            self.pos += s.chars().count();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), Error> {
+        self.skip_ws();
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{s}`")))
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        let line = 1 + self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count();
+        let found = self
+            .peek()
+            .map_or_else(|| "the end".to_string(), |c| format!("`{c}`"));
+        Error::illegal_argument(format!(
+            "Invalid grammar at line {line}: {msg}, found {found}"
+        ))
+    }
+
+    /// Skip whitespace and comments
+    fn skip_ws(&mut self) {
+        loop {
+            if self.peek().map_or(false, char::is_whitespace) {
+                self.pos += 1;
+            } else if self.eat("//") {
+                while self.peek().map_or(false, |c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else if self.eat("/*") {
+                while self.peek().is_some() && !self.eat("*/") {
+                    self.pos += 1;
+                }
+            } else if self.dialect == Dialect::Ebnf && self.eat("(*") {
+                while self.peek().is_some() && !self.eat("*)") {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let start = self.pos;
+        match self.peek() {
+            Some(c) if c.is_alphabetic() || c == '_' => self.pos += 1,
+            _ => return None,
+        }
+        loop {
+            match self.peek() {
+                Some(c) if c.is_alphanumeric() || c == '_' => self.pos += 1,
+                // `rule-name` and `rule.name` in EBNF, but not the exception `a - b`
+                Some('-' | '.')
+                    if self.dialect == Dialect::Ebnf
+                        && self.peek_at(1).map_or(false, char::is_alphanumeric) =>
+                {
+                    self.pos += 1;
+                }
+                _ => break,
+            }
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// An identifier, or a BNF `<rule name>` in EBNF
+    fn rule_name(&mut self) -> Option<String> {
+        if self.dialect == Dialect::Ebnf && self.peek() == Some('<') {
+            let end = self.chars[self.pos..].iter().position(|c| *c == '>')?;
+            let name: String = self.chars[self.pos + 1..self.pos + end].iter().collect();
+            self.pos += end + 1;
+            Some(name.trim().to_string())
+        } else {
+            self.ident()
+        }
+    }
+
+    /// Skip past the next `end` character, outside of brackets and strings
+    fn skip_past(&mut self, end: char) -> Result<(), Error> {
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None => return Err(self.error(&format!("expected `{end}`"))),
+                Some(c) if c == end => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some('{') => self.skip_balanced('{', '}')?,
+                Some('[') => self.skip_balanced('[', ']')?,
+                Some('(') => self.skip_balanced('(', ')')?,
+                Some('\'' | '"') => {
+                    self.literal()?;
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Skip a bracketed block such as an action, including nested blocks and strings
+    fn skip_balanced(&mut self, open: char, close: char) -> Result<(), Error> {
+        let start = self.pos;
+        let mut level = 0_usize;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == '\\' {
+                self.pos += 1;
+            } else if c == open {
+                level += 1;
+            } else if c == close {
+                level -= 1;
+                if level == 0 {
+                    return Ok(());
+                }
+            } else if (c == '"' || c == '\'') && open != '<' {
+                while let Some(s) = self.peek() {
+                    self.pos += 1;
+                    if s == '\\' {
+                        self.pos += 1;
+                    } else if s == c || s == '\n' {
+                        break;
+                    }
+                }
+            }
+        }
+        self.pos = start;
+        Err(self.error(&format!("unbalanced `{open}`")))
+    }
+
+    /// An escaped character, after the backslash
+    fn escape(&mut self) -> Result<char, Error> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("expected an escape"))?;
+        self.pos += 1;
+        Ok(match c {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            '0' => '\0',
+            'u' | 'x' => {
+                let digits: String = if self.eat("{") {
+                    let end = self.chars[self.pos..]
+                        .iter()
+                        .position(|c| *c == '}')
+                        .ok_or_else(|| self.error("unterminated escape"))?;
+                    let digits = self.chars[self.pos..self.pos + end].iter().collect();
+                    self.pos += end + 1;
+                    digits
+                } else {
+                    let len = if c == 'u' { 4 } else { 2 };
+                    let digits = self.chars[self.pos..]
+                        .iter()
+                        .take(len)
+                        .take_while(|c| c.is_ascii_hexdigit())
+                        .collect();
+                    self.pos += len.min(self.chars.len() - self.pos);
+                    digits
+                };
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error("invalid escape"))?
+            }
+            c => c,
+        })
+    }
+
+    /// A quoted literal
+    fn literal(&mut self) -> Result<Vec<u8>, Error> {
+        let quote = self
+            .peek()
+            .ok_or_else(|| self.error("expected a literal"))?;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated literal")),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(value.into_bytes());
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    value.push(self.escape()?);
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    value.push(c);
+                }
+            }
+        }
+    }
+
+    /// A single character of a character set: plain, escaped, or `#xHH` in EBNF
+    fn set_char(&mut self) -> Result<char, Error> {
+        if self.dialect == Dialect::Ebnf && self.starts_with("#x") {
+            self.pos += 2;
+            let len = self.chars[self.pos..]
+                .iter()
+                .take_while(|c| c.is_ascii_hexdigit())
+                .count();
+            let digits: String = self.chars[self.pos..self.pos + len].iter().collect();
+            self.pos += len;
+            return u32::from_str_radix(&digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| self.error("invalid character"));
+        }
+        match self.peek() {
+            None => Err(self.error("unterminated character set")),
+            Some('\\') => {
+                self.pos += 1;
+                self.escape()
+            }
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+        }
+    }
+
+    /// A character set such as `[a-z_]`, or `[^"]` in EBNF
+    fn char_set(&mut self) -> Result<Vec<(char, char)>, Error> {
+        self.pos += 1;
+        let negated = self.dialect == Dialect::Ebnf && self.eat("^");
+        let mut ranges = vec![];
+        while !self.eat("]") {
+            // Unicode properties such as `\p{L}` are approximated by ASCII letters
+            if self.eat("\\p{") || self.eat("\\P{") {
+                self.skip_past('}')?;
+                ranges.push(('a', 'z'));
+                ranges.push(('A', 'Z'));
+                continue;
+            }
+            let from = self.set_char()?;
+            if self.peek() == Some('-') && self.peek_at(1) != Some(']') {
+                self.pos += 1;
+                let to = self.set_char()?;
+                ranges.push((from.min(to), from.max(to)));
+            } else {
+                ranges.push((from, from));
+            }
+        }
+        Ok(if negated { complement(&ranges) } else { ranges })
+    }
+
+    /// Whether a `[` in EBNF starts a character set rather than an optional group
+    fn at_ebnf_char_set(&self) -> bool {
+        let content = self.chars[self.pos + 1..]
+            .iter()
+            .take_while(|c| **c != ']')
+            .collect::<String>();
+        !content.is_empty()
+            && !content.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+            && (content.starts_with('^') || content.contains('-') || content.contains("#x"))
+    }
+
+    /// Whether a new `EBNF` rule starts here, ending the current one if it has no terminator
+    fn at_ebnf_rule_start(&mut self) -> bool {
+        let start = self.pos;
+        let is_start = self.rule_name().is_some() && {
+            self.skip_ws();
+            self.starts_with("::=")
+                || self.starts_with(":=")
+                || (self.starts_with("=") && !self.starts_with("=="))
+                || self.starts_with(":")
+        };
+        self.pos = start;
+        is_start
+    }
+
+    fn ebnf_grammar(&mut self, rules: &mut Vec<ParsedRule>) -> Result<(), Error> {
+        loop {
+            self.skip_ws();
+            if self.peek().is_none() {
+                return Ok(());
+            }
+            let name = self
+                .rule_name()
+                .ok_or_else(|| self.error("expected a rule name"))?;
+            self.skip_ws();
+            if !(self.eat("::=") || self.eat(":=") || self.eat("=") || self.eat(":")) {
+                return Err(self.error("expected `=` or `::=`"));
+            }
+            let expr = self.alternatives(&mut false)?;
+            self.skip_ws();
+            if !self.eat(";") {
+                self.eat(".");
+            }
+            rules.push(ParsedRule {
+                name,
+                expr,
+                lexer: true,
+                skipped: false,
+            });
+        }
+    }
+
+    fn antlr4_grammar(&mut self, rules: &mut Vec<ParsedRule>) -> Result<(), Error> {
+        loop {
+            self.skip_ws();
+            if self.peek().is_none() {
+                return Ok(());
+            }
+            if self.eat("@") {
+                // Named actions such as `@header { ... }` or `@lexer::members { ... }`
+                self.ident();
+                if self.eat("::") {
+                    self.ident();
+                }
+                self.skip_ws();
+                self.skip_balanced('{', '}')?;
+                continue;
+            }
+
+            let mut name = self
+                .ident()
+                .ok_or_else(|| self.error("expected a rule name"))?;
+            self.skip_ws();
+            match name.as_str() {
+                "grammar" | "lexer" | "parser" | "import" | "mode" if self.peek() != Some(':') => {
+                    self.skip_past(';')?;
+                    continue;
+                }
+                "options" | "channels" if self.peek() == Some('{') => {
+                    self.skip_balanced('{', '}')?;
+                    continue;
+                }
+                "tokens" if self.peek() == Some('{') => {
+                    // Tokens without a lexer rule, emitted by actions: we generate nothing for them
+                    self.pos += 1;
+                    loop {
+                        self.skip_ws();
+                        if self.eat("}") {
+                            break;
+                        }
+                        if self.eat(",") {
+                            continue;
+                        }
+                        let token = self.ident().ok_or_else(|| self.error("expected a token"))?;
+                        rules.push(ParsedRule {
+                            name: token,
+                            expr: Expr::Seq(vec![]),
+                            lexer: true,
+                            skipped: false,
+                        });
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            while matches!(
+                name.as_str(),
+                "fragment" | "public" | "private" | "protected"
+            ) {
+                name = self
+                    .ident()
+                    .ok_or_else(|| self.error("expected a rule name"))?;
+                self.skip_ws();
+            }
+
+            // Arguments, `returns`, `locals`, options and actions before the colon
+            self.skip_past(':')?;
+            let mut skipped = false;
+            let expr = self.alternatives(&mut skipped)?;
+            self.expect(";")?;
+            loop {
+                self.skip_ws();
+                let start = self.pos;
+                match self.ident().as_deref() {
+                    Some("catch") => {
+                        self.skip_ws();
+                        self.skip_balanced('[', ']')?;
+                        self.skip_ws();
+                        self.skip_balanced('{', '}')?;
+                    }
+                    Some("finally") => {
+                        self.skip_ws();
+                        self.skip_balanced('{', '}')?;
+                    }
+                    _ => {
+                        self.pos = start;
+                        break;
+                    }
+                }
+            }
+
+            let lexer = name.starts_with(|c: char| c.is_uppercase());
+            rules.push(ParsedRule {
+                name,
+                expr,
+                lexer,
+                skipped: lexer && skipped,
+            });
+        }
+    }
+
+    /// Alternatives separated by `|`. `skipped` is set by `ANTLR4` lexer commands skipping the token.
+    fn alternatives(&mut self, skipped: &mut bool) -> Result<Expr, Error> {
+        let mut alternatives = vec![self.sequence(skipped)?];
+        loop {
+            self.skip_ws();
+            if !self.eat("|") {
+                break;
+            }
+            alternatives.push(self.sequence(skipped)?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            Expr::Alt(alternatives)
+        })
+    }
+
+    fn sequence(&mut self, skipped: &mut bool) -> Result<Expr, Error> {
+        let mut items = vec![];
+        loop {
+            self.skip_ws();
+            let element = match (self.dialect, self.peek()) {
+                (_, None | Some('|' | ')' | ';')) | (Dialect::Ebnf, Some(']' | '}')) => break,
+                (Dialect::Antlr4, _) => self.antlr4_element(skipped)?,
+                (Dialect::Ebnf, Some('.')) if self.peek_at(1).map_or(true, char::is_whitespace) => {
+                    break
+                }
+                (Dialect::Ebnf, _) => {
+                    if self.at_ebnf_rule_start() {
+                        break;
+                    }
+                    self.ebnf_element(skipped)?
+                }
+            };
+            if let Some(element) = element {
+                items.push(self.suffix(element));
+            }
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            Expr::Seq(items)
+        })
+    }
+
+    /// The `?`, `*` and `+` operators after an element, directly following it in `EBNF`
+    fn suffix(&mut self, mut element: Expr) -> Expr {
+        loop {
+            if self.dialect == Dialect::Antlr4 {
+                self.skip_ws();
+            }
+            element = if self.eat("?") {
+                Expr::Opt(Box::new(element))
+            } else if self.eat("*") {
+                Expr::Star(Box::new(element))
+            } else if self.eat("+") {
+                Expr::Plus(Box::new(element))
+            } else {
+                return element;
+            };
+            if self.dialect == Dialect::Antlr4 {
+                // Non-greedy operators generate the same
+                self.eat("?");
+            }
+        }
+    }
+
+    /// An element of an `ANTLR4` alternative, `None` for actions, labels and commands
+    fn antlr4_element(&mut self, skipped: &mut bool) -> Result<Option<Expr>, Error> {
+        match self.peek() {
+            Some('{') => {
+                // Actions and predicates
+                self.skip_balanced('{', '}')?;
+                self.eat("?");
+                Ok(None)
+            }
+            Some('<') => {
+                // Element options such as `<assoc=right>`
+                self.skip_balanced('<', '>')?;
+                Ok(None)
+            }
+            Some('#') => {
+                // Alternative labels
+                self.pos += 1;
+                self.skip_ws();
+                self.ident();
+                Ok(None)
+            }
+            Some('-') if self.starts_with("->") => {
+                self.pos += 2;
+                loop {
+                    self.skip_ws();
+                    match self.peek() {
+                        None | Some('|' | ')' | ';') => break,
+                        Some('(') => self.skip_balanced('(', ')')?,
+                        Some(',') => self.pos += 1,
+                        Some(_) => match self.ident() {
+                            Some(command) => {
+                                if command == "skip" || command == "channel" {
+                                    *skipped = true;
+                                }
+                            }
+                            None => return Err(self.error("invalid lexer command")),
+                        },
+                    }
+                }
+                Ok(None)
+            }
+            _ => self.antlr4_atom(skipped).map(Some),
+        }
+    }
+
+    fn antlr4_atom(&mut self, skipped: &mut bool) -> Result<Expr, Error> {
+        match self.peek() {
+            Some('\'') => {
+                let literal = self.literal()?;
+                self.skip_ws();
+                if self.eat("..") {
+                    self.skip_ws();
+                    let to = self.literal()?;
+                    match (single_char(&literal), single_char(&to)) {
+                        (Some(from), Some(to)) => {
+                            Ok(Expr::CharSet(vec![(from.min(to), from.max(to))]))
+                        }
+                        _ => Err(self.error("invalid range")),
+                    }
+                } else {
+                    Ok(Expr::Literal(literal))
+                }
+            }
+            Some('[') => Ok(Expr::CharSet(self.char_set()?)),
+            Some('.') => {
+                self.pos += 1;
+                Ok(Expr::CharSet(UNIVERSE.to_vec()))
+            }
+            Some('~') => {
+                self.pos += 1;
+                self.skip_ws();
+                let negated = self.antlr4_atom(skipped)?;
+                let ranges = char_ranges(&negated)
+                    .ok_or_else(|| self.error("only characters can be negated"))?;
+                Ok(Expr::CharSet(complement(&ranges)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let group = self.alternatives(skipped)?;
+                self.expect(")")?;
+                Ok(group)
+            }
+            Some(_) => {
+                let name = self
+                    .ident()
+                    .ok_or_else(|| self.error("expected an element"))?;
+                self.skip_ws();
+                // Labels such as `left=expr` or `args+=expr`
+                if (self.starts_with("=") && !self.starts_with("=>")) || self.starts_with("+=") {
+                    self.eat("+");
+                    self.eat("=");
+                    self.skip_ws();
+                    return self.antlr4_atom(skipped);
+                }
+                if name == "EOF" {
+                    Ok(Expr::Seq(vec![]))
+                } else {
+                    Ok(Expr::Ref(name))
+                }
+            }
+            None => Err(self.error("expected an element")),
+        }
+    }
+
+    /// An element of an `EBNF` sequence, `None` for separators, exceptions and special sequences
+    fn ebnf_element(&mut self, skipped: &mut bool) -> Result<Option<Expr>, Error> {
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(None)
+            }
+            Some('-') => {
+                // Exceptions, `a - b`: we generate `a`, ignoring what it excludes
+                self.pos += 1;
+                self.skip_ws();
+                self.ebnf_element(skipped)?;
+                Ok(None)
+            }
+            Some('?') => {
+                // Special sequences, `? ... ?`
+                self.pos += 1;
+                self.skip_past('?')?;
+                Ok(None)
+            }
+            Some('\'' | '"') => Ok(Some(Expr::Literal(self.literal()?))),
+            Some('#') if self.starts_with("#x") => {
+                let c = self.set_char()?;
+                Ok(Some(Expr::Literal(c.to_string().into_bytes())))
+            }
+            Some('[') if self.at_ebnf_char_set() => Ok(Some(Expr::CharSet(self.char_set()?))),
+            Some('[') => {
+                self.pos += 1;
+                let optional = self.alternatives(skipped)?;
+                self.expect("]")?;
+                Ok(Some(Expr::Opt(Box::new(optional))))
+            }
+            Some('{') => {
+                self.pos += 1;
+                let repeated = self.alternatives(skipped)?;
+                self.expect("}")?;
+                Ok(Some(Expr::Star(Box::new(repeated))))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let group = self.alternatives(skipped)?;
+                self.expect(")")?;
+                Ok(Some(group))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                // Repetitions, `3 * a`
+                let len = self.chars[self.pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                let count: String = self.chars[self.pos..self.pos + len].iter().collect();
+                self.pos += len;
+                self.expect("*")?;
+                self.skip_ws();
+                let element = self
+                    .ebnf_element(skipped)?
+                    .ok_or_else(|| self.error("expected an element"))?;
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| self.error("invalid repetition"))?;
+                Ok(Some(Expr::Seq(vec![element; count])))
+            }
+            _ => {
+                let name = self
+                    .rule_name()
+                    .ok_or_else(|| self.error("expected an element"))?;
+                Ok(Some(Expr::Ref(name)))
+            }
+        }
+    }
+}
+
+/// The character of a literal with exactly one character
+fn single_char(literal: &[u8]) -> Option<char> {
+    let s = core::str::from_utf8(literal).ok()?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// The characters an expression matches, if it only matches single characters
+fn char_ranges(expr: &Expr) -> Option<Vec<(char, char)>> {
+    match expr {
+        Expr::Literal(literal) => single_char(literal).map(|c| vec![(c, c)]),
+        Expr::CharSet(ranges) => Some(ranges.clone()),
+        Expr::Alt(alternatives) => {
+            let mut ranges = vec![];
+            for alternative in alternatives {
+                ranges.extend(char_ranges(alternative)?);
+            }
+            Some(ranges)
+        }
+        _ => None,
+    }
+}
+
+/// The characters of the [`UNIVERSE`] not in `ranges`
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut result: Vec<(char, char)> = vec![];
+    for (from, to) in UNIVERSE {
+        for c in *from..=*to {
+            if ranges.iter().any(|(from, to)| (*from..=*to).contains(&c)) {
+                continue;
+            }
+            match result.last_mut() {
+                Some((_, last)) if *last as u32 + 1 == c as u32 => *last = c,
+                _ => result.push((c, c)),
+            }
+        }
+    }
+    result
+}
+
+/// The state of lowering [`ParsedRule`]s to [`GrammarRule`]s
+struct Lowering {
+    names: HashMap<String, usize>,
+    rules: Vec<GrammarRule>,
+    /// Emitted between the elements of `ANTLR4` parser rules, if the lexer skips whitespace
+    separator: Option<Vec<u8>>,
+}
+
+impl Lowering {
+    fn alternatives(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        separated: bool,
+    ) -> Result<Vec<Vec<GrammarSymbol>>, Error> {
+        match expr {
+            Expr::Alt(alternatives) => alternatives
+                .iter()
+                .map(|alternative| self.sequence(name, alternative, separated))
+                .collect(),
+            expr => Ok(vec![self.sequence(name, expr, separated)?]),
+        }
+    }
+
+    fn sequence(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        separated: bool,
+    ) -> Result<Vec<GrammarSymbol>, Error> {
+        let mut symbols = vec![];
+        self.append(name, expr, separated, &mut symbols)?;
+        Ok(symbols)
+    }
+
+    /// Append the symbols of `expr` to `symbols`, separated if needed
+    fn append(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        separated: bool,
+        symbols: &mut Vec<GrammarSymbol>,
+    ) -> Result<(), Error> {
+        let symbol = match expr {
+            Expr::Seq(items) => {
+                for item in items {
+                    let item = self.sequence(name, item, separated)?;
+                    if item.is_empty() {
+                        continue;
+                    }
+                    if let (Some(separator), true) = (&self.separator, separated) {
+                        if !symbols.is_empty() {
+                            symbols.push(GrammarSymbol::Literal(separator.clone()));
+                        }
+                    }
+                    symbols.extend(item);
+                }
+                return Ok(());
+            }
+            Expr::Literal(literal) => GrammarSymbol::Literal(literal.clone()),
+            Expr::CharSet(ranges) => GrammarSymbol::CharSet(ranges.clone()),
+            Expr::Ref(reference) => match self.names.get(reference) {
+                Some(idx) => GrammarSymbol::Rule(*idx),
+                None => {
+                    return Err(Error::illegal_argument(format!(
+                        "Unknown rule {reference} referenced by {name}"
+                    )))
+                }
+            },
+            Expr::Alt(_) => {
+                let alternatives = self.alternatives(name, expr, separated)?;
+                GrammarSymbol::Rule(self.add_rule(name, alternatives))
+            }
+            Expr::Opt(inner) => {
+                let inner = self.sequence(name, inner, separated)?;
+                GrammarSymbol::Rule(self.add_rule(name, vec![vec![], inner]))
+            }
+            Expr::Star(inner) | Expr::Plus(inner) => {
+                // `a*` is `r: | a r`, and `a+` is `r: a | a r`
+                let inner = self.sequence(name, inner, separated)?;
+                let idx = self.add_rule(name, vec![]);
+                let mut recursion = inner.clone();
+                if let (Some(separator), true) = (&self.separator, separated) {
+                    if !inner.is_empty() {
+                        recursion.push(GrammarSymbol::Literal(separator.clone()));
+                    }
+                }
+                recursion.push(GrammarSymbol::Rule(idx));
+                let first = if matches!(expr, Expr::Star(_)) {
+                    vec![]
+                } else {
+                    inner
+                };
+                self.rules[idx].alternatives = vec![first, recursion];
+                GrammarSymbol::Rule(idx)
+            }
+        };
+        symbols.push(symbol);
+        Ok(())
+    }
+
+    /// Add a rule for an operator nested in the rule `name`
+    fn add_rule(&mut self, name: &str, alternatives: Vec<Vec<GrammarSymbol>>) -> usize {
+        let idx = self.rules.len();
+        self.rules.push(GrammarRule {
+            name: format!("{name}#{idx}"),
+            alternatives,
+        });
+        idx
+    }
+}
+
+/// Generates random [`GrammarInput`]s from a [`Grammar`]
+#[derive(Clone, Debug)]
+pub struct GrammarGenerator<'a, S>
+where
+    S: HasRand,
+{
+    grammar: &'a Grammar,
+    max_depth: usize,
+    max_nodes: usize,
+    phantom: PhantomData<S>,
+}
+
+impl<'a, S> Generator<GrammarInput, S> for GrammarGenerator<'a, S>
+where
+    S: HasRand,
+{
+    fn generate(&mut self, state: &mut S) -> Result<GrammarInput, Error> {
+        Ok(GrammarInput::new(
+            self.generate_tree(state.rand_mut(), self.grammar.start()),
+        ))
+    }
+
+    fn generate_dummy(&self, state: &mut S) -> GrammarInput {
+        let mut budget = 0;
+        GrammarInput::new(self.derive(state.rand_mut(), self.grammar.start(), 0, &mut budget))
+    }
+}
+
+impl<'a, S> GrammarGenerator<'a, S>
+where
+    S: HasRand,
+{
+    /// Returns a new [`GrammarGenerator`]
+    #[must_use]
+    pub fn new(grammar: &'a Grammar) -> Self {
+        Self {
+            grammar,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_nodes: DEFAULT_MAX_NODES,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The maximum depth of generated trees, unless the grammar needs deeper ones to terminate
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The number of rule nodes after which only the shortest derivations are chosen
+    #[must_use]
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// The grammar we generate from
+    #[must_use]
+    pub fn grammar(&self) -> &'a Grammar {
+        self.grammar
+    }
+
+    /// Generate a random derivation of the given rule
+    pub fn generate_tree<R>(&self, rand: &mut R, rule: usize) -> DerivationTree
+    where
+        R: Rand,
+    {
+        let mut budget = self.max_nodes;
+        self.derive(rand, rule, self.max_depth, &mut budget)
+    }
+
+    fn derive<R>(
+        &self,
+        rand: &mut R,
+        rule: usize,
+        depth: usize,
+        budget: &mut usize,
+    ) -> DerivationTree
+    where
+        R: Rand,
+    {
+        *budget = budget.saturating_sub(1);
+        let min_depth = self.grammar.min_depth(rule);
+        let depth = if *budget == 0 {
+            min_depth
+        } else {
+            max(depth, min_depth)
+        };
+
+        let alternatives = &self.grammar.rules[rule].alternatives;
+        let candidates: Vec<usize> = (0..alternatives.len())
+            .filter(|alternative| self.grammar.alternative_depth(rule, *alternative) <= depth)
+            .collect();
+        let alternative = candidates[rand.below(candidates.len() as u64) as usize];
+
+        let children = alternatives[alternative]
+            .iter()
+            .map(|symbol| match symbol {
+                GrammarSymbol::Literal(literal) => DerivationNode::Leaf(literal.clone()),
+                GrammarSymbol::CharSet(ranges) => DerivationNode::Leaf(
+                    random_char(rand, ranges).map_or_else(Vec::new, |c| c.to_string().into_bytes()),
+                ),
+                GrammarSymbol::Rule(child) => {
+                    DerivationNode::Tree(self.derive(rand, *child, depth - 1, budget))
+                }
+            })
+            .collect();
+        DerivationTree::new(rule, alternative, children)
+    }
+}
+
+/// A random character out of the given ranges
+fn random_char<R>(rand: &mut R, ranges: &[(char, char)]) -> Option<char>
+where
+    R: Rand,
+{
+    if ranges.is_empty() {
+        return None;
+    }
+    let (from, to) = ranges[rand.below(ranges.len() as u64) as usize];
+    let c = rand.between(u64::from(from as u32), u64::from(to as u32)) as u32;
+    Some(char::from_u32(c).unwrap_or(from))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::{Grammar, GrammarGenerator, GrammarSymbol};
+    use crate::{
+        bolts::rands::StdRand, corpus::InMemoryCorpus, feedbacks::ConstFeedback,
+        generators::Generator, inputs::GrammarInput, state::StdState,
+    };
+
+    const ANTLR4_GRAMMAR: &str = r#"
+grammar Expr;
+options { language = Java; }
+@header { package expr; }
+
+prog : stat+ EOF ;
+stat : ID '=' expr ';' # assign
+     | 'print' expr ';' # print
+     ;
+expr : <assoc=right> left=expr op=('*' | '/') right=expr
+     | expr ('+' | '-') expr
+     | INT
+     | ID
+     | '(' expr ')' {System.out.println("group");}
+     ;
+ID : [a-zA-Z_] [a-zA-Z_0-9]* ;
+INT : DIGIT+ ;
+fragment DIGIT : '0'..'9' ;
+WS : [ \t\r\n]+ -> skip ;
+COMMENT : '/*' .*? '*/' -> channel(HIDDEN) ;
+"#;
+
+    const EBNF_GRAMMAR: &str = r#"
+(* A JSON subset *)
+value = object | array | number | "true" | "false" ;
+object = "{", [ member, { ",", member } ], "}" ;
+member = string, ":", value ;
+array = "[", [ value, { ",", value } ], "]" ;
+string ::= '"' [a-z]* '"'
+number ::= "-"? [0-9]+ ("." [0-9]+)?
+"#;
+
+    fn state(
+    ) -> StdState<GrammarInput, InMemoryCorpus<GrammarInput>, StdRand, InMemoryCorpus<GrammarInput>>
+    {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap()
+    }
+
+    fn generate(grammar: &Grammar, count: usize) -> Vec<Vec<u8>> {
+        let mut state = state();
+        let mut generator = GrammarGenerator::new(grammar);
+        (0..count)
+            .map(|_| {
+                let mut bytes = vec![];
+                generator.generate(&mut state).unwrap().unparse(&mut bytes);
+                bytes
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_antlr4_grammar() {
+        let grammar = Grammar::from_antlr4(ANTLR4_GRAMMAR).unwrap();
+        assert_eq!(grammar.rules()[grammar.start()].name, "prog");
+        let digit = grammar.rule_index("DIGIT").unwrap();
+        assert_eq!(
+            grammar.rules()[digit].alternatives,
+            vec![vec![GrammarSymbol::CharSet(vec![('0', '9')])]]
+        );
+
+        for output in generate(&grammar, 100) {
+            let output = String::from_utf8(output).unwrap();
+            // Statements are separated, as the lexer skips whitespace
+            for stat in output.split_terminator(" ;") {
+                let stat = stat.trim();
+                assert!(
+                    stat.starts_with("print ") || stat.contains(" = "),
+                    "{output}"
+                );
+            }
+            assert!(!output.contains("group"));
+        }
+    }
+
+    #[test]
+    fn test_ebnf_grammar() {
+        let grammar = Grammar::from_ebnf(EBNF_GRAMMAR).unwrap();
+        assert_eq!(grammar.rules()[grammar.start()].name, "value");
+
+        let outputs = generate(&grammar, 200);
+        for output in &outputs {
+            let output = core::str::from_utf8(output).unwrap();
+            assert!(!output.contains(' '), "{output}");
+            assert_eq!(output.matches('{').count(), output.matches('}').count());
+            assert_eq!(output.matches('[').count(), output.matches(']').count());
+        }
+        assert!(outputs.iter().any(|output| output.starts_with(b"{\"")));
+        assert!(outputs.iter().any(|output| output.contains(&b',')));
+    }
+
+    #[test]
+    fn test_grammar_errors() {
+        assert!(Grammar::from_ebnf("a = b ;").is_err());
+        assert!(Grammar::from_ebnf("a = 'x', a ;").is_err());
+        assert!(Grammar::from_antlr4("grammar G; a : 'x' ").is_err());
+        assert!(Grammar::from_ebnf("a = 'x' ;")
+            .unwrap()
+            .with_start("b")
+            .is_err());
+    }
+}
@@ -13,11 +13,19 @@ use crate::{
 pub mod gramatron;
 pub use gramatron::*;
 
+pub mod grammar;
+pub use grammar::{Grammar, GrammarGenerator, GrammarRule, GrammarSymbol};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
 pub use nautilus::*;
 
+#[cfg(feature = "arbitrary_input")]
+pub mod arbitrary;
+#[cfg(feature = "arbitrary_input")]
+pub use self::arbitrary::*;
+
 /// The maximum size of dummy bytes generated by _dummy generator methods
 const DUMMY_BYTES_MAX: usize = 64;
 
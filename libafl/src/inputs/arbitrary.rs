@@ -0,0 +1,151 @@
+//! Structured inputs for harnesses consuming Rust types that implement [`Arbitrary`], usually by `#[derive(Arbitrary)]`.
+//!
+//! The value is mutated as tree of serde values, the way [`serde_json::Value`] represents it:
+//! structs and maps become objects, sequences and tuples become arrays, and enums become their variant name,
+//! or an object with the variant name as only key. After a mutation, the tree is deserialized to the type again,
+//! and mutations that don't fit the type are discarded.
+
+use alloc::{rc::Rc, string::String};
+use core::{cell::RefCell, fmt::Debug, hash::Hasher};
+
+use ahash::AHasher;
+use arbitrary::{Arbitrary, Unstructured};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+
+/// An input that is mutated as tree of serde values, see [`crate::mutators::arbitrary`]
+pub trait HasValueTree {
+    /// The value of this input, as tree
+    fn value_tree(&self) -> Result<Value, Error>;
+
+    /// Replace the value of this input with the value deserialized from `tree`.
+    /// Fails if the tree does not fit the type of the value.
+    fn set_value_tree(&mut self, tree: Value) -> Result<(), Error>;
+
+    /// Generate a new value from the random bytes `data`, as tree
+    fn generate_value_tree(data: &[u8]) -> Result<Value, Error>;
+}
+
+/// An input wrapping a value of a type implementing [`Arbitrary`].
+/// Use [`ArbitraryInput::value`] in in-process harnesses to get the value,
+/// or decode the [`postcard`] encoded target bytes with [`ArbitraryInput::decode`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(bound = "T: Serialize + DeserializeOwned")]
+pub struct ArbitraryInput<T> {
+    value: T,
+}
+
+impl<T> Input for ArbitraryInput<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Debug,
+{
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        hasher.write(&postcard::to_allocvec(&self.value).unwrap_or_default());
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Rc Ref-cell from Input
+impl<T> From<ArbitraryInput<T>> for Rc<RefCell<ArbitraryInput<T>>> {
+    fn from(input: ArbitraryInput<T>) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl<T> HasLen for ArbitraryInput<T>
+where
+    T: Serialize,
+{
+    /// The length of the [`postcard`] encoding of the value
+    #[inline]
+    fn len(&self) -> usize {
+        postcard::to_allocvec(&self.value).map_or(0, |bytes| bytes.len())
+    }
+}
+
+impl<T> HasTargetBytes for ArbitraryInput<T>
+where
+    T: Serialize,
+{
+    /// The [`postcard`] encoding of the value
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(postcard::to_allocvec(&self.value).unwrap_or_default())
+    }
+}
+
+impl<T> HasValueTree for ArbitraryInput<T>
+where
+    T: for<'a> Arbitrary<'a> + Serialize + DeserializeOwned,
+{
+    fn value_tree(&self) -> Result<Value, Error> {
+        Ok(serde_json::to_value(&self.value)?)
+    }
+
+    fn set_value_tree(&mut self, tree: Value) -> Result<(), Error> {
+        self.value = serde_json::from_value(tree)?;
+        Ok(())
+    }
+
+    fn generate_value_tree(data: &[u8]) -> Result<Value, Error> {
+        Ok(serde_json::to_value(
+            Self::from_arbitrary_bytes(data)?.value,
+        )?)
+    }
+}
+
+impl<T> ArbitraryInput<T> {
+    /// Creates a new input wrapping `value`
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self { value }
+    }
+
+    /// The wrapped value
+    #[must_use]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The wrapped value, mutable
+    #[must_use]
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Unwrap the value
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> ArbitraryInput<T>
+where
+    T: for<'a> Arbitrary<'a>,
+{
+    /// Create the value from raw bytes, the way `cargo fuzz` harnesses do.
+    /// Use it to import an existing `cargo fuzz` corpus.
+    pub fn from_arbitrary_bytes(data: &[u8]) -> Result<Self, Error> {
+        T::arbitrary_take_rest(Unstructured::new(data))
+            .map(Self::new)
+            .map_err(|e| Error::illegal_argument(format!("Could not create arbitrary value: {e}")))
+    }
+}
+
+impl<T> ArbitraryInput<T>
+where
+    T: DeserializeOwned,
+{
+    /// Decode the value from the target bytes of an [`ArbitraryInput`], in a harness that only gets bytes
+    pub fn decode(bytes: &[u8]) -> Result<T, Error> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
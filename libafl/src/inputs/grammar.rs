@@ -0,0 +1,183 @@
+//! Derivation trees of a [`crate::generators::Grammar`], the input of grammar-based fuzzing
+//! with grammars loaded at runtime from `ANTLR4` or `EBNF` files.
+
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::{cell::RefCell, convert::From, hash::Hasher};
+
+use ahash::AHasher;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{HasTargetBytes, Input},
+};
+
+/// A child of a [`DerivationTree`] node
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DerivationNode {
+    /// Bytes emitted as they are, from a literal or a character set of the grammar
+    Leaf(Vec<u8>),
+    /// The derivation of a rule
+    Tree(DerivationTree),
+}
+
+/// The derivation of a rule of a [`crate::generators::Grammar`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DerivationTree {
+    /// The index of the derived rule
+    pub rule: usize,
+    /// The index of the alternative of the rule this node was derived with
+    pub alternative: usize,
+    /// The derivations of the symbols of the alternative
+    pub children: Vec<DerivationNode>,
+}
+
+impl DerivationTree {
+    /// Creates a new [`DerivationTree`]
+    #[must_use]
+    pub fn new(rule: usize, alternative: usize, children: Vec<DerivationNode>) -> Self {
+        Self {
+            rule,
+            alternative,
+            children,
+        }
+    }
+
+    /// The number of rule nodes in this tree, including the root
+    #[must_use]
+    pub fn size(&self) -> usize {
+        1 + self.subtrees().map(DerivationTree::size).sum::<usize>()
+    }
+
+    /// The direct subtrees of this node
+    pub fn subtrees(&self) -> impl Iterator<Item = &DerivationTree> {
+        self.children.iter().filter_map(|child| match child {
+            DerivationNode::Tree(tree) => Some(tree),
+            DerivationNode::Leaf(_) => None,
+        })
+    }
+
+    /// All rule nodes of this tree, in pre-order. The root has index `0`.
+    #[must_use]
+    pub fn nodes(&self) -> Vec<&DerivationTree> {
+        let mut nodes = vec![];
+        self.collect_nodes(&mut nodes);
+        nodes
+    }
+
+    fn collect_nodes<'a>(&'a self, nodes: &mut Vec<&'a DerivationTree>) {
+        nodes.push(self);
+        for tree in self.subtrees() {
+            tree.collect_nodes(nodes);
+        }
+    }
+
+    /// The node with the given pre-order index, as in [`DerivationTree::nodes`]
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<&DerivationTree> {
+        self.nodes().get(idx).copied()
+    }
+
+    /// The node with the given pre-order index, as in [`DerivationTree::nodes`], mutable
+    pub fn get_mut(&mut self, mut idx: usize) -> Option<&mut DerivationTree> {
+        self.find_mut(&mut idx)
+    }
+
+    fn find_mut(&mut self, idx: &mut usize) -> Option<&mut DerivationTree> {
+        if *idx == 0 {
+            return Some(self);
+        }
+        *idx -= 1;
+        for child in &mut self.children {
+            if let DerivationNode::Tree(tree) = child {
+                if let Some(found) = tree.find_mut(idx) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    /// Append the bytes this tree derives to `bytes`
+    pub fn unparse(&self, bytes: &mut Vec<u8>) {
+        for child in &self.children {
+            match child {
+                DerivationNode::Leaf(leaf) => bytes.extend_from_slice(leaf),
+                DerivationNode::Tree(tree) => tree.unparse(bytes),
+            }
+        }
+    }
+}
+
+/// An input for grammar fuzzing, the derivation tree of the start rule of a [`crate::generators::Grammar`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GrammarInput {
+    /// The derivation of the start rule
+    tree: DerivationTree,
+}
+
+impl Input for GrammarInput {
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut bytes = vec![];
+        self.unparse(&mut bytes);
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        hasher.write(&bytes);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Rc Ref-cell from Input
+impl From<GrammarInput> for Rc<RefCell<GrammarInput>> {
+    fn from(input: GrammarInput) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl From<DerivationTree> for GrammarInput {
+    fn from(tree: DerivationTree) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl HasLen for GrammarInput {
+    /// The number of rule nodes of the derivation tree
+    #[inline]
+    fn len(&self) -> usize {
+        self.tree.size()
+    }
+}
+
+impl HasTargetBytes for GrammarInput {
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        let mut bytes = vec![];
+        self.unparse(&mut bytes);
+        OwnedSlice::from(bytes)
+    }
+}
+
+impl GrammarInput {
+    /// Creates a new input from the given derivation tree
+    #[must_use]
+    pub fn new(tree: DerivationTree) -> Self {
+        Self { tree }
+    }
+
+    /// The derivation tree of this input
+    #[must_use]
+    pub fn tree(&self) -> &DerivationTree {
+        &self.tree
+    }
+
+    /// The derivation tree of this input, mutable
+    #[must_use]
+    pub fn tree_mut(&mut self) -> &mut DerivationTree {
+        &mut self.tree
+    }
+
+    /// Create a bytes representation of this input
+    pub fn unparse(&self, bytes: &mut Vec<u8>) {
+        bytes.clear();
+        self.tree.unparse(bytes);
+    }
+}
@@ -12,8 +12,20 @@ pub use gramatron::*;
 pub mod generalized;
 pub use generalized::*;
 
+pub mod grammar;
+pub use grammar::{DerivationNode, DerivationTree, GrammarInput};
+
+pub mod multi_message;
+pub use multi_message::MultiMessageInput;
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
+
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+
+#[cfg(feature = "arbitrary_input")]
+pub mod arbitrary;
 use alloc::{
     string::{String, ToString},
     vec::Vec,
@@ -24,8 +36,12 @@ use std::{fs::File, hash::Hash, io::Read, path::Path};
 
 #[cfg(feature = "nautilus")]
 pub use nautilus::*;
+#[cfg(feature = "protobuf")]
+pub use protobuf::*;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "arbitrary_input")]
+pub use self::arbitrary::*;
 #[cfg(feature = "std")]
 use crate::bolts::fs::write_file_atomic;
 use crate::{bolts::ownedref::OwnedSlice, Error};
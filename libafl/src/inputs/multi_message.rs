@@ -0,0 +1,80 @@
+//! The `MultiMessageInput` is a sequence of messages, e.g. the packets sent to a stateful network server,
+//! as opposed to the single blob of a [`crate::inputs::BytesInput`].
+
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::{cell::RefCell, convert::From, hash::Hasher};
+
+use ahash::AHasher;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{HasTargetBytes, Input},
+};
+
+/// An input made of a sequence of messages, each delivered to the target on its own
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MultiMessageInput {
+    /// The messages, in the order they are sent
+    messages: Vec<Vec<u8>>,
+}
+
+impl Input for MultiMessageInput {
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        for message in &self.messages {
+            hasher.write_usize(message.len());
+            hasher.write(message);
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Rc Ref-cell from Input
+impl From<MultiMessageInput> for Rc<RefCell<MultiMessageInput>> {
+    fn from(input: MultiMessageInput) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl From<Vec<Vec<u8>>> for MultiMessageInput {
+    fn from(messages: Vec<Vec<u8>>) -> Self {
+        Self::new(messages)
+    }
+}
+
+impl HasLen for MultiMessageInput {
+    /// The number of messages
+    #[inline]
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+/// The concatenation of all messages, for targets reading the whole conversation at once
+impl HasTargetBytes for MultiMessageInput {
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(self.messages.concat())
+    }
+}
+
+impl MultiMessageInput {
+    /// Creates a new input from the given messages
+    #[must_use]
+    pub fn new(messages: Vec<Vec<u8>>) -> Self {
+        Self { messages }
+    }
+
+    /// The messages of this input
+    #[must_use]
+    pub fn messages(&self) -> &[Vec<u8>] {
+        &self.messages
+    }
+
+    /// The messages of this input, mutable
+    #[must_use]
+    pub fn messages_mut(&mut self) -> &mut Vec<Vec<u8>> {
+        &mut self.messages
+    }
+}
@@ -0,0 +1,258 @@
+//! Structured inputs for targets consuming [protobuf](https://protobuf.dev) messages,
+//! with the message schema loaded at runtime from a compiled descriptor set.
+//!
+//! Compile the descriptor set with `protoc --include_imports --descriptor_set_out=schema.desc schema.proto`,
+//! load it with [`ProtobufInput::load_descriptor_set`], and look up the message type the target consumes
+//! with [`ProtobufInput::message_descriptor`].
+
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::{cell::RefCell, convert::From, hash::Hasher};
+use std::{fs, path::Path};
+
+use ahash::AHasher;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{ownedref::OwnedSlice, HasLen},
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+
+/// The format a [`ProtobufInput`] is serialized to for the target
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProtobufFormat {
+    /// The binary wire format
+    Binary,
+    /// The text format, as parsed by `TextFormat::Parse`
+    Text,
+}
+
+/// An input for structure-aware fuzzing of protobuf messages, stored as dynamic message of a schema loaded at runtime.
+/// The input is (de)serialized by message type name and binary encoding,
+/// so the descriptor set needs to be loaded before inputs are loaded from disk or received from other clients.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProtobufInput {
+    /// The message
+    #[serde(with = "message_serde")]
+    message: DynamicMessage,
+    /// The format the target consumes
+    format: ProtobufFormat,
+}
+
+impl Input for ProtobufInput {
+    /// Generate a name for this input
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        hasher.write(&self.message.encode_to_vec());
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Rc Ref-cell from Input
+impl From<ProtobufInput> for Rc<RefCell<ProtobufInput>> {
+    fn from(input: ProtobufInput) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl HasLen for ProtobufInput {
+    /// The length of the binary encoding of the message
+    #[inline]
+    fn len(&self) -> usize {
+        self.message.encoded_len()
+    }
+}
+
+impl HasTargetBytes for ProtobufInput {
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        match self.format {
+            ProtobufFormat::Binary => OwnedSlice::from(self.message.encode_to_vec()),
+            ProtobufFormat::Text => OwnedSlice::from(self.message.to_text_format().into_bytes()),
+        }
+    }
+}
+
+impl ProtobufInput {
+    /// Creates a new input from the given message
+    #[must_use]
+    pub fn new(message: DynamicMessage, format: ProtobufFormat) -> Self {
+        Self { message, format }
+    }
+
+    /// Load the message types of a compiled `FileDescriptorSet` at `path`,
+    /// to look them up with [`ProtobufInput::message_descriptor`] and to deserialize inputs
+    pub fn load_descriptor_set<P>(path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let data = fs::read(path.as_ref())?;
+        DescriptorPool::decode_global_file_descriptor_set(&*data).map_err(|e| {
+            Error::illegal_argument(format!(
+                "Invalid descriptor set {}: {e}",
+                path.as_ref().display()
+            ))
+        })
+    }
+
+    /// Load the message types of a compiled `FileDescriptorSet`, e.g. embedded with `include_bytes!`
+    pub fn load_descriptor_set_bytes(data: &[u8]) -> Result<(), Error> {
+        DescriptorPool::decode_global_file_descriptor_set(data)
+            .map_err(|e| Error::illegal_argument(format!("Invalid descriptor set: {e}")))
+    }
+
+    /// The loaded message type with the given fully qualified name, e.g. `my.package.Request`
+    pub fn message_descriptor(name: &str) -> Result<MessageDescriptor, Error> {
+        DescriptorPool::global()
+            .get_message_by_name(name)
+            .ok_or_else(|| Error::key_not_found(format!("No message type {name} loaded")))
+    }
+
+    /// Decode an input from the binary wire format, e.g. to load an initial corpus
+    pub fn decode(
+        descriptor: MessageDescriptor,
+        bytes: &[u8],
+        format: ProtobufFormat,
+    ) -> Result<Self, Error> {
+        let message = DynamicMessage::decode(descriptor, bytes)
+            .map_err(|e| Error::illegal_argument(format!("Invalid message: {e}")))?;
+        Ok(Self::new(message, format))
+    }
+
+    /// Parse an input from the text format, e.g. to load an initial corpus
+    pub fn parse_text(
+        descriptor: MessageDescriptor,
+        text: &str,
+        format: ProtobufFormat,
+    ) -> Result<Self, Error> {
+        let message = DynamicMessage::parse_text_format(descriptor, text)
+            .map_err(|e| Error::illegal_argument(format!("Invalid message: {e}")))?;
+        Ok(Self::new(message, format))
+    }
+
+    /// The message of this input
+    #[must_use]
+    pub fn message(&self) -> &DynamicMessage {
+        &self.message
+    }
+
+    /// The message of this input, mutable
+    #[must_use]
+    pub fn message_mut(&mut self) -> &mut DynamicMessage {
+        &mut self.message
+    }
+
+    /// The format the target consumes
+    #[must_use]
+    pub fn format(&self) -> ProtobufFormat {
+        self.format
+    }
+
+    /// Create a bytes representation of this input, in its format
+    pub fn unparse(&self, bytes: &mut Vec<u8>) {
+        bytes.clear();
+        match self.format {
+            ProtobufFormat::Binary => bytes.extend_from_slice(&self.message.encode_to_vec()),
+            ProtobufFormat::Text => {
+                bytes.extend_from_slice(self.message.to_text_format().as_bytes());
+            }
+        }
+    }
+}
+
+/// (De)serializes a [`DynamicMessage`] as the name of its type and its binary encoding
+mod message_serde {
+    use alloc::{string::String, vec::Vec};
+
+    use prost::Message;
+    use prost_reflect::{DescriptorPool, DynamicMessage, ReflectMessage};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(message: &DynamicMessage, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (message.descriptor().full_name(), message.encode_to_vec()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DynamicMessage, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (name, bytes) = <(String, Vec<u8>)>::deserialize(deserializer)?;
+        let descriptor = DescriptorPool::global()
+            .get_message_by_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("No message type {name} loaded")))?;
+        DynamicMessage::decode(descriptor, &*bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::string::String;
+
+    use prost::Message;
+    use prost_reflect::MessageDescriptor;
+
+    use super::{ProtobufFormat, ProtobufInput};
+    use crate::{bolts::AsSlice, inputs::HasTargetBytes};
+
+    /// The compiled descriptor set of
+    /// ```proto
+    /// syntax = "proto3";
+    /// package test;
+    /// message Item { int32 id = 1; string name = 2; }
+    /// message Request { uint64 size = 1; bytes data = 2; repeated Item items = 3; Item first = 4; }
+    /// ```
+    pub(crate) const TEST_DESCRIPTOR_SET: &[u8] = &[
+        0x0a, 0x99, 0x01, 0x0a, 0x0a, 0x74, 0x65, 0x73, 0x74, 0x2e, 0x70, 0x72, 0x6f, 0x74, 0x6f,
+        0x12, 0x04, 0x74, 0x65, 0x73, 0x74, 0x22, 0x20, 0x0a, 0x04, 0x49, 0x74, 0x65, 0x6d, 0x12,
+        0x0a, 0x0a, 0x02, 0x69, 0x64, 0x18, 0x01, 0x20, 0x01, 0x28, 0x05, 0x12, 0x0c, 0x0a, 0x04,
+        0x6e, 0x61, 0x6d, 0x65, 0x18, 0x02, 0x20, 0x01, 0x28, 0x09, 0x22, 0x5b, 0x0a, 0x07, 0x52,
+        0x65, 0x71, 0x75, 0x65, 0x73, 0x74, 0x12, 0x0c, 0x0a, 0x04, 0x73, 0x69, 0x7a, 0x65, 0x18,
+        0x01, 0x20, 0x01, 0x28, 0x04, 0x12, 0x0c, 0x0a, 0x04, 0x64, 0x61, 0x74, 0x61, 0x18, 0x02,
+        0x20, 0x01, 0x28, 0x0c, 0x12, 0x19, 0x0a, 0x05, 0x69, 0x74, 0x65, 0x6d, 0x73, 0x18, 0x03,
+        0x20, 0x03, 0x28, 0x0b, 0x32, 0x0a, 0x2e, 0x74, 0x65, 0x73, 0x74, 0x2e, 0x49, 0x74, 0x65,
+        0x6d, 0x12, 0x19, 0x0a, 0x05, 0x66, 0x69, 0x72, 0x73, 0x74, 0x18, 0x04, 0x20, 0x01, 0x28,
+        0x0b, 0x32, 0x0a, 0x2e, 0x74, 0x65, 0x73, 0x74, 0x2e, 0x49, 0x74, 0x65, 0x6d, 0x62, 0x06,
+        0x70, 0x72, 0x6f, 0x74, 0x6f, 0x33,
+    ];
+
+    /// The `test.Request` message type of the [`TEST_DESCRIPTOR_SET`]
+    pub(crate) fn request_descriptor() -> MessageDescriptor {
+        ProtobufInput::load_descriptor_set_bytes(TEST_DESCRIPTOR_SET).unwrap();
+        ProtobufInput::message_descriptor("test.Request").unwrap()
+    }
+
+    #[test]
+    fn test_protobuf_input() {
+        let descriptor = request_descriptor();
+        let text =
+            r#"size: 3 data: "ab" items { id: 1 name: "x" } items { id: 2 } first { id: 7 }"#;
+        let input =
+            ProtobufInput::parse_text(descriptor.clone(), text, ProtobufFormat::Binary).unwrap();
+
+        // Binary round-trip
+        let bytes = input.target_bytes().as_slice().to_vec();
+        assert_eq!(bytes, input.message().encode_to_vec());
+        let decoded =
+            ProtobufInput::decode(descriptor.clone(), &bytes, ProtobufFormat::Binary).unwrap();
+        assert_eq!(decoded, input);
+
+        // Text round-trip
+        let text_input = ProtobufInput::new(input.message().clone(), ProtobufFormat::Text);
+        let text = String::from_utf8(text_input.target_bytes().as_slice().to_vec()).unwrap();
+        let parsed = ProtobufInput::parse_text(descriptor, &text, ProtobufFormat::Text).unwrap();
+        assert_eq!(parsed, text_input);
+
+        // Serde round-trip, as for the corpus and events
+        let serialized = postcard::to_allocvec(&input).unwrap();
+        let deserialized: ProtobufInput = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, input);
+
+        let mut unparsed = vec![];
+        text_input.unparse(&mut unparsed);
+        assert_eq!(unparsed, text.into_bytes());
+    }
+}
@@ -0,0 +1,375 @@
+//! A monitor that wraps a base one and writes AFL++-compatible `fuzzer_stats` and `plot_data` files,
+//! so that tools such as `afl-whatsup` and `afl-plot` work on `LibAFL` runs.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt::Write as _, time::Duration};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bolts::current_time,
+    monitors::{ClientStats, Monitor, NopMonitor, UserStats},
+};
+
+/// The header of an AFL++ `plot_data` file
+const PLOT_DATA_HEADER: &str = "# relative_time, cycles_done, cur_item, corpus_count, pending_total, pending_favs, map_size, saved_crashes, saved_hangs, max_depth, execs_per_sec, total_execs, edges_found\n";
+
+/// AFL++ updates its stats every few seconds, too
+const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The directory the stats of the client with the given id are written to: `<out_dir>/client_<id>`.
+/// Clients should place their corpora in its `queue` and `crashes` subdirectories, e.g. using
+/// [`crate::corpus::OnDiskCorpus::new_afl_style`], and their timeouts in `hangs`, using
+/// [`crate::corpus::OnDiskCorpus::with_afl_hangs_dir`], to get the layout of an AFL++ output directory.
+#[must_use]
+pub fn afl_client_dir<P>(out_dir: P, client_id: usize) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    out_dir.as_ref().join(format!("client_{client_id}"))
+}
+
+/// What we remember about a client between two updates
+#[derive(Debug, Clone, Default)]
+struct AflClientHistory {
+    corpus_size: u64,
+    objective_size: u64,
+    last_find: Option<Duration>,
+    last_crash: Option<Duration>,
+    execs_at_last_crash: u64,
+    saved_hangs: usize,
+    last_hang: Option<Duration>,
+}
+
+/// Wraps a base monitor and writes a `fuzzer_stats` file for each client, and appends to its `plot_data` file,
+/// in the format of AFL++.
+///
+/// The files are placed in [`afl_client_dir`]. The hangs are counted in its `hangs` subdirectory. Coverage is taken from the [`UserStats::Ratio`] of the map feedback,
+/// named `edges` by default, and stability from the `stability` stat reported by the calibration.
+#[derive(Debug, Clone)]
+pub struct AflStatsMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    out_dir: PathBuf,
+    edges_stat: String,
+    update_interval: Duration,
+    last_update: Duration,
+    history: Vec<AflClientHistory>,
+    command_line: String,
+}
+
+impl<M> Monitor for AflStatsMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        let cur_time = current_time();
+        self.track(sender_id as usize, cur_time);
+
+        if cur_time.saturating_sub(self.last_update) >= self.update_interval {
+            self.last_update = cur_time;
+            for client_id in 0..self.client_stats().len() {
+                // Skip the slots of clients that never executed anything, like the broker's own
+                if self.client_stats()[client_id].executions == 0 {
+                    continue;
+                }
+                if let Err(e) = self.write_client_stats(client_id, cur_time) {
+                    println!("Failed to write the AFL stats of client {client_id}: {e}");
+                }
+            }
+        }
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> AflStatsMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`AflStatsMonitor`], writing the stats of each client to a subdirectory of `out_dir`
+    #[must_use]
+    pub fn new<P>(out_dir: P, base: M) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let command_line = std::env::args().collect::<Vec<_>>().join(" ");
+        Self {
+            base,
+            out_dir: out_dir.into(),
+            edges_stat: "edges".into(),
+            update_interval: DEFAULT_UPDATE_INTERVAL,
+            last_update: Duration::ZERO,
+            history: vec![],
+            command_line,
+        }
+    }
+
+    /// The name of the [`UserStats::Ratio`] reporting the edge coverage, `edges` by default.
+    /// This is the lowercase name of the map observer of the map feedback.
+    #[must_use]
+    pub fn with_edges_stat<S>(mut self, edges_stat: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.edges_stat = edges_stat.into();
+        self
+    }
+
+    /// How often the stats are written, every 5 seconds by default
+    #[must_use]
+    pub fn with_update_interval(mut self, update_interval: Duration) -> Self {
+        self.update_interval = update_interval;
+        self
+    }
+
+    /// Remember when the client last found new testcases and objectives
+    fn track(&mut self, client_id: usize, cur_time: Duration) {
+        let (corpus_size, objective_size, executions) =
+            match self.base.client_stats().get(client_id) {
+                Some(client) => (client.corpus_size, client.objective_size, client.executions),
+                None => return,
+            };
+        if self.history.len() <= client_id {
+            self.history
+                .resize(client_id + 1, AflClientHistory::default());
+        }
+
+        let history = &mut self.history[client_id];
+        if corpus_size > history.corpus_size {
+            history.last_find = Some(cur_time);
+        }
+        if objective_size > history.objective_size {
+            history.last_crash = Some(cur_time);
+            history.execs_at_last_crash = executions;
+        }
+        history.corpus_size = corpus_size;
+        history.objective_size = objective_size;
+    }
+
+    #[allow(clippy::cast_precision_loss, clippy::too_many_lines)]
+    fn write_client_stats(&mut self, client_id: usize, cur_time: Duration) -> std::io::Result<()> {
+        let start_time = self.start_time();
+        let dir = afl_client_dir(&self.out_dir, client_id);
+        fs::create_dir_all(&dir)?;
+
+        // Hangs are not reported by the clients, count them in the `hangs` directory
+        let saved_hangs = count_afl_entries(&dir.join("hangs"));
+        if let Some(history) = self.history.get_mut(client_id) {
+            if saved_hangs > history.saved_hangs {
+                history.last_hang = Some(cur_time);
+            }
+            history.saved_hangs = saved_hangs;
+        }
+        let history = self.history.get(client_id).cloned().unwrap_or_default();
+        let edges_stat = self.edges_stat.clone();
+        let client = &mut self.base.client_stats_mut()[client_id];
+        let exec_sec = client.execs_per_sec(cur_time);
+
+        let (edges_found, total_edges) = match client.user_monitor.get(&edges_stat) {
+            Some(UserStats::Ratio(found, total)) => (*found, *total),
+            _ => (0, 0),
+        };
+        let bitmap_cvg = percentage(edges_found, total_edges);
+        let stability = match client.user_monitor.get("stability") {
+            Some(UserStats::Ratio(unstable, total)) if *total > 0 => {
+                100.0 - percentage(*unstable, *total)
+            }
+            _ => 100.0,
+        };
+
+        let run_time = cur_time.saturating_sub(start_time);
+        let banner = format!("client_{client_id}");
+
+        let mut stats = String::new();
+        let mut stat = |key: &str, value: &dyn core::fmt::Display| {
+            writeln!(&mut stats, "{key:<18}: {value}").unwrap();
+        };
+        stat("start_time", &start_time.as_secs());
+        stat("last_update", &cur_time.as_secs());
+        stat("run_time", &run_time.as_secs());
+        stat("fuzzer_pid", &std::process::id());
+        stat("cycles_done", &0);
+        stat("cycles_wo_finds", &0);
+        stat(
+            "time_wo_finds",
+            &cur_time
+                .saturating_sub(history.last_find.unwrap_or(start_time))
+                .as_secs(),
+        );
+        stat("execs_done", &client.executions);
+        stat("execs_per_sec", &format!("{:.2}", exec_sec as f64));
+        stat("execs_ps_last_min", &format!("{:.2}", exec_sec as f64));
+        stat("corpus_count", &client.corpus_size);
+        stat("corpus_favored", &0);
+        stat("corpus_found", &client.corpus_size);
+        stat("corpus_imported", &0);
+        stat("corpus_variable", &0);
+        stat("max_depth", &0);
+        stat("cur_item", &0);
+        stat("pending_favs", &0);
+        stat("pending_total", &0);
+        stat("stability", &format!("{stability:.2}%"));
+        stat("bitmap_cvg", &format!("{bitmap_cvg:.2}%"));
+        stat("saved_crashes", &client.objective_size);
+        stat("saved_hangs", &saved_hangs);
+        stat(
+            "last_find",
+            &history.last_find.map_or(0, |time| time.as_secs()),
+        );
+        stat(
+            "last_crash",
+            &history.last_crash.map_or(0, |time| time.as_secs()),
+        );
+        stat(
+            "last_hang",
+            &history.last_hang.map_or(0, |time| time.as_secs()),
+        );
+        stat(
+            "execs_since_crash",
+            &(client.executions - history.execs_at_last_crash.min(client.executions)),
+        );
+        stat("edges_found", &edges_found);
+        stat("total_edges", &total_edges);
+        for (key, value) in &client.user_monitor {
+            if *key == edges_stat || key == "stability" {
+                continue;
+            }
+            let key: String = key
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect();
+            stat(&key, value);
+        }
+        stat("afl_banner", &banner);
+        stat(
+            "afl_version",
+            &concat!("libafl-", env!("CARGO_PKG_VERSION")),
+        );
+        stat("target_mode", &"default");
+        stat("command_line", &self.command_line);
+
+        // Write the stats atomically, scripts may read them at any time
+        let stats_path = dir.join("fuzzer_stats");
+        let tmp_path = dir.join(".fuzzer_stats_tmp");
+        File::create(&tmp_path)?.write_all(stats.as_bytes())?;
+        fs::rename(&tmp_path, &stats_path)?;
+
+        let mut plot_data = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(dir.join("plot_data"))?;
+        if plot_data.metadata()?.len() == 0 {
+            plot_data.write_all(PLOT_DATA_HEADER.as_bytes())?;
+        }
+        writeln!(
+            plot_data,
+            "{}, 0, 0, {}, 0, 0, {bitmap_cvg:.2}%, {}, {saved_hangs}, 0, {:.2}, {}, {edges_found}",
+            run_time.as_secs(),
+            client.corpus_size,
+            client.objective_size,
+            exec_sec as f64,
+            client.executions,
+        )?;
+        Ok(())
+    }
+}
+
+impl AflStatsMonitor<NopMonitor> {
+    /// Create new [`AflStatsMonitor`] without a base
+    #[must_use]
+    pub fn nop<P>(out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(out_dir, NopMonitor::new())
+    }
+}
+
+/// The number of AFL-style testcases in `dir`, zero if it does not exist
+fn count_afl_entries(dir: &Path) -> usize {
+    fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("id:"))
+            .count()
+    })
+}
+
+/// `part` of `total` in percent
+#[allow(clippy::cast_precision_loss)]
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+    use core::time::Duration;
+    use std::fs;
+
+    use super::{afl_client_dir, AflStatsMonitor};
+    use crate::monitors::{Monitor, UserStats};
+
+    #[test]
+    fn test_afl_stats_monitor() {
+        let out_dir = std::env::temp_dir().join(format!("afl_stats_test_{}", std::process::id()));
+        let mut monitor = AflStatsMonitor::nop(&out_dir).with_update_interval(Duration::ZERO);
+
+        let client = monitor.client_stats_mut_for(1);
+        client.update_corpus_size(12);
+        client.update_objective_size(1);
+        client.update_executions(1000, crate::bolts::current_time());
+        client.update_user_stats("edges".to_string(), UserStats::Ratio(16, 64));
+        client.update_user_stats("stability".to_string(), UserStats::Ratio(1, 4));
+        monitor.display("Testcase".to_string(), 1);
+        let dir = afl_client_dir(&out_dir, 1);
+        fs::create_dir_all(dir.join("hangs")).unwrap();
+        fs::write(dir.join("hangs").join("id:000000,time:0,execs:0"), b"").unwrap();
+        monitor.display("Testcase".to_string(), 1);
+
+        let stats = fs::read_to_string(dir.join("fuzzer_stats")).unwrap();
+        assert!(stats.contains("execs_done        : 1000\n"));
+        assert!(stats.contains("corpus_count      : 12\n"));
+        assert!(stats.contains("saved_crashes     : 1\n"));
+        assert!(stats.contains("saved_hangs       : 1\n"));
+        assert!(!stats.contains("last_hang         : 0\n"));
+        assert!(stats.contains("bitmap_cvg        : 25.00%\n"));
+        assert!(stats.contains("stability         : 75.00%\n"));
+        assert!(stats.contains("edges_found       : 16\n"));
+        // The broker's slot never executed anything
+        assert!(!afl_client_dir(&out_dir, 0).exists());
+
+        let plot_data = fs::read_to_string(dir.join("plot_data")).unwrap();
+        let lines: Vec<&str> = plot_data.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("# relative_time"));
+        assert_eq!(lines[1].split(", ").count(), 13);
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -9,12 +9,22 @@ pub mod tui;
 
 #[cfg(feature = "std")]
 pub mod disk;
+
+#[cfg(feature = "std")]
+pub mod afl;
+
+#[cfg(feature = "std")]
+pub mod prometheus;
 use alloc::{fmt::Debug, string::String, vec::Vec};
 use core::{fmt, time::Duration};
 
+#[cfg(feature = "std")]
+pub use afl::{afl_client_dir, AflStatsMonitor};
 #[cfg(feature = "std")]
 pub use disk::{OnDiskJSONMonitor, OnDiskTOMLMonitor};
 use hashbrown::HashMap;
+#[cfg(feature = "std")]
+pub use prometheus::PrometheusMonitor;
 use serde::{Deserialize, Serialize};
 
 use crate::bolts::{current_time, format_duration_hms};
@@ -0,0 +1,407 @@
+//! A monitor that wraps a base one and serves the current statistics over HTTP,
+//! in the `OpenMetrics` text format scraped by Prometheus.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{fmt::Write as _, time::Duration};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    thread,
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+use crate::{
+    bolts::current_time,
+    monitors::{ClientStats, Monitor, NopMonitor, UserStats},
+    Error,
+};
+
+/// The content type of the `OpenMetrics` text format
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// How long a scraper may take to send its request, the metrics are served by a single thread
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Wraps a base monitor and serves the current statistics as `OpenMetrics` on `http://<addr>/metrics`.
+#[derive(Debug, Clone)]
+pub struct PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    local_addr: SocketAddr,
+    metrics: Arc<Mutex<String>>,
+}
+
+impl<M> Monitor for PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&mut self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn display(&mut self, event_msg: String, sender_id: u32) {
+        let metrics = self.render();
+        *self.metrics.lock().unwrap() = metrics;
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> PrometheusMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`PrometheusMonitor`], serving the metrics on the given address, e.g. `127.0.0.1:9090`.
+    /// Binding to port `0` picks a free port, see [`PrometheusMonitor::local_addr`].
+    pub fn new<A>(addr: A, base: M) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let metrics = Arc::new(Mutex::new(String::from("# EOF\n")));
+
+        let served = metrics.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A failing scraper must not take down the fuzzer
+                let _ = serve_metrics(stream, &served);
+            }
+        });
+
+        Ok(Self {
+            base,
+            local_addr,
+            metrics,
+        })
+    }
+
+    /// The address the metrics are served on
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Render the current statistics in the `OpenMetrics` text format
+    fn render(&mut self) -> String {
+        let cur_time = current_time();
+        let run_time = cur_time.saturating_sub(self.start_time()).as_secs();
+
+        let mut out = String::new();
+        metric_family(
+            &mut out,
+            "libafl_run_time_seconds",
+            "gauge",
+            "Time since the fuzzing run started",
+        );
+        writeln!(&mut out, "libafl_run_time_seconds {run_time}").unwrap();
+        metric_family(&mut out, "libafl_clients", "gauge", "The number of clients");
+        writeln!(&mut out, "libafl_clients {}", self.client_stats().len()).unwrap();
+
+        client_metric(
+            &mut out,
+            self.client_stats(),
+            (
+                "libafl_executions",
+                "counter",
+                "The executions of each client",
+            ),
+            |client| client.executions,
+        );
+        client_metric(
+            &mut out,
+            self.client_stats(),
+            (
+                "libafl_corpus_size",
+                "gauge",
+                "The corpus size of each client",
+            ),
+            |client| client.corpus_size,
+        );
+        client_metric(
+            &mut out,
+            self.client_stats(),
+            (
+                "libafl_objectives",
+                "gauge",
+                "The objectives found by each client",
+            ),
+            |client| client.objective_size,
+        );
+        let exec_sec: Vec<u64> = self
+            .client_stats_mut()
+            .iter_mut()
+            .map(|client| client.execs_per_sec(cur_time))
+            .collect();
+        metric_family(
+            &mut out,
+            "libafl_exec_per_sec",
+            "gauge",
+            "The executions per second of each client",
+        );
+        for (i, exec_sec) in exec_sec.iter().enumerate() {
+            writeln!(&mut out, "libafl_exec_per_sec{{client=\"{i}\"}} {exec_sec}").unwrap();
+        }
+
+        self.render_user_stats(&mut out);
+
+        #[cfg(feature = "introspection")]
+        self.render_introspection(&mut out);
+
+        out.push_str("# EOF\n");
+        out
+    }
+
+    /// Render the [`UserStats`] of each client, the textual ones as `info` metrics
+    #[allow(clippy::cast_precision_loss)]
+    fn render_user_stats(&self, out: &mut String) {
+        metric_family(
+            out,
+            "libafl_user_stats",
+            "gauge",
+            "The numerical user stats of each client",
+        );
+        for (i, client) in self.client_stats().iter().enumerate() {
+            for (name, stat) in &client.user_monitor {
+                let value = match stat {
+                    UserStats::Number(n) => *n as f64,
+                    UserStats::Float(f) => *f,
+                    UserStats::Ratio(a, b) => {
+                        if *b == 0 {
+                            0.0
+                        } else {
+                            *a as f64 / *b as f64
+                        }
+                    }
+                    UserStats::String(_) => continue,
+                };
+                writeln!(
+                    out,
+                    "libafl_user_stats{{client=\"{i}\",name=\"{}\"}} {value}",
+                    escape_label(name)
+                )
+                .unwrap();
+            }
+        }
+        metric_family(
+            out,
+            "libafl_user_stats_text",
+            "info",
+            "The textual user stats of each client",
+        );
+        for (i, client) in self.client_stats().iter().enumerate() {
+            for (name, stat) in &client.user_monitor {
+                if let UserStats::String(s) = stat {
+                    writeln!(
+                        out,
+                        "libafl_user_stats_text_info{{client=\"{i}\",name=\"{}\",value=\"{}\"}} 1",
+                        escape_label(name),
+                        escape_label(s)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    /// Render the [`crate::monitors::ClientPerfMonitor`] breakdown of each client, in clock cycles
+    #[cfg(feature = "introspection")]
+    fn render_introspection(&self, out: &mut String) {
+        metric_family(
+            out,
+            "libafl_perf_cycles",
+            "gauge",
+            "The clock cycles spent in each part of the fuzzer",
+        );
+        for (i, client) in self.client_stats().iter().enumerate() {
+            let perf = &client.introspection_monitor;
+            writeln!(
+                out,
+                "libafl_perf_cycles{{client=\"{i}\",part=\"elapsed\"}} {}",
+                perf.elapsed_cycles()
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "libafl_perf_cycles{{client=\"{i}\",part=\"scheduler\"}} {}",
+                perf.scheduler_cycles()
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "libafl_perf_cycles{{client=\"{i}\",part=\"manager\"}} {}",
+                perf.manager_cycles()
+            )
+            .unwrap();
+            for (stage_index, features) in perf.used_stages() {
+                for (feature_index, cycles) in features.iter().enumerate() {
+                    let feature: PerfFeature = feature_index.into();
+                    writeln!(
+                        out,
+                        "libafl_perf_cycles{{client=\"{i}\",part=\"stage\",stage=\"{stage_index}\",feature=\"{feature:?}\"}} {cycles}"
+                    )
+                    .unwrap();
+                }
+            }
+            for (name, cycles) in perf.feedbacks() {
+                writeln!(
+                    out,
+                    "libafl_perf_cycles{{client=\"{i}\",part=\"feedback\",feedback=\"{}\"}} {cycles}",
+                    escape_label(name)
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+impl PrometheusMonitor<NopMonitor> {
+    /// Create a new [`PrometheusMonitor`] without a base
+    pub fn nop<A>(addr: A) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::new(addr, NopMonitor::new())
+    }
+}
+
+/// Write the `TYPE` and `HELP` lines of a metric family
+fn metric_family(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# TYPE {name} {kind}\n# HELP {name} {help}").unwrap();
+}
+
+/// Write a metric family with one sample per client
+fn client_metric<F>(
+    out: &mut String,
+    clients: &[ClientStats],
+    (name, kind, help): (&str, &str, &str),
+    value: F,
+) where
+    F: Fn(&ClientStats) -> u64,
+{
+    metric_family(out, name, kind, help);
+    let sample = if kind == "counter" {
+        format!("{name}_total")
+    } else {
+        name.to_string()
+    };
+    for (i, client) in clients.iter().enumerate() {
+        writeln!(out, "{sample}{{client=\"{i}\"}} {}", value(client)).unwrap();
+    }
+}
+
+/// Escape a label value as required by the `OpenMetrics` text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answer a single HTTP request, serving the metrics on `/metrics`
+fn serve_metrics(stream: TcpStream, metrics: &Mutex<String>) -> Result<(), Error> {
+    // A stalled scraper must not block the ones after it
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut stream = stream;
+    let mut parts = request_line.split_whitespace();
+    if parts.next() == Some("GET")
+        && parts
+            .next()
+            .map_or(false, |path| path.starts_with("/metrics"))
+    {
+        let body = metrics.lock().unwrap().to_string();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: {OPENMETRICS_CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+    } else {
+        stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use super::PrometheusMonitor;
+    use crate::monitors::{Monitor, UserStats};
+
+    #[test]
+    fn test_prometheus_scrape() {
+        let mut monitor = PrometheusMonitor::nop("127.0.0.1:0").unwrap();
+        let client = monitor.client_stats_mut_for(1);
+        client.update_corpus_size(42);
+        client.update_user_stats("stability".to_string(), UserStats::Ratio(3, 4));
+        client.update_user_stats(
+            "mode".to_string(),
+            UserStats::String("\"fast\"".to_string()),
+        );
+        monitor.display("Testcase".to_string(), 1);
+
+        let mut stream = TcpStream::connect(monitor.local_addr()).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("libafl_corpus_size{client=\"1\"} 42\n"));
+        assert!(response.contains("libafl_user_stats{client=\"1\",name=\"stability\"} 0.75\n"));
+        assert!(response.contains("name=\"mode\",value=\"\\\"fast\\\"\"} 1\n"));
+        assert!(response.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_prometheus_stalled_scraper() {
+        let mut monitor = PrometheusMonitor::nop("127.0.0.1:0").unwrap();
+        monitor.display("Testcase".to_string(), 1);
+
+        // Connect without ever sending a request
+        let _stalled = TcpStream::connect(monitor.local_addr()).unwrap();
+
+        let mut stream = TcpStream::connect(monitor.local_addr()).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+}
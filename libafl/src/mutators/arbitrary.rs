@@ -0,0 +1,600 @@
+//! Mutators for inputs with a [`HasValueTree`], such as [`crate::inputs::ArbitraryInput`]s:
+//! they change scalar fields, switch enum variants by regenerating fields, grow and shrink collections,
+//! and splice fields between inputs.
+//!
+//! Fields are addressed by [JSON pointers](https://www.rfc-editor.org/rfc/rfc6901) into the value tree.
+//! Mutated trees that don't deserialize to the type of the input are discarded, and the mutation is skipped.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use serde_json::{Number, Value};
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type, Named},
+    },
+    corpus::Corpus,
+    inputs::{HasValueTree, UsesInput},
+    mutators::{MutationResult, Mutator},
+    state::{HasCorpus, HasRand},
+    Error,
+};
+
+/// The maximum number of elements the mutators grow a collection to
+pub const MAX_COLLECTION_LEN: usize = 64;
+
+/// The maximum number of chars the mutators grow a string to
+pub const MAX_STRING_LEN: usize = 4096;
+
+/// The number of random bytes fields are regenerated from
+pub const REGENERATE_DATA_LEN: usize = 256;
+
+const INTERESTING_INTS: [i128; 16] = [
+    0,
+    1,
+    -1,
+    16,
+    32,
+    64,
+    100,
+    127,
+    128,
+    255,
+    256,
+    1024,
+    32767,
+    65535,
+    i32::MAX as i128,
+    u32::MAX as i128,
+];
+
+const INTERESTING_F64: [f64; 7] = [0.0, -0.0, 1.0, -1.0, 0.5, f64::MAX, f64::MIN_POSITIVE];
+
+/// Collect the JSON pointers to all values in `tree`, including `tree` itself
+fn collect_pointers(tree: &Value, prefix: &mut String, pointers: &mut Vec<String>) {
+    pointers.push(prefix.clone());
+    let len = prefix.len();
+    match tree {
+        Value::Array(elements) => {
+            for (idx, element) in elements.iter().enumerate() {
+                prefix.push('/');
+                prefix.push_str(&idx.to_string());
+                collect_pointers(element, prefix, pointers);
+                prefix.truncate(len);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                prefix.push('/');
+                prefix.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                collect_pointers(field, prefix, pointers);
+                prefix.truncate(len);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// All JSON pointers into `tree`
+fn pointers(tree: &Value) -> Vec<String> {
+    let mut pointers = vec![];
+    collect_pointers(tree, &mut String::new(), &mut pointers);
+    pointers
+}
+
+/// Set the mutated `tree` as value of `input`, if it changed and fits the type of the input
+fn set_mutated<I>(input: &mut I, tree: Value, original: &Value) -> MutationResult
+where
+    I: HasValueTree,
+{
+    if tree != *original && input.set_value_tree(tree).is_ok() {
+        MutationResult::Mutated
+    } else {
+        MutationResult::Skipped
+    }
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn mutate_int<R: Rand>(rand: &mut R, value: i128) -> i128 {
+    match rand.below(4) {
+        0 => INTERESTING_INTS[rand.below(INTERESTING_INTS.len() as u64) as usize],
+        1 => {
+            let delta = 1 + i128::from(rand.below(16));
+            if rand.below(2) == 0 {
+                value + delta
+            } else {
+                value - delta
+            }
+        }
+        2 => -value,
+        // Mostly flip bits that also exist in narrow integer types
+        _ => {
+            let bits = if rand.below(2) == 0 { 8 } else { 64 };
+            value ^ (1 << rand.below(bits))
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mutate_f64<R: Rand>(rand: &mut R, value: f64) -> f64 {
+    match rand.below(4) {
+        0 => INTERESTING_F64[rand.below(INTERESTING_F64.len() as u64) as usize],
+        1 => -value,
+        2 => value + (rand.below(32) as f64 - 16.0),
+        _ => value * 2.0,
+    }
+}
+
+/// Insert, remove or change a random char
+fn mutate_string<R: Rand>(rand: &mut R, string: &str) -> String {
+    let mut chars: Vec<char> = string.chars().collect();
+    let len = chars.len() as u64;
+    let c = char::from(rand.below(0x80) as u8);
+    match rand.below(3) {
+        0 if len > 0 => {
+            chars.remove(rand.below(len) as usize);
+        }
+        1 if len > 0 => chars[rand.below(len) as usize] = c,
+        _ if chars.len() < MAX_STRING_LEN => chars.insert(rand.below(len + 1) as usize, c),
+        _ => chars.clear(),
+    }
+    chars.into_iter().collect()
+}
+
+/// Mutate a scalar value, returns `false` for arrays, objects and `null`
+fn mutate_scalar<R: Rand>(rand: &mut R, value: &mut Value) -> bool {
+    match value {
+        Value::Bool(b) => *b = !*b,
+        Value::Number(n) => {
+            let int = n
+                .as_u64()
+                .map(i128::from)
+                .or_else(|| n.as_i64().map(i128::from));
+            let mutated = match int {
+                Some(int) => {
+                    let int = mutate_int(rand, int);
+                    u64::try_from(int)
+                        .map(Number::from)
+                        .or_else(|_| i64::try_from(int).map(Number::from))
+                        .ok()
+                }
+                None => Number::from_f64(mutate_f64(rand, n.as_f64().unwrap_or_default())),
+            };
+            match mutated {
+                Some(mutated) => *n = mutated,
+                None => return false,
+            }
+        }
+        Value::String(s) => *s = mutate_string(rand, s),
+        Value::Null | Value::Array(_) | Value::Object(_) => return false,
+    }
+    true
+}
+
+/// Changes a random number, bool or string in the value tree of an input
+#[derive(Default, Debug)]
+pub struct ArbitraryScalarMutator;
+
+impl<S> Mutator<S> for ArbitraryScalarMutator
+where
+    S: UsesInput + HasRand,
+    S::Input: HasValueTree,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let original = input.value_tree()?;
+        let mut pointers = pointers(&original);
+        pointers.retain(|pointer| {
+            matches!(
+                original.pointer(pointer),
+                Some(Value::Bool(_) | Value::Number(_) | Value::String(_))
+            )
+        });
+        if pointers.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let pointer = &pointers[state.rand_mut().below(pointers.len() as u64) as usize];
+
+        let mut tree = original.clone();
+        let changed = match tree.pointer_mut(pointer) {
+            Some(value) => mutate_scalar(state.rand_mut(), value),
+            None => false,
+        };
+        if changed {
+            Ok(set_mutated(input, tree, &original))
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+}
+
+impl Named for ArbitraryScalarMutator {
+    fn name(&self) -> &str {
+        "ArbitraryScalarMutator"
+    }
+}
+
+impl ArbitraryScalarMutator {
+    /// Creates a new [`ArbitraryScalarMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random field in the value tree of an input by the same field of a freshly generated value.
+/// This switches enum variants, and gives options and collections new contents.
+#[derive(Default, Debug)]
+pub struct ArbitraryRegenerateMutator;
+
+impl<S> Mutator<S> for ArbitraryRegenerateMutator
+where
+    S: UsesInput + HasRand,
+    S::Input: HasValueTree,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let rand = state.rand_mut();
+        let len = 1 + rand.below(REGENERATE_DATA_LEN as u64) as usize;
+        let data: Vec<u8> = (0..len).map(|_| rand.below(256) as u8).collect();
+        let generated = match S::Input::generate_value_tree(&data) {
+            Ok(generated) => generated,
+            Err(_) => return Ok(MutationResult::Skipped),
+        };
+
+        let original = input.value_tree()?;
+        let mut pointers = pointers(&original);
+        pointers.retain(|pointer| {
+            !pointer.is_empty()
+                && generated
+                    .pointer(pointer)
+                    .map_or(false, |value| Some(value) != original.pointer(pointer))
+        });
+        if pointers.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let pointer = &pointers[state.rand_mut().below(pointers.len() as u64) as usize];
+
+        let mut tree = original.clone();
+        match (tree.pointer_mut(pointer), generated.pointer(pointer)) {
+            (Some(value), Some(replacement)) => {
+                *value = replacement.clone();
+                Ok(set_mutated(input, tree, &original))
+            }
+            _ => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+impl Named for ArbitraryRegenerateMutator {
+    fn name(&self) -> &str {
+        "ArbitraryRegenerateMutator"
+    }
+}
+
+impl ArbitraryRegenerateMutator {
+    /// Creates a new [`ArbitraryRegenerateMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Removes an element of a random collection in the value tree of an input, duplicates one, or swaps two
+#[derive(Default, Debug)]
+pub struct ArbitraryCollectionMutator;
+
+impl<S> Mutator<S> for ArbitraryCollectionMutator
+where
+    S: UsesInput + HasRand,
+    S::Input: HasValueTree,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let original = input.value_tree()?;
+        let mut pointers = pointers(&original);
+        pointers.retain(|pointer| {
+            matches!(original.pointer(pointer), Some(Value::Array(elements)) if !elements.is_empty())
+        });
+        if pointers.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let pointer = &pointers[state.rand_mut().below(pointers.len() as u64) as usize];
+
+        let mut tree = original.clone();
+        let elements = match tree.pointer_mut(pointer) {
+            Some(Value::Array(elements)) => elements,
+            _ => return Ok(MutationResult::Skipped),
+        };
+        let rand = state.rand_mut();
+        let len = elements.len() as u64;
+        match rand.below(3) {
+            // Remove
+            0 => {
+                elements.remove(rand.below(len) as usize);
+            }
+            // Duplicate
+            1 if elements.len() < MAX_COLLECTION_LEN => {
+                let element = elements[rand.below(len) as usize].clone();
+                elements.insert(rand.below(len + 1) as usize, element);
+            }
+            // Swap
+            _ => elements.swap(rand.below(len) as usize, rand.below(len) as usize),
+        }
+        Ok(set_mutated(input, tree, &original))
+    }
+}
+
+impl Named for ArbitraryCollectionMutator {
+    fn name(&self) -> &str {
+        "ArbitraryCollectionMutator"
+    }
+}
+
+impl ArbitraryCollectionMutator {
+    /// Creates a new [`ArbitraryCollectionMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random field in the value tree of an input by the same field of another input of the corpus
+#[derive(Default, Debug)]
+pub struct ArbitrarySpliceMutator;
+
+impl<S> Mutator<S> for ArbitrarySpliceMutator
+where
+    S: UsesInput + HasRand + HasCorpus,
+    S::Input: HasValueTree,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        // We don't want to use the testcase we're already using for splicing
+        let count = state.corpus().count();
+        let idx = state.rand_mut().below(count as u64) as usize;
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let other = {
+            let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+            other_testcase.load_input()?.value_tree()?
+        };
+
+        let original = input.value_tree()?;
+        let mut pointers = pointers(&original);
+        pointers.retain(|pointer| {
+            !pointer.is_empty()
+                && other
+                    .pointer(pointer)
+                    .map_or(false, |value| Some(value) != original.pointer(pointer))
+        });
+        if pointers.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let pointer = &pointers[state.rand_mut().below(pointers.len() as u64) as usize];
+
+        let mut tree = original.clone();
+        match (tree.pointer_mut(pointer), other.pointer(pointer)) {
+            (Some(value), Some(replacement)) => {
+                *value = replacement.clone();
+                Ok(set_mutated(input, tree, &original))
+            }
+            _ => Ok(MutationResult::Skipped),
+        }
+    }
+}
+
+impl Named for ArbitrarySpliceMutator {
+    fn name(&self) -> &str {
+        "ArbitrarySpliceMutator"
+    }
+}
+
+impl ArbitrarySpliceMutator {
+    /// Creates a new [`ArbitrarySpliceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations that compose the arbitrary mutator
+pub type ArbitraryMutationsType = tuple_list_type!(
+    ArbitraryScalarMutator,
+    ArbitraryRegenerateMutator,
+    ArbitraryCollectionMutator,
+    ArbitrarySpliceMutator
+);
+
+/// Get the mutations that operate on the value trees of inputs with a [`HasValueTree`]
+#[must_use]
+pub fn arbitrary_mutations() -> ArbitraryMutationsType {
+    tuple_list!(
+        ArbitraryScalarMutator::new(),
+        ArbitraryRegenerateMutator::new(),
+        ArbitraryCollectionMutator::new(),
+        ArbitrarySpliceMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use arbitrary::{Arbitrary, Unstructured};
+    use serde::{Deserialize, Serialize};
+
+    use super::{
+        ArbitraryCollectionMutator, ArbitraryRegenerateMutator, ArbitraryScalarMutator,
+        ArbitrarySpliceMutator,
+    };
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::ArbitraryInput,
+        mutators::{MutationResult, Mutator},
+        state::{HasCorpus, StdState},
+    };
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    enum Shape {
+        Point,
+        Circle { radius: u32 },
+        Line(u8, u8),
+    }
+
+    impl<'a> Arbitrary<'a> for Shape {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+            Ok(match u8::arbitrary(u)? % 3 {
+                0 => Shape::Point,
+                1 => Shape::Circle {
+                    radius: u32::arbitrary(u)?,
+                },
+                _ => Shape::Line(u8::arbitrary(u)?, u8::arbitrary(u)?),
+            })
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Drawing {
+        name: String,
+        visible: bool,
+        scale: u16,
+        shapes: Vec<Shape>,
+    }
+
+    impl<'a> Arbitrary<'a> for Drawing {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+            Ok(Self {
+                name: String::arbitrary(u)?,
+                visible: bool::arbitrary(u)?,
+                scale: u16::arbitrary(u)?,
+                shapes: Vec::arbitrary(u)?,
+            })
+        }
+    }
+
+    fn drawing(name: &str, scale: u16, shapes: Vec<Shape>) -> ArbitraryInput<Drawing> {
+        ArbitraryInput::new(Drawing {
+            name: name.into(),
+            visible: true,
+            scale,
+            shapes,
+        })
+    }
+
+    #[test]
+    fn test_arbitrary_mutators() {
+        let mut corpus = InMemoryCorpus::new();
+        corpus
+            .add(Testcase::new(drawing(
+                "other",
+                7,
+                vec![Shape::Point, Shape::Line(1, 2), Shape::Point],
+            )))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        let input = drawing("drawing", 1000, vec![Shape::Circle { radius: 3 }]);
+
+        let mut mutated = 0;
+        for _ in 0..100 {
+            let mut scalar = input.clone();
+            if ArbitraryScalarMutator::new()
+                .mutate(&mut state, &mut scalar, 0)
+                .unwrap()
+                == MutationResult::Mutated
+            {
+                assert_ne!(scalar, input);
+                assert_eq!(scalar.value().shapes.len(), 1);
+                mutated += 1;
+            }
+        }
+        assert!(mutated > 0);
+
+        let mut mutated = 0;
+        for _ in 0..100 {
+            let mut collection = input.clone();
+            if ArbitraryCollectionMutator::new()
+                .mutate(&mut state, &mut collection, 0)
+                .unwrap()
+                == MutationResult::Mutated
+            {
+                let shapes = &collection.value().shapes;
+                assert!(shapes.is_empty() || shapes.len() == 2);
+                assert_eq!(collection.value().name, "drawing");
+                mutated += 1;
+            }
+        }
+        assert!(mutated > 0);
+
+        let mut mutated = 0;
+        for _ in 0..100 {
+            let mut regenerated = input.clone();
+            if ArbitraryRegenerateMutator::new()
+                .mutate(&mut state, &mut regenerated, 0)
+                .unwrap()
+                == MutationResult::Mutated
+            {
+                assert_ne!(regenerated, input);
+                mutated += 1;
+            }
+        }
+        assert!(mutated > 0);
+
+        // The only other testcase has a different name, scale and shapes
+        let mut spliced = input.clone();
+        assert_eq!(
+            ArbitrarySpliceMutator::new()
+                .mutate(&mut state, &mut spliced, 0)
+                .unwrap(),
+            MutationResult::Mutated
+        );
+        let other = state
+            .corpus()
+            .get(0)
+            .unwrap()
+            .borrow()
+            .input()
+            .clone()
+            .unwrap();
+        let value = spliced.value();
+        assert!(
+            value.name == other.value().name
+                || value.scale == other.value().scale
+                || value.shapes == other.value().shapes
+                || value.shapes[0] == other.value().shapes[0]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Mutators for the derivation trees of [`GrammarInput`]s: they replace subtrees by new derivations,
+//! by subtrees of other inputs derived from the same rule, or repeat recursive derivations.
+
+use alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type, Named},
+    },
+    corpus::Corpus,
+    generators::GrammarGenerator,
+    inputs::{DerivationTree, GrammarInput, UsesInput},
+    mutators::{MutationResult, Mutator},
+    state::{HasCorpus, HasRand},
+    Error,
+};
+
+/// The maximum number of rule nodes the mutators grow a derivation tree to
+pub const MAX_TREE_NODES: usize = 4096;
+
+/// The maximum number of times a recursion is repeated at once
+const MAX_RECURSIONS: u64 = 8;
+
+/// Replaces a random subtree of a [`GrammarInput`] by a new derivation of the same rule
+#[derive(Debug)]
+pub struct GrammarRegenerateMutator<'a, S>
+where
+    S: HasRand,
+{
+    generator: &'a GrammarGenerator<'a, S>,
+}
+
+impl<'a, S> Mutator<S> for GrammarRegenerateMutator<'a, S>
+where
+    S: UsesInput<Input = GrammarInput> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut GrammarInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let size = input.tree().size();
+        let idx = state.rand_mut().below(size as u64) as usize;
+        let node = input.tree_mut().get_mut(idx).unwrap();
+        let tree = self.generator.generate_tree(state.rand_mut(), node.rule);
+        if tree == *node || size - node.size() + tree.size() > MAX_TREE_NODES {
+            return Ok(MutationResult::Skipped);
+        }
+        *node = tree;
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl<'a, S> Named for GrammarRegenerateMutator<'a, S>
+where
+    S: HasRand,
+{
+    fn name(&self) -> &str {
+        "GrammarRegenerateMutator"
+    }
+}
+
+impl<'a, S> GrammarRegenerateMutator<'a, S>
+where
+    S: HasRand,
+{
+    /// Creates a new [`GrammarRegenerateMutator`].
+    #[must_use]
+    pub fn new(generator: &'a GrammarGenerator<'a, S>) -> Self {
+        Self { generator }
+    }
+}
+
+/// Replaces a random subtree of a [`GrammarInput`] by a subtree of another input from the corpus,
+/// derived from the same rule
+#[derive(Default, Debug)]
+pub struct GrammarSpliceMutator;
+
+impl<S> Mutator<S> for GrammarSpliceMutator
+where
+    S: UsesInput<Input = GrammarInput> + HasRand + HasCorpus,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut GrammarInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        // We don't want to use the testcase we're already using for splicing
+        let count = state.corpus().count();
+        let idx = state.rand_mut().below(count as u64) as usize;
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let rand_num = state.rand_mut().next() as usize;
+        let other_rand_num = state.rand_mut().next() as usize;
+
+        let (at, subtree) = {
+            let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+            let other = other_testcase.load_input()?;
+            let mut by_rule: HashMap<usize, Vec<&DerivationTree>> = HashMap::default();
+            for node in other.tree().nodes() {
+                by_rule.entry(node.rule).or_default().push(node);
+            }
+
+            // The nodes of our input we have a replacement for
+            let candidates: Vec<(usize, usize)> = input
+                .tree()
+                .nodes()
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| by_rule.contains_key(&node.rule))
+                .map(|(idx, node)| (idx, node.rule))
+                .collect();
+            if candidates.is_empty() {
+                return Ok(MutationResult::Skipped);
+            }
+            let (at, rule) = candidates[rand_num % candidates.len()];
+            let replacements = &by_rule[&rule];
+            let subtree = replacements[other_rand_num % replacements.len()].clone();
+            (at, subtree)
+        };
+
+        let size = input.tree().size();
+        let node = input.tree_mut().get_mut(at).unwrap();
+        if subtree == *node || size - node.size() + subtree.size() > MAX_TREE_NODES {
+            return Ok(MutationResult::Skipped);
+        }
+        *node = subtree;
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for GrammarSpliceMutator {
+    fn name(&self) -> &str {
+        "GrammarSpliceMutator"
+    }
+}
+
+impl GrammarSpliceMutator {
+    /// Creates a new [`GrammarSpliceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Repeats a recursion of a [`GrammarInput`]: a subtree containing a derivation of its own rule
+/// is nested into itself a random number of times, e.g. turning `(a)` into `(((a)))`.
+#[derive(Default, Debug)]
+pub struct GrammarRecursionMutator;
+
+impl<S> Mutator<S> for GrammarRecursionMutator
+where
+    S: UsesInput<Input = GrammarInput> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut GrammarInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let mut recursions = vec![];
+        collect_recursions(input.tree(), &mut 0, &mut vec![], &mut recursions);
+        if recursions.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let (outer, inner) = recursions[state.rand_mut().below(recursions.len() as u64) as usize];
+
+        // Nest the outer tree into the place of its inner derivation, over and over
+        let size = input.tree().size();
+        let outer_tree = input.tree().get(outer).unwrap().clone();
+        let mut nested = outer_tree.clone();
+        for _ in 0..=state.rand_mut().below(MAX_RECURSIONS) {
+            let mut next = outer_tree.clone();
+            if size - outer_tree.size() + next.size() - 1 + nested.size() > MAX_TREE_NODES {
+                break;
+            }
+            *next.get_mut(inner).unwrap() = nested;
+            nested = next;
+        }
+        if nested == outer_tree {
+            return Ok(MutationResult::Skipped);
+        }
+        *input.tree_mut().get_mut(outer).unwrap() = nested;
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for GrammarRecursionMutator {
+    fn name(&self) -> &str {
+        "GrammarRecursionMutator"
+    }
+}
+
+impl GrammarRecursionMutator {
+    /// Creates a new [`GrammarRecursionMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Collect the recursions of a tree, as the pre-order index of the outer node and the index of the
+/// innermost derivation of the same rule relative to it
+fn collect_recursions(
+    tree: &DerivationTree,
+    idx: &mut usize,
+    ancestors: &mut Vec<(usize, usize)>,
+    recursions: &mut Vec<(usize, usize)>,
+) {
+    let own = *idx;
+    *idx += 1;
+    if let Some((_, outer)) = ancestors.iter().rev().find(|(rule, _)| *rule == tree.rule) {
+        recursions.push((*outer, own - outer));
+    }
+    ancestors.push((tree.rule, own));
+    for subtree in tree.subtrees() {
+        collect_recursions(subtree, idx, ancestors, recursions);
+    }
+    ancestors.pop();
+}
+
+/// Tuple type of the mutations that compose the grammar mutator
+pub type GrammarMutationsType<'a, S> = tuple_list_type!(
+    GrammarRegenerateMutator<'a, S>,
+    GrammarSpliceMutator,
+    GrammarRecursionMutator
+);
+
+/// Get the mutations that operate on the derivation trees of [`GrammarInput`]s
+#[must_use]
+pub fn grammar_mutations<'a, S>(
+    generator: &'a GrammarGenerator<'a, S>,
+) -> GrammarMutationsType<'a, S>
+where
+    S: HasRand,
+{
+    tuple_list!(
+        GrammarRegenerateMutator::new(generator),
+        GrammarSpliceMutator::new(),
+        GrammarRecursionMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GrammarRecursionMutator, GrammarRegenerateMutator, GrammarSpliceMutator};
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        generators::{Generator, Grammar, GrammarGenerator},
+        inputs::GrammarInput,
+        mutators::{MutationResult, Mutator},
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn test_grammar_mutators() {
+        let grammar = Grammar::from_ebnf(
+            "expr ::= term | '(' expr ')' | expr '+' term
+             term ::= [0-9]",
+        )
+        .unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<GrammarInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut generator = GrammarGenerator::new(&grammar).with_max_depth(4);
+        for _ in 0..4 {
+            let input = generator.generate(&mut state).unwrap();
+            state.corpus_mut().add(Testcase::new(input)).unwrap();
+        }
+
+        let mut unparsed = vec![];
+        let mut input = generator.generate(&mut state).unwrap();
+        let mut regenerate = GrammarRegenerateMutator::new(&generator);
+        let mut splice = GrammarSpliceMutator::new();
+        let mut recursion = GrammarRecursionMutator::new();
+        let mut mutated = 0;
+        for _ in 0..100 {
+            for res in [
+                regenerate.mutate(&mut state, &mut input, 0).unwrap(),
+                splice.mutate(&mut state, &mut input, 0).unwrap(),
+                recursion.mutate(&mut state, &mut input, 0).unwrap(),
+            ] {
+                if res == MutationResult::Mutated {
+                    mutated += 1;
+                }
+            }
+            input.unparse(&mut unparsed);
+            let unparsed = core::str::from_utf8(&unparsed).unwrap();
+            assert_eq!(unparsed.matches('(').count(), unparsed.matches(')').count());
+            assert!(unparsed.ends_with(|c: char| c.is_ascii_digit() || c == ')'));
+            assert!(input.tree().size() <= super::MAX_TREE_NODES);
+        }
+        assert!(mutated > 100);
+    }
+}
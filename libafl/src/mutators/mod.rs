@@ -10,15 +10,32 @@ pub mod encoded_mutations;
 pub use encoded_mutations::*;
 pub mod mopt_mutator;
 pub use mopt_mutator::*;
+pub mod thompson_mutator;
+pub use thompson_mutator::*;
 pub mod gramatron;
 pub use gramatron::*;
+pub mod grammar;
+pub use grammar::{
+    grammar_mutations, GrammarMutationsType, GrammarRecursionMutator, GrammarRegenerateMutator,
+    GrammarSpliceMutator,
+};
 pub mod grimoire;
 pub use grimoire::*;
+pub mod multi_message;
+pub use multi_message::*;
 
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
 pub use nautilus::*;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "protobuf")]
+pub use protobuf::*;
+#[cfg(feature = "arbitrary_input")]
+pub mod arbitrary;
+#[cfg(feature = "arbitrary_input")]
+pub use self::arbitrary::*;
 
 use crate::{
     bolts::tuples::{HasConstLen, Named},
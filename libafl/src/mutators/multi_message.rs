@@ -0,0 +1,246 @@
+//! Mutators for the [`MultiMessageInput`], operating on whole messages:
+//! they drop, duplicate, and splice messages, keeping the messages themselves intact.
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type, Named},
+    },
+    corpus::Corpus,
+    inputs::{MultiMessageInput, UsesInput},
+    mutators::{MutationResult, Mutator},
+    state::{HasCorpus, HasRand},
+    Error,
+};
+
+/// The maximum number of messages the mutators grow an input to
+pub const MAX_MESSAGES: usize = 256;
+
+/// Drops a random message of a [`MultiMessageInput`]
+#[derive(Default, Debug)]
+pub struct MessageDeleteMutator;
+
+impl<S> Mutator<S> for MessageDeleteMutator
+where
+    S: UsesInput<Input = MultiMessageInput> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MultiMessageInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count <= 1 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let idx = state.rand_mut().below(count as u64) as usize;
+        input.messages_mut().remove(idx);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageDeleteMutator {
+    fn name(&self) -> &str {
+        "MessageDeleteMutator"
+    }
+}
+
+impl MessageDeleteMutator {
+    /// Creates a new [`MessageDeleteMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Duplicates a random message of a [`MultiMessageInput`], inserting the copy at a random position
+#[derive(Default, Debug)]
+pub struct MessageDuplicateMutator;
+
+impl<S> Mutator<S> for MessageDuplicateMutator
+where
+    S: UsesInput<Input = MultiMessageInput> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MultiMessageInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count == 0 || count >= MAX_MESSAGES {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let from = state.rand_mut().below(count as u64) as usize;
+        let to = state.rand_mut().below(count as u64 + 1) as usize;
+        let message = input.messages()[from].clone();
+        input.messages_mut().insert(to, message);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageDuplicateMutator {
+    fn name(&self) -> &str {
+        "MessageDuplicateMutator"
+    }
+}
+
+impl MessageDuplicateMutator {
+    /// Creates a new [`MessageDuplicateMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Splices a [`MultiMessageInput`] with another one from the corpus:
+/// the messages after a random position are replaced by a random run of messages of the other input.
+#[derive(Default, Debug)]
+pub struct MessageSpliceMutator;
+
+impl<S> Mutator<S> for MessageSpliceMutator
+where
+    S: UsesInput<Input = MultiMessageInput> + HasRand + HasCorpus,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MultiMessageInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        // We don't want to use the testcase we're already using for splicing
+        let count = state.corpus().count();
+        let idx = state.rand_mut().below(count as u64) as usize;
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let other_count = {
+            let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+            other_testcase.load_input()?.messages().len()
+        };
+        if other_count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let at = state.rand_mut().below(input.messages().len() as u64 + 1) as usize;
+        let from = state.rand_mut().below(other_count as u64) as usize;
+        let len = 1 + state.rand_mut().below((other_count - from) as u64) as usize;
+        let len = len.min(MAX_MESSAGES.saturating_sub(at));
+        if len == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+        let other = other_testcase.load_input()?;
+        input.messages_mut().truncate(at);
+        input
+            .messages_mut()
+            .extend_from_slice(&other.messages()[from..from + len]);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for MessageSpliceMutator {
+    fn name(&self) -> &str {
+        "MessageSpliceMutator"
+    }
+}
+
+impl MessageSpliceMutator {
+    /// Creates a new [`MessageSpliceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations that compose the multi-message mutator
+pub type MultiMessageMutationsType = tuple_list_type!(
+    MessageDeleteMutator,
+    MessageDuplicateMutator,
+    MessageSpliceMutator
+);
+
+/// Get the mutations that operate on whole messages of a [`MultiMessageInput`]
+#[must_use]
+pub fn multi_message_mutations() -> MultiMessageMutationsType {
+    tuple_list!(
+        MessageDeleteMutator::new(),
+        MessageDuplicateMutator::new(),
+        MessageSpliceMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{MessageDeleteMutator, MessageDuplicateMutator, MessageSpliceMutator};
+    use crate::{
+        bolts::rands::StdRand,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::MultiMessageInput,
+        mutators::{MutationResult, Mutator},
+        state::StdState,
+    };
+
+    fn messages(input: &MultiMessageInput) -> Vec<&[u8]> {
+        input.messages().iter().map(Vec::as_slice).collect()
+    }
+
+    #[test]
+    fn test_message_mutators() {
+        let mut corpus = InMemoryCorpus::new();
+        corpus
+            .add(Testcase::new(MultiMessageInput::new(vec![
+                b"HELO".to_vec(),
+                b"QUIT".to_vec(),
+            ])))
+            .unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let mut input = MultiMessageInput::new(vec![
+            b"USER a".to_vec(),
+            b"PASS b".to_vec(),
+            b"LIST".to_vec(),
+        ]);
+
+        let res = MessageDeleteMutator::new()
+            .mutate(&mut state, &mut input, 0)
+            .unwrap();
+        assert_eq!(res, MutationResult::Mutated);
+        assert_eq!(input.messages().len(), 2);
+
+        let res = MessageDuplicateMutator::new()
+            .mutate(&mut state, &mut input, 0)
+            .unwrap();
+        assert_eq!(res, MutationResult::Mutated);
+        assert_eq!(input.messages().len(), 3);
+
+        let res = MessageSpliceMutator::new()
+            .mutate(&mut state, &mut input, 0)
+            .unwrap();
+        assert_eq!(res, MutationResult::Mutated);
+        let spliced = messages(&input);
+        assert!(spliced.last() == Some(&&b"HELO"[..]) || spliced.last() == Some(&&b"QUIT"[..]));
+    }
+}
@@ -0,0 +1,625 @@
+//! Mutators for [`ProtobufInput`]s: they change scalar fields, add, remove or duplicate elements of
+//! repeated fields, and cross over submessages of the same type between inputs.
+
+use alloc::{string::String, vec::Vec};
+
+use prost::bytes::Bytes;
+use prost_reflect::{
+    DynamicMessage, FieldDescriptor, Kind, MessageDescriptor, ReflectMessage, Value,
+};
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{tuple_list, tuple_list_type, Named},
+    },
+    corpus::Corpus,
+    inputs::{ProtobufInput, UsesInput},
+    mutators::{MutationResult, Mutator},
+    state::{HasCorpus, HasRand},
+    Error,
+};
+
+/// The maximum number of elements the mutators grow a repeated field to
+pub const MAX_REPEATED_LEN: usize = 64;
+
+/// The maximum length the mutators grow `string` and `bytes` fields to
+pub const MAX_FIELD_BYTES: usize = 4096;
+
+const INTERESTING_I64: [i64; 12] = [
+    0,
+    1,
+    -1,
+    16,
+    127,
+    128,
+    255,
+    32767,
+    i32::MIN as i64,
+    i32::MAX as i64,
+    i64::MIN,
+    i64::MAX,
+];
+
+const INTERESTING_U64: [u64; 9] = [0, 1, 16, 127, 128, 255, 65535, u32::MAX as u64, u64::MAX];
+
+const INTERESTING_F64: [f64; 8] = [
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f64::NAN,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    f64::MIN_POSITIVE,
+];
+
+/// The path from the root message to a field, through set message fields and elements of repeated fields.
+/// A step without index is a singular field, or a repeated field itself.
+type FieldPath = Vec<(FieldDescriptor, Option<usize>)>;
+
+/// Collect the paths to all fields of `message` and its submessages, and to the elements of its repeated fields.
+/// Map fields are not supported.
+fn collect_paths(message: &DynamicMessage, prefix: &mut FieldPath, paths: &mut Vec<FieldPath>) {
+    for field in message.descriptor().fields() {
+        if field.is_map() {
+            continue;
+        }
+        prefix.push((field.clone(), None));
+        paths.push(prefix.clone());
+        prefix.pop();
+
+        if field.is_list() {
+            if let Some(list) = message.get_field(&field).as_list() {
+                for (idx, element) in list.iter().enumerate() {
+                    prefix.push((field.clone(), Some(idx)));
+                    paths.push(prefix.clone());
+                    if let Value::Message(submessage) = element {
+                        collect_paths(submessage, prefix, paths);
+                    }
+                    prefix.pop();
+                }
+            }
+        } else if message.has_field(&field) {
+            if let Value::Message(submessage) = &*message.get_field(&field) {
+                prefix.push((field.clone(), None));
+                collect_paths(submessage, prefix, paths);
+                prefix.pop();
+            }
+        }
+    }
+}
+
+/// If the path ends at a value of the field, not at a repeated field itself
+fn is_value(path: &FieldPath) -> bool {
+    let (field, idx) = path.last().unwrap();
+    idx.is_some() || !field.is_list()
+}
+
+/// The message type of the value at the end of the path, if it is a message
+fn message_type(path: &FieldPath) -> Option<MessageDescriptor> {
+    if is_value(path) {
+        if let Kind::Message(descriptor) = path.last().unwrap().0.kind() {
+            return Some(descriptor);
+        }
+    }
+    None
+}
+
+/// If the path ends at a value of a message type
+fn is_message(path: &FieldPath) -> bool {
+    message_type(path).is_some()
+}
+
+/// Collect copies of all submessages of `message`, in set fields and repeated fields
+fn collect_messages(message: &DynamicMessage, messages: &mut Vec<DynamicMessage>) {
+    for field in message.descriptor().fields() {
+        if field.is_map() || !message.has_field(&field) {
+            continue;
+        }
+        let value = message.get_field(&field);
+        let elements = match value.as_list() {
+            Some(list) => list.iter().collect(),
+            None => vec![&*value],
+        };
+        for element in elements {
+            if let Value::Message(submessage) = element {
+                messages.push(submessage.clone());
+                collect_messages(submessage, messages);
+            }
+        }
+    }
+}
+
+/// The message containing the last field of the path
+fn parent_mut<'a>(
+    message: &'a mut DynamicMessage,
+    path: &[(FieldDescriptor, Option<usize>)],
+) -> Option<&'a mut DynamicMessage> {
+    match path.split_first() {
+        None => Some(message),
+        Some(((field, idx), rest)) => {
+            let mut value = message.get_field_mut(field);
+            if let Some(idx) = idx {
+                value = value.as_list_mut()?.get_mut(*idx)?;
+            }
+            parent_mut(value.as_message_mut()?, rest)
+        }
+    }
+}
+
+/// Call `f` with the value at the end of the path, and the field it belongs to.
+/// Singular fields are set again, so that setting a member of a `oneof` clears the others.
+fn with_value_mut<F>(message: &mut DynamicMessage, path: &FieldPath, f: F) -> bool
+where
+    F: FnOnce(&mut Value, &FieldDescriptor) -> bool,
+{
+    let (field, idx) = path.last().unwrap();
+    let parent = match parent_mut(message, &path[..path.len() - 1]) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    if let Some(idx) = idx {
+        match parent
+            .get_field_mut(field)
+            .as_list_mut()
+            .and_then(|list| list.get_mut(*idx))
+        {
+            Some(element) => f(element, field),
+            None => false,
+        }
+    } else if field.is_list() {
+        f(parent.get_field_mut(field), field)
+    } else {
+        let mut value = parent.get_field(field).into_owned();
+        let changed = f(&mut value, field);
+        if changed {
+            parent.set_field(field, value);
+        }
+        changed
+    }
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn mutate_i64<R: Rand>(rand: &mut R, value: i64) -> i64 {
+    match rand.below(3) {
+        0 => INTERESTING_I64[rand.below(INTERESTING_I64.len() as u64) as usize],
+        1 => {
+            let delta = 1 + rand.below(16) as i64;
+            if rand.below(2) == 0 {
+                value.wrapping_add(delta)
+            } else {
+                value.wrapping_sub(delta)
+            }
+        }
+        _ => rand.next() as i64,
+    }
+}
+
+fn mutate_u64<R: Rand>(rand: &mut R, value: u64) -> u64 {
+    match rand.below(3) {
+        0 => INTERESTING_U64[rand.below(INTERESTING_U64.len() as u64) as usize],
+        1 => {
+            let delta = 1 + rand.below(16);
+            if rand.below(2) == 0 {
+                value.wrapping_add(delta)
+            } else {
+                value.wrapping_sub(delta)
+            }
+        }
+        _ => rand.next(),
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mutate_f64<R: Rand>(rand: &mut R, value: f64) -> f64 {
+    match rand.below(4) {
+        0 => INTERESTING_F64[rand.below(INTERESTING_F64.len() as u64) as usize],
+        1 => -value,
+        2 => value + (rand.below(32) as f64 - 16.0),
+        _ => f64::from_bits(rand.next()),
+    }
+}
+
+/// Insert, remove or change a random byte
+fn mutate_bytes<R: Rand>(rand: &mut R, bytes: &mut Vec<u8>) {
+    let len = bytes.len() as u64;
+    match rand.below(3) {
+        0 if len > 0 => {
+            bytes.remove(rand.below(len) as usize);
+        }
+        1 if len > 0 => {
+            bytes[rand.below(len) as usize] ^= 1_u8 << rand.below(8);
+        }
+        _ if bytes.len() < MAX_FIELD_BYTES => {
+            bytes.insert(rand.below(len + 1) as usize, rand.below(256) as u8);
+        }
+        _ => bytes.clear(),
+    }
+}
+
+/// Mutate a scalar value of the field, returns `false` for messages, repeated and map fields
+fn mutate_scalar<R: Rand>(rand: &mut R, value: &mut Value, field: &FieldDescriptor) -> bool {
+    match value {
+        Value::Bool(value) => *value = !*value,
+        Value::I32(value) => *value = mutate_i64(rand, i64::from(*value)) as i32,
+        Value::I64(value) => *value = mutate_i64(rand, *value),
+        Value::U32(value) => *value = mutate_u64(rand, u64::from(*value)) as u32,
+        Value::U64(value) => *value = mutate_u64(rand, *value),
+        Value::F32(value) => *value = mutate_f64(rand, f64::from(*value)) as f32,
+        Value::F64(value) => *value = mutate_f64(rand, *value),
+        Value::String(value) => {
+            let mut bytes = core::mem::take(value).into_bytes();
+            mutate_bytes(rand, &mut bytes);
+            *value = String::from_utf8_lossy(&bytes).into_owned();
+        }
+        Value::Bytes(value) => {
+            let mut bytes = value.to_vec();
+            mutate_bytes(rand, &mut bytes);
+            *value = Bytes::from(bytes);
+        }
+        Value::EnumNumber(number) => {
+            let numbers: Vec<i32> = match field.kind() {
+                Kind::Enum(descriptor) => descriptor.values().map(|value| value.number()).collect(),
+                _ => vec![],
+            };
+            // Mostly known values, sometimes an unknown one
+            *number = if numbers.is_empty() || rand.below(8) == 0 {
+                mutate_i64(rand, i64::from(*number)) as i32
+            } else {
+                numbers[rand.below(numbers.len() as u64) as usize]
+            };
+        }
+        Value::Message(_) | Value::List(_) | Value::Map(_) => return false,
+    }
+    true
+}
+
+/// Changes the value of a random scalar field of a [`ProtobufInput`], in the message or its submessages.
+/// Unset fields are set.
+#[derive(Default, Debug)]
+pub struct ProtobufScalarMutator;
+
+impl<S> Mutator<S> for ProtobufScalarMutator
+where
+    S: UsesInput<Input = ProtobufInput> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ProtobufInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let mut paths = vec![];
+        collect_paths(input.message(), &mut vec![], &mut paths);
+        paths.retain(|path| is_value(path) && !is_message(path));
+        if paths.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let path = &paths[state.rand_mut().below(paths.len() as u64) as usize];
+
+        let rand = state.rand_mut();
+        if with_value_mut(input.message_mut(), path, |value, field| {
+            mutate_scalar(rand, value, field)
+        }) {
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+}
+
+impl Named for ProtobufScalarMutator {
+    fn name(&self) -> &str {
+        "ProtobufScalarMutator"
+    }
+}
+
+impl ProtobufScalarMutator {
+    /// Creates a new [`ProtobufScalarMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Adds a new element to a random repeated field of a [`ProtobufInput`], removes one, or duplicates one
+#[derive(Default, Debug)]
+pub struct ProtobufRepeatedMutator;
+
+impl<S> Mutator<S> for ProtobufRepeatedMutator
+where
+    S: UsesInput<Input = ProtobufInput> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ProtobufInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let mut paths = vec![];
+        collect_paths(input.message(), &mut vec![], &mut paths);
+        paths.retain(|path| !is_value(path));
+        if paths.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let path = &paths[state.rand_mut().below(paths.len() as u64) as usize];
+
+        let rand = state.rand_mut();
+        if with_value_mut(input.message_mut(), path, |value, field| {
+            let list = match value.as_list_mut() {
+                Some(list) => list,
+                None => return false,
+            };
+            let len = list.len() as u64;
+            match rand.below(3) {
+                // Remove
+                0 if len > 0 => {
+                    list.remove(rand.below(len) as usize);
+                }
+                // Duplicate
+                1 if len > 0 && list.len() < MAX_REPEATED_LEN => {
+                    let element = list[rand.below(len) as usize].clone();
+                    list.insert(rand.below(len + 1) as usize, element);
+                }
+                // Add
+                _ if list.len() < MAX_REPEATED_LEN => {
+                    let mut element = Value::default_value(&field.kind());
+                    mutate_scalar(rand, &mut element, field);
+                    list.insert(rand.below(len + 1) as usize, element);
+                }
+                _ => return false,
+            }
+            true
+        }) {
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+}
+
+impl Named for ProtobufRepeatedMutator {
+    fn name(&self) -> &str {
+        "ProtobufRepeatedMutator"
+    }
+}
+
+impl ProtobufRepeatedMutator {
+    /// Creates a new [`ProtobufRepeatedMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random submessage of a [`ProtobufInput`] by a submessage of the same type
+/// from another input of the corpus
+#[derive(Default, Debug)]
+pub struct ProtobufCrossoverMutator;
+
+impl<S> Mutator<S> for ProtobufCrossoverMutator
+where
+    S: UsesInput<Input = ProtobufInput> + HasRand + HasCorpus,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ProtobufInput,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        // We don't want to use the testcase we're already using for crossover
+        let count = state.corpus().count();
+        let idx = state.rand_mut().below(count as u64) as usize;
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let mut paths = vec![];
+        collect_paths(input.message(), &mut vec![], &mut paths);
+        paths.retain(is_message);
+        if paths.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let rand_num = state.rand_mut().next() as usize;
+        let other_rand_num = state.rand_mut().next() as usize;
+
+        let (path, submessage) = {
+            let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
+            let other = other_testcase.load_input()?;
+            let mut other_messages = vec![];
+            collect_messages(other.message(), &mut other_messages);
+
+            // The submessages of our input we have a replacement for
+            let candidates: Vec<&FieldPath> = paths
+                .iter()
+                .filter(|path| {
+                    let descriptor = message_type(path);
+                    other_messages
+                        .iter()
+                        .any(|message| descriptor.as_ref() == Some(&message.descriptor()))
+                })
+                .collect();
+            if candidates.is_empty() {
+                return Ok(MutationResult::Skipped);
+            }
+            let path = candidates[rand_num % candidates.len()].clone();
+            let descriptor = message_type(&path);
+            let replacements: Vec<&DynamicMessage> = other_messages
+                .iter()
+                .filter(|message| descriptor.as_ref() == Some(&message.descriptor()))
+                .collect();
+            let submessage = replacements[other_rand_num % replacements.len()].clone();
+            (path, submessage)
+        };
+
+        if with_value_mut(input.message_mut(), &path, |value, _| {
+            if value.as_message() == Some(&submessage) {
+                return false;
+            }
+            *value = Value::Message(submessage);
+            true
+        }) {
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+}
+
+impl Named for ProtobufCrossoverMutator {
+    fn name(&self) -> &str {
+        "ProtobufCrossoverMutator"
+    }
+}
+
+impl ProtobufCrossoverMutator {
+    /// Creates a new [`ProtobufCrossoverMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations that compose the protobuf mutator
+pub type ProtobufMutationsType = tuple_list_type!(
+    ProtobufScalarMutator,
+    ProtobufRepeatedMutator,
+    ProtobufCrossoverMutator
+);
+
+/// Get the mutations that operate on the messages of [`ProtobufInput`]s
+#[must_use]
+pub fn protobuf_mutations() -> ProtobufMutationsType {
+    tuple_list!(
+        ProtobufScalarMutator::new(),
+        ProtobufRepeatedMutator::new(),
+        ProtobufCrossoverMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use prost::Message;
+    use prost_reflect::Value;
+
+    use super::{ProtobufCrossoverMutator, ProtobufRepeatedMutator, ProtobufScalarMutator};
+    use crate::{
+        bolts::{rands::StdRand, AsSlice},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{
+            protobuf::tests::request_descriptor, HasTargetBytes, ProtobufFormat, ProtobufInput,
+        },
+        mutators::{MutationResult, Mutator},
+        state::{HasCorpus, StdState},
+    };
+
+    type TestState = StdState<
+        ProtobufInput,
+        InMemoryCorpus<ProtobufInput>,
+        StdRand,
+        InMemoryCorpus<ProtobufInput>,
+    >;
+
+    fn input(text: &str) -> ProtobufInput {
+        ProtobufInput::parse_text(request_descriptor(), text, ProtobufFormat::Binary).unwrap()
+    }
+
+    fn test_state() -> TestState {
+        let mut corpus = InMemoryCorpus::new();
+        corpus
+            .add(Testcase::new(input(r#"first { id: 1000 name: "other" }"#)))
+            .unwrap();
+        StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap()
+    }
+
+    /// Mutate `input` a few times with `mutator`, and check that every mutant survives a serialization round-trip
+    fn mutants<M>(
+        mutator: &mut M,
+        state: &mut TestState,
+        input: &ProtobufInput,
+    ) -> Vec<ProtobufInput>
+    where
+        M: Mutator<TestState>,
+    {
+        let mut mutants = vec![];
+        for _ in 0..100 {
+            let mut mutant = input.clone();
+            if mutator.mutate(state, &mut mutant, 0).unwrap() == MutationResult::Mutated {
+                let bytes = mutant.target_bytes().as_slice().to_vec();
+                let decoded =
+                    ProtobufInput::decode(request_descriptor(), &bytes, ProtobufFormat::Binary)
+                        .unwrap();
+                assert_eq!(decoded.message().encode_to_vec(), bytes);
+                mutants.push(mutant);
+            }
+        }
+        mutants
+    }
+
+    #[test]
+    fn test_protobuf_scalar_mutator() {
+        let mut state = test_state();
+        let input = input(r#"size: 3 data: "ab" items { id: 1 name: "x" }"#);
+        let mutants = mutants(&mut ProtobufScalarMutator::new(), &mut state, &input);
+        assert!(!mutants.is_empty());
+        assert!(mutants.iter().any(|mutant| *mutant != input));
+        // Only values change, never the number of elements
+        for mutant in &mutants {
+            let items = mutant.message().get_field_by_name("items").unwrap();
+            assert_eq!(items.as_list().unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_protobuf_repeated_mutator() {
+        let mut state = test_state();
+        let input = input("items { id: 1 } items { id: 2 }");
+        let lens: Vec<usize> = mutants(&mut ProtobufRepeatedMutator::new(), &mut state, &input)
+            .iter()
+            .map(|mutant| {
+                let items = mutant.message().get_field_by_name("items").unwrap();
+                items.as_list().unwrap().len()
+            })
+            .collect();
+        assert!(lens.contains(&1));
+        assert!(lens.contains(&3));
+    }
+
+    #[test]
+    fn test_protobuf_crossover_mutator() {
+        let mut state = test_state();
+        // Not the current testcase, so that the other one is picked
+        state
+            .corpus_mut()
+            .add(Testcase::new(input("size: 1")))
+            .unwrap();
+        *state.corpus_mut().current_mut() = Some(1);
+
+        let input = input("size: 3 first { id: 1 }");
+        let mutants = mutants(&mut ProtobufCrossoverMutator::new(), &mut state, &input);
+        assert!(!mutants.is_empty());
+        for mutant in &mutants {
+            let first = mutant.message().get_field_by_name("first").unwrap();
+            let first = first.as_message().unwrap();
+            assert_eq!(*first.get_field_by_name("id").unwrap(), Value::I32(1000));
+            assert_eq!(
+                *mutant.message().get_field_by_name("size").unwrap(),
+                Value::U64(3)
+            );
+        }
+    }
+}
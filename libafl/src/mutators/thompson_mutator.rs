@@ -0,0 +1,750 @@
+//! A mutation scheduler choosing mutation operators by Thompson sampling,
+//! see <https://en.wikipedia.org/wiki/Thompson_sampling> and the bandit formulation of `MOpt`'s problem in
+//! <https://www.usenix.org/conference/usenixsecurity22/presentation/wu-mingyuan>.
+//!
+//! Every operator is an arm of a multi-armed bandit, and pays off when an input it mutated is added to the corpus.
+//! The success rate of each operator is modeled with a Beta distribution, both over all testcases and for each testcase,
+//! and the operator with the highest sample from its distribution is applied next.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug, Write},
+    marker::PhantomData,
+};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{
+        rands::Rand,
+        tuples::{Named, NamedTuple},
+    },
+    corpus::Corpus,
+    mutators::{
+        ComposedByMutations, LogMutationMetadata, MutationResult, Mutator, MutatorsTuple,
+        ScheduledMutator,
+    },
+    state::{HasCorpus, HasMetadata, HasNamedMetadata, HasRand, State},
+    Error,
+};
+
+/// The default number of trials the statistics over all testcases count as in the distribution of a testcase
+pub const DEFAULT_PRIOR_WEIGHT: f64 = 1000.0;
+
+/// The successes and failures of a mutation operator, or of a stacking depth.
+/// Its success rate is modeled as `Beta(successes + 1, failures + 1)`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MutationStats {
+    /// The number of mutated inputs that were added to the corpus
+    pub successes: u64,
+    /// The number of mutated inputs that were not added to the corpus, or mutations that were skipped
+    pub failures: u64,
+}
+
+impl MutationStats {
+    /// Count a success or a failure
+    pub fn record(&mut self, success: bool) {
+        if success {
+            self.successes += 1;
+        } else {
+            self.failures += 1;
+        }
+    }
+
+    /// The number of times the operator was used
+    #[must_use]
+    pub fn trials(&self) -> u64 {
+        self.successes + self.failures
+    }
+
+    /// The mean of the Beta distribution, the estimated success rate
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> f64 {
+        (self.successes as f64 + 1.0) / (self.trials() as f64 + 2.0)
+    }
+}
+
+/// The statistics of the mutation operators of a [`ThompsonScheduledMutator`].
+/// It is placed in the named metadata of the state, under the name of the mutator, for the statistics over all testcases,
+/// and in the [`ThompsonTestcaseMetadata`] of each testcase for the statistics of mutating it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThompsonMetadata {
+    /// The statistics of each mutation operator, by index in the mutations
+    pub operators: Vec<MutationStats>,
+    /// The statistics of each stacking depth, where depth `d` applies `2^(d + 1)` mutations.
+    /// Empty if the stacking depth is not sampled.
+    pub depths: Vec<MutationStats>,
+}
+
+crate::impl_serdeany!(ThompsonMetadata);
+
+impl ThompsonMetadata {
+    /// Creates new [`ThompsonMetadata`] for `operators` mutation operators and `depths` stacking depths
+    #[must_use]
+    pub fn new(operators: usize, depths: usize) -> Self {
+        Self {
+            operators: vec![MutationStats::default(); operators],
+            depths: vec![MutationStats::default(); depths],
+        }
+    }
+}
+
+/// The statistics of mutating a [`crate::corpus::Testcase`], of each [`ThompsonScheduledMutator`] by its name
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThompsonTestcaseMetadata {
+    /// The statistics of each mutator
+    pub mutators: HashMap<String, ThompsonMetadata>,
+}
+
+crate::impl_serdeany!(ThompsonTestcaseMetadata);
+
+/// A sample of the uniform distribution over `(0, 1)`
+#[allow(clippy::cast_precision_loss)]
+fn sample_uniform<R: Rand>(rand: &mut R) -> f64 {
+    ((rand.next() >> 11) as f64 + 0.5) / (1_u64 << 53) as f64
+}
+
+/// A sample of the standard normal distribution, with the Box-Muller transform
+fn sample_normal<R: Rand>(rand: &mut R) -> f64 {
+    let u1 = sample_uniform(rand);
+    let u2 = sample_uniform(rand);
+    libm::sqrt(-2.0 * libm::log(u1)) * libm::cos(2.0 * core::f64::consts::PI * u2)
+}
+
+/// A sample of `Gamma(shape, 1)` for `shape >= 1`, with the method of Marsaglia and Tsang
+#[allow(clippy::many_single_char_names)]
+fn sample_gamma<R: Rand>(rand: &mut R, shape: f64) -> f64 {
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / libm::sqrt(9.0 * d);
+    loop {
+        let x = sample_normal(rand);
+        let v = 1.0 + c * x;
+        if v <= 0.0 {
+            continue;
+        }
+        let v = v * v * v;
+        let u = sample_uniform(rand);
+        if libm::log(u) < 0.5 * x * x + d - d * v + d * libm::log(v) {
+            return d * v;
+        }
+    }
+}
+
+/// A sample of `Beta(alpha, beta)` for `alpha, beta >= 1`
+fn sample_beta<R: Rand>(rand: &mut R, alpha: f64, beta: f64) -> f64 {
+    let x = sample_gamma(rand, alpha);
+    let y = sample_gamma(rand, beta);
+    x / (x + y)
+}
+
+/// Draw a sample of the Beta distribution of each arm.
+/// The global statistics are scaled down to count as at most `prior_weight` trials,
+/// and act as prior for the statistics of the testcase.
+#[allow(clippy::cast_precision_loss)]
+fn sample_arms<R: Rand>(
+    rand: &mut R,
+    global: &[MutationStats],
+    testcase: Option<&[MutationStats]>,
+    prior_weight: f64,
+) -> Vec<f64> {
+    global
+        .iter()
+        .enumerate()
+        .map(|(idx, stats)| {
+            let scale = if stats.trials() as f64 > prior_weight {
+                prior_weight / stats.trials() as f64
+            } else {
+                1.0
+            };
+            let mut alpha = 1.0 + stats.successes as f64 * scale;
+            let mut beta = 1.0 + stats.failures as f64 * scale;
+            if let Some(local) = testcase.and_then(|testcase| testcase.get(idx)) {
+                alpha += local.successes as f64;
+                beta += local.failures as f64;
+            }
+            sample_beta(rand, alpha, beta)
+        })
+        .collect()
+}
+
+/// Pick the arm with the highest sample of its Beta distribution, see [`sample_arms`]
+fn sample_arm<R: Rand>(
+    rand: &mut R,
+    global: &[MutationStats],
+    testcase: Option<&[MutationStats]>,
+    prior_weight: f64,
+) -> usize {
+    let mut best = 0;
+    let mut best_sample = f64::MIN;
+    for (idx, sample) in sample_arms(rand, global, testcase, prior_weight)
+        .into_iter()
+        .enumerate()
+    {
+        if sample > best_sample {
+            best = idx;
+            best_sample = sample;
+        }
+    }
+    best
+}
+
+/// Pick an arm with a probability proportional to its sample, to vary the arms of a stack drawn once
+fn pick_weighted<R: Rand>(rand: &mut R, samples: &[f64]) -> usize {
+    let mut x = sample_uniform(rand) * samples.iter().sum::<f64>();
+    for (idx, sample) in samples.iter().enumerate() {
+        if x < *sample {
+            return idx;
+        }
+        x -= sample;
+    }
+    samples.len() - 1
+}
+
+/// A [`ScheduledMutator`] choosing the mutation operators, and optionally the number of stacked mutations,
+/// by Thompson sampling.
+///
+/// The operators that mutated an input are rewarded when the input is added to the corpus,
+/// operators that skipped count as failures.
+/// The statistics are kept in [`ThompsonMetadata`] in the state, and in each testcase that is fuzzed,
+/// so operators that pay off for a testcase are preferred on it.
+/// They are stored under the [`Named::name`] of the mutator, which defaults to the names of its mutations,
+/// so several mutators in one fuzzer keep separate statistics.
+/// Like the [`crate::mutators::LoggerScheduledMutator`], it places the operators that created a new
+/// testcase in its [`LogMutationMetadata`], and [`ThompsonScheduledMutator::report`] lists the statistics.
+pub struct ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    name: String,
+    mutations: MT,
+    max_stack_pow: u64,
+    sample_depth: bool,
+    prior_weight: f64,
+    /// The operators applied in the last run, and whether they mutated the input
+    mutation_log: Vec<(usize, bool)>,
+    /// The stacking depth of the last run, if sampled
+    last_depth: Option<usize>,
+    phantom: PhantomData<S>,
+}
+
+impl<MT, S> Debug for ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ThompsonScheduledMutator with {} mutations for Input type {}",
+            self.mutations.len(),
+            core::any::type_name::<S::Input>()
+        )
+    }
+}
+
+impl<MT, S> Mutator<S> for ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    #[inline]
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        self.scheduled_mutate(state, input, stage_idx)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        _stage_idx: i32,
+        corpus_idx: Option<usize>,
+    ) -> Result<(), Error> {
+        let success = corpus_idx.is_some();
+        let empty = self.empty_metadata();
+
+        if !self.fits(state.named_metadata().get::<ThompsonMetadata>(&self.name)) {
+            state.add_named_metadata(empty.clone(), &self.name);
+        }
+        self.record(
+            state
+                .named_metadata_mut()
+                .get_mut::<ThompsonMetadata>(&self.name)
+                .unwrap(),
+            success,
+        );
+
+        if let Some(idx) = *state.corpus().current() {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            if !testcase.has_metadata::<ThompsonTestcaseMetadata>() {
+                testcase.add_metadata(ThompsonTestcaseMetadata::default());
+            }
+            let mutators = &mut testcase
+                .metadata_mut()
+                .get_mut::<ThompsonTestcaseMetadata>()
+                .unwrap()
+                .mutators;
+            let metadata = mutators.entry(self.name.clone()).or_default();
+            if !self.fits(Some(metadata)) {
+                *metadata = empty;
+            }
+            self.record(metadata, success);
+        }
+
+        if let Some(idx) = corpus_idx {
+            let mut log = Vec::<String>::new();
+            for (mutation, mutated) in &self.mutation_log {
+                if *mutated {
+                    log.push(String::from(self.mutations.name(*mutation).unwrap()));
+                }
+            }
+            state
+                .corpus()
+                .get(idx)?
+                .borrow_mut()
+                .add_metadata(LogMutationMetadata::new(log));
+        }
+
+        // Always reset the log for each run
+        self.mutation_log.clear();
+        self.last_depth = None;
+        Ok(())
+    }
+}
+
+impl<MT, S> Named for ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<MT, S> ComposedByMutations<MT, S> for ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    /// Get the mutations
+    #[inline]
+    fn mutations(&self) -> &MT {
+        &self.mutations
+    }
+
+    // Get the mutations (mutable)
+    #[inline]
+    fn mutations_mut(&mut self) -> &mut MT {
+        &mut self.mutations
+    }
+}
+
+impl<MT, S> ScheduledMutator<MT, S> for ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    /// Compute the number of iterations used to apply stacked mutations
+    fn iterations(&self, state: &mut S, _: &S::Input) -> u64 {
+        let (global, testcase) = self.load_metadata(state);
+        1 << (1 + self.choose_depth(state, &global, testcase.as_ref()))
+    }
+
+    /// Get the next mutation to apply
+    fn schedule(&self, state: &mut S, _: &S::Input) -> usize {
+        debug_assert!(!self.mutations().is_empty());
+        let (global, testcase) = self.load_metadata(state);
+        sample_arm(
+            state.rand_mut(),
+            &global.operators,
+            testcase
+                .as_ref()
+                .map(|testcase| testcase.operators.as_slice()),
+            self.prior_weight,
+        )
+    }
+
+    fn scheduled_mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        debug_assert!(!self.mutations().is_empty());
+        // Load the statistics once, they only change in `post_exec`
+        let (global, testcase) = self.load_metadata(state);
+        let testcase_operators = testcase
+            .as_ref()
+            .map(|testcase| testcase.operators.as_slice());
+
+        let depth = self.choose_depth(state, &global, testcase.as_ref());
+        self.last_depth = if self.sample_depth { Some(depth) } else { None };
+        self.mutation_log.clear();
+
+        // Draw the posteriors once for the whole stack, a Beta sample per operator is costly
+        let samples = sample_arms(
+            state.rand_mut(),
+            &global.operators,
+            testcase_operators,
+            self.prior_weight,
+        );
+
+        let mut r = MutationResult::Skipped;
+        for _ in 0..(1_u64 << (1 + depth)) {
+            let idx = pick_weighted(state.rand_mut(), &samples);
+            let outcome = self
+                .mutations_mut()
+                .get_and_mutate(idx, state, input, stage_idx)?;
+            self.mutation_log
+                .push((idx, outcome == MutationResult::Mutated));
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl<MT, S> ThompsonScheduledMutator<MT, S>
+where
+    MT: MutatorsTuple<S> + NamedTuple,
+    S: State + HasRand + HasCorpus + HasNamedMetadata,
+{
+    /// Create a new [`ThompsonScheduledMutator`] instance specifying mutations
+    pub fn new(mutations: MT) -> Self {
+        Self::with_max_stack_pow(mutations, 7)
+    }
+
+    /// Create a new [`ThompsonScheduledMutator`] instance specifying mutations and the maximun number of iterations
+    pub fn with_max_stack_pow(mutations: MT, max_stack_pow: u64) -> Self {
+        let name = (0..mutations.len())
+            .map(|idx| mutations.name(idx).unwrap())
+            .collect::<Vec<_>>()
+            .join(",");
+        Self {
+            name: format!("ThompsonScheduledMutator({name})"),
+            mutations,
+            max_stack_pow,
+            sample_depth: false,
+            prior_weight: DEFAULT_PRIOR_WEIGHT,
+            mutation_log: vec![],
+            last_depth: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The name the statistics of this mutator are stored under, to tell apart mutators with the same mutations
+    #[must_use]
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = String::from(name);
+        self
+    }
+
+    /// Also choose the number of stacked mutations by Thompson sampling, instead of uniformly
+    #[must_use]
+    pub fn sample_stacking_depth(mut self, sample_depth: bool) -> Self {
+        self.sample_depth = sample_depth;
+        self
+    }
+
+    /// The number of trials the statistics over all testcases count as at most, when sampling for a testcase.
+    /// The lower it is, the faster the schedule adapts to a testcase.
+    #[must_use]
+    pub fn prior_weight(mut self, prior_weight: f64) -> Self {
+        self.prior_weight = prior_weight;
+        self
+    }
+
+    /// The statistics over all testcases of each mutation operator, by name
+    pub fn operator_stats<'a>(&'a self, state: &S) -> Vec<(&'a str, MutationStats)> {
+        let (global, _) = self.load_metadata(state);
+        global
+            .operators
+            .iter()
+            .enumerate()
+            .map(|(idx, stats)| (self.mutations.name(idx).unwrap(), *stats))
+            .collect()
+    }
+
+    /// A report of the statistics over all testcases, one line per mutation operator,
+    /// from the most to the least successful one
+    #[allow(clippy::cast_precision_loss)]
+    pub fn report(&self, state: &S) -> String {
+        let mut operators = self.operator_stats(state);
+        operators.sort_by(|(_, a), (_, b)| b.mean().total_cmp(&a.mean()));
+        let mut report = String::new();
+        for (name, stats) in operators {
+            writeln!(
+                report,
+                "{name}: {}/{} finds ({:.4}%)",
+                stats.successes,
+                stats.trials(),
+                stats.successes as f64 * 100.0 / stats.trials().max(1) as f64
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    /// Empty statistics for the mutations and stacking depths of this mutator
+    fn empty_metadata(&self) -> ThompsonMetadata {
+        let depths = if self.sample_depth {
+            self.max_stack_pow as usize
+        } else {
+            0
+        };
+        ThompsonMetadata::new(self.mutations.len(), depths)
+    }
+
+    /// If `metadata` exists and has statistics for the mutations and stacking depths of this mutator
+    fn fits(&self, metadata: Option<&ThompsonMetadata>) -> bool {
+        let empty = self.empty_metadata();
+        metadata.map_or(false, |metadata| {
+            metadata.operators.len() == empty.operators.len()
+                && metadata.depths.len() == empty.depths.len()
+        })
+    }
+
+    /// The statistics over all testcases, and of the current testcase
+    fn load_metadata(&self, state: &S) -> (ThompsonMetadata, Option<ThompsonMetadata>) {
+        let global = state.named_metadata().get::<ThompsonMetadata>(&self.name);
+        let global = if self.fits(global) {
+            global.unwrap().clone()
+        } else {
+            self.empty_metadata()
+        };
+        let testcase = state.corpus().current().and_then(|idx| {
+            let testcase = state.corpus().get(idx).ok()?.borrow();
+            let metadata = testcase
+                .metadata()
+                .get::<ThompsonTestcaseMetadata>()?
+                .mutators
+                .get(&self.name);
+            if self.fits(metadata) {
+                metadata.cloned()
+            } else {
+                None
+            }
+        });
+        (global, testcase)
+    }
+
+    /// The stacking depth to use, sampled or uniform
+    fn choose_depth(
+        &self,
+        state: &mut S,
+        global: &ThompsonMetadata,
+        testcase: Option<&ThompsonMetadata>,
+    ) -> usize {
+        if self.sample_depth {
+            sample_arm(
+                state.rand_mut(),
+                &global.depths,
+                testcase.map(|testcase| testcase.depths.as_slice()),
+                self.prior_weight,
+            )
+        } else {
+            state.rand_mut().below(self.max_stack_pow) as usize
+        }
+    }
+
+    /// Record the outcome of the last run in `metadata`
+    fn record(&self, metadata: &mut ThompsonMetadata, success: bool) {
+        for (idx, mutated) in &self.mutation_log {
+            if let Some(stats) = metadata.operators.get_mut(*idx) {
+                stats.record(success && *mutated);
+            }
+        }
+        if let Some(stats) = self
+            .last_depth
+            .and_then(|depth| metadata.depths.get_mut(depth))
+        {
+            stats.record(success);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pick_weighted, sample_arm, sample_beta, MutationStats, ThompsonMetadata,
+        ThompsonScheduledMutator, ThompsonTestcaseMetadata,
+    };
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, HasConstLen, Named},
+        },
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasBytesVec},
+        mutators::{
+            mutations::{BitFlipMutator, BytesDeleteMutator},
+            scheduled::havoc_mutations,
+            MutationResult, Mutator,
+        },
+        state::{HasCorpus, HasMetadata, HasNamedMetadata, StdState},
+    };
+
+    #[test]
+    fn test_sample_beta() {
+        let mut rand = StdRand::with_seed(0x1337);
+        let mean = (0..1000)
+            .map(|_| sample_beta(&mut rand, 3.0, 7.0))
+            .sum::<f64>()
+            / 1000.0;
+        assert!((mean - 0.3).abs() < 0.05);
+
+        // An arm with a much better success rate wins nearly always
+        let good = MutationStats {
+            successes: 50,
+            failures: 50,
+        };
+        let bad = MutationStats {
+            successes: 1,
+            failures: 99,
+        };
+        let wins = (0..100)
+            .filter(|_| sample_arm(&mut rand, &[bad, good, bad], None, 1000.0) == 1)
+            .count();
+        assert!(wins > 95);
+
+        // The statistics of the testcase outweigh the scaled down global ones
+        let testcase = [good, bad, bad];
+        let global = [
+            bad,
+            MutationStats {
+                successes: 50_000,
+                failures: 50_000,
+            },
+            bad,
+        ];
+        let wins = (0..100)
+            .filter(|_| sample_arm(&mut rand, &global, Some(&testcase), 10.0) == 0)
+            .count();
+        assert!(wins > 95);
+
+        // A stack drawn once still varies its arms, in proportion to their samples
+        let picks = (0..1000)
+            .filter(|_| pick_weighted(&mut rand, &[0.25, 0.75]) == 1)
+            .count();
+        assert!((650..850).contains(&picks));
+        assert_eq!(pick_weighted(&mut rand, &[0.0, 0.5, 0.0]), 1);
+    }
+
+    #[test]
+    fn test_thompson_mutator() {
+        let mut corpus: InMemoryCorpus<BytesInput> = InMemoryCorpus::new();
+        corpus
+            .add(Testcase::new(vec![b'a', b'b', b'c'].into()))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0x1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        *state.corpus_mut().current_mut() = Some(0);
+
+        let mut mutator = ThompsonScheduledMutator::with_max_stack_pow(
+            tuple_list!(BitFlipMutator::new(), BytesDeleteMutator::new()),
+            2,
+        )
+        .sample_stacking_depth(true);
+
+        // Pretend that every input with a bit flip that was not shortened is interesting
+        for _ in 0..200 {
+            let mut input = BytesInput::new(vec![b'a'; 64]);
+            assert_eq!(
+                mutator.mutate(&mut state, &mut input, 0).unwrap(),
+                MutationResult::Mutated
+            );
+            let corpus_idx = if input.bytes().len() == 64 {
+                Some(state.corpus_mut().add(Testcase::new(input)).unwrap())
+            } else {
+                None
+            };
+            mutator.post_exec(&mut state, 0, corpus_idx).unwrap();
+        }
+
+        assert_eq!(
+            mutator.name(),
+            "ThompsonScheduledMutator(BitFlipMutator,BytesDeleteMutator)"
+        );
+        let global = state
+            .named_metadata()
+            .get::<ThompsonMetadata>(mutator.name())
+            .unwrap()
+            .clone();
+        // A deletion may remove no bytes, so it is rewarded now and then
+        assert!(global.operators[0].mean() > global.operators[1].mean());
+        assert!(global.operators[0].trials() > global.operators[1].trials());
+        assert!(global.depths.iter().any(|stats| stats.successes > 0));
+        assert_eq!(
+            state
+                .corpus()
+                .get(0)
+                .unwrap()
+                .borrow()
+                .metadata()
+                .get::<ThompsonTestcaseMetadata>()
+                .unwrap()
+                .mutators
+                .get(mutator.name()),
+            Some(&global)
+        );
+        assert!(mutator.report(&state).starts_with("BitFlipMutator: "));
+
+        // Other mutators keep their statistics apart, also with the same mutations
+        let mut havoc = ThompsonScheduledMutator::new(havoc_mutations());
+        let mut renamed = ThompsonScheduledMutator::new(tuple_list!(
+            BitFlipMutator::new(),
+            BytesDeleteMutator::new()
+        ))
+        .with_name("renamed");
+        for other in [&mut havoc as &mut dyn Mutator<_>, &mut renamed] {
+            let mut input = BytesInput::new(vec![b'a'; 16]);
+            other.mutate(&mut state, &mut input, 0).unwrap();
+            other.post_exec(&mut state, 0, None).unwrap();
+        }
+        assert_eq!(
+            state
+                .named_metadata()
+                .get::<ThompsonMetadata>(havoc.name())
+                .unwrap()
+                .operators
+                .len(),
+            havoc_mutations().len()
+        );
+        assert!(state
+            .named_metadata()
+            .get::<ThompsonMetadata>("renamed")
+            .unwrap()
+            .operators
+            .iter()
+            .all(|stats| stats.successes == 0));
+        assert_eq!(
+            state
+                .named_metadata()
+                .get::<ThompsonMetadata>(mutator.name()),
+            Some(&global)
+        );
+        let testcase = state.corpus().get(0).unwrap().borrow();
+        let mutators = &testcase
+            .metadata()
+            .get::<ThompsonTestcaseMetadata>()
+            .unwrap()
+            .mutators;
+        assert_eq!(mutators.len(), 3);
+        assert_eq!(mutators.get(mutator.name()), Some(&global));
+    }
+}
@@ -25,6 +25,7 @@ use crate::{
     inputs::{HasBytesVec, UsesInput},
     mutators::{buffer_self_copy, mutations::buffer_copy, MutationResult, Mutator, Named},
     observers::cmp::{CmpValues, CmpValuesMetadata},
+    stages::colorization::ColorizationMetadata,
     state::{HasMaxSize, HasMetadata, HasRand},
     Error,
 };
@@ -603,6 +604,58 @@ impl I2SRandReplace {
     }
 }
 
+/// A `I2SColorizedReplace` [`Mutator`] replaces a comparison operand at the exact input offset it was
+/// found at by the [`crate::stages::ColorizationStage`], with the other operand of that comparison.
+/// It needs a valid [`ColorizationMetadata`] in the state.
+#[derive(Debug, Default)]
+pub struct I2SColorizedReplace;
+
+impl<S> Mutator<S> for I2SColorizedReplace
+where
+    S: UsesInput + HasMetadata + HasRand + HasMaxSize,
+    S::Input: HasBytesVec,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let matches_len = match state.metadata().get::<ColorizationMetadata>() {
+            Some(meta) if !meta.matches.is_empty() => meta.matches.len(),
+            _ => return Ok(MutationResult::Skipped),
+        };
+        let idx = state.rand_mut().below(matches_len as u64) as usize;
+
+        let meta = state.metadata().get::<ColorizationMetadata>().unwrap();
+        let found = &meta.matches[idx];
+        let end = found.offset + found.pattern.len();
+
+        // The input may have been mutated since it was colorized, check that the operand is still in place
+        let bytes = input.bytes_mut();
+        if end > bytes.len() || bytes[found.offset..end] != found.pattern[..] {
+            return Ok(MutationResult::Skipped);
+        }
+        bytes[found.offset..end].copy_from_slice(&found.replacement);
+
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for I2SColorizedReplace {
+    fn name(&self) -> &str {
+        "I2SColorizedReplace"
+    }
+}
+
+impl I2SColorizedReplace {
+    /// Creates a new `I2SColorizedReplace` struct.
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "std")]
@@ -4,12 +4,17 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use core::{fmt::Debug, marker::PhantomData};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    mem::size_of,
+};
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    bolts::{ownedref::OwnedRefMut, tuples::Named, AsMutSlice, AsSlice},
+    bolts::{ownedref::OwnedRefMut, shmem::ShMem, tuples::Named, AsMutSlice, AsSlice},
+    executors::ExitKind,
     inputs::UsesInput,
     observers::Observer,
     state::HasMetadata,
@@ -17,7 +22,7 @@ use crate::{
 };
 
 /// Compare values collected during a run
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CmpValues {
     /// Two u8 values
     U8((u8, u8)),
@@ -277,3 +282,366 @@ where
         }
     }
 }
+
+/// The number of cmps in an AFL++ cmplog map
+pub const AFL_CMP_MAP_W: usize = 65536;
+/// The number of executions logged per instruction in an AFL++ cmplog map
+pub const AFL_CMP_MAP_H: usize = 32;
+/// The number of executions logged per routine in an AFL++ cmplog map
+pub const AFL_CMP_MAP_RTN_H: usize = AFL_CMP_MAP_H / 2;
+/// The length of a routine argument logged by AFL++
+const AFL_CMP_RTN_LEN: usize = 31;
+
+/// AFL++ cmplog type of a compare instruction
+pub const AFL_CMP_TYPE_INS: u8 = 1;
+/// AFL++ cmplog type of a call to a compare routine, like `strcmp`
+pub const AFL_CMP_TYPE_RTN: u8 = 2;
+
+/// The header of a cmp in an AFL++ cmplog map, the 64 bit wide bitfield `struct cmp_header` of `AFL++` 4:
+/// `hits: 24, id: 24, shape: 5, type: 2, attribute: 4, overflow: 1, reserved: 4`
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AFLppCmpHeader(u64);
+
+impl AFLppCmpHeader {
+    /// How often the cmp was executed
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        (self.0 & 0xff_ffff) as usize
+    }
+
+    /// The size of the operands, minus one
+    #[must_use]
+    pub fn shape(&self) -> usize {
+        ((self.0 >> 48) & 0x1f) as usize
+    }
+
+    /// [`AFL_CMP_TYPE_INS`] or [`AFL_CMP_TYPE_RTN`]
+    #[must_use]
+    pub fn kind(&self) -> u8 {
+        ((self.0 >> 53) & 0x3) as u8
+    }
+}
+
+/// The operands of a compare instruction logged by AFL++
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AFLppCmpOperands {
+    v0: u64,
+    v1: u64,
+    v0_128: u64,
+    v1_128: u64,
+}
+
+/// The arguments of a compare routine logged by AFL++
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AFLppCmpFnOperands {
+    v0: [u8; AFL_CMP_RTN_LEN],
+    v0_len: u8,
+    v1: [u8; AFL_CMP_RTN_LEN],
+    v1_len: u8,
+}
+
+/// Union of the operands and the routine arguments of an AFL++ cmplog map
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union AFLppCmpVals {
+    operands: [[AFLppCmpOperands; AFL_CMP_MAP_H]; AFL_CMP_MAP_W],
+    fn_operands: [[AFLppCmpFnOperands; AFL_CMP_MAP_RTN_H]; AFL_CMP_MAP_W],
+}
+
+impl Debug for AFLppCmpVals {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AFLppCmpVals").finish_non_exhaustive()
+    }
+}
+
+/// The shared memory layout of the cmplog map of AFL++, `struct cmp_map`.
+/// It is filled by targets compiled with `AFL_LLVM_CMPLOG=1`, e.g. with `afl-clang-lto`,
+/// that find the id of the shared memory in the `__AFL_CMPLOG_SHM_ID` env var.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AFLppCmpMap {
+    headers: [AFLppCmpHeader; AFL_CMP_MAP_W],
+    vals: AFLppCmpVals,
+}
+
+impl Debug for AFLppCmpMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AFLppCmpMap").finish_non_exhaustive()
+    }
+}
+
+impl AFLppCmpMap {
+    /// Use the given shared memory as [`AFLppCmpMap`].
+    /// It has to be at least `size_of::<AFLppCmpMap>()` bytes large.
+    pub fn from_shmem<SHM>(shmem: &mut SHM) -> Result<&mut Self, Error>
+    where
+        SHM: ShMem,
+    {
+        let map = shmem.as_mut_slice();
+        if map.len() < size_of::<Self>() {
+            return Err(Error::illegal_argument(format!(
+                "The shared memory of {} bytes is too small for an AFL++ cmplog map of {} bytes",
+                map.len(),
+                size_of::<Self>()
+            )));
+        }
+        // Shared maps are page aligned, and the map consists of integers and bytes only
+        #[allow(clippy::cast_ptr_alignment)]
+        Ok(unsafe { &mut *(map.as_mut_ptr() as *mut Self) })
+    }
+
+    /// The header of the cmp with the given index
+    #[must_use]
+    pub fn header(&self, idx: usize) -> &AFLppCmpHeader {
+        &self.headers[idx]
+    }
+}
+
+impl CmpMap for AFLppCmpMap {
+    fn len(&self) -> usize {
+        AFL_CMP_MAP_W
+    }
+
+    fn executions_for(&self, idx: usize) -> usize {
+        self.headers[idx].hits()
+    }
+
+    fn usable_executions_for(&self, idx: usize) -> usize {
+        if self.headers[idx].kind() == AFL_CMP_TYPE_INS {
+            self.executions_for(idx).min(AFL_CMP_MAP_H)
+        } else {
+            self.executions_for(idx).min(AFL_CMP_MAP_RTN_H)
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn values_of(&self, idx: usize, execution: usize) -> Option<CmpValues> {
+        let header = &self.headers[idx];
+        match header.kind() {
+            AFL_CMP_TYPE_INS => {
+                let operands = unsafe { &self.vals.operands[idx][execution] };
+                match header.shape() + 1 {
+                    1 => Some(CmpValues::U8((operands.v0 as u8, operands.v1 as u8))),
+                    2 => Some(CmpValues::U16((operands.v0 as u16, operands.v1 as u16))),
+                    4 => Some(CmpValues::U32((operands.v0 as u32, operands.v1 as u32))),
+                    8 => Some(CmpValues::U64((operands.v0, operands.v1))),
+                    16 => {
+                        // 128 bit operands are split in a low and a high half
+                        let join = |low: u64, high: u64| {
+                            let mut bytes = low.to_ne_bytes().to_vec();
+                            bytes.extend_from_slice(&high.to_ne_bytes());
+                            bytes
+                        };
+                        Some(CmpValues::Bytes((
+                            join(operands.v0, operands.v0_128),
+                            join(operands.v1, operands.v1_128),
+                        )))
+                    }
+                    _ => None,
+                }
+            }
+            AFL_CMP_TYPE_RTN => {
+                let operands = unsafe { &self.vals.fn_operands[idx][execution] };
+                // String compares mark their actual lengths with the high bit
+                let len = |logged_len: u8| {
+                    if logged_len & 0x80 == 0 {
+                        header.shape() + 1
+                    } else {
+                        (logged_len & 0x7f) as usize
+                    }
+                    .min(AFL_CMP_RTN_LEN)
+                };
+                Some(CmpValues::Bytes((
+                    operands.v0[..len(operands.v0_len)].to_vec(),
+                    operands.v1[..len(operands.v1_len)].to_vec(),
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        // Like AFL++, we only reset the headers
+        self.headers.fill(AFLppCmpHeader::default());
+        Ok(())
+    }
+}
+
+/// A [`CmpObserver`] for the cmplog map of an AFL++ cmplog binary, e.g. one run by a
+/// [`crate::executors::ForkserverExecutor`] built with `cmplog_shmem`.
+#[derive(Debug)]
+pub struct AFLppCmpLogObserver<'a, S>
+where
+    S: UsesInput,
+{
+    map: OwnedRefMut<'a, AFLppCmpMap>,
+    add_meta: bool,
+    name: String,
+    phantom: PhantomData<S>,
+}
+
+impl<'a, S> CmpObserver<AFLppCmpMap, S> for AFLppCmpLogObserver<'a, S>
+where
+    S: UsesInput + Debug + HasMetadata,
+{
+    fn usable_count(&self) -> usize {
+        self.map.as_ref().len()
+    }
+
+    fn cmp_map(&self) -> &AFLppCmpMap {
+        self.map.as_ref()
+    }
+
+    fn cmp_map_mut(&mut self) -> &mut AFLppCmpMap {
+        self.map.as_mut()
+    }
+}
+
+impl<'a, S> Observer<S> for AFLppCmpLogObserver<'a, S>
+where
+    S: UsesInput + Debug + HasMetadata,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.map.as_mut().reset()
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        _input: &S::Input,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        if self.add_meta {
+            self.add_cmpvalues_meta(state);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, S> Named for AFLppCmpLogObserver<'a, S>
+where
+    S: UsesInput,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<'a, S> AFLppCmpLogObserver<'a, S>
+where
+    S: UsesInput,
+{
+    /// Creates a new [`AFLppCmpLogObserver`] with the given name and map.
+    /// If `add_meta` is set, the logged values are added to the [`struct@CmpValuesMetadata`] after each run.
+    #[must_use]
+    pub fn new(name: &'static str, map: &'a mut AFLppCmpMap, add_meta: bool) -> Self {
+        Self {
+            map: OwnedRefMut::Ref(map),
+            add_meta,
+            name: name.to_string(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    use core::mem::size_of;
+
+    use super::{
+        AFLppCmpMap, CmpMap, CmpValues, AFL_CMP_MAP_H, AFL_CMP_MAP_RTN_H, AFL_CMP_MAP_W,
+        AFL_CMP_TYPE_INS, AFL_CMP_TYPE_RTN,
+    };
+
+    /// A raw AFL++ cmplog map, as written by a target
+    struct RawCmpMap(Vec<u64>);
+
+    impl RawCmpMap {
+        fn new() -> Self {
+            Self(vec![0; size_of::<AFLppCmpMap>() / 8])
+        }
+
+        /// Write the `struct cmp_header` bitfield of the cmp at `idx`
+        fn header(&mut self, idx: usize, hits: u64, shape: u64, kind: u8) {
+            self.0[idx] = hits | (idx as u64) << 24 | shape << 48 | u64::from(kind) << 53;
+        }
+
+        /// Write the `struct cmp_operands` of an execution of the instruction at `idx`
+        fn operands(&mut self, idx: usize, execution: usize, operands: [u64; 4]) {
+            let offset = AFL_CMP_MAP_W + (idx * AFL_CMP_MAP_H + execution) * 4;
+            self.0[offset..offset + 4].copy_from_slice(&operands);
+        }
+
+        /// Write the `struct cmpfn_operands` of an execution of the routine at `idx`
+        fn fn_operands(
+            &mut self,
+            idx: usize,
+            execution: usize,
+            v0: &[u8],
+            v0_len: u8,
+            v1: &[u8],
+            v1_len: u8,
+        ) {
+            let mut raw = [0_u8; 64];
+            raw[..v0.len()].copy_from_slice(v0);
+            raw[31] = v0_len;
+            raw[32..32 + v1.len()].copy_from_slice(v1);
+            raw[63] = v1_len;
+            let offset = AFL_CMP_MAP_W + (idx * AFL_CMP_MAP_RTN_H + execution) * 8;
+            for (word, bytes) in self.0[offset..offset + 8].iter_mut().zip(raw.chunks(8)) {
+                *word = u64::from_ne_bytes(bytes.try_into().unwrap());
+            }
+        }
+
+        fn map(&self) -> &AFLppCmpMap {
+            unsafe { &*(self.0.as_ptr() as *const AFLppCmpMap) }
+        }
+    }
+
+    #[test]
+    fn test_aflpp_cmp_map_values() {
+        let mut raw = RawCmpMap::new();
+        // A 4 byte compare, executed twice
+        raw.header(3, 2, 3, AFL_CMP_TYPE_INS);
+        raw.operands(3, 0, [0x1122_3344, 0x5566_7788, 0, 0]);
+        raw.operands(3, 1, [0xdead_beef_0000_0001, 2, 0, 0]);
+        // A 16 byte compare, split in halves
+        raw.header(5, 1, 15, AFL_CMP_TYPE_INS);
+        raw.operands(5, 0, [1, 2, 3, 4]);
+        // A routine compare, executed more often than logged
+        raw.header(7, 40, 7, AFL_CMP_TYPE_RTN);
+        raw.fn_operands(7, 0, b"hello", 0x80 | 5, b"worldwide", 0);
+        // An unknown type
+        raw.header(9, 1, 0, 3);
+
+        let map = raw.map();
+        assert_eq!(map.executions_for(3), 2);
+        assert_eq!(map.usable_executions_for(3), 2);
+        assert_eq!(
+            map.values_of(3, 0),
+            Some(CmpValues::U32((0x1122_3344, 0x5566_7788)))
+        );
+        assert_eq!(map.values_of(3, 1), Some(CmpValues::U32((1, 2))));
+
+        let half = |low: u64, high: u64| [low.to_ne_bytes(), high.to_ne_bytes()].concat();
+        assert_eq!(
+            map.values_of(5, 0),
+            Some(CmpValues::Bytes((half(1, 3), half(2, 4))))
+        );
+
+        assert_eq!(map.executions_for(7), 40);
+        assert_eq!(map.usable_executions_for(7), AFL_CMP_MAP_RTN_H);
+        // Without the high bit, the length is the shape
+        assert_eq!(
+            map.values_of(7, 0),
+            Some(CmpValues::Bytes((b"hello".to_vec(), b"worldwid".to_vec())))
+        );
+
+        assert_eq!(map.values_of(9, 0), None);
+        assert_eq!(map.executions_for(0), 0);
+    }
+}
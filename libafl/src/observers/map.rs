@@ -21,7 +21,7 @@ use crate::{
     bolts::{
         ownedref::{OwnedRefMut, OwnedSliceMut},
         tuples::Named,
-        AsIter, AsIterMut, AsMutSlice, AsSlice, HasLen,
+        AsIter, AsIterMut, AsMutSlice, AsSlice, HasLen, Truncate,
     },
     executors::ExitKind,
     inputs::UsesInput,
@@ -237,6 +237,16 @@ where
     }
 }
 
+impl<'a, T> Truncate for StdMapObserver<'a, T>
+where
+    T: Default + Copy + 'static + Serialize + serde::de::DeserializeOwned,
+{
+    /// Only observe the first `new_len` entries of the map, e.g. the size a target reports
+    fn truncate(&mut self, new_len: usize) {
+        self.map.truncate(new_len);
+    }
+}
+
 impl<'a, 'it, T> AsIter<'it> for StdMapObserver<'a, T>
 where
     T: Bounded
@@ -1101,6 +1111,15 @@ where
     }
 }
 
+impl<M> Truncate for HitcountsMapObserver<M>
+where
+    M: Named + Serialize + serde::de::DeserializeOwned + Truncate,
+{
+    fn truncate(&mut self, new_len: usize) {
+        self.base.truncate(new_len);
+    }
+}
+
 impl<M> MapObserver for HitcountsMapObserver<M>
 where
     M: MapObserver<Entry = u8>,
@@ -16,6 +16,9 @@ pub mod stacktrace;
 #[cfg(feature = "std")]
 pub use stacktrace::*;
 
+pub mod response;
+pub use response::*;
+
 pub mod concolic;
 
 // Rust is breaking this with 'error: intrinsic safety mismatch between list of intrinsics within the compiler and core library intrinsics for intrinsic `type_id`' and so we disable this component for the moment
@@ -113,6 +116,18 @@ where
     #[inline]
     #[allow(unused_variables)]
     fn observe_stderr(&mut self, stderr: &str) {}
+
+    /// If this observer observes the responses of a network target
+    #[inline]
+    fn observes_responses(&self) -> bool {
+        false
+    }
+
+    /// React to a new response of a network target, called once per message sent
+    /// To use this, always return `true` from `observes_responses`
+    #[inline]
+    #[allow(unused_variables)]
+    fn observe_response(&mut self, response: &[u8]) {}
 }
 
 /// Defines the observer type shared across traits of the type.
@@ -158,6 +173,12 @@ where
     fn observe_stdout(&mut self, stdout: &str);
     /// Runs `observe_stderr` for all stderr observers in the list
     fn observe_stderr(&mut self, stderr: &str);
+
+    /// Returns true if a response observer was added to the list
+    fn observes_responses(&self) -> bool;
+
+    /// Runs `observe_response` for all response observers in the list
+    fn observe_response(&mut self, response: &[u8]);
 }
 
 impl<S> ObserversTuple<S> for ()
@@ -211,6 +232,17 @@ where
     #[inline]
     #[allow(unused_variables)]
     fn observe_stderr(&mut self, stderr: &str) {}
+
+    /// Returns true if a response observer was added to the list
+    #[inline]
+    fn observes_responses(&self) -> bool {
+        false
+    }
+
+    /// Runs `observe_response` for all response observers in the list
+    #[inline]
+    #[allow(unused_variables)]
+    fn observe_response(&mut self, response: &[u8]) {}
 }
 
 impl<Head, Tail, S> ObserversTuple<S> for (Head, Tail)
@@ -274,6 +306,19 @@ where
         self.0.observe_stderr(stderr);
         self.1.observe_stderr(stderr);
     }
+
+    /// Returns true if a response observer was added to the list
+    #[inline]
+    fn observes_responses(&self) -> bool {
+        self.0.observes_responses() || self.1.observes_responses()
+    }
+
+    /// Runs `observe_response` for all response observers in the list
+    #[inline]
+    fn observe_response(&mut self, response: &[u8]) {
+        self.0.observe_response(response);
+        self.1.observe_response(response);
+    }
 }
 
 /// A trait for [`Observer`]`s` with a hash field
@@ -995,6 +1040,14 @@ pub mod pybind {
 
         #[inline]
         fn observe_stdout(&mut self, _: &str) {}
+
+        #[inline]
+        fn observes_responses(&self) -> bool {
+            false
+        }
+
+        #[inline]
+        fn observe_response(&mut self, _: &[u8]) {}
     }
 
     impl MatchName for PythonObserversTuple {
@@ -0,0 +1,309 @@
+//! The [`ResponseCodeObserver`] tracks the response codes of a network server as state coverage,
+//! in the spirit of [`AFLNet`](https://github.com/aflnet/aflnet).
+//! The executor must explicitly support this observer,
+//! for example, it is supported by the [`crate::executors::NetworkExecutor`].
+
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hasher};
+
+use ahash::AHasher;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{tuples::Named, AsIter, AsIterMut, AsMutSlice, AsSlice, HasLen},
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{MapObserver, Observer},
+    Error,
+};
+
+/// Extracts the response code from a server response, if any
+pub type ResponseCodeExtractor = fn(&[u8]) -> Option<u32>;
+
+/// Extracts the first number starting a whitespace-separated word of the response, such as
+/// `220` for `220 FTP server ready`, `200` for `HTTP/1.1 200 OK` or `250` for `250-SIZE 1000`.
+#[must_use]
+pub fn leading_number_code(response: &[u8]) -> Option<u32> {
+    response
+        .split(u8::is_ascii_whitespace)
+        .find(|word| word.first().map_or(false, u8::is_ascii_digit))
+        .map(|word| {
+            word.iter()
+                .take_while(|b| b.is_ascii_digit())
+                .fold(0_u32, |code, b| {
+                    code.wrapping_mul(10).wrapping_add(u32::from(b - b'0'))
+                })
+        })
+}
+
+/// A [`MapObserver`] tracking the state transitions of a network server, as seen in its response codes.
+/// Each pair of consecutive response codes is hashed to an entry in the `base` map, so it can be used with a
+/// [`crate::feedbacks::MaxMapFeedback`] to consider inputs reaching new server states interesting.
+///
+/// The `base` map is usually an owned map, e.g. `StdMapObserver::new_owned("states", vec![0_u8; 4096])`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(bound = "M: serde::de::DeserializeOwned")]
+pub struct ResponseCodeObserver<M>
+where
+    M: Serialize,
+{
+    base: M,
+    codes: Vec<u32>,
+    #[serde(skip, default = "default_extractor")]
+    extractor: ResponseCodeExtractor,
+}
+
+fn default_extractor() -> ResponseCodeExtractor {
+    leading_number_code
+}
+
+impl<M> ResponseCodeObserver<M>
+where
+    M: MapObserver<Entry = u8>,
+{
+    /// Creates a new [`ResponseCodeObserver`], extracting the codes with [`leading_number_code`]
+    pub fn new(base: M) -> Self {
+        Self::with_extractor(base, leading_number_code)
+    }
+
+    /// Creates a new [`ResponseCodeObserver`], extracting the codes with the given function
+    pub fn with_extractor(base: M, extractor: ResponseCodeExtractor) -> Self {
+        Self {
+            base,
+            codes: vec![],
+            extractor,
+        }
+    }
+
+    /// The response codes of the last run, in order
+    #[must_use]
+    pub fn codes(&self) -> &[u32] {
+        &self.codes
+    }
+
+    /// Record the transition from the previous response code to `code` in the map
+    fn record(&mut self, code: u32) {
+        let mut hasher = AHasher::new_with_keys(0, 0);
+        hasher.write_u32(self.codes.last().copied().unwrap_or(0));
+        hasher.write_u32(code);
+        let idx = (hasher.finish() % self.base.usable_count() as u64) as usize;
+        let entry = self.base.get_mut(idx);
+        *entry = entry.saturating_add(1);
+        self.codes.push(code);
+    }
+}
+
+impl<S, M> Observer<S> for ResponseCodeObserver<M>
+where
+    M: MapObserver<Entry = u8> + Observer<S>,
+    S: UsesInput,
+{
+    #[inline]
+    fn pre_exec(&mut self, state: &mut S, input: &S::Input) -> Result<(), Error> {
+        self.codes.clear();
+        self.base.pre_exec(state, input)
+    }
+
+    #[inline]
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        input: &S::Input,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.base.post_exec(state, input, exit_kind)
+    }
+
+    #[inline]
+    fn observes_responses(&self) -> bool {
+        true
+    }
+
+    fn observe_response(&mut self, response: &[u8]) {
+        if let Some(code) = (self.extractor)(response) {
+            self.record(code);
+        }
+    }
+}
+
+impl<M> Named for ResponseCodeObserver<M>
+where
+    M: Named + Serialize + serde::de::DeserializeOwned,
+{
+    #[inline]
+    fn name(&self) -> &str {
+        self.base.name()
+    }
+}
+
+impl<M> HasLen for ResponseCodeObserver<M>
+where
+    M: MapObserver,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+}
+
+impl<M> MapObserver for ResponseCodeObserver<M>
+where
+    M: MapObserver<Entry = u8>,
+{
+    type Entry = u8;
+
+    #[inline]
+    fn initial(&self) -> u8 {
+        self.base.initial()
+    }
+
+    #[inline]
+    fn initial_mut(&mut self) -> &mut u8 {
+        self.base.initial_mut()
+    }
+
+    #[inline]
+    fn usable_count(&self) -> usize {
+        self.base.usable_count()
+    }
+
+    #[inline]
+    fn get(&self, idx: usize) -> &u8 {
+        self.base.get(idx)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, idx: usize) -> &mut u8 {
+        self.base.get_mut(idx)
+    }
+
+    /// Count the set bytes in the map
+    fn count_bytes(&self) -> u64 {
+        self.base.count_bytes()
+    }
+
+    /// Reset the map
+    #[inline]
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.base.reset_map()
+    }
+
+    fn hash(&self) -> u64 {
+        self.base.hash()
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        self.base.to_vec()
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        self.base.how_many_set(indexes)
+    }
+}
+
+impl<M> AsSlice<u8> for ResponseCodeObserver<M>
+where
+    M: MapObserver + AsSlice<u8>,
+{
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        self.base.as_slice()
+    }
+}
+
+impl<M> AsMutSlice<u8> for ResponseCodeObserver<M>
+where
+    M: MapObserver + AsMutSlice<u8>,
+{
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.base.as_mut_slice()
+    }
+}
+
+impl<'it, M> AsIter<'it> for ResponseCodeObserver<M>
+where
+    M: Named + Serialize + serde::de::DeserializeOwned + AsIter<'it, Item = u8>,
+{
+    type Item = u8;
+    type IntoIter = <M as AsIter<'it>>::IntoIter;
+
+    fn as_iter(&'it self) -> Self::IntoIter {
+        self.base.as_iter()
+    }
+}
+
+impl<'it, M> AsIterMut<'it> for ResponseCodeObserver<M>
+where
+    M: Named + Serialize + serde::de::DeserializeOwned + AsIterMut<'it, Item = u8>,
+{
+    type Item = u8;
+    type IntoIter = <M as AsIterMut<'it>>::IntoIter;
+
+    fn as_iter_mut(&'it mut self) -> Self::IntoIter {
+        self.base.as_iter_mut()
+    }
+}
+
+impl<'it, M> IntoIterator for &'it ResponseCodeObserver<M>
+where
+    M: Named + Serialize + serde::de::DeserializeOwned,
+    &'it M: IntoIterator<Item = &'it u8>,
+{
+    type Item = &'it u8;
+    type IntoIter = <&'it M as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.base.into_iter()
+    }
+}
+
+impl<'it, M> IntoIterator for &'it mut ResponseCodeObserver<M>
+where
+    M: Named + Serialize + serde::de::DeserializeOwned,
+    &'it mut M: IntoIterator<Item = &'it mut u8>,
+{
+    type Item = &'it mut u8;
+    type IntoIter = <&'it mut M as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.base.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{leading_number_code, ResponseCodeObserver};
+    use crate::{
+        inputs::BytesInput,
+        observers::{MapObserver, Observer, StdMapObserver},
+        state::NopState,
+    };
+
+    #[test]
+    fn test_leading_number_code() {
+        assert_eq!(leading_number_code(b"220 FTP server ready\r\n"), Some(220));
+        assert_eq!(
+            leading_number_code(b"HTTP/1.1 404 Not Found\r\n"),
+            Some(404)
+        );
+        assert_eq!(leading_number_code(b"250-SIZE 1000\r\n"), Some(250));
+        assert_eq!(leading_number_code(b"+OK POP3 ready"), None);
+        assert_eq!(leading_number_code(b""), None);
+    }
+
+    #[test]
+    fn test_response_code_observer() {
+        let mut observer =
+            ResponseCodeObserver::new(StdMapObserver::new_owned("states", vec![0_u8; 64]));
+        let mut state = NopState::<BytesInput>::new();
+        let input = BytesInput::new(vec![]);
+
+        Observer::<NopState<BytesInput>>::pre_exec(&mut observer, &mut state, &input).unwrap();
+        Observer::<NopState<BytesInput>>::observe_response(&mut observer, b"220 ready");
+        Observer::<NopState<BytesInput>>::observe_response(&mut observer, b"no code");
+        Observer::<NopState<BytesInput>>::observe_response(&mut observer, b"331 password?");
+        assert_eq!(observer.codes(), [220, 331]);
+        assert_eq!(observer.count_bytes(), 2);
+    }
+}
@@ -1,6 +1,10 @@
 //! the ``StacktraceObserver`` looks up the stacktrace on the execution thread and computes a hash for it for dedupe
 
-use alloc::string::{String, ToString};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
 use std::{
     fmt::Debug,
     fs::{self, File},
@@ -9,7 +13,7 @@ use std::{
     process::ChildStderr,
 };
 
-use backtrace::Backtrace;
+use backtrace::{Backtrace, BacktraceFrame};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
@@ -22,29 +26,166 @@ use crate::{
     Error,
 };
 
-/// Collects the backtrace via [`Backtrace`] and [`Debug`]
-/// ([`Debug`] is currently used for dev purposes, symbols hash will be used eventually)
+static CRASH_CONTEXT_SET: AtomicBool = AtomicBool::new(false);
+static CRASH_SIGNAL: AtomicI32 = AtomicI32::new(0);
+static CRASH_FAULT_ADDRESS: AtomicU64 = AtomicU64::new(0);
+static CRASH_PC: AtomicU64 = AtomicU64::new(0);
+
+/// The context of a crash, as seen by the signal handler that caught it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashContext {
+    /// The signal number
+    pub signal: i32,
+    /// The faulting address, from the `siginfo_t`
+    pub fault_address: u64,
+    /// The address of the faulting instruction, from the `ucontext_t`, if known
+    pub pc: Option<u64>,
+}
+
+/// Record the context of a crash caught in the current process.
+/// The in-process crash handlers call this before running the observers, so that the backtraces
+/// start at the faulting frame instead of inside the handler.
+pub fn set_crash_context(context: CrashContext) {
+    CRASH_SIGNAL.store(context.signal, Ordering::SeqCst);
+    CRASH_FAULT_ADDRESS.store(context.fault_address, Ordering::SeqCst);
+    CRASH_PC.store(context.pc.unwrap_or(0), Ordering::SeqCst);
+    CRASH_CONTEXT_SET.store(true, Ordering::SeqCst);
+}
+
+/// The context recorded by the last [`set_crash_context`], if any
 #[must_use]
-pub fn collect_backtrace() -> u64 {
-    let b = Backtrace::new();
-    if b.frames().is_empty() {
-        return 0;
+pub fn crash_context() -> Option<CrashContext> {
+    if !CRASH_CONTEXT_SET.load(Ordering::SeqCst) {
+        return None;
+    }
+    let pc = CRASH_PC.load(Ordering::SeqCst);
+    Some(CrashContext {
+        signal: CRASH_SIGNAL.load(Ordering::SeqCst),
+        fault_address: CRASH_FAULT_ADDRESS.load(Ordering::SeqCst),
+        pc: (pc != 0).then_some(pc),
+    })
+}
+
+/// Take the context recorded by the last [`set_crash_context`], if any
+pub fn take_crash_context() -> Option<CrashContext> {
+    let context = crash_context();
+    CRASH_CONTEXT_SET.store(false, Ordering::SeqCst);
+    context
+}
+
+/// The frames of `backtrace` belonging to the crashing code.
+/// With a crash context, the frames of the signal handler are skipped: the backtrace starts at the
+/// faulting instruction or, failing that, right after the signal trampoline.
+/// Without one, only the frame collecting the backtrace is skipped.
+fn crash_frames<'b>(
+    backtrace: &'b Backtrace,
+    context: Option<&CrashContext>,
+) -> &'b [BacktraceFrame] {
+    let frames = backtrace.frames();
+    if frames.is_empty() {
+        return frames;
+    }
+    if context.is_some() {
+        if let Some(pc) = context.and_then(|context| context.pc) {
+            if let Some(idx) = frames.iter().position(|frame| frame.ip() as u64 == pc) {
+                return &frames[idx..];
+            }
+        }
+        let trampoline = frames.iter().rposition(|frame| {
+            frame.symbols().iter().any(|symbol| {
+                symbol.name().map_or(false, |name| {
+                    let name = name.to_string();
+                    name.contains("__restore_rt")
+                        || name.contains("sigreturn")
+                        || name.contains("_sigtramp")
+                })
+            })
+        });
+        if let Some(idx) = trampoline {
+            return &frames[idx + 1..];
+        }
     }
+    &frames[1..]
+}
+
+fn backtrace_hash(frames: &[BacktraceFrame]) -> u64 {
     let mut hash = 0;
-    for frame in &b.frames()[1..] {
+    for frame in frames {
         hash ^= frame.ip() as u64;
     }
-    // will use symbols later
-    // let trace = format!("{:?}", b);
-    // eprintln!("{}", trace);
-    // println!(
-    //     "backtrace collected with hash={} at pid={}",
-    //     hash,
-    //     std::process::id()
-    // );
     hash
 }
 
+fn backtrace_report_frames(frames: &[BacktraceFrame], max_frames: usize) -> Vec<CrashFrame> {
+    frames
+        .iter()
+        .take(max_frames)
+        .map(|frame| {
+            let symbol = frame.symbols().first();
+            CrashFrame {
+                address: frame.ip() as u64,
+                function: symbol
+                    .and_then(backtrace::BacktraceSymbol::name)
+                    .map(|n| n.to_string()),
+                location: symbol.and_then(|s| {
+                    s.filename()
+                        .map(|f| format!("{}:{}", f.display(), s.lineno().unwrap_or(0)))
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Collects the backtrace via [`Backtrace`] and [`Debug`]
+/// ([`Debug`] is currently used for dev purposes, symbols hash will be used eventually)
+/// Inside a crash handler, the backtrace starts at the context recorded with [`set_crash_context`].
+#[must_use]
+pub fn collect_backtrace() -> u64 {
+    let b = Backtrace::new();
+    backtrace_hash(crash_frames(&b, crash_context().as_ref()))
+}
+
+/// Collects the top `max_frames` frames of the current backtrace, symbolized with [`Backtrace`].
+/// Inside a crash handler, the backtrace starts at the context recorded with [`set_crash_context`].
+#[must_use]
+pub fn collect_backtrace_frames(max_frames: usize) -> Vec<CrashFrame> {
+    let b = Backtrace::new();
+    backtrace_report_frames(crash_frames(&b, crash_context().as_ref()), max_frames)
+}
+
+/// A single frame of a crash backtrace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrashFrame {
+    /// The address of the frame
+    pub address: u64,
+    /// The symbolized function name, if available
+    pub function: Option<String>,
+    /// The source location or module of the frame, if available
+    pub location: Option<String>,
+}
+
+/// A structured description of a crash, as collected by an [`ObserverWithCrashReport`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashReport {
+    /// The signal that terminated the target, if known
+    pub signal: Option<i32>,
+    /// The faulting address, if known
+    pub fault_address: Option<u64>,
+    /// The bug class reported by the sanitizer, e.g. `heap-buffer-overflow`
+    pub bug_class: Option<String>,
+    /// The frames of the backtrace, the innermost first
+    pub frames: Vec<CrashFrame>,
+}
+
+/// A trait for [`Observer`]`s` collecting a [`CrashReport`] for crashing runs
+pub trait ObserverWithCrashReport {
+    /// The report of the last run, if it crashed
+    fn crash_report(&self) -> Option<&CrashReport>;
+}
+
+/// The default number of frames kept in a [`CrashReport`]
+pub const DEFAULT_CRASH_REPORT_FRAMES: usize = 16;
+
 /// An enum encoding the types of harnesses
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum HarnessType {
@@ -61,6 +202,7 @@ pub struct BacktraceObserver<'a> {
     observer_name: String,
     hash: OwnedRefMut<'a, Option<u64>>,
     harness_type: HarnessType,
+    report: Option<CrashReport>,
 }
 
 impl<'a> BacktraceObserver<'a> {
@@ -75,10 +217,19 @@ impl<'a> BacktraceObserver<'a> {
             observer_name: observer_name.to_string(),
             hash: OwnedRefMut::Ref(backtrace_hash),
             harness_type,
+            report: None,
         }
     }
 }
 
+impl<'a> ObserverWithCrashReport for BacktraceObserver<'a> {
+    /// The symbolized backtrace of the last in-process crash.
+    /// Crashes in child processes are not reported, as only the hash is shared with the parent.
+    fn crash_report(&self) -> Option<&CrashReport> {
+        self.report.as_ref()
+    }
+}
+
 impl<'a> ObserverWithHashField for BacktraceObserver<'a> {
     /// Gets the hash value of this observer.
     #[must_use]
@@ -109,9 +260,19 @@ where
     ) -> Result<(), Error> {
         if self.harness_type == HarnessType::InProcess {
             if exit_kind == &ExitKind::Crash {
-                self.update_hash(collect_backtrace());
+                let context = take_crash_context();
+                let b = Backtrace::new();
+                let frames = crash_frames(&b, context.as_ref());
+                self.update_hash(backtrace_hash(frames));
+                self.report = Some(CrashReport {
+                    signal: context.map(|context| context.signal),
+                    fault_address: context.map(|context| context.fault_address),
+                    bug_class: None,
+                    frames: backtrace_report_frames(frames, DEFAULT_CRASH_REPORT_FRAMES),
+                });
             } else {
                 self.clear_hash();
+                self.report = None;
             }
         }
         Ok(())
@@ -125,7 +286,9 @@ where
     ) -> Result<(), Error> {
         if self.harness_type == HarnessType::Child {
             if exit_kind == &ExitKind::Crash {
-                self.update_hash(collect_backtrace());
+                let context = take_crash_context();
+                let b = Backtrace::new();
+                self.update_hash(backtrace_hash(crash_frames(&b, context.as_ref())));
             } else {
                 self.clear_hash();
             }
@@ -173,6 +336,8 @@ pub fn get_asan_runtime_flags() -> String {
 pub struct AsanBacktraceObserver {
     observer_name: String,
     hash: Option<u64>,
+    #[serde(default)]
+    report: Option<CrashReport>,
 }
 
 impl AsanBacktraceObserver {
@@ -182,6 +347,7 @@ impl AsanBacktraceObserver {
         Self {
             observer_name: observer_name.to_string(),
             hash: None,
+            report: None,
         }
     }
 
@@ -218,6 +384,71 @@ impl AsanBacktraceObserver {
             hash ^= u64::from_str_radix(g.as_str(), 16).unwrap();
         });
         self.update_hash(hash);
+        let report = parse_asan_report(output);
+        self.report = (report.bug_class.is_some() || !report.frames.is_empty()).then_some(report);
+    }
+}
+
+/// Parse the bug class, the faulting address and the frames of the first report in an ASAN error output
+#[must_use]
+pub fn parse_asan_report(output: &str) -> CrashReport {
+    let error_matcher = Regex::new(
+        "ERROR: AddressSanitizer: ([A-Za-z0-9_-]+)(?: on (?:unknown )?address (?:0x)?([0-9a-f]+))?",
+    )
+    .unwrap();
+    let frame_matcher =
+        Regex::new("(?m)^\\s*#([0-9]+) 0x([0-9a-f]+)(?: in (\\S+))?(?: +\\(?([^)\\n]*)\\)?)?$")
+            .unwrap();
+
+    let mut report = CrashReport::default();
+    if let Some(m) = error_matcher.captures(output) {
+        let bug_class = m.get(1).unwrap().as_str();
+        report.signal = asan_bug_class_signal(bug_class);
+        report.bug_class = Some(bug_class.to_string());
+        report.fault_address = m
+            .get(2)
+            .and_then(|a| u64::from_str_radix(a.as_str(), 16).ok());
+    }
+
+    for m in frame_matcher.captures_iter(output) {
+        // Only keep the backtrace of the faulting access, not the allocation/free ones
+        if m.get(1).unwrap().as_str() == "0" && !report.frames.is_empty() {
+            break;
+        }
+        report.frames.push(CrashFrame {
+            address: u64::from_str_radix(m.get(2).unwrap().as_str(), 16).unwrap_or(0),
+            function: m.get(3).map(|f| f.as_str().to_string()),
+            location: m
+                .get(4)
+                .map(|l| l.as_str().trim().to_string())
+                .filter(|l| !l.is_empty()),
+        });
+    }
+    report
+}
+
+/// The signal ASAN reports as bug class for deadly signals, e.g. `SEGV`
+#[cfg(unix)]
+fn asan_bug_class_signal(bug_class: &str) -> Option<i32> {
+    match bug_class {
+        "SEGV" => Some(libc::SIGSEGV),
+        "BUS" => Some(libc::SIGBUS),
+        "FPE" => Some(libc::SIGFPE),
+        "ILL" => Some(libc::SIGILL),
+        "ABRT" => Some(libc::SIGABRT),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn asan_bug_class_signal(_bug_class: &str) -> Option<i32> {
+    None
+}
+
+impl ObserverWithCrashReport for AsanBacktraceObserver {
+    /// The report parsed from the last ASAN output
+    fn crash_report(&self) -> Option<&CrashReport> {
+        self.report.as_ref()
     }
 }
 
@@ -250,6 +481,7 @@ where
     S: UsesInput,
 {
     fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.report = None;
         Ok(())
     }
 
@@ -279,3 +511,135 @@ impl Named for AsanBacktraceObserver {
         &self.observer_name
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use alloc::{boxed::Box, vec::Vec};
+
+    use super::parse_asan_report;
+    #[cfg(unix)]
+    use super::{
+        set_crash_context, BacktraceObserver, CrashContext, CrashReport, HarnessType,
+        ObserverWithCrashReport,
+    };
+    #[cfg(unix)]
+    use crate::{
+        bolts::{
+            minibsod::{crash_pc, fault_address},
+            os::unix_signals::{setup_signal_handler, siginfo_t, ucontext_t, Handler, Signal},
+        },
+        executors::ExitKind,
+        feedbacks::triage::{bucket_hash, DEFAULT_BUCKET_DEPTH},
+        inputs::BytesInput,
+        observers::Observer,
+        state::NopState,
+    };
+
+    /// Collects the report of each signal, the way the in-process crash handler does
+    #[cfg(unix)]
+    struct ReportingHandler {
+        reports: Vec<CrashReport>,
+    }
+
+    #[cfg(unix)]
+    impl Handler for ReportingHandler {
+        fn handle(&mut self, signal: Signal, info: siginfo_t, context: &mut ucontext_t) {
+            set_crash_context(CrashContext {
+                signal: signal as i32,
+                fault_address: fault_address(&info) as u64,
+                pc: crash_pc(context).map(|pc| pc as u64),
+            });
+            let mut hash = None;
+            let mut observer =
+                BacktraceObserver::new("backtrace", &mut hash, HarnessType::InProcess);
+            observer
+                .post_exec(
+                    &mut NopState::<BytesInput>::new(),
+                    &BytesInput::new(vec![]),
+                    &ExitKind::Crash,
+                )
+                .unwrap();
+            self.reports.push(observer.crash_report().cloned().unwrap());
+        }
+
+        fn signals(&self) -> Vec<Signal> {
+            vec![Signal::SigHangUp]
+        }
+    }
+
+    #[cfg(unix)]
+    #[inline(never)]
+    fn crash_site_a() -> i32 {
+        unsafe { libc::raise(libc::SIGHUP) };
+        core::hint::black_box(1)
+    }
+
+    #[cfg(unix)]
+    #[inline(never)]
+    fn crash_site_b() -> i32 {
+        unsafe { libc::raise(libc::SIGHUP) };
+        core::hint::black_box(2)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_crash_sites_bucketing() {
+        let handler = Box::leak(Box::new(ReportingHandler { reports: vec![] }));
+        unsafe { setup_signal_handler(handler) }.unwrap();
+        crash_site_a();
+        crash_site_b();
+
+        let reports = &handler.reports;
+        assert_eq!(reports.len(), 2);
+        for (report, site) in reports.iter().zip(["crash_site_a", "crash_site_b"]) {
+            assert_eq!(report.signal, Some(libc::SIGHUP));
+            assert!(report.fault_address.is_some());
+            let top = &report.frames[..DEFAULT_BUCKET_DEPTH];
+            // The frames of the signal handler are not part of the report
+            assert!(!top.iter().any(|frame| frame
+                .function
+                .as_ref()
+                .map_or(false, |f| f.contains("handle_signal")
+                    || f.contains("ReportingHandler"))));
+            assert!(top
+                .iter()
+                .any(|frame| frame.function.as_ref().map_or(false, |f| f.contains(site))));
+        }
+        assert_ne!(
+            bucket_hash(&reports[0], DEFAULT_BUCKET_DEPTH),
+            bucket_hash(&reports[1], DEFAULT_BUCKET_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_parse_asan_report() {
+        let output = "==1234==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x55d5 bp 0x7ffc sp 0x7ffc
+READ of size 1 at 0x602000000011 thread T0
+    #0 0x55d5c1a2b3c4 in parse_header /src/target/parser.c:42:13
+    #1 0x55d5c1a2b4d5 in LLVMFuzzerTestOneInput /src/target/harness.c:10:5
+    #2 0x7f0011223344  (/lib/x86_64-linux-gnu/libc.so.6+0x21c86)
+
+0x602000000011 is located 0 bytes to the right of 1-byte region
+allocated by thread T0 here:
+    #0 0x55d5c1000000 in malloc
+    #1 0x55d5c1a2b4d5 in LLVMFuzzerTestOneInput /src/target/harness.c:8:5
+";
+        let report = parse_asan_report(output);
+        assert_eq!(report.bug_class.as_deref(), Some("heap-buffer-overflow"));
+        assert_eq!(report.fault_address, Some(0x6020_0000_0011));
+        assert_eq!(report.signal, None);
+        assert_eq!(report.frames.len(), 3);
+        assert_eq!(report.frames[0].address, 0x55d5_c1a2_b3c4);
+        assert_eq!(report.frames[0].function.as_deref(), Some("parse_header"));
+        assert_eq!(
+            report.frames[0].location.as_deref(),
+            Some("/src/target/parser.c:42:13")
+        );
+        assert_eq!(report.frames[2].function, None);
+        assert_eq!(
+            report.frames[2].location.as_deref(),
+            Some("/lib/x86_64-linux-gnu/libc.so.6+0x21c86")
+        );
+    }
+}
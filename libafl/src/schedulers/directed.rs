@@ -0,0 +1,376 @@
+//! Directed fuzzing in the spirit of `AFLGo` (`https://github.com/aflgo/aflgo`).
+//! Each [`Testcase`] is assigned the distance of the edges it covers to a set of target sites,
+//! and a simulated-annealing power schedule moves the energy from exploration to
+//! the testcases closest to the targets over time.
+//!
+//! The per-edge distances are usually computed at compile time by `libafl_cc` from the
+//! control flow graph dumped by the ``AFLCoverage`` pass.
+
+use alloc::string::{String, ToString};
+use core::{marker::PhantomData, time::Duration};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bolts::{current_time, AsSlice},
+    corpus::{Corpus, Testcase},
+    feedbacks::MapIndexesMetadata,
+    inputs::UsesInput,
+    schedulers::{
+        testcase_score::{CorpusPowerTestcaseScore, TestcaseScore},
+        Scheduler,
+    },
+    state::{HasCorpus, HasMetadata, UsesState},
+    Error,
+};
+
+/// The default time after which the annealing schedule is fully in the exploitation phase
+pub const DEFAULT_TIME_TO_EXPLOITATION: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum factor the power of a [`Testcase`] is multiplied or divided by
+const MAX_FACTOR: f64 = 32.0;
+
+/// The state metadata for directed fuzzing, holding the distance of each map index to the targets
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirectedMetadata {
+    /// map index -> distance to the targets
+    distances: HashMap<usize, f64>,
+    /// The smallest testcase distance seen so far
+    min_distance: f64,
+    /// The largest testcase distance seen so far
+    max_distance: f64,
+    /// The time the directed scheduler started
+    start_time: Duration,
+    /// The time after which the annealing schedule exploits only
+    time_to_exploitation: Duration,
+}
+
+crate::impl_serdeany!(DirectedMetadata);
+
+impl DirectedMetadata {
+    /// Creates a new [`struct@DirectedMetadata`] from a map of map index -> distance to the targets
+    #[must_use]
+    pub fn new(distances: HashMap<usize, f64>) -> Self {
+        Self {
+            distances,
+            min_distance: f64::MAX,
+            max_distance: 0.0,
+            start_time: current_time(),
+            time_to_exploitation: DEFAULT_TIME_TO_EXPLOITATION,
+        }
+    }
+
+    /// Loads the distances from a file, with one `<map index> <distance>` pair per line,
+    /// as written by `ControlFlowGraph::write_distances_to_file` in `libafl_cc`.
+    #[cfg(feature = "std")]
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let content = fs::read_to_string(path)?;
+        let mut distances = HashMap::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut splitter = line.split_whitespace();
+            let (idx, distance) = match (splitter.next(), splitter.next()) {
+                (Some(idx), Some(distance)) => (idx, distance),
+                _ => {
+                    return Err(Error::illegal_argument(format!(
+                        "Illegal line in distance file: {line}"
+                    )))
+                }
+            };
+            let idx = idx.parse::<usize>().map_err(|_| {
+                Error::illegal_argument(format!("Illegal map index in distance file: {line}"))
+            })?;
+            let distance = distance.parse::<f64>().map_err(|_| {
+                Error::illegal_argument(format!("Illegal distance in distance file: {line}"))
+            })?;
+            distances.insert(idx, distance);
+        }
+        Ok(Self::new(distances))
+    }
+
+    /// Sets the time after which the annealing schedule is fully in the exploitation phase
+    #[must_use]
+    pub fn with_time_to_exploitation(mut self, time_to_exploitation: Duration) -> Self {
+        self.time_to_exploitation = time_to_exploitation;
+        self
+    }
+
+    /// The distance of a map index to the targets, if it can reach any target
+    #[must_use]
+    pub fn distance(&self, idx: usize) -> Option<f64> {
+        self.distances.get(&idx).copied()
+    }
+
+    /// The distances of all map indexes that can reach a target
+    #[must_use]
+    pub fn distances(&self) -> &HashMap<usize, f64> {
+        &self.distances
+    }
+
+    /// The smallest testcase distance seen so far
+    #[must_use]
+    pub fn min_distance(&self) -> f64 {
+        self.min_distance
+    }
+
+    /// The largest testcase distance seen so far
+    #[must_use]
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance
+    }
+
+    /// The time the directed scheduler started
+    #[must_use]
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
+    /// The time after which the annealing schedule exploits only
+    #[must_use]
+    pub fn time_to_exploitation(&self) -> Duration {
+        self.time_to_exploitation
+    }
+
+    /// Computes the distance of a testcase as the mean distance of the covered map indexes that reach a target.
+    /// Returns `None` if none of the covered indexes can reach a target.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn testcase_distance(&self, indexes: &[usize]) -> Option<f64> {
+        let (sum, count) = indexes
+            .iter()
+            .filter_map(|idx| self.distance(*idx))
+            .fold((0.0, 0_usize), |(sum, count), d| (sum + d, count + 1));
+        if count == 0 {
+            None
+        } else {
+            Some(sum / count as f64)
+        }
+    }
+
+    /// Normalizes a testcase distance to `[0, 1]` using the smallest and largest distances seen so far
+    #[must_use]
+    pub fn normalized_distance(&self, distance: f64) -> f64 {
+        if self.max_distance > self.min_distance {
+            (distance - self.min_distance) / (self.max_distance - self.min_distance)
+        } else {
+            // All testcases are equally distant
+            0.5
+        }
+    }
+
+    /// The temperature of the annealing schedule, going from `1.0` (exploration) to `0.0` (exploitation).
+    /// It cools down exponentially, reaching `0.05` at `time_to_exploitation`.
+    #[must_use]
+    pub fn temperature(&self) -> f64 {
+        let elapsed = current_time().saturating_sub(self.start_time).as_secs_f64();
+        let tx = self.time_to_exploitation.as_secs_f64();
+        if tx <= 0.0 {
+            return 0.0;
+        }
+        libm::pow(20.0, -elapsed / tx)
+    }
+
+    /// The annealing power factor of a testcase with the given normalized distance
+    #[must_use]
+    pub fn power_factor(&self, normalized_distance: f64) -> f64 {
+        let temperature = self.temperature();
+        let p = (1.0 - normalized_distance) * (1.0 - temperature) + 0.5 * temperature;
+        libm::pow(2.0, 2.0 * libm::log2(MAX_FACTOR) * (p - 0.5))
+    }
+}
+
+/// A testcase metadata holding the distance of a testcase to the targets
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirectedTestcaseMetadata {
+    /// The mean distance of the covered map indexes to the targets, `None` if no target is reachable
+    distance: Option<f64>,
+}
+
+crate::impl_serdeany!(DirectedTestcaseMetadata);
+
+impl DirectedTestcaseMetadata {
+    /// Creates a new [`struct@DirectedTestcaseMetadata`]
+    #[must_use]
+    pub fn new(distance: Option<f64>) -> Self {
+        Self { distance }
+    }
+
+    /// The distance of the testcase to the targets, `None` if no target is reachable
+    #[must_use]
+    pub fn distance(&self) -> Option<f64> {
+        self.distance
+    }
+}
+
+/// A [`TestcaseScore`] multiplying the score of the base `F` with the simulated-annealing
+/// power factor of `AFLGo`, based on the distance of the testcase to the targets.
+/// Testcases that cannot reach a target get the score of the farthest testcase.
+#[derive(Debug, Clone)]
+pub struct DirectedTestcaseScore<F, S> {
+    phantom: PhantomData<(F, S)>,
+}
+
+impl<F, S> TestcaseScore<S> for DirectedTestcaseScore<F, S>
+where
+    F: TestcaseScore<S>,
+    S: HasCorpus + HasMetadata,
+{
+    fn compute(entry: &mut Testcase<S::Input>, state: &S) -> Result<f64, Error> {
+        let score = F::compute(entry, state)?;
+
+        let meta = state
+            .metadata()
+            .get::<DirectedMetadata>()
+            .ok_or_else(|| Error::key_not_found("DirectedMetadata not found".to_string()))?;
+
+        let normalized = entry
+            .metadata()
+            .get::<DirectedTestcaseMetadata>()
+            .and_then(DirectedTestcaseMetadata::distance)
+            .map_or(1.0, |d| meta.normalized_distance(d));
+
+        Ok(score * meta.power_factor(normalized))
+    }
+}
+
+/// The `AFLGo` power schedule: the power schedule of `AFL++` weighted by the distance to the targets
+pub type DirectedPowerTestcaseScore<S> = DirectedTestcaseScore<CorpusPowerTestcaseScore<S>, S>;
+
+/// A scheduler computing the distance to the targets of each [`Testcase`] added to the corpus,
+/// from the map indexes it covered, and then delegating the scheduling to the base scheduler.
+/// It needs a [`struct@DirectedMetadata`] in the state and the [`MapIndexesMetadata`] of each testcase,
+/// so the map feedback must be created tracking the indexes.
+#[derive(Debug, Clone)]
+pub struct DirectedScheduler<CS> {
+    base: CS,
+}
+
+impl<CS> UsesState for DirectedScheduler<CS>
+where
+    CS: UsesState,
+{
+    type State = CS::State;
+}
+
+impl<CS> Scheduler for DirectedScheduler<CS>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasMetadata,
+{
+    /// Add an entry to the corpus and compute its distance to the targets
+    fn on_add(&self, state: &mut CS::State, idx: usize) -> Result<(), Error> {
+        self.update_distance(state, idx)?;
+        self.base.on_add(state, idx)
+    }
+
+    /// Replaces the testcase at the given idx
+    fn on_replace(
+        &self,
+        state: &mut CS::State,
+        idx: usize,
+        testcase: &Testcase<<CS::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        self.update_distance(state, idx)?;
+        self.base.on_replace(state, idx, testcase)
+    }
+
+    /// Removes an entry from the corpus
+    fn on_remove(
+        &self,
+        state: &mut CS::State,
+        idx: usize,
+        testcase: &Option<Testcase<<CS::State as UsesInput>::Input>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    /// Gets the next entry from the base scheduler
+    fn next(&self, state: &mut CS::State) -> Result<usize, Error> {
+        self.base.next(state)
+    }
+}
+
+impl<CS> DirectedScheduler<CS>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasMetadata,
+{
+    /// Creates a new [`DirectedScheduler`] wrapping the `base` scheduler
+    #[must_use]
+    pub fn new(base: CS) -> Self {
+        Self { base }
+    }
+
+    /// Computes the distance of the testcase at `idx` and updates the distance bounds in the state
+    pub fn update_distance(&self, state: &mut CS::State, idx: usize) -> Result<(), Error> {
+        let distance = {
+            let meta = state.metadata().get::<DirectedMetadata>().ok_or_else(|| {
+                Error::key_not_found(String::from(
+                    "DirectedMetadata needed by DirectedScheduler not found",
+                ))
+            })?;
+            let testcase = state.corpus().get(idx)?.borrow();
+            let indexes = testcase.metadata().get::<MapIndexesMetadata>().ok_or_else(|| {
+                Error::key_not_found(String::from(
+                    "MapIndexesMetadata needed by DirectedScheduler not found, make sure the MapFeedback tracks indexes",
+                ))
+            })?;
+            meta.testcase_distance(indexes.as_slice())
+        };
+
+        if let Some(d) = distance {
+            let meta = state.metadata_mut().get_mut::<DirectedMetadata>().unwrap();
+            if d < meta.min_distance {
+                meta.min_distance = d;
+            }
+            if d > meta.max_distance {
+                meta.max_distance = d;
+            }
+        }
+
+        state
+            .corpus()
+            .get(idx)?
+            .borrow_mut()
+            .add_metadata(DirectedTestcaseMetadata::new(distance));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use hashbrown::HashMap;
+
+    use super::DirectedMetadata;
+
+    #[test]
+    fn test_directed_distances() {
+        let mut distances = HashMap::new();
+        distances.insert(1, 2.0);
+        distances.insert(5, 4.0);
+        let mut meta = DirectedMetadata::new(distances);
+
+        assert_eq!(meta.testcase_distance(&[0, 1, 5]), Some(3.0));
+        assert_eq!(meta.testcase_distance(&[0, 2]), None);
+
+        meta.min_distance = 2.0;
+        meta.max_distance = 4.0;
+        assert!((meta.normalized_distance(3.0) - 0.5).abs() < f64::EPSILON);
+
+        // Fully in the exploitation phase, the closest testcases get the maximum power
+        meta = meta.with_time_to_exploitation(Duration::ZERO);
+        assert!((meta.power_factor(0.0) - 32.0).abs() < 1e-9);
+        assert!((meta.power_factor(1.0) - 1.0 / 32.0).abs() < 1e-9);
+    }
+}
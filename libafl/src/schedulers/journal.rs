@@ -0,0 +1,97 @@
+//! The [`JournalScheduler`] records the decisions of a scheduler to a [`crate::events::Journal`],
+//! or checks them against a recorded journal when replaying a session.
+
+use core::fmt::{self, Debug, Formatter};
+
+use crate::{
+    corpus::Testcase, events::SharedJournal, inputs::UsesInput, schedulers::Scheduler,
+    state::UsesState, Error,
+};
+
+/// Wraps a [`Scheduler`] to record its decisions to a [`crate::events::Journal`].
+/// When the journal replays a session, the decisions of the wrapped scheduler are compared to the recorded ones,
+/// and the first decision that diverges is reported as [`Error::IllegalState`].
+/// Once all decisions have been replayed, `next` returns [`Error::ShuttingDown`].
+pub struct JournalScheduler<CS>
+where
+    CS: UsesState,
+{
+    base: CS,
+    journal: SharedJournal<<CS::State as UsesInput>::Input>,
+}
+
+impl<CS> Debug for JournalScheduler<CS>
+where
+    CS: UsesState + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JournalScheduler")
+            .field("base", &self.base)
+            .field("journal", &self.journal.borrow().path())
+            .finish()
+    }
+}
+
+impl<CS> UsesState for JournalScheduler<CS>
+where
+    CS: UsesState,
+{
+    type State = CS::State;
+}
+
+impl<CS> Scheduler for JournalScheduler<CS>
+where
+    CS: Scheduler,
+{
+    fn on_add(&self, state: &mut CS::State, idx: usize) -> Result<(), Error> {
+        self.base.on_add(state, idx)
+    }
+
+    fn on_replace(
+        &self,
+        state: &mut CS::State,
+        idx: usize,
+        prev: &Testcase<<CS::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        self.base.on_replace(state, idx, prev)
+    }
+
+    fn on_remove(
+        &self,
+        state: &mut CS::State,
+        idx: usize,
+        testcase: &Option<Testcase<<CS::State as UsesInput>::Input>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    fn next(&self, state: &mut CS::State) -> Result<usize, Error> {
+        let mut journal = self.journal.borrow_mut();
+        if journal.is_replaying() {
+            let recorded = journal.replay_scheduled()?;
+            // Let the wrapped scheduler decide anyway, so that it consumes the same random numbers
+            let idx = self.base.next(state)?;
+            if idx != recorded {
+                return Err(Error::illegal_state(format!(
+                    "Replay diverged at scheduler decision {}: chose corpus entry {idx}, recorded {recorded}",
+                    journal.decisions()
+                )));
+            }
+            Ok(idx)
+        } else {
+            let idx = self.base.next(state)?;
+            journal.record_scheduled(idx)?;
+            Ok(idx)
+        }
+    }
+}
+
+impl<CS> JournalScheduler<CS>
+where
+    CS: UsesState,
+{
+    /// Wrap `base`, recording to, or replaying from the `journal`
+    pub fn new(base: CS, journal: SharedJournal<<CS::State as UsesInput>::Input>) -> Self {
+        Self { base, journal }
+    }
+}
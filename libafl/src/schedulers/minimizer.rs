@@ -13,6 +13,7 @@ use crate::{
     feedbacks::MapIndexesMetadata,
     inputs::UsesInput,
     schedulers::{LenTimeMulTestcaseScore, Scheduler, TestcaseScore},
+    stages::calibrate::UnstableEntriesMetadata,
     state::{HasCorpus, HasMetadata, HasRand, UsesState},
     Error,
 };
@@ -212,7 +213,12 @@ where
                     idx
                 ))
             })?;
+            let unstable = state.metadata().get::<UnstableEntriesMetadata>();
             for elem in meta.as_slice() {
+                // Entries the calibration found to be unstable don't make a testcase favored
+                if unstable.map_or(false, |unstable| unstable.is_unstable(*elem)) {
+                    continue;
+                }
                 if let Some(old_idx) = state
                     .metadata()
                     .get::<TopRatedsMetadata>()
@@ -278,10 +284,12 @@ where
             Some(val) => val,
         };
 
+        let unstable = state.metadata().get::<UnstableEntriesMetadata>();
         let mut acc = HashSet::new();
 
         for (key, idx) in &top_rated.map {
-            if !acc.contains(key) {
+            if !acc.contains(key) && !unstable.map_or(false, |unstable| unstable.is_unstable(*key))
+            {
                 let mut entry = state.corpus().get(*idx)?.borrow_mut();
                 let meta = entry.metadata().get::<M>().ok_or_else(|| {
                     Error::key_not_found(format!(
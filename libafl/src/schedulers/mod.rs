@@ -27,6 +27,14 @@ use alloc::borrow::ToOwned;
 
 pub use powersched::PowerQueueScheduler;
 
+pub mod directed;
+pub use directed::{DirectedPowerTestcaseScore, DirectedScheduler, DirectedTestcaseScore};
+
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "std")]
+pub use journal::JournalScheduler;
+
 use crate::{
     bolts::rands::Rand,
     corpus::{Corpus, Testcase},
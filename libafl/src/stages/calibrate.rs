@@ -6,7 +6,7 @@ use alloc::{
 };
 use core::{fmt::Debug, marker::PhantomData, time::Duration};
 
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use num_traits::Bounded;
 use serde::{Deserialize, Serialize};
 
@@ -32,10 +32,16 @@ crate::impl_serdeany!(UnstableEntriesMetadata);
 /// The metadata to keep unstable entries
 /// In libafl, the stability is the number of the unstable entries divided by the size of the map
 /// This is different from AFL++, which shows the number of the unstable entries divided by the number of filled entries.
+///
+/// The unstable entries are ignored by the [`MapFeedback`], the [`crate::corpus::MapCorpusMinimizer`], and the
+/// favored entries of the [`crate::schedulers::MinimizerScheduler`].
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct UnstableEntriesMetadata {
     unstable_entries: HashSet<usize>,
     map_len: usize,
+    /// For each unstable entry, the number of recalibrations in a row it was hit and stable
+    #[serde(default)]
+    stable_recalibrations: HashMap<usize, usize>,
 }
 
 impl UnstableEntriesMetadata {
@@ -45,6 +51,7 @@ impl UnstableEntriesMetadata {
         Self {
             unstable_entries: entries,
             map_len,
+            stable_recalibrations: HashMap::default(),
         }
     }
 
@@ -59,6 +66,36 @@ impl UnstableEntriesMetadata {
     pub fn map_len(&self) -> usize {
         self.map_len
     }
+
+    /// Whether the given map entry is unstable
+    #[inline]
+    #[must_use]
+    pub fn is_unstable(&self, idx: usize) -> bool {
+        self.unstable_entries.contains(&idx)
+    }
+
+    /// Mark the given map entry as unstable
+    pub fn add_unstable(&mut self, idx: usize) {
+        self.unstable_entries.insert(idx);
+        self.stable_recalibrations.remove(&idx);
+    }
+
+    /// Record that an unstable map entry was hit and stable during a recalibration.
+    /// After `threshold` such recalibrations in a row, the entry is considered stable again and `true` is returned.
+    pub fn add_stable_recalibration(&mut self, idx: usize, threshold: usize) -> bool {
+        if !self.unstable_entries.contains(&idx) {
+            return false;
+        }
+        let count = self.stable_recalibrations.entry(idx).or_insert(0);
+        *count += 1;
+        if *count >= threshold {
+            self.stable_recalibrations.remove(&idx);
+            self.unstable_entries.remove(&idx);
+            true
+        } else {
+            false
+        }
+    }
 }
 
 /// The calibration stage will measure the average exec time and the target's stability for this input.
@@ -68,11 +105,15 @@ pub struct CalibrationStage<O, OT, S> {
     map_name: String,
     stage_max: usize,
     track_stability: bool,
+    recalibration_interval: Option<Duration>,
+    last_recalibration: Duration,
     phantom: PhantomData<(O, OT, S)>,
 }
 
 const CAL_STAGE_START: usize = 4; // AFL++'s CAL_CYCLES_FAST + 1
 const CAL_STAGE_MAX: usize = 8; // AFL++'s CAL_CYCLES + 1
+/// The number of recalibrations in a row an unstable entry has to be stable in to become stable again
+const STABLE_RECALIBRATIONS: usize = 3;
 
 impl<O, OT, S> UsesState for CalibrationStage<O, OT, S>
 where
@@ -101,8 +142,18 @@ where
         mgr: &mut EM,
         corpus_idx: usize,
     ) -> Result<(), Error> {
-        // Run this stage only once for each corpus entry
+        // Run this stage only once for each corpus entry,
+        // but periodically recalibrate the stability with the entries being fuzzed
         if state.corpus().get(corpus_idx)?.borrow_mut().fuzz_level() > 0 {
+            if let Some(interval) = self.recalibration_interval {
+                let cur_time = current_time();
+                if self.track_stability
+                    && cur_time.saturating_sub(self.last_recalibration) >= interval
+                {
+                    self.last_recalibration = cur_time;
+                    return self.recalibrate(fuzzer, executor, state, mgr, corpus_idx);
+                }
+            }
             return Ok(());
         }
 
@@ -228,7 +279,7 @@ where
                     .get_mut::<UnstableEntriesMetadata>()
                     .unwrap();
                 for item in unstable_entries {
-                    existing.unstable_entries.insert(item); // Insert newly found items
+                    existing.add_unstable(item); // Insert newly found items
                 }
                 existing.map_len = map_len;
             } else {
@@ -306,6 +357,8 @@ where
             map_name: map_feedback.name().to_string(),
             stage_max: CAL_STAGE_START,
             track_stability: true,
+            recalibration_interval: None,
+            last_recalibration: Duration::ZERO,
             phantom: PhantomData,
         }
     }
@@ -325,7 +378,316 @@ where
             map_name: map_feedback.name().to_string(),
             stage_max: CAL_STAGE_START,
             track_stability: false,
+            recalibration_interval: None,
+            last_recalibration: Duration::ZERO,
             phantom: PhantomData,
         }
     }
+
+    /// Every `interval`, run the entry being fuzzed again to recalibrate the stability:
+    /// entries found to be unstable are added to the [`UnstableEntriesMetadata`], and unstable entries
+    /// that were hit and stable in a few recalibrations in a row are removed from it again.
+    #[must_use]
+    pub fn with_recalibration_interval(mut self, interval: Duration) -> Self {
+        self.recalibration_interval = Some(interval);
+        self.last_recalibration = current_time();
+        self
+    }
+
+    /// Recalibrate the stability with the given corpus entry
+    fn recalibrate<E, EM, Z>(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        mgr: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error>
+    where
+        E: Executor<EM, Z, State = S> + HasObservers<Observers = OT>,
+        EM: UsesState<State = S>,
+        Z: UsesState<State = S>,
+        for<'de> <O as MapObserver>::Entry: Serialize + Deserialize<'de> + 'static,
+    {
+        let input = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .load_input()?
+            .clone();
+
+        let mut map_first: Option<Vec<O::Entry>> = None;
+        let mut initial = O::Entry::default();
+        let mut unstable_entries = HashSet::new();
+        for _ in 0..CAL_STAGE_MAX {
+            executor.observers_mut().pre_exec_all(state, &input)?;
+            let exit_kind = executor.run_target(fuzzer, state, mgr, &input)?;
+            executor
+                .observers_mut()
+                .post_exec_all(state, &input, &exit_kind)?;
+            if exit_kind != ExitKind::Ok {
+                continue;
+            }
+
+            let observer = executor
+                .observers()
+                .match_name::<O>(&self.map_observer_name)
+                .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?;
+            initial = observer.initial();
+            let map = observer.to_vec();
+            match &map_first {
+                None => map_first = Some(map),
+                Some(map_first) => {
+                    for (idx, (first, cur)) in map_first.iter().zip(map.iter()).enumerate() {
+                        if *first != *cur {
+                            unstable_entries.insert(idx);
+                        }
+                    }
+                }
+            }
+        }
+        let map_first = match map_first {
+            Some(map_first) => map_first,
+            None => return Ok(()),
+        };
+        let map_len = map_first.len();
+
+        if !state.has_metadata::<UnstableEntriesMetadata>() {
+            state.add_metadata(UnstableEntriesMetadata::new(HashSet::new(), map_len));
+        }
+        let meta = state
+            .metadata_mut()
+            .get_mut::<UnstableEntriesMetadata>()
+            .unwrap();
+        meta.map_len = map_len;
+        for idx in &unstable_entries {
+            meta.add_unstable(*idx);
+        }
+        // Entries not hit by this input tell nothing about their stability
+        let stable_candidates: Vec<usize> = meta
+            .unstable_entries
+            .iter()
+            .filter(|idx| {
+                !unstable_entries.contains(*idx)
+                    && map_first
+                        .get(**idx)
+                        .map_or(false, |first| *first != initial)
+            })
+            .copied()
+            .collect();
+        let stable_entries: Vec<usize> = stable_candidates
+            .into_iter()
+            .filter(|idx| meta.add_stable_recalibration(*idx, STABLE_RECALIBRATIONS))
+            .collect();
+
+        let history_map = &mut state
+            .named_metadata_mut()
+            .get_mut::<MapFeedbackMetadata<O::Entry>>(&self.map_name)
+            .ok_or_else(|| Error::key_not_found("MapFeedbackMetadata not found".to_string()))?
+            .history_map;
+        if history_map.len() < map_len {
+            history_map.resize(map_len, O::Entry::default());
+        }
+        for idx in unstable_entries {
+            history_map[idx] = O::Entry::max_value();
+        }
+        // Let the feedback report these entries again, once they exceed what we just saw
+        for idx in stable_entries {
+            history_map[idx] = map_first[idx];
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{CalibrationStage, UnstableEntriesMetadata};
+    use crate::{
+        bolts::{
+            rands::StdRand,
+            tuples::{tuple_list, tuple_list_type},
+        },
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::{Event, EventFirer},
+        executors::{Executor, ExitKind, HasObservers},
+        feedbacks::{ConstFeedback, Feedback, MaxMapFeedback},
+        inputs::{BytesInput, UsesInput},
+        monitors::UserStats,
+        observers::{MapObserver, ObserversTuple, StdMapObserver, UsesObservers},
+        schedulers::QueueScheduler,
+        state::{HasCorpus, HasMetadata, StdState, UsesState},
+        Error, StdFuzzer,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+    type TestObservers = tuple_list_type!(StdMapObserver<'static, u8>);
+    type TestFuzzer =
+        StdFuzzer<QueueScheduler<TestState>, ConstFeedback, ConstFeedback, TestObservers>;
+
+    /// Hits entry 1 on every run, entry 5 with alternating counts, and entry 9 once `extra` is set
+    #[derive(Debug)]
+    struct FlakyExecutor {
+        observers: TestObservers,
+        runs: u8,
+        extra: bool,
+    }
+
+    impl UsesState for FlakyExecutor {
+        type State = TestState;
+    }
+
+    impl UsesObservers for FlakyExecutor {
+        type Observers = TestObservers;
+    }
+
+    impl HasObservers for FlakyExecutor {
+        fn observers(&self) -> &TestObservers {
+            &self.observers
+        }
+
+        fn observers_mut(&mut self) -> &mut TestObservers {
+            &mut self.observers
+        }
+    }
+
+    impl<EM, Z> Executor<EM, Z> for FlakyExecutor
+    where
+        EM: UsesState<State = TestState>,
+        Z: UsesState<State = TestState>,
+    {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut TestState,
+            _mgr: &mut EM,
+            _input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            let map = &mut self.observers.0;
+            *map.get_mut(1) = 1;
+            *map.get_mut(5) = 1 + self.runs % 2;
+            self.runs = self.runs.wrapping_add(1);
+            if self.extra {
+                *map.get_mut(9) = 1;
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    /// Records the user stats fired by the feedback
+    #[derive(Debug, Default)]
+    struct StatsRecorder {
+        stats: Vec<UserStats>,
+    }
+
+    impl UsesState for StatsRecorder {
+        type State = TestState;
+    }
+
+    impl EventFirer for StatsRecorder {
+        fn fire(
+            &mut self,
+            _state: &mut TestState,
+            event: Event<<TestState as UsesInput>::Input>,
+        ) -> Result<(), Error> {
+            if let Event::UpdateUserStats { value, .. } = event {
+                self.stats.push(value);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_recalibrate_unstable_entries() {
+        let observer = StdMapObserver::new_owned("map", vec![0_u8; 64]);
+        let mut feedback = MaxMapFeedback::new(&observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        let input = BytesInput::new(vec![b'a']);
+        state
+            .corpus_mut()
+            .add(Testcase::new(input.clone()))
+            .unwrap();
+
+        let mut calibration = CalibrationStage::<_, TestObservers, _>::new(&feedback);
+        let mut fuzzer: TestFuzzer = StdFuzzer::new(
+            QueueScheduler::new(),
+            ConstFeedback::new(false),
+            ConstFeedback::new(false),
+        );
+        let mut executor = FlakyExecutor {
+            observers: tuple_list!(observer),
+            runs: 0,
+            extra: false,
+        };
+        let mut mgr = StatsRecorder::default();
+        let mut is_interesting = |fuzzer: &mut TestFuzzer,
+                                  executor: &mut FlakyExecutor,
+                                  state: &mut TestState,
+                                  mgr: &mut StatsRecorder| {
+            executor
+                .observers_mut()
+                .pre_exec_all(state, &input)
+                .unwrap();
+            let exit_kind = executor.run_target(fuzzer, state, mgr, &input).unwrap();
+            executor
+                .observers_mut()
+                .post_exec_all(state, &input, &exit_kind)
+                .unwrap();
+            let interesting = feedback
+                .is_interesting(state, mgr, &input, executor.observers(), &exit_kind)
+                .unwrap();
+            feedback.discard_metadata(state, &input).unwrap();
+            interesting
+        };
+
+        // The flaky entry keeps producing novelties, until the recalibration finds it
+        assert!(is_interesting(
+            &mut fuzzer,
+            &mut executor,
+            &mut state,
+            &mut mgr
+        ));
+        assert!(is_interesting(
+            &mut fuzzer,
+            &mut executor,
+            &mut state,
+            &mut mgr
+        ));
+
+        calibration
+            .recalibrate(&mut fuzzer, &mut executor, &mut state, &mut mgr, 0)
+            .unwrap();
+        let meta = state.metadata().get::<UnstableEntriesMetadata>().unwrap();
+        assert!(meta.is_unstable(5));
+        assert!(!meta.is_unstable(1));
+
+        for _ in 0..4 {
+            assert!(!is_interesting(
+                &mut fuzzer,
+                &mut executor,
+                &mut state,
+                &mut mgr
+            ));
+        }
+
+        // A new stable entry is still reported, and the unstable one is not counted as filled
+        executor.extra = true;
+        assert!(is_interesting(
+            &mut fuzzer,
+            &mut executor,
+            &mut state,
+            &mut mgr
+        ));
+        assert!(matches!(mgr.stats.last(), Some(UserStats::Ratio(2, 64))));
+    }
 }
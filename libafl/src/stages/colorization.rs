@@ -0,0 +1,478 @@
+//! The colorization stage finds out which input bytes end up as operands of comparisons.
+//! It randomizes ("colorizes") the input while keeping its coverage stable, traces both the original
+//! and the colorized input with a [`crate::observers::CmpObserver`], and records the offsets in the input
+//! where each comparison operand can be found. This is the input-to-state inference of `RedQueen`,
+//! also known as the cmplog mode of `AFL++`.
+
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::PerfFeature;
+use crate::{
+    bolts::{rands::Rand, tuples::MatchName},
+    corpus::Corpus,
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{HasBytesVec, UsesInput},
+    mark_feature_time,
+    observers::{
+        cmp::{CmpValues, CmpValuesMetadata},
+        MapObserver, ObserversTuple,
+    },
+    stages::Stage,
+    start_timer,
+    state::{HasClientPerfMonitor, HasCorpus, HasExecutions, HasMetadata, HasRand, UsesState},
+    Error,
+};
+
+/// The default maximum number of executions the colorization of a single input may take
+pub const DEFAULT_COLORIZATION_MAX_EXECUTIONS: usize = 1000;
+
+/// An input-to-state correspondence: the bytes at `offset` in the input end up as one operand of a
+/// comparison, and `replacement` is the other operand, encoded the same way.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct I2SMatch {
+    /// The offset in the input where `pattern` was found
+    pub offset: usize,
+    /// The bytes of the input-dependent comparison operand
+    pub pattern: Vec<u8>,
+    /// The bytes of the other comparison operand
+    pub replacement: Vec<u8>,
+}
+
+/// A metadata holding the result of the colorization of a testcase.
+/// It is attached to the colorized testcase and copied to the state whenever the testcase is
+/// scheduled, so that the [`crate::mutators::I2SColorizedReplace`] mutator can use it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ColorizationMetadata {
+    /// The ranges of the input that could be randomized without changing the coverage
+    pub colorized_ranges: Vec<Range<usize>>,
+    /// The input-to-state correspondences found for this input
+    pub matches: Vec<I2SMatch>,
+}
+
+crate::impl_serdeany!(ColorizationMetadata);
+
+impl ColorizationMetadata {
+    /// Creates a new [`struct@ColorizationMetadata`]
+    #[must_use]
+    pub fn new(colorized_ranges: Vec<Range<usize>>, matches: Vec<I2SMatch>) -> Self {
+        Self {
+            colorized_ranges,
+            matches,
+        }
+    }
+
+    /// Returns `true` if the byte at `idx` was colorized
+    #[must_use]
+    pub fn is_colorized(&self, idx: usize) -> bool {
+        self.colorized_ranges.iter().any(|r| r.contains(&idx))
+    }
+}
+
+/// Replaces a byte with a random one of the same kind (digit, lowercase, uppercase),
+/// so that parsers are less likely to take a different path.
+fn colorize_byte<R: Rand>(rand: &mut R, byte: u8) -> u8 {
+    let (base, range) = match byte {
+        b'0'..=b'9' => (b'0', 10),
+        b'a'..=b'z' => (b'a', 26),
+        b'A'..=b'Z' => (b'A', 26),
+        b' ' | b'\t' | b'\r' | b'\n' => return byte,
+        _ => (0, 256),
+    };
+    // Pick a different byte of the same kind
+    let delta = 1 + rand.below(range - 1) as u8;
+    let off = (u64::from(byte - base) + u64::from(delta)) % range;
+    base + off as u8
+}
+
+/// Returns the possible encodings of a comparison operand, together with the matching encoding of the other operand.
+fn operand_encodings(values: &CmpValues, first: bool) -> Vec<(Vec<u8>, Vec<u8>)> {
+    macro_rules! encode {
+        ($v:expr) => {{
+            let (op, other) = if first { ($v.0, $v.1) } else { ($v.1, $v.0) };
+            let mut res = vec![(op.to_le_bytes().to_vec(), other.to_le_bytes().to_vec())];
+            if core::mem::size_of_val(&op) > 1 {
+                res.push((op.to_be_bytes().to_vec(), other.to_be_bytes().to_vec()));
+            }
+            res
+        }};
+    }
+    match values {
+        CmpValues::U8(v) => encode!(v),
+        CmpValues::U16(v) => encode!(v),
+        CmpValues::U32(v) => encode!(v),
+        CmpValues::U64(v) => encode!(v),
+        CmpValues::Bytes(v) => {
+            let (op, other) = if first { (&v.0, &v.1) } else { (&v.1, &v.0) };
+            vec![(op.clone(), other.clone())]
+        }
+    }
+}
+
+/// Looks for the comparison operands that changed with the colorization, and
+/// finds their position in the original input.
+fn find_i2s_matches(
+    meta: &ColorizationMetadata,
+    orig_bytes: &[u8],
+    colorized_bytes: &[u8],
+    orig_cmps: &[CmpValues],
+    colorized_cmps: &[CmpValues],
+) -> Vec<I2SMatch> {
+    let mut matches = vec![];
+    for (orig, colored) in orig_cmps.iter().zip(colorized_cmps.iter()) {
+        if orig == colored {
+            continue;
+        }
+        for first in [true, false] {
+            for ((pattern, replacement), (colored_pattern, _)) in operand_encodings(orig, first)
+                .into_iter()
+                .zip(operand_encodings(colored, first))
+            {
+                let len = pattern.len();
+                if len == 0
+                    || pattern == replacement
+                    || pattern == colored_pattern
+                    || colored_pattern.len() != len
+                    || len > orig_bytes.len()
+                {
+                    continue;
+                }
+                for offset in 0..=(orig_bytes.len() - len) {
+                    if !meta.is_colorized(offset)
+                        || orig_bytes[offset..offset + len] != pattern[..]
+                        || colorized_bytes[offset..offset + len] != colored_pattern[..]
+                    {
+                        continue;
+                    }
+                    let found = I2SMatch {
+                        offset,
+                        pattern: pattern.clone(),
+                        replacement: replacement.clone(),
+                    };
+                    if !matches.contains(&found) {
+                        matches.push(found);
+                    }
+                }
+            }
+        }
+    }
+    matches
+}
+
+/// A stage that colorizes the input and infers input-to-state correspondences using a tracer executor
+/// with a [`crate::observers::CmpObserver`] that fills the [`struct@CmpValuesMetadata`].
+#[derive(Clone, Debug)]
+pub struct ColorizationStage<EM, O, TE, Z> {
+    map_observer_name: String,
+    tracer_executor: TE,
+    max_executions: usize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(EM, O, Z)>,
+}
+
+impl<EM, O, TE, Z> UsesState for ColorizationStage<EM, O, TE, Z>
+where
+    TE: UsesState,
+{
+    type State = TE::State;
+}
+
+impl<E, EM, O, TE, Z> Stage<E, EM, Z> for ColorizationStage<EM, O, TE, Z>
+where
+    O: MapObserver,
+    E: Executor<EM, Z> + HasObservers + UsesState<State = TE::State>,
+    TE: Executor<EM, Z> + HasObservers,
+    TE::State: HasClientPerfMonitor + HasExecutions + HasMetadata + HasCorpus + HasRand,
+    <TE::State as UsesInput>::Input: HasBytesVec,
+    EM: UsesState<State = TE::State>,
+    Z: UsesState<State = TE::State>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut TE::State,
+        manager: &mut EM,
+        corpus_idx: usize,
+    ) -> Result<(), Error> {
+        // Colorize each corpus entry only once
+        let known = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow()
+            .metadata()
+            .get::<ColorizationMetadata>()
+            .cloned();
+        if let Some(meta) = known {
+            state.add_metadata(meta);
+            return Ok(());
+        }
+
+        start_timer!(state);
+        let original = state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .load_input()?
+            .clone();
+        mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+
+        let meta = if original.bytes().is_empty() {
+            ColorizationMetadata::default()
+        } else {
+            self.colorize(fuzzer, executor, state, manager, &original)?
+        };
+
+        state
+            .corpus()
+            .get(corpus_idx)?
+            .borrow_mut()
+            .add_metadata(meta.clone());
+        state.add_metadata(meta);
+
+        Ok(())
+    }
+}
+
+impl<EM, O, TE, Z> ColorizationStage<EM, O, TE, Z>
+where
+    O: MapObserver,
+    TE: HasObservers,
+    TE::State: HasClientPerfMonitor + HasExecutions + HasMetadata + HasCorpus + HasRand,
+    <TE::State as UsesInput>::Input: HasBytesVec,
+    EM: UsesState<State = TE::State>,
+    Z: UsesState<State = TE::State>,
+{
+    /// Creates a new [`ColorizationStage`], using the given map observer to check for coverage stability.
+    #[must_use]
+    pub fn new(map_observer: &O, tracer_executor: TE) -> Self {
+        Self::from_name(map_observer.name(), tracer_executor)
+    }
+
+    /// Creates a new [`ColorizationStage`] from the name of the map observer.
+    #[must_use]
+    pub fn from_name(map_observer_name: &str, tracer_executor: TE) -> Self {
+        Self {
+            map_observer_name: map_observer_name.to_string(),
+            tracer_executor,
+            max_executions: DEFAULT_COLORIZATION_MAX_EXECUTIONS,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of executions spent to colorize a single input
+    #[must_use]
+    pub fn with_max_executions(mut self, max_executions: usize) -> Self {
+        self.max_executions = max_executions;
+        self
+    }
+
+    /// Gets the underlying tracer executor
+    pub fn executor(&self) -> &TE {
+        &self.tracer_executor
+    }
+
+    /// Runs the input in the main executor and returns the hash of the coverage map, or `None` if it did not exit normally.
+    fn coverage_hash<E>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut TE::State,
+        manager: &mut EM,
+        input: &<TE::State as UsesInput>::Input,
+    ) -> Result<Option<u64>, Error>
+    where
+        E: Executor<EM, Z> + HasObservers + UsesState<State = TE::State>,
+    {
+        start_timer!(state);
+        executor.observers_mut().pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        *state.executions_mut() += 1;
+
+        start_timer!(state);
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        if exit_kind != ExitKind::Ok {
+            return Ok(None);
+        }
+
+        let hash = executor
+            .observers()
+            .match_name::<O>(&self.map_observer_name)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found".to_string()))?
+            .hash();
+        Ok(Some(hash))
+    }
+
+    /// Runs the input in the tracer executor and returns the logged comparisons.
+    fn trace(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut TE::State,
+        manager: &mut EM,
+        input: &<TE::State as UsesInput>::Input,
+    ) -> Result<Vec<CmpValues>, Error>
+    where
+        TE: Executor<EM, Z>,
+    {
+        if let Some(meta) = state.metadata_mut().get_mut::<CmpValuesMetadata>() {
+            meta.list.clear();
+        }
+
+        start_timer!(state);
+        self.tracer_executor
+            .observers_mut()
+            .pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = self
+            .tracer_executor
+            .run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        *state.executions_mut() += 1;
+
+        start_timer!(state);
+        self.tracer_executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        Ok(state
+            .metadata()
+            .get::<CmpValuesMetadata>()
+            .map(|meta| meta.list.clone())
+            .unwrap_or_default())
+    }
+
+    /// Colorizes the input and infers the input-to-state correspondences
+    fn colorize<E>(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut TE::State,
+        manager: &mut EM,
+        original: &<TE::State as UsesInput>::Input,
+    ) -> Result<ColorizationMetadata, Error>
+    where
+        E: Executor<EM, Z> + HasObservers + UsesState<State = TE::State>,
+        TE: Executor<EM, Z>,
+    {
+        let orig_hash = match self.coverage_hash(fuzzer, executor, state, manager, original)? {
+            Some(hash) => hash,
+            // Do not colorize inputs that crash or time out
+            None => return Ok(ColorizationMetadata::default()),
+        };
+
+        let orig_bytes = original.bytes().to_vec();
+        let random_bytes: Vec<u8> = orig_bytes
+            .iter()
+            .map(|&b| colorize_byte(state.rand_mut(), b))
+            .collect();
+
+        // Bisect the ranges that change the coverage when randomized
+        let mut colorized = original.clone();
+        let mut candidate = original.clone();
+        let mut colorized_ranges = vec![];
+        let mut pending = VecDeque::new();
+        pending.push_back(0..orig_bytes.len());
+        let mut executions = 1;
+
+        while let Some(range) = pending.pop_front() {
+            if executions >= self.max_executions {
+                break;
+            }
+            candidate.bytes_mut().clear();
+            candidate.bytes_mut().extend_from_slice(colorized.bytes());
+            candidate.bytes_mut()[range.clone()].copy_from_slice(&random_bytes[range.clone()]);
+
+            executions += 1;
+            if self.coverage_hash(fuzzer, executor, state, manager, &candidate)? == Some(orig_hash)
+            {
+                colorized.bytes_mut()[range.clone()].copy_from_slice(&random_bytes[range.clone()]);
+                colorized_ranges.push(range);
+            } else if range.len() > 1 {
+                let mid = range.start + range.len() / 2;
+                pending.push_back(range.start..mid);
+                pending.push_back(mid..range.end);
+            }
+        }
+        colorized_ranges.sort_by_key(|r| r.start);
+
+        let meta = ColorizationMetadata::new(colorized_ranges, vec![]);
+        if meta.colorized_ranges.is_empty() {
+            return Ok(meta);
+        }
+
+        let orig_cmps = self.trace(fuzzer, state, manager, original)?;
+        let colorized_cmps = self.trace(fuzzer, state, manager, &colorized)?;
+
+        let matches = find_i2s_matches(
+            &meta,
+            &orig_bytes,
+            colorized.bytes(),
+            &orig_cmps,
+            &colorized_cmps,
+        );
+        Ok(ColorizationMetadata::new(meta.colorized_ranges, matches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{colorize_byte, find_i2s_matches, ColorizationMetadata, I2SMatch};
+    use crate::{bolts::rands::StdRand, observers::cmp::CmpValues};
+
+    #[test]
+    fn test_colorize_byte() {
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..100 {
+            let digit = colorize_byte(&mut rand, b'5');
+            assert!(digit.is_ascii_digit() && digit != b'5');
+            let lower = colorize_byte(&mut rand, b'q');
+            assert!(lower.is_ascii_lowercase() && lower != b'q');
+            assert_ne!(colorize_byte(&mut rand, 0xff), 0xff);
+        }
+    }
+
+    #[test]
+    fn test_find_i2s_matches() {
+        let orig = b"XXXXABCDXXXX".to_vec();
+        let colorized = b"YYYYEFGHYYYY".to_vec();
+        let meta = ColorizationMetadata::new(vec![0..orig.len()], vec![]);
+        let orig_cmps = vec![CmpValues::U32((
+            u32::from_le_bytes(*b"ABCD"),
+            u32::from_le_bytes(*b"MAGI"),
+        ))];
+        let colorized_cmps = vec![CmpValues::U32((
+            u32::from_le_bytes(*b"EFGH"),
+            u32::from_le_bytes(*b"MAGI"),
+        ))];
+        let matches = find_i2s_matches(&meta, &orig, &colorized, &orig_cmps, &colorized_cmps);
+        assert_eq!(
+            matches,
+            vec![I2SMatch {
+                offset: 4,
+                pattern: b"ABCD".to_vec(),
+                replacement: b"MAGI".to_vec(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! The [`JournalReplayStage`] evaluates the testcases another client sent while a session was recorded,
+//! when replaying the session from its [`crate::events::Journal`].
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    events::{EventFirer, SharedJournal},
+    executors::{Executor, HasObservers},
+    fuzzer::{EvaluatorObservers, ExecutionProcessor},
+    inputs::UsesInput,
+    stages::Stage,
+    state::{HasClientPerfMonitor, UsesState},
+    Error,
+};
+
+/// Replays the testcases imported from other clients, recorded in a [`crate::events::Journal`].
+/// Add it as last stage, since the event manager imported them after all stages ran.
+/// While recording, this stage does nothing.
+pub struct JournalReplayStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    journal: SharedJournal<<E::State as UsesInput>::Input>,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> Debug for JournalReplayStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JournalReplayStage")
+            .field("journal", &self.journal.borrow().path())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E, EM, Z> UsesState for JournalReplayStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for JournalReplayStage<E, EM, Z>
+where
+    E: Executor<EM, Z> + HasObservers,
+    for<'a> E::Observers: Deserialize<'a>,
+    EM: EventFirer<State = E::State>,
+    Z: EvaluatorObservers<E::Observers, State = E::State>
+        + ExecutionProcessor<E::Observers, State = E::State>,
+    E::State: HasClientPerfMonitor,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut E::State,
+        manager: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        if !self.journal.borrow().is_replaying() {
+            return Ok(());
+        }
+
+        let testcases = self.journal.borrow_mut().replay_new_testcases();
+        for (input, exit_kind, observers_buf) in testcases {
+            // Process the testcase the way the event manager did
+            if let Some(observers_buf) = observers_buf {
+                let observers: E::Observers = postcard::from_bytes(&observers_buf)?;
+                fuzzer.process_execution(state, manager, input, &observers, &exit_kind, false)?;
+            } else {
+                fuzzer.evaluate_input_with_observers::<E, EM>(
+                    state, executor, manager, input, false,
+                )?;
+            }
+        }
+
+        #[cfg(feature = "introspection")]
+        state.introspection_monitor_mut().finish_stage();
+
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> JournalReplayStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    /// Create a new [`JournalReplayStage`], replaying the imported testcases of the `journal`
+    #[must_use]
+    pub fn new(journal: SharedJournal<<E::State as UsesInput>::Input>) -> Self {
+        Self {
+            journal,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use serial_test::serial;
+
+    use super::JournalReplayStage;
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list, AsSlice},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::{Journal, SharedJournal, SimpleEventManager},
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::{ConstFeedback, MaxMapFeedback},
+        fuzzer::{EvaluatorObservers, Fuzzer},
+        inputs::{BytesInput, HasTargetBytes},
+        monitors::SimpleMonitor,
+        mutators::{havoc_mutations, StdScheduledMutator},
+        observers::StdMapObserver,
+        schedulers::{JournalScheduler, RandScheduler},
+        stages::StdMutationalStage,
+        state::{HasCorpus, StdState},
+        Error, StdFuzzer,
+    };
+
+    static mut MAP: [u8; 32] = [0; 32];
+    static mut EXECUTED: Vec<Vec<u8>> = Vec::new();
+
+    /// Fuzz for 100 iterations, importing a testcase every 10 iterations while recording,
+    /// and return the inputs the harness executed
+    fn fuzz_session(journal: &SharedJournal<BytesInput>) -> Vec<Vec<u8>> {
+        unsafe { EXECUTED.clear() };
+        let observer = StdMapObserver::new("map", unsafe { &mut MAP });
+        let mut feedback = MaxMapFeedback::new(&observer);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(journal.borrow().seed()),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![0])))
+            .unwrap();
+
+        let scheduler = JournalScheduler::new(RandScheduler::new(), journal.clone());
+        let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|_| {}));
+        let mut harness = |input: &BytesInput| {
+            let bytes = input.target_bytes().as_slice().to_vec();
+            unsafe {
+                MAP[bytes.len() % 16] = 1;
+                if let Some(byte) = bytes.first() {
+                    MAP[16 + (*byte as usize % 16)] = 1;
+                }
+                EXECUTED.push(bytes);
+            }
+            ExitKind::Ok
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        let mutator = StdScheduledMutator::new(havoc_mutations());
+        let mut stages = tuple_list!(
+            StdMutationalStage::new(mutator),
+            JournalReplayStage::new(journal.clone())
+        );
+        for i in 0..100_u8 {
+            match fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr) {
+                Err(Error::ShuttingDown) => break,
+                res => res.unwrap(),
+            };
+            if !journal.borrow().is_replaying() && i % 10 == 0 {
+                // Import a testcase, like the `LlmpEventManager` does
+                let input = BytesInput::new(vec![i; usize::from(i / 10) + 2]);
+                journal
+                    .borrow_mut()
+                    .record_new_testcase(&input, &ExitKind::Ok, None)
+                    .unwrap();
+                fuzzer
+                    .evaluate_input_with_observers(
+                        &mut state,
+                        &mut executor,
+                        &mut mgr,
+                        input,
+                        false,
+                    )
+                    .unwrap();
+            }
+        }
+        unsafe { EXECUTED.clone() }
+    }
+
+    #[test]
+    #[serial]
+    fn test_journal_replay() {
+        let path = std::env::temp_dir().join("libafl_test_journal_replay");
+
+        let recorded = fuzz_session(&Journal::record(&path, 1337).unwrap().into_shared());
+        let journal = Journal::replay(&path).unwrap().into_shared();
+        let replayed = fuzz_session(&journal);
+        assert_eq!(journal.borrow().decisions(), 100);
+        assert_eq!(recorded, replayed);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -27,6 +27,9 @@ pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 pub mod generalization;
 pub use generalization::GeneralizationStage;
 
+pub mod colorization;
+pub use colorization::{ColorizationMetadata, ColorizationStage};
+
 pub mod owned;
 pub use owned::StagesOwnedList;
 
@@ -37,6 +40,11 @@ pub use concolic::ConcolicTracingStage;
 #[cfg(feature = "std")]
 pub use concolic::SimpleConcolicMutationalStage;
 
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "std")]
+pub use journal::JournalReplayStage;
+
 #[cfg(feature = "std")]
 pub mod sync;
 use core::{convert::From, marker::PhantomData};
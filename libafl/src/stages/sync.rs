@@ -1,7 +1,8 @@
 //| The [`MutationalStage`] is the default stage used during fuzzing.
 //! For the current input, it will perform a range of random mutations, and then run them in the executor.
 
-use core::marker::PhantomData;
+use alloc::string::String;
+use core::{marker::PhantomData, time::Duration};
 use std::{
     fs,
     path::{Path, PathBuf},
@@ -11,6 +12,8 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    bolts::current_time,
+    corpus::Corpus,
     fuzzer::Evaluator,
     inputs::{Input, UsesInput},
     stages::Stage,
@@ -186,3 +189,420 @@ where
         }
     }
 }
+
+/// How often the [`AflSyncStage`] syncs by default, AFL++ does not sync more often, either
+const AFL_SYNC_DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Metadata of the [`AflSyncStage`], tracking the state of our own AFL queue
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AflSyncMetadata {
+    /// The AFL id of the next exported testcase
+    pub next_id: usize,
+    /// The last time we synced
+    pub last_sync: Duration,
+}
+
+crate::impl_serdeany!(AflSyncMetadata);
+
+/// Metadata of a testcase the [`AflSyncStage`] exported to our own AFL queue
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AflSyncTestcaseMetadata {
+    /// The AFL id the testcase was exported as
+    pub id: usize,
+}
+
+crate::impl_serdeany!(AflSyncTestcaseMetadata);
+
+/// Parses the id of an AFL queue entry from its file name, e.g. `42` from `id:000042,src:000023,op:havoc`
+#[must_use]
+pub fn afl_queue_id(file_name: &str) -> Option<usize> {
+    file_name
+        .strip_prefix("id:")
+        .or_else(|| file_name.strip_prefix("id_"))?
+        .split([',', '_'])
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// A stage that syncs with other fuzzer instances sharing an AFL++ output directory, i.e., the `-o` directory of AFL++,
+/// holding the `queue` of each instance in `<sync_dir>/<instance>/queue`.
+///
+/// New queue entries of the other instances are imported in the order of their ids,
+/// and the next id to import from each instance is stored in `<sync_dir>/<own_name>/.synced/<instance>`, like AFL++ does.
+/// Our own corpus entries are exported to `<sync_dir>/<own_name>/queue` with AFL names,
+/// imported ones as `id:000042,sync:<instance>,src:000023`, so AFL++ instances can import them in turn.
+/// Exported testcases are marked with [`AflSyncTestcaseMetadata`], so removing or replacing corpus entries
+/// does not keep new ones from being exported.
+/// Entries of other instances that were synced from us are not imported again.
+///
+/// The corpus of the fuzzer should not be kept in `<sync_dir>/<own_name>/queue` itself, as it's exported there.
+#[derive(Debug)]
+pub struct AflSyncStage<CB, E, EM, Z> {
+    sync_dir: PathBuf,
+    own_name: String,
+    interval: Duration,
+    load_callback: CB,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<CB, E, EM, Z> UsesState for AflSyncStage<CB, E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<CB, E, EM, Z> Stage<E, EM, Z> for AflSyncStage<CB, E, EM, Z>
+where
+    CB: FnMut(&mut Z, &mut Z::State, &Path) -> Result<<Z::State as UsesInput>::Input, Error>,
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: Evaluator<E, EM>,
+    Z::State: HasClientPerfMonitor + HasCorpus + HasRand + HasMetadata,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+        _corpus_idx: usize,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        if !state.has_metadata::<AflSyncMetadata>() {
+            let next_id = self.first_free_id()?;
+            state.add_metadata(AflSyncMetadata {
+                next_id,
+                last_sync: Duration::ZERO,
+            });
+        }
+        let last_sync = state.metadata().get::<AflSyncMetadata>().unwrap().last_sync;
+        if now.saturating_sub(last_sync) < self.interval {
+            return Ok(());
+        }
+
+        // First, export what we found ourselves since the last sync
+        for idx in 0..state.corpus().count() {
+            self.export(state, idx, None)?;
+        }
+
+        for entry in fs::read_dir(&self.sync_dir)? {
+            let entry = entry?;
+            let instance = entry.file_name().to_string_lossy().into_owned();
+            if instance == self.own_name || instance.starts_with('.') {
+                continue;
+            }
+            let queue_dir = entry.path().join("queue");
+            if queue_dir.is_dir() {
+                self.import_from(&instance, &queue_dir, fuzzer, executor, state, manager)?;
+            }
+        }
+
+        state
+            .metadata_mut()
+            .get_mut::<AflSyncMetadata>()
+            .unwrap()
+            .last_sync = now;
+
+        #[cfg(feature = "introspection")]
+        state.introspection_monitor_mut().finish_stage();
+
+        Ok(())
+    }
+}
+
+impl<CB, E, EM, Z> AflSyncStage<CB, E, EM, Z>
+where
+    CB: FnMut(&mut Z, &mut Z::State, &Path) -> Result<<Z::State as UsesInput>::Input, Error>,
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: Evaluator<E, EM>,
+    Z::State: HasClientPerfMonitor + HasCorpus + HasRand + HasMetadata,
+{
+    /// Creates a new [`AflSyncStage`], syncing with the instances in `sync_dir`, as the instance `own_name`
+    pub fn new(sync_dir: PathBuf, own_name: &str, load_callback: CB) -> Result<Self, Error> {
+        let own_dir = sync_dir.join(own_name);
+        fs::create_dir_all(own_dir.join("queue"))?;
+        fs::create_dir_all(own_dir.join(".synced"))?;
+        Ok(Self {
+            sync_dir,
+            own_name: own_name.into(),
+            interval: AFL_SYNC_DEFAULT_INTERVAL,
+            load_callback,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Sets how often to sync, every minute by default
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Our own queue, other instances import from
+    fn own_queue_dir(&self) -> PathBuf {
+        self.sync_dir.join(&self.own_name).join("queue")
+    }
+
+    /// The file storing the next id to import from `instance`
+    fn cursor_path(&self, instance: &str) -> PathBuf {
+        self.sync_dir
+            .join(&self.own_name)
+            .join(".synced")
+            .join(instance)
+    }
+
+    /// The id following the highest one already in our own queue, e.g. from a previous run
+    fn first_free_id(&self) -> Result<usize, Error> {
+        let mut next_id = 0;
+        for entry in fs::read_dir(self.own_queue_dir())? {
+            if let Some(id) = afl_queue_id(&entry?.file_name().to_string_lossy()) {
+                next_id = next_id.max(id + 1);
+            }
+        }
+        Ok(next_id)
+    }
+
+    /// Exports the corpus entry at `idx` to our own queue, unless it was exported before.
+    /// `origin` is the instance and id it was imported from.
+    fn export(
+        &mut self,
+        state: &mut Z::State,
+        idx: usize,
+        origin: Option<(&str, usize)>,
+    ) -> Result<(), Error> {
+        let input = {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            if testcase.has_metadata::<AflSyncTestcaseMetadata>() {
+                return Ok(());
+            }
+            testcase.load_input()?.clone()
+        };
+
+        let meta = state.metadata_mut().get_mut::<AflSyncMetadata>().unwrap();
+        let id = meta.next_id;
+        meta.next_id += 1;
+        let file_name = match origin {
+            Some((instance, src)) => format!("id:{id:06},sync:{instance},src:{src:06}"),
+            None => format!("id:{id:06}"),
+        };
+        input.to_file(self.own_queue_dir().join(file_name))?;
+
+        state
+            .corpus()
+            .get(idx)?
+            .borrow_mut()
+            .add_metadata(AflSyncTestcaseMetadata { id });
+        Ok(())
+    }
+
+    /// Imports all new entries in the `queue_dir` of `instance`
+    fn import_from(
+        &mut self,
+        instance: &str,
+        queue_dir: &Path,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let cursor_path = self.cursor_path(instance);
+        let cursor = match fs::read(&cursor_path) {
+            Ok(bytes) if bytes.len() == 4 => u32::from_ne_bytes(bytes.try_into().unwrap()) as usize,
+            _ => 0,
+        };
+
+        // Don't import what the other instance synced from us
+        let own_sync = format!(",sync:{},", self.own_name);
+        let mut new_entries = vec![];
+        for entry in fs::read_dir(queue_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(id) = afl_queue_id(&file_name) {
+                if id >= cursor && !file_name.contains(&own_sync) {
+                    new_entries.push((id, entry.path()));
+                }
+            }
+        }
+        if new_entries.is_empty() {
+            return Ok(());
+        }
+        new_entries.sort_unstable();
+
+        let mut next_cursor = cursor;
+        for (id, path) in new_entries {
+            next_cursor = id + 1;
+            if fs::metadata(&path).map_or(true, |attr| !attr.is_file() || attr.len() == 0) {
+                continue;
+            }
+            let input = (self.load_callback)(fuzzer, state, &path)?;
+            if let (_, Some(idx)) = fuzzer.evaluate_input(state, executor, manager, input)? {
+                self.export(state, idx, Some((instance, id)))?;
+            }
+        }
+
+        // AFL++ stores the next id to import as native `u32`, too
+        fs::write(cursor_path, (next_cursor as u32).to_ne_bytes())?;
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> AflSyncStage<SyncFromDiskFunction<Z::State, Z>, E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: Evaluator<E, EM>,
+    Z::State: HasClientPerfMonitor + HasCorpus + HasRand + HasMetadata,
+{
+    /// Creates a new [`AflSyncStage`] invoking `Input::from_file` to load inputs
+    pub fn with_from_file(sync_dir: PathBuf, own_name: &str) -> Result<Self, Error> {
+        fn load_callback<S: UsesInput, Z>(
+            _: &mut Z,
+            _: &mut S,
+            p: &Path,
+        ) -> Result<S::Input, Error> {
+            Input::from_file(p)
+        }
+        Self::new(sync_dir, own_name, load_callback::<_, _>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use core::time::Duration;
+    use std::fs;
+
+    use serial_test::serial;
+
+    use super::{afl_queue_id, AflSyncStage};
+    use crate::{
+        bolts::{rands::StdRand, tuples::tuple_list, AsSlice},
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::SimpleEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::{ConstFeedback, MaxMapFeedback},
+        inputs::{BytesInput, HasTargetBytes},
+        monitors::SimpleMonitor,
+        observers::StdMapObserver,
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, StdState},
+        StdFuzzer,
+    };
+
+    static mut MAP: [u8; 16] = [0; 16];
+
+    /// The sorted names of the entries in the `queue` of `instance`
+    fn queue(sync_dir: &std::path::Path, instance: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(sync_dir.join(instance).join("queue"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort_unstable_by_key(|name| afl_queue_id(name));
+        names
+    }
+
+    #[test]
+    #[serial]
+    fn test_afl_sync_roundtrip() {
+        let sync_dir = std::env::temp_dir().join("libafl_test_afl_sync");
+        let _ = fs::remove_dir_all(&sync_dir);
+
+        let observer = StdMapObserver::new("map", unsafe { &mut MAP });
+        let mut feedback = MaxMapFeedback::new(&observer);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|_| {}));
+        let mut harness = |input: &BytesInput| {
+            unsafe { MAP[input.target_bytes().as_slice().len() % 16] = 1 };
+            ExitKind::Ok
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        // Another instance, which found two entries, the second one already synced from us
+        fs::create_dir_all(sync_dir.join("other").join("queue")).unwrap();
+        fs::write(sync_dir.join("other/queue/id:000000,orig:seed"), b"AB").unwrap();
+        fs::write(
+            sync_dir.join("other/queue/id:000001,sync:main,src:000000"),
+            b"ABC",
+        )
+        .unwrap();
+
+        let mut stage = AflSyncStage::with_from_file(sync_dir.clone(), "main")
+            .unwrap()
+            .with_interval(Duration::ZERO);
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![0])))
+            .unwrap();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr, 0)
+            .unwrap();
+
+        assert_eq!(state.corpus().count(), 2);
+        assert_eq!(
+            queue(&sync_dir, "main"),
+            ["id:000000", "id:000001,sync:other,src:000000"]
+        );
+        assert_eq!(
+            fs::read(sync_dir.join("main/queue/id:000001,sync:other,src:000000")).unwrap(),
+            b"AB"
+        );
+
+        // Replace our own entry, keeping the corpus size, and sync again
+        state.corpus_mut().remove(0).unwrap();
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![1, 2, 3, 4])))
+            .unwrap();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr, 0)
+            .unwrap();
+        assert_eq!(state.corpus().count(), 2);
+        assert_eq!(
+            queue(&sync_dir, "main"),
+            ["id:000000", "id:000001,sync:other,src:000000", "id:000002"]
+        );
+
+        // Only entries the other instance found since the last sync are imported
+        fs::write(
+            sync_dir.join("other/queue/id:000002,src:000000"),
+            b"ABCDEFG",
+        )
+        .unwrap();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr, 0)
+            .unwrap();
+        assert_eq!(state.corpus().count(), 3);
+        assert_eq!(
+            queue(&sync_dir, "main"),
+            [
+                "id:000000",
+                "id:000001,sync:other,src:000000",
+                "id:000002",
+                "id:000003,sync:other,src:000002"
+            ]
+        );
+
+        fs::remove_dir_all(sync_dir).unwrap();
+    }
+}
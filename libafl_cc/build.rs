@@ -291,8 +291,10 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
     };
 
     for pass in &[
+        "cmplog-instructions-pass.cc",
         "cmplog-routines-pass.cc",
         "afl-coverage-pass.cc",
+        "afl-lto-coverage-pass.cc",
         "autotokens-pass.cc",
         "coverage-accounting-pass.cc",
     ] {
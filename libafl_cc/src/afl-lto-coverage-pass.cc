@@ -0,0 +1,381 @@
+/*
+   LibAFL - LLVM link time edge coverage instrumentation
+   -----------------------------------------------------
+
+   Based on the AFL++ LLVM-mode instrumentation pass (afl-coverage-pass.cc)
+   and on the idea of the AFL++ LTO mode, assigning collision free edge IDs
+   when the whole program is visible to the linker.
+
+   Copyright 2015, 2016 Google Inc. All rights reserved.
+   Copyright 2019-2020 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+   This pass runs at link time, when the target is compiled and linked with
+   -flto. Every edge of the whole program gets its own, sequential index in
+   the coverage map, instead of the random basic block IDs the compile time
+   pass xors together. The final map size is passed to the runtime of
+   libafl_targets by a constructor.
+
+ */
+
+#include "llvm/Config/llvm-config.h"
+
+/* lld can load plugins for the new pass manager only since LLVM 15 */
+#if LLVM_VERSION_MAJOR < 15
+  #undef USE_NEW_PM
+#endif
+
+#include "common-llvm.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+#ifndef _WIN32
+  #include <unistd.h>
+#else
+  #include <io.h>
+#endif
+#include <sys/types.h>
+#include <sys/stat.h>
+#include <fcntl.h>
+
+#include <algorithm>
+#include <map>
+#include <string>
+#include <vector>
+
+#include "llvm/Support/CommandLine.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/Analysis/CFG.h"
+#include "llvm/IR/CFG.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/ADT/DenseMap.h"
+#include "llvm/ADT/SmallPtrSet.h"
+#include "llvm/Support/FormatVariadic.h"
+#include "llvm/Transforms/Utils/BasicBlockUtils.h"
+#include "llvm/Transforms/Utils/ModuleUtils.h"
+
+#ifndef USE_NEW_PM
+  #include "llvm/Pass.h"
+#endif
+
+using namespace llvm;
+
+static cl::opt<bool> Debug("debug", cl::desc("Debug prints"), cl::init(false),
+                           cl::NotHidden);
+static cl::opt<bool> NotZero("not_zero",
+                             cl::desc("Never hit 0 again in the hitcount"),
+                             cl::init(true), cl::NotHidden);
+static cl::opt<bool> ThreadSafe("thread_safe",
+                                cl::desc("Use the thread safe instrumentation"),
+                                cl::init(false), cl::NotHidden);
+static cl::opt<bool> DumpCFG(
+    "dump_afl_cfg", cl::desc("Dump CFG containing the link time edge IDs"),
+    cl::init(false), cl::NotHidden);
+static cl::opt<std::string> DumpCFGPath(
+    "dump_afl_cfg_path",
+    cl::desc("Path to dump CFG containing the link time edge IDs"),
+    cl::init(".cfg"), cl::NotHidden);
+
+namespace {
+
+/* Where the counter of an edge is incremented */
+struct EdgeSite {
+  BasicBlock *Where;
+  /* Before the terminator of Where, instead of at its first insertion point */
+  bool     AtEnd;
+  uint32_t ID;
+};
+
+#ifdef USE_NEW_PM
+class AFLLTOCoverage : public PassInfoMixin<AFLLTOCoverage> {
+ public:
+  AFLLTOCoverage() {
+  }
+
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+class AFLLTOCoverage : public ModulePass {
+ public:
+  static char ID;
+  AFLLTOCoverage() : ModulePass(ID) {
+  }
+
+  bool runOnModule(Module &M) override;
+#endif
+
+ protected:
+  /* Index 0 is the "outside" node of the dumped CFG, edges start at 1 */
+  uint32_t next_id = 1;
+
+  /* The edge IDs leaving each basic block */
+  DenseMap<BasicBlock *, std::vector<uint32_t>> bb_to_out_ids;
+  /* The basic block each edge ID enters, with its function name */
+  std::map<uint32_t, std::pair<std::string, BasicBlock *>> id_to_bb;
+  std::map<std::string, uint32_t>                          entry_ids;
+
+  uint32_t newID(Function &F, BasicBlock *To) {
+    uint32_t id = next_id++;
+    id_to_bb[id] = std::make_pair(F.getName().str(), To);
+    return id;
+  }
+
+  void collectEdges(Function &F, std::vector<EdgeSite> &sites);
+  void instrumentEdge(Module &M, GlobalVariable *AFLMapPtr, EdgeSite &site);
+  void addMapSizeCtor(Module &M);
+  void dumpCFG();
+};
+
+}  // namespace
+
+#ifdef USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "AFLLTOCoverage", "v0.1",
+          /* lambda to insert our pass into the link time pipeline. */
+          [](PassBuilder &PB) {
+            PB.registerFullLinkTimeOptimizationLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(AFLLTOCoverage());
+                });
+          }};
+}
+#else
+char AFLLTOCoverage::ID = 0;
+#endif
+
+/* Decide where the counter of each edge of F goes, splitting the edges into
+   blocks with several predecessors. The edges we cannot split, into landing
+   pads or from indirectbr and callbr, share one ID per destination block. */
+void AFLLTOCoverage::collectEdges(Function &F, std::vector<EdgeSite> &sites) {
+  std::vector<std::pair<BasicBlock *, BasicBlock *>> edges;
+  for (auto &BB : F) {
+    SmallPtrSet<BasicBlock *, 8> seen;
+    for (BasicBlock *Succ : successors(&BB)) {
+      if (seen.insert(Succ).second) { edges.push_back({&BB, Succ}); }
+    }
+  }
+
+  BasicBlock *Entry = &F.getEntryBlock();
+  uint32_t    entry_id = newID(F, Entry);
+  entry_ids[F.getName().str()] = entry_id;
+  sites.push_back({Entry, false, entry_id});
+
+  DenseMap<BasicBlock *, uint32_t> shared_ids;
+  for (auto &edge : edges) {
+    BasicBlock  *From = edge.first;
+    BasicBlock  *To = edge.second;
+    Instruction *Term = From->getTerminator();
+    uint32_t     id;
+
+    if (To->getUniquePredecessor()) {
+      // The edge is the only way into To
+      id = newID(F, To);
+      sites.push_back({To, false, id});
+    } else if (Term->getNumSuccessors() == 1 && !isa<IndirectBrInst>(Term) &&
+               !isa<CallBrInst>(Term)) {
+      // The edge is the only way out of From
+      id = newID(F, To);
+      sites.push_back({From, true, id});
+    } else {
+      BasicBlock *Split = nullptr;
+      if (!isa<IndirectBrInst>(Term) && !isa<CallBrInst>(Term) &&
+          !To->isEHPad()) {
+        Split = SplitCriticalEdge(
+            Term, GetSuccessorNumber(From, To),
+            CriticalEdgeSplittingOptions().setMergeIdenticalEdges());
+      }
+      if (Split) {
+        id = newID(F, To);
+        sites.push_back({Split, false, id});
+      } else {
+        auto shared = shared_ids.find(To);
+        if (shared != shared_ids.end()) {
+          id = shared->second;
+        } else {
+          id = newID(F, To);
+          shared_ids[To] = id;
+          sites.push_back({To, false, id});
+        }
+      }
+    }
+
+    std::vector<uint32_t> &out_ids = bb_to_out_ids[From];
+    if (std::find(out_ids.begin(), out_ids.end(), id) == out_ids.end()) {
+      out_ids.push_back(id);
+    }
+  }
+}
+
+void AFLLTOCoverage::instrumentEdge(Module &M, GlobalVariable *AFLMapPtr,
+                                    EdgeSite &site) {
+  LLVMContext &C = M.getContext();
+  IntegerType *Int8Ty = IntegerType::getInt8Ty(C);
+  ConstantInt *One = ConstantInt::get(Int8Ty, 1);
+
+  BasicBlock::iterator IP = site.AtEnd
+                               ? site.Where->getTerminator()->getIterator()
+                               : site.Where->getFirstInsertionPt();
+  // catchswitch blocks have no place for the counter
+  if (IP == site.Where->end()) { return; }
+  IRBuilder<> IRB(&*IP);
+
+  /* Load SHM pointer */
+
+  LoadInst *MapPtr = IRB.CreateLoad(
+#if LLVM_VERSION_MAJOR >= 14
+      PointerType::get(Int8Ty, 0),
+#endif
+      AFLMapPtr);
+  MapPtr->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+  Value *MapPtrIdx = IRB.CreateGEP(
+#if LLVM_VERSION_MAJOR >= 14
+      Int8Ty,
+#endif
+      MapPtr, ConstantInt::get(IRB.getInt32Ty(), site.ID));
+
+  /* Update bitmap */
+
+  if (ThreadSafe) {
+    IRB.CreateAtomicRMW(llvm::AtomicRMWInst::BinOp::Add, MapPtrIdx, One,
+#if LLVM_VERSION_MAJOR >= 13
+                        llvm::MaybeAlign(1),
+#endif
+                        llvm::AtomicOrdering::Monotonic);
+  } else {
+    LoadInst *Counter = IRB.CreateLoad(
+#if LLVM_VERSION_MAJOR >= 14
+        Int8Ty,
+#endif
+        MapPtrIdx);
+    Counter->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+    Value *Incr = IRB.CreateAdd(Counter, One);
+
+    if (NotZero) {
+      /* Never wrap around to zero, see afl-coverage-pass.cc */
+      ConstantInt *Zero = ConstantInt::get(Int8Ty, 0);
+      auto         cf = IRB.CreateICmpEQ(Incr, Zero);
+      auto         carry = IRB.CreateZExt(cf, Int8Ty);
+      Incr = IRB.CreateAdd(Incr, carry);
+    }
+
+    IRB.CreateStore(Incr, MapPtrIdx)
+        ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+  }
+}
+
+/* Pass the map size to the runtime before main, it sets __afl_map_size and
+   checks that the map is large enough once the map is attached */
+void AFLLTOCoverage::addMapSizeCtor(Module &M) {
+  LLVMContext &C = M.getContext();
+  IntegerType *SizeTy = M.getDataLayout().getIntPtrType(C);
+
+  FunctionCallee SetMapSize = M.getOrInsertFunction(
+      "__libafl_set_edges_map_size", Type::getVoidTy(C), SizeTy);
+  Function *Ctor =
+      Function::Create(FunctionType::get(Type::getVoidTy(C), false),
+                       GlobalValue::InternalLinkage, "__libafl_lto_init", &M);
+
+  IRBuilder<> IRB(BasicBlock::Create(C, "", Ctor));
+  IRB.CreateCall(SetMapSize, {ConstantInt::get(SizeTy, next_id)});
+  IRB.CreateRetVoid();
+
+  appendToGlobalCtors(M, Ctor, 0);
+}
+
+/* Dump the CFG in the format of libafl_cc/src/cfg.rs. The nodes are the edge
+   IDs, each followed by the IDs of the edges leaving the block it enters. The
+   "##lto" header tells the parser the map index of an edge is the ID of the
+   node it enters, instead of (prev_loc >> 1) ^ cur_loc. */
+void AFLLTOCoverage::dumpCFG() {
+  int fd;
+#ifndef _WIN32
+  if ((fd = open(DumpCFGPath.c_str(), O_WRONLY | O_CREAT | O_TRUNC, 0644)) <
+      0)
+#else
+  if ((fd = _open(DumpCFGPath.c_str(), O_WRONLY | O_CREAT | O_TRUNC, 0644)) <
+      0)
+#endif
+    FATAL("Could not open/create CFG dump file.");
+
+  std::string cfg = "##lto\n";
+  for (auto &record : entry_ids) {
+    // Dump function entry edges
+    cfg += formatv("$${0}+{1}\n", record.first, record.second);
+  }
+  for (auto &record : id_to_bb) {
+    // Dump the edges following each edge
+    cfg += formatv("%%{0}+{1}\n", record.second.first, record.first);
+    for (uint32_t out_id : bb_to_out_ids.lookup(record.second.second)) {
+      cfg += formatv("->{0}\n", out_id).str();
+    }
+  }
+  if (Debug) { errs() << "CFG: \n" << cfg; }
+  if (write(fd, cfg.c_str(), cfg.length()) <= 0)
+    FATAL("Failed to dump CFG.\n");
+  close(fd);
+}
+
+#ifdef USE_NEW_PM
+PreservedAnalyses AFLLTOCoverage::run(Module &M, ModuleAnalysisManager &MAM) {
+#else
+bool AFLLTOCoverage::runOnModule(Module &M) {
+#endif
+  LLVMContext &C = M.getContext();
+
+  GlobalVariable *AFLMapPtr = M.getGlobalVariable("__afl_area_ptr");
+  if (!AFLMapPtr) {
+    AFLMapPtr = new GlobalVariable(
+        M, PointerType::get(IntegerType::getInt8Ty(C), 0), false,
+        GlobalValue::ExternalLinkage, 0, "__afl_area_ptr");
+  }
+
+  /* Split the edges and assign the IDs first, then instrument */
+
+  std::vector<EdgeSite> sites;
+  for (auto &F : M) {
+    if (F.isDeclaration() || F.getName().startswith("__libafl") ||
+        F.getName().startswith("__afl") || F.getName().startswith("asan.") ||
+        F.getName().startswith("sancov.")) {
+      continue;
+    }
+    collectEdges(F, sites);
+  }
+
+  for (auto &site : sites) {
+    instrumentEdge(M, AFLMapPtr, site);
+  }
+
+  addMapSizeCtor(M);
+
+  if (DumpCFG) { dumpCFG(); }
+
+  if (Debug) {
+    fprintf(stderr, "Instrumented %zu edge locations, map size %u.\n",
+            sites.size(), next_id);
+  }
+
+#ifdef USE_NEW_PM
+  return PreservedAnalyses::none();
+#else
+  return true;
+#endif
+}
+
+#ifndef USE_NEW_PM
+static void registerAFLLTOPass(const PassManagerBuilder &,
+                               legacy::PassManagerBase &PM) {
+  PM.add(new AFLLTOCoverage());
+}
+
+static RegisterStandardPasses RegisterAFLLTOPass(
+    PassManagerBuilder::EP_FullLinkTimeOptimizationLast, registerAFLLTOPass);
+#endif
@@ -1,5 +1,8 @@
 //! LLVM style control flow graph with information of AFL-style index of the each
 //! edges, use together with ``AFLCoverage`` pass having --dump-afl-cfg flag enabled.
+//!
+//! The ``AFLLTOCoverage`` pass dumps the same format, starting with a ``##lto`` line.
+//! Its nodes are the edge IDs assigned at link time, and the index of an edge is the ID of its ``bottom_node_loc``.
 use core::borrow::Borrow;
 use std::{
     collections::{BinaryHeap, HashMap, HashSet},
@@ -20,7 +23,8 @@ pub struct CfgEdge<T>
 where
     T: HasWeight<T>,
 {
-    /// The index of the coverage map AFL inserts to, which is (``prev_loc`` >> 1) ^ ``cur_loc``.
+    /// The index of the coverage map AFL inserts to, which is (``prev_loc`` >> 1) ^ ``cur_loc``,
+    /// or ``cur_loc`` for link time edge IDs.
     pub xored_loc: usize,
     /// The from node's index (i.e., ``prev_loc``) in the edge.
     pub top_node_loc: usize,
@@ -99,6 +103,11 @@ where
         let map_size = option_env!("LIBAFL_EDGES_MAP_SIZE")
             .map_or(Ok(65536), str::parse)
             .expect("Could not parse LIBAFL_EDGES_MAP_SIZE");
+        Self::with_map_size(map_size)
+    }
+
+    /// Creates an empty CFG for a coverage map of `map_size` entries.
+    fn with_map_size(map_size: usize) -> Self {
         Self {
             edges: (0..map_size).map(|_| None).collect(),
             func_to_entry_bb: HashMap::default(),
@@ -124,6 +133,8 @@ where
     T: HasWeight<T>,
 {
     current_bb: usize,
+    /// The nodes are link time edge IDs, from a ``##lto`` dump
+    lto: bool,
     bb_to_func: HashMap<usize, String>,
     bb_to_successors: HashMap<usize, Vec<usize>>,
     func_to_entry_bb: HashMap<String, usize>,
@@ -137,6 +148,7 @@ where
     pub fn new() -> Self {
         Self {
             current_bb: 0,
+            lto: false,
             bb_to_func: HashMap::default(),
             bb_to_successors: HashMap::default(),
             func_to_entry_bb: HashMap::default(),
@@ -173,6 +185,10 @@ where
                 self.current_bb = splitter.next().expect(FAILED_TO_PARSE).parse().expect("");
                 self.bb_to_func.insert(self.current_bb, func_name);
             }
+            "##" => {
+                // "##lto": The nodes are edge IDs assigned at link time by the ``AFLLTOCoverage`` pass.
+                self.lto = line_content == "lto";
+            }
             "$$" => {
                 // "$${function name}+{index}": Function {function name}'s entry block is {index}.
                 let mut splitter = line_content.split('+');
@@ -189,9 +205,23 @@ where
         true
     }
 
+    /// The index of the coverage map for the edge from ``top`` to ``bottom``.
+    fn edge_index(&self, top: usize, bottom: usize) -> usize {
+        if self.lto {
+            bottom
+        } else {
+            (top >> 1) ^ bottom
+        }
+    }
+
     /// Convert current state to a [`ControlFlowGraph`].
     pub fn to_cfg(&self) -> ControlFlowGraph<T> {
-        let mut cfg = ControlFlowGraph::new();
+        let mut cfg = if self.lto {
+            // The link time edge IDs are sequential, the map ends after the last one
+            ControlFlowGraph::with_map_size(self.bb_to_func.keys().max().map_or(0, |id| id + 1))
+        } else {
+            ControlFlowGraph::new()
+        };
         let mut entry_bb_locs: Vec<usize> = vec![];
         for (func_name, entry_bb) in &self.func_to_entry_bb {
             entry_bb_locs.push(*entry_bb);
@@ -202,7 +232,9 @@ where
             };
             if let Some(successors) = self.bb_to_successors.get(entry_bb) {
                 for successor in successors {
-                    entry.add_successor(*successor);
+                    entry
+                        .successor_edges
+                        .push(self.edge_index(*entry_bb, *successor));
                 }
             }
             cfg.create_func_entry(func_name, entry);
@@ -220,7 +252,7 @@ where
                 _ => self.bb_to_func.get(bb_loc).unwrap(),
             };
             for successor_loc in successor_locs {
-                let xored_loc = (*bb_loc >> 1) ^ (*successor_loc);
+                let xored_loc = self.edge_index(*bb_loc, *successor_loc);
                 let mut edge = CfgEdge {
                     xored_loc,
                     top_node_loc: *bb_loc,
@@ -232,7 +264,9 @@ where
                 };
                 if let Some(successors_of_successor) = self.bb_to_successors.get(successor_loc) {
                     for successor_of_successor in successors_of_successor {
-                        edge.add_successor(*successor_of_successor);
+                        edge.successor_basic_blocks.push(*successor_of_successor);
+                        edge.successor_edges
+                            .push(self.edge_index(*successor_loc, *successor_of_successor));
                     }
                 }
                 cfg.insert_edge(xored_loc, edge);
@@ -331,6 +365,60 @@ where
         }
         distances
     }
+
+    /// Get the indexes of all edges in the function ``func_name``.
+    #[must_use]
+    pub fn get_edges_in_function(&self, func_name: &str) -> Vec<usize> {
+        self.edges
+            .iter()
+            .flatten()
+            .filter(|edge| edge.calling_func == func_name)
+            .map(|edge| edge.xored_loc)
+            .collect()
+    }
+
+    /// Calculate the distance from every edge to the ``targets`` edges, as the harmonic mean
+    /// of the shortest distances to each reachable target, like ``AFLGo`` does.
+    ///
+    /// Edges that cannot reach any target would not be inserted in the returned hash map.
+    #[must_use]
+    pub fn calculate_distances_to_targets(&self, targets: &[usize]) -> HashMap<usize, f64> {
+        let mut distances = HashMap::new();
+        for edge in self.edges.iter().flatten() {
+            let from_edge = self.calculate_distances_to_all_edges(edge.xored_loc);
+            let (inverse_sum, count) = targets
+                .iter()
+                .filter_map(|target| from_edge.get(target))
+                .fold((0.0, 0_u32), |(sum, count), distance| {
+                    (sum + 1.0 / f64::from(*distance), count + 1)
+                });
+            if count > 0 {
+                distances.insert(edge.xored_loc, f64::from(count) / inverse_sum);
+            }
+        }
+        distances
+    }
+
+    /// Write the distances from every edge to the ``targets`` edges to a file,
+    /// with one ``{edge index} {distance}`` pair per line.
+    ///
+    /// The file can be loaded by ``DirectedMetadata::from_file`` in ``libafl``.
+    pub fn write_distances_to_file(
+        &self,
+        targets: &[usize],
+        file_name: &str,
+    ) -> std::io::Result<()> {
+        let mut distances: Vec<(usize, f64)> = self
+            .calculate_distances_to_targets(targets)
+            .into_iter()
+            .collect();
+        distances.sort_by_key(|(idx, _)| *idx);
+        let content: String = distances
+            .iter()
+            .map(|(idx, distance)| format!("{} {}\n", idx, distance))
+            .collect();
+        std::fs::write(file_name, content)
+    }
 }
 
 impl<T> Default for ControlFlowGraph<T>
@@ -399,4 +487,42 @@ mod tests {
         assert_eq!(*distances.get(&((26911 >> 1) ^ 41925)).unwrap(), 2);
         assert!(distances.get(&((41864 >> 1) ^ 52706)).is_none());
     }
+
+    #[test]
+    fn test_distances_to_targets() {
+        let cfg: ControlFlowGraph<TestMetaData> = ControlFlowGraph::from_content(TEST_GRAPH_STR);
+        let target = (26911 >> 1) ^ 41925;
+        let distances = cfg.calculate_distances_to_targets(&[target]);
+        assert_eq!(*distances.get(&target).unwrap(), 1.0);
+        assert_eq!(*distances.get(&((41864 >> 1) ^ 26911)).unwrap(), 2.0);
+        assert!(distances.get(&((26911 >> 1) ^ 52706)).is_none());
+        assert_eq!(cfg.get_edges_in_function("_ZN7MyClass1VEi").len(), 1);
+    }
+
+    // main (entry) ──► 7 ──► 8 ──► 9 ◄─┐
+    //                        │     ├───┘
+    //                        ▼     │
+    //                        10 ◄──┘
+    const TEST_LTO_GRAPH_STR: &str =
+        "##lto\n$$main+7\n%%main+7\n->8\n%%main+8\n->9\n->10\n%%main+9\n->9\n->10\n%%main+10\n";
+
+    #[test]
+    fn test_lto_cfg_from_str() {
+        let cfg: ControlFlowGraph<TestMetaData> =
+            ControlFlowGraph::from_content(TEST_LTO_GRAPH_STR);
+        let entry = cfg.get_entry("main").unwrap();
+        assert_eq!(entry.node_loc, 7);
+        assert_eq!(entry.successor_edges, vec![8]);
+
+        let edge = cfg.get_edge(8).unwrap();
+        assert_eq!(edge.top_node_loc, 7);
+        assert_eq!(edge.successor_edges, vec![9, 10]);
+        assert_eq!(cfg.get_edge(7).unwrap().top_node_loc, 0);
+        assert!(cfg.get_edge(10).is_some());
+
+        let distances = cfg.calculate_distances_to_all_edges(8);
+        assert_eq!(*distances.get(&9).unwrap(), 2);
+        assert_eq!(*distances.get(&10).unwrap(), 2);
+        assert!(distances.get(&7).is_none());
+    }
 }
@@ -29,11 +29,17 @@ include!(concat!(env!("OUT_DIR"), "/clang_constants.rs"));
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LLVMPasses {
-    //CmpLogIns,
+    /// The CmpLog pass for instructions, logging the operands of integer, floating point and switch compares
+    CmpLogIns,
     /// The CmpLog pass
     CmpLogRtn,
     /// The AFL coverage pass
     AFLCoverage,
+    /// The AFL coverage pass for link time optimization.
+    /// It runs on the whole program when linking with `lld`, and gives every edge a unique, sequential index
+    /// in the coverage map. The map size is set in `__afl_map_size` of `libafl_targets` before `main`.
+    /// Adding it compiles and links with `-flto`. Pass its arguments with [`ClangWrapper::add_link_time_passes_arg`].
+    AFLCoverageLTO,
     /// The Autotoken pass
     AutoTokens,
     /// The Coverage Accouting (BB metric) pass
@@ -45,10 +51,14 @@ impl LLVMPasses {
     #[must_use]
     pub fn path(&self) -> PathBuf {
         match self {
+            LLVMPasses::CmpLogIns => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("cmplog-instructions-pass.{}", dll_extension())),
             LLVMPasses::CmpLogRtn => PathBuf::from(env!("OUT_DIR"))
                 .join(format!("cmplog-routines-pass.{}", dll_extension())),
             LLVMPasses::AFLCoverage => PathBuf::from(env!("OUT_DIR"))
                 .join(format!("afl-coverage-pass.{}", dll_extension())),
+            LLVMPasses::AFLCoverageLTO => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("afl-lto-coverage-pass.{}", dll_extension())),
             LLVMPasses::AutoTokens => {
                 PathBuf::from(env!("OUT_DIR")).join(format!("autotokens-pass.{}", dll_extension()))
             }
@@ -56,6 +66,12 @@ impl LLVMPasses {
                 .join(format!("coverage-accounting-pass.{}", dll_extension())),
         }
     }
+
+    /// If the pass runs at link time, on the whole program, instead of on each compiled module
+    #[must_use]
+    pub fn is_link_time(&self) -> bool {
+        matches!(self, LLVMPasses::AFLCoverageLTO)
+    }
 }
 
 /// Wrap Clang
@@ -83,6 +99,7 @@ pub struct ClangWrapper {
     link_args: Vec<String>,
     passes: Vec<LLVMPasses>,
     passes_args: Vec<String>,
+    link_time_passes_args: Vec<String>,
 }
 
 #[allow(clippy::match_same_arms)] // for the linking = false wip for "shared"
@@ -302,7 +319,11 @@ impl CompilerWrapper for ClangWrapper {
         } else {
             args.push("-flegacy-pass-manager".into());
         }
-        for pass in &self.passes {
+        let lto = self.passes.iter().any(LLVMPasses::is_link_time);
+        if lto {
+            args.push("-flto".into());
+        }
+        for pass in self.passes.iter().filter(|pass| !pass.is_link_time()) {
             if self.use_new_pm {
                 // https://github.com/llvm/llvm-project/issues/56137
                 // Need this -Xclang -load -Xclang -<pass>.so thing even with the new PM
@@ -333,6 +354,10 @@ impl CompilerWrapper for ClangWrapper {
                 args.push("none".into());
             }
 
+            if lto {
+                args.extend(self.link_time_args());
+            }
+
             args.extend_from_slice(self.link_args.as_slice());
 
             if cfg!(unix) {
@@ -399,6 +424,7 @@ impl ClangWrapper {
             link_args: vec![],
             passes: vec![],
             passes_args: vec![],
+            link_time_passes_args: vec![],
             is_silent: false,
         }
     }
@@ -442,6 +468,39 @@ impl ClangWrapper {
         self
     }
 
+    /// Add an argument for the link time LLVM passes, such as [`LLVMPasses::AFLCoverageLTO`]
+    pub fn add_link_time_passes_arg<S>(&mut self, arg: S) -> &'_ mut Self
+    where
+        S: AsRef<str>,
+    {
+        self.link_time_passes_args.push(arg.as_ref().to_string());
+        self
+    }
+
+    /// The arguments to run the link time passes in `lld`
+    fn link_time_args(&self) -> Vec<String> {
+        // `lld` loads plugins for the new pass manager only since LLVM 15, the passes are built for the legacy one before
+        let lto_new_pm = LIBAFL_CC_LLVM_VERSION.map_or(false, |ver| ver >= 15);
+        let lto_legacy_pm_flag = LIBAFL_CC_LLVM_VERSION.map_or(false, |ver| ver >= 13);
+
+        let mut args = vec!["-fuse-ld=lld".to_string()];
+        if !lto_new_pm && lto_legacy_pm_flag {
+            args.push("-Wl,--lto-legacy-pass-manager".into());
+        }
+        for pass in self.passes.iter().filter(|pass| pass.is_link_time()) {
+            let path = pass.path().into_os_string().into_string().unwrap();
+            // Loading the pass with -load registers its arguments, like -Xclang -load when compiling
+            args.push(format!("-Wl,-mllvm=-load={path}"));
+            if lto_new_pm {
+                args.push(format!("-Wl,--load-pass-plugin={path}"));
+            }
+        }
+        for passes_arg in &self.link_time_passes_args {
+            args.push(format!("-Wl,-mllvm={passes_arg}"));
+        }
+        args
+    }
+
     /// Set if linking
     pub fn linking(&mut self, value: bool) -> &'_ mut Self {
         self.linking = value;
@@ -463,7 +522,7 @@ impl ClangWrapper {
 
 #[cfg(test)]
 mod tests {
-    use crate::{ClangWrapper, CompilerWrapper};
+    use crate::{ClangWrapper, CompilerWrapper, LLVMPasses};
 
     #[test]
     fn test_clang_version() {
@@ -475,4 +534,20 @@ mod tests {
             println!("Ignored error {res:?} - clang is probably not installed.");
         }
     }
+
+    #[test]
+    fn test_lto_args() {
+        let args = ClangWrapper::new()
+            .add_pass(LLVMPasses::AFLCoverageLTO)
+            .add_link_time_passes_arg("-dump_afl_cfg")
+            .parse_args(&["my-clang", "fuzz.c", "-o", "fuzz"])
+            .unwrap()
+            .command()
+            .unwrap();
+        assert!(args.iter().any(|arg| arg == "-flto"));
+        assert!(args.iter().any(|arg| arg == "-fuse-ld=lld"));
+        assert!(args.iter().any(|arg| arg == "-Wl,-mllvm=-dump_afl_cfg"));
+        // The link time pass is not loaded into the compiler
+        assert!(!args.iter().any(|arg| arg == "-load"));
+    }
 }
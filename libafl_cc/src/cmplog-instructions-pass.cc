@@ -0,0 +1,419 @@
+/*
+   american fuzzy lop++ - LLVM CmpLog instrumentation
+   --------------------------------------------------
+
+   Written by Andrea Fioraldi <andreafioraldi@gmail.com>
+
+   Copyright 2015, 2016 Google Inc. All rights reserved.
+   Copyright 2019-2020 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+#ifndef _WIN32
+  #include <unistd.h>
+  #include <sys/time.h>
+#endif
+
+#include <list>
+#include <string>
+#include <fstream>
+#include "llvm/Config/llvm-config.h"
+
+#if USE_NEW_PM
+  #include "llvm/Passes/PassPlugin.h"
+  #include "llvm/Passes/PassBuilder.h"
+  #include "llvm/IR/PassManager.h"
+#else
+  #include "llvm/IR/LegacyPassManager.h"
+#endif
+
+#include "llvm/ADT/Statistic.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/Module.h"
+#include "llvm/Support/Debug.h"
+#include "llvm/Support/raw_ostream.h"
+#include "llvm/Transforms/IPO/PassManagerBuilder.h"
+#include "llvm/Transforms/Utils/BasicBlockUtils.h"
+#include "llvm/Pass.h"
+#include "llvm/Analysis/ValueTracking.h"
+
+#if LLVM_VERSION_MAJOR > 3 || \
+    (LLVM_VERSION_MAJOR == 3 && LLVM_VERSION_MINOR > 4)
+  #include "llvm/IR/Verifier.h"
+  #include "llvm/IR/DebugInfo.h"
+#else
+  #include "llvm/Analysis/Verifier.h"
+  #include "llvm/DebugInfo.h"
+  #define nullptr 0
+#endif
+
+#include <set>
+
+using namespace llvm;
+
+namespace {
+
+/* Function that we never instrument or analyze */
+/* Note: this ignore check is also called in isInInstrumentList() */
+bool isIgnoreFunction(const llvm::Function *F) {
+  // Starting from "LLVMFuzzer" these are functions used in libfuzzer based
+  // fuzzing campaign installations, e.g. oss-fuzz
+
+  static constexpr const char *ignoreList[] = {
+
+      "asan.",
+      "llvm.",
+      "sancov.",
+      "__ubsan",
+      "ign.",
+      "__afl",
+      "_fini",
+      "__libc_",
+      "__asan",
+      "__msan",
+      "__cmplog",
+      "__sancov",
+      "__san",
+      "__cxx_",
+      "__decide_deferred",
+      "_GLOBAL",
+      "_ZZN6__asan",
+      "_ZZN6__lsan",
+      "msan.",
+      "LLVMFuzzerM",
+      "LLVMFuzzerC",
+      "LLVMFuzzerI",
+      "maybe_duplicate_stderr",
+      "discard_output",
+      "close_stdout",
+      "dup_and_close_stderr",
+      "maybe_close_fd_mask",
+      "ExecuteFilesOnyByOne"
+
+  };
+
+  for (auto const &ignoreListFunc : ignoreList) {
+    if (F->getName().startswith(ignoreListFunc)) { return true; }
+  }
+
+  static constexpr const char *ignoreSubstringList[] = {
+
+      "__asan",       "__msan",     "__ubsan", "__lsan",
+      "__san",        "__sanitize", "__cxx",   "_GLOBAL__",
+      "DebugCounter", "DwarfDebug", "DebugLoc"
+
+  };
+
+  for (auto const &ignoreListFunc : ignoreSubstringList) {
+    // hexcoder: F->getName().contains() not avaiilable in llvm 3.8.0
+    if (StringRef::npos != F->getName().find(ignoreListFunc)) { return true; }
+  }
+
+  return false;
+}
+
+#if USE_NEW_PM
+class CmpLogInstructions : public PassInfoMixin<CmpLogInstructions> {
+ public:
+  CmpLogInstructions() {
+#else
+
+class CmpLogInstructions : public ModulePass {
+ public:
+  static char ID;
+  CmpLogInstructions() : ModulePass(ID) {
+#endif
+  }
+
+#if USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool        runOnModule(Module &M) override;
+
+  #if LLVM_VERSION_MAJOR < 4
+  const char *getPassName() const override {
+  #else
+  StringRef getPassName() const override {
+
+  #endif
+    return "cmplog instructions";
+  }
+#endif
+
+ private:
+  bool hookInstrs(Module &M);
+};
+
+}  // namespace
+
+#if USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "CmpLogInstructions", "v0.1",
+          [](PassBuilder &PB) {
+  #if LLVM_VERSION_MAJOR <= 13
+            using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+  #endif
+            PB.registerOptimizerLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(CmpLogInstructions());
+                });
+          }};
+}
+#else
+char CmpLogInstructions::ID = 0;
+#endif
+
+/* The size in bytes the operands of type Ty are logged with, or 0 if they
+   are not logged. Integers narrower than a byte, such as booleans, wider
+   than 64 bits, pointers and vectors are skipped. */
+static unsigned getOperandSize(Type *Ty) {
+  unsigned bits;
+
+  if (Ty->isIntegerTy()) {
+    bits = Ty->getIntegerBitWidth();
+  } else if (Ty->isHalfTy()) {
+    bits = 16;
+  } else if (Ty->isFloatTy()) {
+    bits = 32;
+  } else if (Ty->isDoubleTy()) {
+    bits = 64;
+  } else {
+    return 0;
+  }
+
+  if (bits < 8 || bits > 64) { return 0; }
+  if (bits <= 8) { return 1; }
+  if (bits <= 16) { return 2; }
+  if (bits <= 32) { return 4; }
+  return 8;
+}
+
+/* Cast V to the integer type IntTy of the hook, reinterpreting the bits of
+   floating point values */
+static Value *castOperand(IRBuilder<> &IRB, Value *V, IntegerType *IntTy) {
+  Type *Ty = V->getType();
+  if (Ty->isFloatingPointTy()) {
+    V = IRB.CreateBitCast(
+        V, IntegerType::get(Ty->getContext(), Ty->getPrimitiveSizeInBits()));
+  }
+  return IRB.CreateZExtOrTrunc(V, IntTy);
+}
+
+bool CmpLogInstructions::hookInstrs(Module &M) {
+  std::vector<CmpInst *>    cmps;
+  std::vector<SwitchInst *> switches;
+  LLVMContext              &C = M.getContext();
+
+  Type        *VoidTy = Type::getVoidTy(C);
+  IntegerType *Int8Ty = IntegerType::getInt8Ty(C);
+  IntegerType *Int16Ty = IntegerType::getInt16Ty(C);
+  IntegerType *Int32Ty = IntegerType::getInt32Ty(C);
+  IntegerType *Int64Ty = IntegerType::getInt64Ty(C);
+
+#if LLVM_VERSION_MAJOR < 9
+  Constant *
+#else
+  FunctionCallee
+#endif
+      c1 = M.getOrInsertFunction("__cmplog_ins_hook1", VoidTy, Int8Ty, Int8Ty
+#if LLVM_VERSION_MAJOR < 5
+                                 ,
+                                 NULL
+#endif
+      );
+#if LLVM_VERSION_MAJOR < 9
+  Function *cmplogHookIns1 = cast<Function>(c1);
+#else
+  FunctionCallee cmplogHookIns1 = c1;
+#endif
+
+#if LLVM_VERSION_MAJOR < 9
+  Constant *
+#else
+  FunctionCallee
+#endif
+      c2 = M.getOrInsertFunction("__cmplog_ins_hook2", VoidTy, Int16Ty, Int16Ty
+#if LLVM_VERSION_MAJOR < 5
+                                 ,
+                                 NULL
+#endif
+      );
+#if LLVM_VERSION_MAJOR < 9
+  Function *cmplogHookIns2 = cast<Function>(c2);
+#else
+  FunctionCallee cmplogHookIns2 = c2;
+#endif
+
+#if LLVM_VERSION_MAJOR < 9
+  Constant *
+#else
+  FunctionCallee
+#endif
+      c4 = M.getOrInsertFunction("__cmplog_ins_hook4", VoidTy, Int32Ty, Int32Ty
+#if LLVM_VERSION_MAJOR < 5
+                                 ,
+                                 NULL
+#endif
+      );
+#if LLVM_VERSION_MAJOR < 9
+  Function *cmplogHookIns4 = cast<Function>(c4);
+#else
+  FunctionCallee cmplogHookIns4 = c4;
+#endif
+
+#if LLVM_VERSION_MAJOR < 9
+  Constant *
+#else
+  FunctionCallee
+#endif
+      c8 = M.getOrInsertFunction("__cmplog_ins_hook8", VoidTy, Int64Ty, Int64Ty
+#if LLVM_VERSION_MAJOR < 5
+                                 ,
+                                 NULL
+#endif
+      );
+#if LLVM_VERSION_MAJOR < 9
+  Function *cmplogHookIns8 = cast<Function>(c8);
+#else
+  FunctionCallee cmplogHookIns8 = c8;
+#endif
+
+  /* iterate over all functions, bbs and instruction and collect the compares
+   */
+  for (auto &F : M) {
+    if (isIgnoreFunction(&F)) { continue; }
+
+    for (auto &BB : F) {
+      for (auto &IN : BB) {
+        CmpInst    *cmpInst = nullptr;
+        SwitchInst *switchInst = nullptr;
+
+        if ((cmpInst = dyn_cast<CmpInst>(&IN))) {
+          // Nothing to learn if both sides are known at compile time
+          if (isa<Constant>(cmpInst->getOperand(0)) &&
+              isa<Constant>(cmpInst->getOperand(1))) {
+            continue;
+          }
+          if (cmpInst->getPredicate() == CmpInst::FCMP_FALSE ||
+              cmpInst->getPredicate() == CmpInst::FCMP_TRUE) {
+            continue;
+          }
+          if (!getOperandSize(cmpInst->getOperand(0)->getType())) { continue; }
+          cmps.push_back(cmpInst);
+        } else if ((switchInst = dyn_cast<SwitchInst>(&IN))) {
+          if (!switchInst->getNumCases() ||
+              isa<Constant>(switchInst->getCondition())) {
+            continue;
+          }
+          if (!getOperandSize(switchInst->getCondition()->getType())) {
+            continue;
+          }
+          switches.push_back(switchInst);
+        }
+      }
+    }
+  }
+
+  if (!cmps.size() && !switches.size()) { return false; }
+
+  /* log both operands with the hook of their size, the runtime keys the
+     entries in the cmplog map by the call site */
+  auto hookOperands = [&](IRBuilder<> &IRB, Value *v1, Value *v2) {
+    std::vector<Value *> args;
+    switch (getOperandSize(v1->getType())) {
+      case 1:
+        args.push_back(castOperand(IRB, v1, Int8Ty));
+        args.push_back(castOperand(IRB, v2, Int8Ty));
+        IRB.CreateCall(cmplogHookIns1, args);
+        break;
+      case 2:
+        args.push_back(castOperand(IRB, v1, Int16Ty));
+        args.push_back(castOperand(IRB, v2, Int16Ty));
+        IRB.CreateCall(cmplogHookIns2, args);
+        break;
+      case 4:
+        args.push_back(castOperand(IRB, v1, Int32Ty));
+        args.push_back(castOperand(IRB, v2, Int32Ty));
+        IRB.CreateCall(cmplogHookIns4, args);
+        break;
+      case 8:
+        args.push_back(castOperand(IRB, v1, Int64Ty));
+        args.push_back(castOperand(IRB, v2, Int64Ty));
+        IRB.CreateCall(cmplogHookIns8, args);
+        break;
+      default:
+        break;
+    }
+  };
+
+  for (auto &cmpInst : cmps) {
+    IRBuilder<> IRB(cmpInst->getParent());
+    IRB.SetInsertPoint(cmpInst);
+
+    hookOperands(IRB, cmpInst->getOperand(0), cmpInst->getOperand(1));
+  }
+
+  for (auto &switchInst : switches) {
+    IRBuilder<> IRB(switchInst->getParent());
+    IRB.SetInsertPoint(switchInst);
+
+    // One hook per case, so every case gets its own entry in the map
+    Value *cond = switchInst->getCondition();
+    for (auto &caseIt : switchInst->cases()) {
+      hookOperands(IRB, cond, caseIt.getCaseValue());
+    }
+  }
+
+  return true;
+}
+
+#if USE_NEW_PM
+PreservedAnalyses CmpLogInstructions::run(Module                &M,
+                                          ModuleAnalysisManager &MAM) {
+#else
+bool CmpLogInstructions::runOnModule(Module &M) {
+#endif
+  bool modified = hookInstrs(M);
+
+#if USE_NEW_PM
+  auto PA = modified ? PreservedAnalyses::none() : PreservedAnalyses::all();
+#endif
+  verifyModule(M);
+
+#if USE_NEW_PM
+  return PA;
+#else
+  return modified;
+#endif
+}
+
+#if USE_NEW_PM
+#else
+static void registerCmpLogInstructionsPass(const PassManagerBuilder &,
+                                           legacy::PassManagerBase &PM) {
+  auto p = new CmpLogInstructions();
+  PM.add(p);
+}
+
+static RegisterStandardPasses RegisterCmpLogInstructionsPass(
+    PassManagerBuilder::EP_OptimizerLast, registerCmpLogInstructionsPass);
+
+static RegisterStandardPasses RegisterCmpLogInstructionsPass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerCmpLogInstructionsPass);
+
+  #if LLVM_VERSION_MAJOR >= 11
+static RegisterStandardPasses RegisterCmpLogInstructionsPassLTO(
+    PassManagerBuilder::EP_FullLinkTimeOptimizationLast,
+    registerCmpLogInstructionsPass);
+  #endif
+
+#endif
@@ -50,12 +50,13 @@ pub fn build() {
 
     // Make sure we have at most one architecutre feature set
     // Else, we default to `x86_64` - having a default makes CI easier :)
-    assert_unique_feature!("arm", "aarch64", "i386", "i86_64");
+    assert_unique_feature!("arm", "aarch64", "i386", "i86_64", "mips", "ppc", "riscv64");
 
-    // Make sure that we don't have BE set for any architecture other than arm
+    // Make sure that we don't have BE set for any architecture other than arm and mips
+    // (ppc is always BE, so the feature changes nothing for it)
     // Sure aarch64 may support BE, but its not in common usage and we don't
     // need it yet and so haven't tested it
-    assert_unique_feature!("be", "aarch64", "i386", "i86_64");
+    assert_unique_feature!("be", "aarch64", "i386", "i86_64", "riscv64");
 
     let mut cpu_target = if cfg!(feature = "x86_64") {
         "x86_64".to_string()
@@ -65,10 +66,16 @@ pub fn build() {
         "aarch64".to_string()
     } else if cfg!(feature = "i386") {
         "i386".to_string()
+    } else if cfg!(feature = "mips") {
+        "mips".to_string()
+    } else if cfg!(feature = "ppc") {
+        "ppc".to_string()
+    } else if cfg!(feature = "riscv64") {
+        "riscv64".to_string()
     } else {
         env::var("CPU_TARGET").unwrap_or_else(|_| {
             println!(
-                "cargo:warning=No architecture feature enabled or CPU_TARGET env specified for libafl_qemu, supported: arm, aarch64, i386, x86_64 - defaulting to x86_64"
+                "cargo:warning=No architecture feature enabled or CPU_TARGET env specified for libafl_qemu, supported: arm, aarch64, i386, x86_64, mips, ppc, riscv64 - defaulting to x86_64"
             );
             "x86_64".to_string()
         })
@@ -101,6 +108,12 @@ pub fn build() {
         cpu_target += "eb";
     }
 
+    // Unlike arm, QEMU builds different targets for big and little endian mips,
+    // in both emulation modes. mips is big endian, the default is little endian.
+    if !cfg!(feature = "be") && cfg!(feature = "mips") && !cfg!(feature = "clippy") {
+        cpu_target += "el";
+    }
+
     if std::env::var("DOCS_RS").is_ok() {
         return; // only build when we're not generating docs
     }
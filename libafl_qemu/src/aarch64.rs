@@ -4,6 +4,8 @@ use pyo3::prelude::*;
 pub use strum_macros::EnumIter;
 pub use syscall_numbers::aarch64::*;
 
+use crate::GuestAddr;
+
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
 #[repr(i32)]
 pub enum Regs {
@@ -50,6 +52,24 @@ impl Regs {
     pub const Lr: Regs = Regs::X30;
 }
 
+/// The registers holding the first integer arguments of a function, following the AAPCS64
+pub const ARGUMENT_REGS: &[Regs] = &[
+    Regs::X0,
+    Regs::X1,
+    Regs::X2,
+    Regs::X3,
+    Regs::X4,
+    Regs::X5,
+    Regs::X6,
+    Regs::X7,
+];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::X0;
+/// The register holding the return address of a function
+pub const RETURN_ADDRESS_REG: Option<Regs> = Some(Regs::Lr);
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 0;
+
 #[cfg(feature = "python")]
 impl IntoPy<PyObject> for Regs {
     fn into_py(self, py: Python) -> PyObject {
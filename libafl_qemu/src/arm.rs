@@ -4,6 +4,8 @@ use pyo3::prelude::*;
 pub use strum_macros::EnumIter;
 pub use syscall_numbers::arm::*;
 
+use crate::GuestAddr;
+
 /// Registers for the ARM instruction set.
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
 #[repr(i32)]
@@ -40,6 +42,15 @@ impl Regs {
     pub const Cpsr: Regs = Regs::R25;
 }
 
+/// The registers holding the first integer arguments of a function, following the AAPCS
+pub const ARGUMENT_REGS: &[Regs] = &[Regs::R0, Regs::R1, Regs::R2, Regs::R3];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::R0;
+/// The register holding the return address of a function
+pub const RETURN_ADDRESS_REG: Option<Regs> = Some(Regs::Lr);
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 0;
+
 #[cfg(feature = "python")]
 impl IntoPy<PyObject> for Regs {
     fn into_py(self, py: Python) -> PyObject {
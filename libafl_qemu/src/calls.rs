@@ -1,18 +1,71 @@
+use core::ptr::addr_of;
+
 use capstone::prelude::*;
-use libafl::inputs::UsesInput;
+use hashbrown::HashSet;
+use libafl::{
+    bolts::{ownedref::OwnedRefMut, tuples::Named},
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{HarnessType, Observer, ObserverWithHashField},
+    Error,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     capstone,
-    helper::{QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
+    helper::{hash_me, QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
     hooks::QemuHooks,
-    Emulator, GuestAddr, Regs,
+    Emulator, GuestAddr,
 };
 
+/// The guest callstack, as the return addresses of the active calls, the innermost last.
+/// It is not a field of the [`QemuCallTracerHelper`], so that the [`QemuCallStackObserver`] can read it
+/// in the crash handler. There is only one [`Emulator`] per process, so only one callstack.
+static mut CALLSTACK: Vec<GuestAddr> = Vec::new();
+
+/// The calls capstone does not put in the call group, by mnemonic
+#[cfg(cpu_target = "arm")]
+const CALL_MNEMONICS: &[&str] = &["bl", "blx"];
+#[cfg(cpu_target = "aarch64")]
+const CALL_MNEMONICS: &[&str] = &["bl", "blr"];
+#[cfg(cpu_target = "mips")]
+const CALL_MNEMONICS: &[&str] = &["jal", "jalr", "jalr.hb", "bal", "bgezal", "bltzal"];
+#[cfg(cpu_target = "ppc")]
+const CALL_MNEMONICS: &[&str] = &["bl", "bla", "bctrl", "blrl"];
+#[cfg(cpu_target = "riscv64")]
+const CALL_MNEMONICS: &[&str] = &["jal", "jalr", "c.jal", "c.jalr"];
+#[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+const CALL_MNEMONICS: &[&str] = &[];
+
+/// The returns capstone does not put in the return group, by mnemonic and operands
+#[cfg(cpu_target = "arm")]
+const RET_INSNS: &[(&str, &str)] = &[("bx", "lr")];
+#[cfg(cpu_target = "aarch64")]
+const RET_INSNS: &[(&str, &str)] = &[("ret", ""), ("ret", "x30")];
+#[cfg(cpu_target = "mips")]
+const RET_INSNS: &[(&str, &str)] = &[("jr", "$ra")];
+#[cfg(cpu_target = "ppc")]
+const RET_INSNS: &[(&str, &str)] = &[("blr", "")];
+#[cfg(cpu_target = "riscv64")]
+const RET_INSNS: &[(&str, &str)] = &[("ret", ""), ("c.jr", "ra")];
+#[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+const RET_INSNS: &[(&str, &str)] = &[];
+
+/// Where the return address of a return instruction is read from
+#[derive(Debug, Clone, Copy)]
+enum RetKind {
+    /// The usual place for the architecture, see [`Emulator::read_return_address`]
+    Default,
+    /// Popped from the stack, at this offset from the stack pointer (`pop {r4, pc}`)
+    #[cfg(cpu_target = "arm")]
+    Stack(GuestAddr),
+}
+
 #[derive(Debug)]
 pub struct QemuCallTracerHelper {
     filter: QemuInstrumentationFilter,
     cs: Capstone,
-    callstack: Vec<GuestAddr>,
+    hooked: HashSet<GuestAddr>,
 }
 
 impl QemuCallTracerHelper {
@@ -21,7 +74,7 @@ impl QemuCallTracerHelper {
         Self {
             filter,
             cs: capstone().detail(true).build().unwrap(),
-            callstack: vec![],
+            hooked: HashSet::new(),
         }
     }
 
@@ -30,13 +83,80 @@ impl QemuCallTracerHelper {
         self.filter.allowed(addr)
     }
 
+    /// The return addresses of the active guest calls, the innermost last
     #[must_use]
+    #[allow(clippy::unused_self)]
     pub fn callstack(&self) -> &[GuestAddr] {
-        &self.callstack
+        unsafe { &*addr_of!(CALLSTACK) }
     }
 
+    #[allow(clippy::unused_self)]
     pub fn reset(&mut self) {
-        self.callstack.clear();
+        unsafe { CALLSTACK.clear() };
+    }
+
+    /// Whether `insn` calls a function
+    fn is_call(insn: &Insn, insn_detail: &InsnDetail) -> bool {
+        if insn_detail
+            .groups()
+            .any(|group| u32::from(group.0) == capstone::InsnGroupType::CS_GRP_CALL)
+        {
+            return true;
+        }
+
+        let mnemonic = insn.mnemonic().unwrap_or_default();
+        // `jal offset` and `jalr rs` are the aliases of the calls linking to `ra`, others are jumps
+        #[cfg(cpu_target = "riscv64")]
+        {
+            let op_str = insn.op_str().unwrap_or_default();
+            if op_str.contains(',') && !op_str.starts_with("ra,") {
+                return false;
+            }
+        }
+        CALL_MNEMONICS.contains(&mnemonic)
+    }
+
+    /// Whether `insn` returns from a function, and where it reads the return address from
+    fn ret_kind(insn: &Insn, insn_detail: &InsnDetail) -> Option<RetKind> {
+        let mnemonic = insn.mnemonic().unwrap_or_default();
+        let op_str = insn.op_str().unwrap_or_default();
+
+        // `pop {r4, pc}` and `ldm sp!, {r4, pc}` return to the last popped word
+        #[cfg(cpu_target = "arm")]
+        if (mnemonic.starts_with("pop")
+            || (mnemonic.starts_with("ldm") && op_str.starts_with("sp!")))
+            && op_str.contains("pc")
+        {
+            let regs = op_str
+                .find('{')
+                .map_or(1, |start| op_str[start..].matches(',').count() + 1);
+            return Some(RetKind::Stack(4 * (regs as GuestAddr - 1)));
+        }
+
+        if insn_detail
+            .groups()
+            .any(|group| u32::from(group.0) == capstone::InsnGroupType::CS_GRP_RET)
+            || RET_INSNS.contains(&(mnemonic, op_str))
+        {
+            Some(RetKind::Default)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `insn` ends the basic block
+    fn ends_block(insn_detail: &InsnDetail) -> bool {
+        insn_detail.groups().any(|group| {
+            matches!(
+                u32::from(group.0),
+                capstone::InsnGroupType::CS_GRP_INVALID
+                    | capstone::InsnGroupType::CS_GRP_JUMP
+                    | capstone::InsnGroupType::CS_GRP_BRANCH_RELATIVE
+                    | capstone::InsnGroupType::CS_GRP_INT
+                    | capstone::InsnGroupType::CS_GRP_IRET
+                    | capstone::InsnGroupType::CS_GRP_PRIVILEGE
+            )
+        })
     }
 }
 
@@ -62,57 +182,47 @@ where
     }
 }
 
-/*pub fn on_call<QT, S>(hooks: &mut QemuHooks<'_, QT, S>, _state: Option<&mut S>, pc: GuestAddr)
-where
+/// Push the return address of a call
+fn push_frame(ret_addr: GuestAddr) {
+    // eprintln!("CALL @ 0x{:#x}", ret_addr);
+    unsafe { CALLSTACK.push(ret_addr) };
+}
 
-    QT: QemuHelperTuple<S>,
-{
-}*/
+/// Pop the frames up to the one returning to `ret_addr`.
+/// Returns to addresses not in the callstack, e.g. from code that is not instrumented, leave it as it is.
+fn pop_frames(ret_addr: GuestAddr) {
+    // eprintln!("RET @ 0x{:#x}", ret_addr);
+    // Returns to Thumb code set bit 0 of the address, the return address of the call is pushed without it
+    #[cfg(cpu_target = "arm")]
+    let ret_addr = ret_addr & !1;
+    unsafe {
+        if let Some(idx) = CALLSTACK.iter().rposition(|addr| *addr == ret_addr) {
+            CALLSTACK.truncate(idx);
+        }
+    }
+}
 
 pub fn on_ret<QT, S>(hooks: &mut QemuHooks<'_, QT, S>, _state: Option<&mut S>, _pc: GuestAddr)
 where
     S: UsesInput,
     QT: QemuHelperTuple<S>,
 {
-    #[cfg(cpu_target = "x86_64")]
-    let ret_addr = {
-        let emu = hooks.emulator();
-        let stack_ptr: GuestAddr = emu.read_reg(Regs::Rsp).unwrap();
-        let mut ret_addr = [0; 8];
-        unsafe { emu.read_mem(stack_ptr, &mut ret_addr) };
-        GuestAddr::from_le_bytes(ret_addr)
-    };
-
-    #[cfg(cpu_target = "i386")]
-    let ret_addr = {
-        let emu = hooks.emulator();
-        let stack_ptr: GuestAddr = emu.read_reg(Regs::Esp).unwrap();
-        let mut ret_addr = [0; 4];
-        unsafe { emu.read_mem(stack_ptr, &mut ret_addr) };
-        GuestAddr::from_le_bytes(ret_addr)
-    };
-
-    #[cfg(any(cpu_target = "arm", cpu_target = "aarch64"))]
-    let ret_addr = {
-        let emu = hooks.emulator();
-        let ret_addr: GuestAddr = emu.read_reg(Regs::Lr).unwrap();
-        ret_addr
-    };
-
-    // eprintln!("RET @ 0x{:#x}", ret_addr);
-
-    if let Some(h) = hooks
-        .helpers_mut()
-        .match_first_type_mut::<QemuCallTracerHelper>()
-    {
-        while let Some(addr) = h.callstack.pop() {
-            if addr == ret_addr {
-                break;
-            }
-        }
+    if let Ok(ret_addr) = hooks.emulator().read_return_address() {
+        pop_frames(ret_addr);
     }
 }
 
+#[cfg(cpu_target = "arm")]
+fn read_stack_word(emu: &Emulator, offset: GuestAddr) -> Option<GuestAddr> {
+    let sp: GuestAddr = emu.read_reg(crate::Regs::Sp).ok()?;
+    let mut buf = [0; 4];
+    unsafe { emu.read_mem(sp + offset, &mut buf) };
+    #[cfg(feature = "be")]
+    return Some(GuestAddr::from_be_bytes(buf));
+    #[cfg(not(feature = "be"))]
+    return Some(GuestAddr::from_le_bytes(buf));
+}
+
 pub fn gen_blocks_calls<QT, S>(
     hooks: &mut QemuHooks<'_, QT, S>,
     _state: Option<&mut S>,
@@ -122,8 +232,14 @@ where
     S: UsesInput,
     QT: QemuHelperTuple<S>,
 {
-    let emu = hooks.emulator();
-    if let Some(h) = hooks.helpers().match_first_type::<QemuCallTracerHelper>() {
+    let emu = hooks.emulator().clone();
+    let mut calls: Vec<(GuestAddr, GuestAddr)> = vec![];
+    let mut rets: Vec<(GuestAddr, RetKind)> = vec![];
+
+    if let Some(h) = hooks
+        .helpers_mut()
+        .match_first_type_mut::<QemuCallTracerHelper>()
+    {
         if !h.must_instrument(pc.into()) {
             return None;
         }
@@ -144,46 +260,34 @@ where
 
         let mut iaddr = pc;
 
-        'disasm: while let Ok(insns) = h.cs.disasm_count(code, iaddr.into(), 1) {
+        while let Ok(insns) = h.cs.disasm_count(code, iaddr.into(), 1) {
             if insns.is_empty() {
                 break;
             }
             let insn = insns.first().unwrap();
             let insn_detail: InsnDetail = h.cs.insn_detail(insn).unwrap();
-            for detail in insn_detail.groups() {
-                match u32::from(detail.0) {
-                    capstone::InsnGroupType::CS_GRP_CALL => {
-                        // hooks.instruction_closure(insn.address() as GuestAddr, on_call, false);
-                        let call_len = insn.bytes().len() as GuestAddr;
-                        let call_cb = move |hooks: &mut QemuHooks<'_, QT, S>, _, pc| {
-                            // eprintln!("CALL @ 0x{:#x}", pc + call_len);
-                            if let Some(h) = hooks
-                                .helpers_mut()
-                                .match_first_type_mut::<QemuCallTracerHelper>()
-                            {
-                                h.callstack.push(pc + call_len);
-                            }
-                        };
-                        unsafe {
-                            hooks.instruction_closure(
-                                insn.address() as GuestAddr,
-                                Box::new(call_cb),
-                                false,
-                            );
-                        }
-                    }
-                    capstone::InsnGroupType::CS_GRP_RET => {
-                        hooks.instruction(insn.address() as GuestAddr, on_ret, false);
-                        break 'disasm;
-                    }
-                    capstone::InsnGroupType::CS_GRP_INVALID
-                    | capstone::InsnGroupType::CS_GRP_JUMP
-                    | capstone::InsnGroupType::CS_GRP_IRET
-                    | capstone::InsnGroupType::CS_GRP_PRIVILEGE => {
-                        break 'disasm;
-                    }
-                    _ => {}
+            let addr = insn.address() as GuestAddr;
+
+            // Blocks are generated again when the code is jumped into from the middle of a block,
+            // or translated again, so hook each instruction once
+            if let Some(kind) = QemuCallTracerHelper::ret_kind(insn, &insn_detail) {
+                if h.hooked.insert(addr) {
+                    rets.push((addr, kind));
                 }
+                break;
+            }
+            if QemuCallTracerHelper::is_call(insn, &insn_detail) {
+                let call_len = insn.bytes().len() as GuestAddr;
+                // The call returns after its delay slot
+                #[cfg(cpu_target = "mips")]
+                let call_len = 2 * call_len;
+                if h.hooked.insert(addr) {
+                    calls.push((addr, addr + call_len));
+                }
+                break;
+            }
+            if QemuCallTracerHelper::ends_block(&insn_detail) {
+                break;
             }
 
             iaddr += insn.bytes().len() as GuestAddr;
@@ -194,10 +298,209 @@ where
             }
             #[cfg(emulation_mode = "systemmode")]
             unsafe {
-                emu.read_mem(pc, code);
+                emu.read_mem(iaddr, code);
             } // TODO handle faults
         }
     }
 
+    for (addr, ret_addr) in calls {
+        let call_cb = move |_: &mut QemuHooks<'_, QT, S>, _, _| push_frame(ret_addr);
+        unsafe {
+            hooks.instruction_closure(addr, Box::new(call_cb), false);
+        }
+    }
+    for (addr, kind) in rets {
+        match kind {
+            RetKind::Default => hooks.instruction(addr, on_ret::<QT, S>, false),
+            #[cfg(cpu_target = "arm")]
+            RetKind::Stack(offset) => {
+                let ret_cb = move |hooks: &mut QemuHooks<'_, QT, S>, _, _| {
+                    if let Some(ret_addr) = read_stack_word(hooks.emulator(), offset) {
+                        pop_frames(ret_addr);
+                    }
+                };
+                unsafe {
+                    hooks.instruction_closure(addr, Box::new(ret_cb), false);
+                }
+            }
+        }
+    }
+
     None
 }
+
+/// The default number of frames hashed by the [`QemuCallStackObserver`]
+pub const DEFAULT_CALLSTACK_HASH_FRAMES: usize = 16;
+
+/// Hash the innermost `max_frames` return addresses of the guest callstack
+#[must_use]
+pub fn hash_callstack(max_frames: usize) -> u64 {
+    let callstack = unsafe { &*addr_of!(CALLSTACK) };
+    callstack
+        .iter()
+        .rev()
+        .take(max_frames)
+        .fold(0, |hash, addr| hash_me(hash ^ u64::from(*addr)))
+}
+
+/// An observer hashing the innermost frames of the guest callstack, as tracked by the [`QemuCallTracerHelper`],
+/// when the target crashes or `QASan` reports an error.
+/// Use it with a [`libafl::feedbacks::NewHashFeedback`] to only keep the crashes with a new callstack.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QemuCallStackObserver<'a> {
+    observer_name: String,
+    hash: OwnedRefMut<'a, Option<u64>>,
+    max_frames: usize,
+    harness_type: HarnessType,
+}
+
+impl<'a> QemuCallStackObserver<'a> {
+    /// Creates a new [`QemuCallStackObserver`] hashing the innermost [`DEFAULT_CALLSTACK_HASH_FRAMES`] frames.
+    /// With a [`crate::QemuForkExecutor`], use [`HarnessType::Child`] and a `callstack_hash` in shared memory.
+    #[must_use]
+    pub fn new(
+        observer_name: &str,
+        callstack_hash: &'a mut Option<u64>,
+        harness_type: HarnessType,
+    ) -> Self {
+        Self::with_max_frames(
+            observer_name,
+            callstack_hash,
+            harness_type,
+            DEFAULT_CALLSTACK_HASH_FRAMES,
+        )
+    }
+
+    /// Creates a new [`QemuCallStackObserver`] hashing the innermost `max_frames` frames
+    #[must_use]
+    pub fn with_max_frames(
+        observer_name: &str,
+        callstack_hash: &'a mut Option<u64>,
+        harness_type: HarnessType,
+        max_frames: usize,
+    ) -> Self {
+        Self {
+            observer_name: observer_name.to_string(),
+            hash: OwnedRefMut::Ref(callstack_hash),
+            max_frames,
+            harness_type,
+        }
+    }
+
+    fn update_on_exit(&mut self, exit_kind: &ExitKind) {
+        if exit_kind == &ExitKind::Crash {
+            self.update_hash(hash_callstack(self.max_frames));
+        } else {
+            self.clear_hash();
+        }
+    }
+}
+
+impl<'a> ObserverWithHashField for QemuCallStackObserver<'a> {
+    /// Gets the hash value of this observer.
+    #[must_use]
+    fn hash(&self) -> &Option<u64> {
+        self.hash.as_ref()
+    }
+
+    /// Updates the hash value of this observer.
+    fn update_hash(&mut self, hash: u64) {
+        *self.hash.as_mut() = Some(hash);
+    }
+
+    /// Clears the current hash value
+    fn clear_hash(&mut self) {
+        *self.hash.as_mut() = None;
+    }
+}
+
+impl<'a, S> Observer<S> for QemuCallStackObserver<'a>
+where
+    S: UsesInput,
+{
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &S::Input,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        if self.harness_type == HarnessType::InProcess {
+            self.update_on_exit(exit_kind);
+        }
+        Ok(())
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &S::Input,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        if self.harness_type == HarnessType::Child {
+            self.update_on_exit(exit_kind);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Named for QemuCallStackObserver<'a> {
+    fn name(&self) -> &str {
+        &self.observer_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::addr_of;
+
+    use super::{hash_callstack, pop_frames, push_frame, CALLSTACK};
+    use crate::GuestAddr;
+
+    fn callstack() -> Vec<GuestAddr> {
+        unsafe { (*addr_of!(CALLSTACK)).clone() }
+    }
+
+    // A single test, as the callstack is global
+    #[test]
+    fn test_callstack() {
+        unsafe { CALLSTACK.clear() };
+        push_frame(0x1000);
+        push_frame(0x2000);
+        push_frame(0x3000);
+        assert_eq!(callstack(), [0x1000, 0x2000, 0x3000]);
+
+        // Only the innermost frames are hashed
+        let hash = hash_callstack(16);
+        let inner_hash = hash_callstack(2);
+        assert_ne!(hash, inner_hash);
+        unsafe { CALLSTACK[0] = 0x1100 };
+        assert_ne!(hash_callstack(16), hash);
+        assert_eq!(hash_callstack(2), inner_hash);
+        unsafe { CALLSTACK[0] = 0x1000 };
+        assert_eq!(hash_callstack(16), hash);
+
+        pop_frames(0x3000);
+        assert_eq!(callstack(), [0x1000, 0x2000]);
+        assert_ne!(hash_callstack(16), hash);
+
+        // Returns to addresses not in the callstack leave it as it is
+        pop_frames(0x4000);
+        assert_eq!(callstack(), [0x1000, 0x2000]);
+
+        // Returns past several frames, e.g. by `longjmp`, pop them all
+        push_frame(0x3000);
+        pop_frames(0x2000);
+        assert_eq!(callstack(), [0x1000]);
+
+        #[cfg(cpu_target = "arm")]
+        {
+            push_frame(0x2000);
+            pop_frames(0x2001);
+            assert_eq!(callstack(), [0x1000]);
+        }
+
+        pop_frames(0x1000);
+        assert!(callstack().is_empty());
+        assert_eq!(hash_callstack(16), 0);
+    }
+}
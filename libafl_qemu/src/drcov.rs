@@ -0,0 +1,212 @@
+//! Record the basic blocks executed by the guest, and write them to [`DrCov`](https://dynamorio.org/page_drcov.html) files.
+//!
+//! Replay a corpus with the [`QemuDrCovHelper`] to load the coverage of a campaign in tools like
+//! [Lighthouse](https://github.com/gaasedelen/lighthouse) or [Cartographer](https://github.com/nccgroup/Cartographer),
+//! the same way as the coverage of `libafl_frida`.
+
+use std::path::PathBuf;
+
+use capstone::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use libafl::inputs::{Input, UsesInput};
+use libafl_targets::drcov::{DrCovBasicBlock, DrCovWriter};
+use rangemap::RangeMap;
+
+use crate::{
+    capstone,
+    emu::{Emulator, GuestAddr, GuestUsize},
+    helper::{QemuHelper, QemuHelperTuple, QemuInstrumentationFilter},
+    hooks::QemuHooks,
+};
+
+/// Records the basic blocks executed for each input, and writes them to `<output_dir>/<input name>.drcov`,
+/// with offsets relative to the module they belong to
+#[derive(Debug)]
+pub struct QemuDrCovHelper {
+    filter: QemuInstrumentationFilter,
+    output_dir: PathBuf,
+    full_trace: bool,
+    cs: Capstone,
+    block_lengths: HashMap<GuestAddr, GuestUsize>,
+    trace: Vec<GuestAddr>,
+    seen: HashSet<GuestAddr>,
+}
+
+impl QemuDrCovHelper {
+    /// Create a new [`QemuDrCovHelper`] writing the `DrCov` files to `output_dir`.
+    /// With `full_trace`, every execution of a block is recorded, in order, instead of each block once.
+    #[must_use]
+    pub fn new(filter: QemuInstrumentationFilter, output_dir: PathBuf, full_trace: bool) -> Self {
+        std::fs::create_dir_all(&output_dir)
+            .expect("failed to create directory for coverage files");
+        Self {
+            filter,
+            output_dir,
+            full_trace,
+            cs: capstone().detail(true).build().unwrap(),
+            block_lengths: HashMap::new(),
+            trace: vec![],
+            seen: HashSet::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: u64) -> bool {
+        self.filter.allowed(addr)
+    }
+
+    /// The modules currently mapped in the guest, with their ids in the `DrCov` module table.
+    /// A module spans all the mappings of its file.
+    #[must_use]
+    pub fn module_mapping(emulator: &Emulator) -> RangeMap<usize, (u16, String)> {
+        let mut modules: Vec<(String, usize, usize)> = vec![];
+        for map in emulator.mappings() {
+            let path = match map.path() {
+                Some(path) if !path.is_empty() => path,
+                _ => continue,
+            };
+            let (start, end) = (map.start() as usize, map.end() as usize);
+            if let Some(module) = modules.iter_mut().find(|module| module.0 == path) {
+                module.1 = module.1.min(start);
+                module.2 = module.2.max(end);
+            } else {
+                modules.push((path.to_string(), start, end));
+            }
+        }
+        modules.sort_by_key(|module| module.1);
+
+        let mut module_mapping = RangeMap::new();
+        for (id, (path, start, end)) in modules.into_iter().enumerate() {
+            module_mapping.insert(start..end, (id as u16, path));
+        }
+        module_mapping
+    }
+
+    /// The length of the basic block starting at `pc`, up to its first control flow instruction
+    fn block_length(&self, emulator: &Emulator, pc: GuestAddr) -> GuestUsize {
+        let mut code = unsafe { std::slice::from_raw_parts(emulator.g2h(pc), 512) };
+        let mut iaddr = pc;
+
+        'disasm: while let Ok(insns) = self.cs.disasm_count(code, iaddr.into(), 1) {
+            if insns.is_empty() {
+                break;
+            }
+            let insn = insns.first().unwrap();
+            iaddr += insn.bytes().len() as GuestAddr;
+
+            let insn_detail: InsnDetail = self.cs.insn_detail(insn).unwrap();
+            for detail in insn_detail.groups() {
+                match u32::from(detail.0) {
+                    capstone::InsnGroupType::CS_GRP_CALL
+                    | capstone::InsnGroupType::CS_GRP_RET
+                    | capstone::InsnGroupType::CS_GRP_JUMP
+                    | capstone::InsnGroupType::CS_GRP_INT
+                    | capstone::InsnGroupType::CS_GRP_IRET
+                    | capstone::InsnGroupType::CS_GRP_BRANCH_RELATIVE
+                    | capstone::InsnGroupType::CS_GRP_INVALID
+                    | capstone::InsnGroupType::CS_GRP_PRIVILEGE => {
+                        // The delay slot runs before the branch is taken
+                        #[cfg(cpu_target = "mips")]
+                        {
+                            iaddr += 4;
+                        }
+                        break 'disasm;
+                    }
+                    _ => {}
+                }
+            }
+
+            code = unsafe { std::slice::from_raw_parts(emulator.g2h(iaddr), 512) };
+        }
+
+        // Always count at least an instruction, for undecodable blocks
+        (iaddr - pc).max(1)
+    }
+
+    /// Write the recorded blocks of the last execution to `path`, skipping the blocks outside of any module
+    fn write_drcov(&self, emulator: &Emulator, path: PathBuf) {
+        let module_mapping = Self::module_mapping(emulator);
+        let blocks: Vec<DrCovBasicBlock> = self
+            .trace
+            .iter()
+            .filter(|pc| module_mapping.contains_key(&(**pc as usize)))
+            .map(|pc| {
+                let len = self.block_lengths.get(pc).copied().unwrap_or(1);
+                DrCovBasicBlock::new_with_size(*pc as usize, len as usize)
+            })
+            .collect();
+        DrCovWriter::new(&module_mapping)
+            .write(path, &blocks)
+            .expect("failed to write DrCov file");
+    }
+}
+
+impl<S> QemuHelper<S> for QemuDrCovHelper
+where
+    S: UsesInput,
+{
+    fn first_exec<QT>(&self, hooks: &QemuHooks<'_, QT, S>)
+    where
+        QT: QemuHelperTuple<S>,
+    {
+        hooks.blocks(
+            Some(gen_drcov_block_lengths::<QT, S>),
+            Some(trace_drcov_block::<QT, S>),
+        );
+    }
+
+    fn pre_exec(&mut self, _emulator: &Emulator, _input: &S::Input) {
+        self.trace.clear();
+        self.seen.clear();
+    }
+
+    fn post_exec(&mut self, emulator: &Emulator, input: &S::Input) {
+        let path = self
+            .output_dir
+            .join(format!("{}.drcov", input.generate_name(0)));
+        self.write_drcov(emulator, path);
+    }
+}
+
+pub fn gen_drcov_block_lengths<QT, S>(
+    hooks: &mut QemuHooks<'_, QT, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    S: UsesInput,
+    QT: QemuHelperTuple<S>,
+{
+    let emu = hooks.emulator().clone();
+    if let Some(h) = hooks
+        .helpers_mut()
+        .match_first_type_mut::<QemuDrCovHelper>()
+    {
+        if !h.must_instrument(pc.into()) {
+            return None;
+        }
+        if !h.block_lengths.contains_key(&pc) {
+            let len = h.block_length(&emu, pc);
+            h.block_lengths.insert(pc, len);
+        }
+    }
+    // GuestAddress is u32 for 32 bit guests
+    #[allow(clippy::unnecessary_cast)]
+    Some(pc as u64)
+}
+
+pub fn trace_drcov_block<QT, S>(hooks: &mut QemuHooks<'_, QT, S>, _state: Option<&mut S>, id: u64)
+where
+    S: UsesInput,
+    QT: QemuHelperTuple<S>,
+{
+    if let Some(h) = hooks
+        .helpers_mut()
+        .match_first_type_mut::<QemuDrCovHelper>()
+    {
+        let pc = id as GuestAddr;
+        if h.full_trace || h.seen.insert(pc) {
+            h.trace.push(pc);
+        }
+    }
+}
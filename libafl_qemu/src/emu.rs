@@ -3,6 +3,7 @@
 use core::{
     convert::Into,
     ffi::c_void,
+    mem::size_of,
     ptr::{addr_of, addr_of_mut, null},
 };
 #[cfg(emulation_mode = "usermode")]
@@ -15,11 +16,11 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use num_traits::Num;
 use strum_macros::EnumIter;
 
-#[cfg(not(any(cpu_target = "x86_64", cpu_target = "aarch64")))]
+#[cfg(not(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64")))]
 /// `GuestAddr` is u32 for 32-bit targets
 pub type GuestAddr = u32;
 
-#[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64"))]
+#[cfg(any(cpu_target = "x86_64", cpu_target = "aarch64", cpu_target = "riscv64"))]
 /// `GuestAddr` is u64 for 64-bit targets
 pub type GuestAddr = u64;
 
@@ -545,6 +546,86 @@ impl CPU {
         }
     }
 
+    /// Read a guest word from the stack, in the byte order of the guest
+    fn read_stack_word(&self, addr: GuestAddr) -> GuestAddr {
+        let mut buf = [0; size_of::<GuestAddr>()];
+        unsafe { self.read_mem(addr, &mut buf) };
+        #[cfg(any(feature = "be", cpu_target = "ppc"))]
+        let val = GuestAddr::from_be_bytes(buf);
+        #[cfg(not(any(feature = "be", cpu_target = "ppc")))]
+        let val = GuestAddr::from_le_bytes(buf);
+        val
+    }
+
+    /// Write a guest word to the stack, in the byte order of the guest
+    fn write_stack_word(&self, addr: GuestAddr, val: GuestAddr) {
+        #[cfg(any(feature = "be", cpu_target = "ppc"))]
+        let buf = val.to_be_bytes();
+        #[cfg(not(any(feature = "be", cpu_target = "ppc")))]
+        let buf = val.to_le_bytes();
+        unsafe { self.write_mem(addr, &buf) };
+    }
+
+    /// The address of the `idx`-th function argument passed on the stack, at the entry point of the function
+    fn stack_argument_addr(&self, idx: usize) -> Result<GuestAddr, String> {
+        let sp: GuestAddr = self.read_reg(crate::Regs::Sp)?;
+        let word = size_of::<GuestAddr>() as GuestAddr;
+        Ok(sp
+            + crate::STACK_ARGUMENTS_OFFSET
+            + word * (idx - crate::ARGUMENT_REGS.len()) as GuestAddr)
+    }
+
+    /// Read the `idx`-th integer or pointer argument of the current function, following the standard
+    /// calling convention of the guest.
+    /// Only valid at the entry point of the function, before the stack pointer or the argument registers change.
+    pub fn read_function_argument(&self, idx: usize) -> Result<GuestAddr, String> {
+        match crate::ARGUMENT_REGS.get(idx) {
+            Some(reg) => self.read_reg(*reg),
+            None => Ok(self.read_stack_word(self.stack_argument_addr(idx)?)),
+        }
+    }
+
+    /// Overwrite the `idx`-th integer or pointer argument of the current function, see [`CPU::read_function_argument`]
+    pub fn write_function_argument(&self, idx: usize, val: GuestAddr) -> Result<(), String> {
+        match crate::ARGUMENT_REGS.get(idx) {
+            Some(reg) => self.write_reg(*reg, val),
+            None => {
+                self.write_stack_word(self.stack_argument_addr(idx)?, val);
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the integer or pointer value returned by a function, right after it returned
+    pub fn read_return_value(&self) -> Result<GuestAddr, String> {
+        self.read_reg(crate::RETURN_VALUE_REG)
+    }
+
+    /// Overwrite the integer or pointer value returned by a function, right after it returned
+    pub fn write_return_value(&self, val: GuestAddr) -> Result<(), String> {
+        self.write_reg(crate::RETURN_VALUE_REG, val)
+    }
+
+    /// Read the return address of the current function.
+    /// Valid at the entry point of the function and at its return instruction.
+    pub fn read_return_address(&self) -> Result<GuestAddr, String> {
+        match crate::RETURN_ADDRESS_REG {
+            Some(reg) => self.read_reg(reg),
+            None => Ok(self.read_stack_word(self.read_reg(crate::Regs::Sp)?)),
+        }
+    }
+
+    /// Overwrite the return address of the current function, see [`CPU::read_return_address`]
+    pub fn write_return_address(&self, val: GuestAddr) -> Result<(), String> {
+        match crate::RETURN_ADDRESS_REG {
+            Some(reg) => self.write_reg(reg, val),
+            None => {
+                self.write_stack_word(self.read_reg(crate::Regs::Sp)?, val);
+                Ok(())
+            }
+        }
+    }
+
     pub fn cpu_reset(&self) {
         unsafe { cpu_reset(self.ptr) };
     }
@@ -687,6 +768,32 @@ impl Emulator {
         self.current_cpu().unwrap().read_reg(reg)
     }
 
+    pub fn read_function_argument(&self, idx: usize) -> Result<GuestAddr, String> {
+        self.current_cpu().unwrap().read_function_argument(idx)
+    }
+
+    pub fn write_function_argument(&self, idx: usize, val: GuestAddr) -> Result<(), String> {
+        self.current_cpu()
+            .unwrap()
+            .write_function_argument(idx, val)
+    }
+
+    pub fn read_return_value(&self) -> Result<GuestAddr, String> {
+        self.current_cpu().unwrap().read_return_value()
+    }
+
+    pub fn write_return_value(&self, val: GuestAddr) -> Result<(), String> {
+        self.current_cpu().unwrap().write_return_value(val)
+    }
+
+    pub fn read_return_address(&self) -> Result<GuestAddr, String> {
+        self.current_cpu().unwrap().read_return_address()
+    }
+
+    pub fn write_return_address(&self, val: GuestAddr) -> Result<(), String> {
+        self.current_cpu().unwrap().write_return_address(val)
+    }
+
     pub fn set_breakpoint(&self, addr: GuestAddr) {
         unsafe {
             libafl_qemu_set_breakpoint(addr.into());
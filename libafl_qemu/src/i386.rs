@@ -5,6 +5,8 @@ use pyo3::prelude::*;
 pub use strum_macros::EnumIter;
 pub use syscall_numbers::x86::*;
 
+use crate::GuestAddr;
+
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
 #[repr(i32)]
 pub enum Regs {
@@ -27,6 +29,15 @@ impl Regs {
     pub const Pc: Regs = Regs::Eip;
 }
 
+/// The registers holding the first integer arguments of a function, following cdecl (none, all of them are passed on the stack)
+pub const ARGUMENT_REGS: &[Regs] = &[];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::Eax;
+/// The register holding the return address of a function, `None` as it is pushed on the stack by `call`
+pub const RETURN_ADDRESS_REG: Option<Regs> = None;
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 4;
+
 #[cfg(feature = "python")]
 impl IntoPy<PyObject> for Regs {
     fn into_py(self, py: Python) -> PyObject {
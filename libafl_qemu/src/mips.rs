@@ -0,0 +1,89 @@
+use capstone::arch::{BuildsCapstone, BuildsCapstoneEndian};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+pub use strum_macros::EnumIter;
+pub use syscall_numbers::mips::*;
+
+use crate::GuestAddr;
+
+/// Registers for the MIPS32 instruction set, in big- or little-endian.
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
+#[repr(i32)]
+pub enum Regs {
+    R0 = 0,
+    At = 1,
+    V0 = 2,
+    V1 = 3,
+    A0 = 4,
+    A1 = 5,
+    A2 = 6,
+    A3 = 7,
+    T0 = 8,
+    T1 = 9,
+    T2 = 10,
+    T3 = 11,
+    T4 = 12,
+    T5 = 13,
+    T6 = 14,
+    T7 = 15,
+    S0 = 16,
+    S1 = 17,
+    S2 = 18,
+    S3 = 19,
+    S4 = 20,
+    S5 = 21,
+    S6 = 22,
+    S7 = 23,
+    T8 = 24,
+    T9 = 25,
+    K0 = 26,
+    K1 = 27,
+    Gp = 28,
+    Sp = 29,
+    Fp = 30,
+    Ra = 31,
+    Sr = 32,
+    Lo = 33,
+    Hi = 34,
+    Badvaddr = 35,
+    Cause = 36,
+    Pc = 37,
+}
+
+/// alias registers
+#[allow(non_upper_case_globals)]
+impl Regs {
+    pub const Zero: Regs = Regs::R0;
+    pub const S8: Regs = Regs::Fp;
+}
+
+/// The registers holding the first integer arguments of a function, following the o32 ABI
+pub const ARGUMENT_REGS: &[Regs] = &[Regs::A0, Regs::A1, Regs::A2, Regs::A3];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::V0;
+/// The register holding the return address of a function
+pub const RETURN_ADDRESS_REG: Option<Regs> = Some(Regs::Ra);
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function.
+/// The o32 ABI reserves stack space for the four arguments passed in registers, before it.
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 16;
+
+#[cfg(feature = "python")]
+impl IntoPy<PyObject> for Regs {
+    fn into_py(self, py: Python) -> PyObject {
+        let n: i32 = self.into();
+        n.into_py(py)
+    }
+}
+
+/// Return a MIPS ArchCapstoneBuilder
+pub fn capstone() -> capstone::arch::mips::ArchCapstoneBuilder {
+    #[cfg(feature = "be")]
+    let endian = capstone::Endian::Big;
+    #[cfg(not(feature = "be"))]
+    let endian = capstone::Endian::Little;
+    capstone::Capstone::new()
+        .mips()
+        .mode(capstone::arch::mips::ArchMode::Mips32)
+        .endian(endian)
+}
@@ -0,0 +1,96 @@
+use capstone::arch::{BuildsCapstone, BuildsCapstoneEndian};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+pub use strum_macros::EnumIter;
+pub use syscall_numbers::powerpc::*;
+
+use crate::GuestAddr;
+
+/// Registers for the 32-bit PowerPC instruction set.
+/// The floating point registers, 64 bits wide, are not exposed.
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
+#[repr(i32)]
+pub enum Regs {
+    R0 = 0,
+    R1 = 1,
+    R2 = 2,
+    R3 = 3,
+    R4 = 4,
+    R5 = 5,
+    R6 = 6,
+    R7 = 7,
+    R8 = 8,
+    R9 = 9,
+    R10 = 10,
+    R11 = 11,
+    R12 = 12,
+    R13 = 13,
+    R14 = 14,
+    R15 = 15,
+    R16 = 16,
+    R17 = 17,
+    R18 = 18,
+    R19 = 19,
+    R20 = 20,
+    R21 = 21,
+    R22 = 22,
+    R23 = 23,
+    R24 = 24,
+    R25 = 25,
+    R26 = 26,
+    R27 = 27,
+    R28 = 28,
+    R29 = 29,
+    R30 = 30,
+    R31 = 31,
+    Nip = 64,
+    Msr = 65,
+    Cr = 66,
+    Lr = 67,
+    Ctr = 68,
+    Xer = 69,
+    Fpscr = 70,
+}
+
+/// alias registers
+#[allow(non_upper_case_globals)]
+impl Regs {
+    pub const Sp: Regs = Regs::R1;
+    pub const Pc: Regs = Regs::Nip;
+}
+
+/// The registers holding the first integer arguments of a function, following the System V PowerPC ABI
+pub const ARGUMENT_REGS: &[Regs] = &[
+    Regs::R3,
+    Regs::R4,
+    Regs::R5,
+    Regs::R6,
+    Regs::R7,
+    Regs::R8,
+    Regs::R9,
+    Regs::R10,
+];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::R3;
+/// The register holding the return address of a function
+pub const RETURN_ADDRESS_REG: Option<Regs> = Some(Regs::Lr);
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function.
+/// It skips the back chain and the saved link register words of the caller frame.
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 8;
+
+#[cfg(feature = "python")]
+impl IntoPy<PyObject> for Regs {
+    fn into_py(self, py: Python) -> PyObject {
+        let n: i32 = self.into();
+        n.into_py(py)
+    }
+}
+
+/// Return a PowerPC ArchCapstoneBuilder
+pub fn capstone() -> capstone::arch::ppc::ArchCapstoneBuilder {
+    capstone::Capstone::new()
+        .ppc()
+        .mode(capstone::arch::ppc::ArchMode::Mode32)
+        .endian(capstone::Endian::Big)
+}
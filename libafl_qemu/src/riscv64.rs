@@ -0,0 +1,91 @@
+use capstone::arch::{BuildsCapstone, BuildsCapstoneExtraMode};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+pub use strum_macros::EnumIter;
+pub use syscall_numbers::riscv64::*;
+
+use crate::GuestAddr;
+
+/// Registers for the RISC-V 64 instruction set, named after their ABI names.
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
+#[repr(i32)]
+pub enum Regs {
+    Zero = 0,
+    Ra = 1,
+    Sp = 2,
+    Gp = 3,
+    Tp = 4,
+    T0 = 5,
+    T1 = 6,
+    T2 = 7,
+    S0 = 8,
+    S1 = 9,
+    A0 = 10,
+    A1 = 11,
+    A2 = 12,
+    A3 = 13,
+    A4 = 14,
+    A5 = 15,
+    A6 = 16,
+    A7 = 17,
+    S2 = 18,
+    S3 = 19,
+    S4 = 20,
+    S5 = 21,
+    S6 = 22,
+    S7 = 23,
+    S8 = 24,
+    S9 = 25,
+    S10 = 26,
+    S11 = 27,
+    T3 = 28,
+    T4 = 29,
+    T5 = 30,
+    T6 = 31,
+    Pc = 32,
+}
+
+/// alias registers
+#[allow(non_upper_case_globals)]
+impl Regs {
+    pub const Fp: Regs = Regs::S0;
+}
+
+/// The registers holding the first integer arguments of a function, following the standard RISC-V calling convention
+pub const ARGUMENT_REGS: &[Regs] = &[
+    Regs::A0,
+    Regs::A1,
+    Regs::A2,
+    Regs::A3,
+    Regs::A4,
+    Regs::A5,
+    Regs::A6,
+    Regs::A7,
+];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::A0;
+/// The register holding the return address of a function
+pub const RETURN_ADDRESS_REG: Option<Regs> = Some(Regs::Ra);
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 0;
+
+#[cfg(feature = "python")]
+impl IntoPy<PyObject> for Regs {
+    fn into_py(self, py: Python) -> PyObject {
+        let n: i32 = self.into();
+        n.into_py(py)
+    }
+}
+
+/// Return a RISC-V 64 ArchCapstoneBuilder, decoding compressed instructions too
+pub fn capstone() -> capstone::arch::riscv::ArchCapstoneBuilder {
+    capstone::Capstone::new()
+        .riscv()
+        .mode(capstone::arch::riscv::ArchMode::RiscV64)
+        .extra_mode(
+            [capstone::arch::riscv::ArchExtraMode::RiscVC]
+                .iter()
+                .copied(),
+        )
+}
@@ -5,6 +5,8 @@ use pyo3::prelude::*;
 pub use strum_macros::EnumIter;
 pub use syscall_numbers::x86_64::*;
 
+use crate::GuestAddr;
+
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, EnumIter)]
 #[repr(i32)]
 pub enum Regs {
@@ -35,6 +37,22 @@ impl Regs {
     pub const Pc: Regs = Regs::Rip;
 }
 
+/// The registers holding the first integer arguments of a function, following the System V AMD64 ABI
+pub const ARGUMENT_REGS: &[Regs] = &[
+    Regs::Rdi,
+    Regs::Rsi,
+    Regs::Rdx,
+    Regs::Rcx,
+    Regs::R8,
+    Regs::R9,
+];
+/// The register holding the integer return value of a function
+pub const RETURN_VALUE_REG: Regs = Regs::Rax;
+/// The register holding the return address of a function, `None` as it is pushed on the stack by `call`
+pub const RETURN_ADDRESS_REG: Option<Regs> = None;
+/// The offset from the stack pointer of the first argument passed on the stack, at the entry point of a function
+pub const STACK_ARGUMENTS_OFFSET: GuestAddr = 8;
+
 #[cfg(feature = "python")]
 impl IntoPy<PyObject> for Regs {
     fn into_py(self, py: Python) -> PyObject {
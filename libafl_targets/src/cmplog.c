@@ -47,6 +47,26 @@ void __libafl_targets_cmplog_instructions(uintptr_t k, uint8_t shape, uint64_t a
 
 }
 
+// Hooks inserted by the CmpLog instructions pass of libafl_cc before integer,
+// floating point and switch compares, keyed by their call site.
+// The shape is the size of the operands in bytes.
+
+#define CMPLOG_INS_HOOK(size, type)                                     \
+  void __cmplog_ins_hook##size(type arg1, type arg2) {                  \
+                                                                        \
+    uintptr_t k = RETADDR;                                              \
+    k = (k >> 4) ^ (k << 8);                                            \
+    k &= CMPLOG_MAP_W - 1;                                              \
+                                                                        \
+    __libafl_targets_cmplog(k, size, (uint64_t)arg1, (uint64_t)arg2);   \
+                                                                        \
+  }
+
+CMPLOG_INS_HOOK(1, uint8_t)
+CMPLOG_INS_HOOK(2, uint16_t)
+CMPLOG_INS_HOOK(4, uint32_t)
+CMPLOG_INS_HOOK(8, uint64_t)
+
 // POSIX shenanigan to see if an area is mapped.
 // If it is mapped as X-only, we have a problem, so maybe we should add a check
 // to avoid to call it on .text addresses
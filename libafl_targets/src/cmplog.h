@@ -50,6 +50,11 @@ extern uint8_t libafl_cmplog_enabled;
 void __libafl_targets_cmplog_instructions(uintptr_t k, uint8_t shape,
                                           uint64_t arg1, uint64_t arg2);
 
+void __cmplog_ins_hook1(uint8_t arg1, uint8_t arg2);
+void __cmplog_ins_hook2(uint16_t arg1, uint16_t arg2);
+void __cmplog_ins_hook4(uint32_t arg1, uint32_t arg2);
+void __cmplog_ins_hook8(uint64_t arg1, uint64_t arg2);
+
 void __libafl_targets_cmplog_routines(uintptr_t k, uint8_t *ptr1,
                                       uint8_t *ptr2);
 
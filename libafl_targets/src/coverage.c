@@ -1,5 +1,9 @@
 #include "common.h"
 
+#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+
 typedef uint32_t prev_loc_t;
 
 /* Maximum ngram size */
@@ -37,3 +41,28 @@ MAYBE_THREAD_LOCAL prev_loc_t __afl_prev_loc[NGRAM_SIZE_MAX];
 MAYBE_THREAD_LOCAL prev_loc_t __afl_prev_caller[CTX_MAX_K];
 MAYBE_THREAD_LOCAL uint32_t   __afl_prev_ctx;
 MAYBE_THREAD_LOCAL prev_loc_t __afl_acc_prev_loc;
+
+extern size_t __afl_map_size;
+
+// Called before main by the AFLCoverageLTO pass of libafl_cc, with the number
+// of edge IDs it assigned at link time. The edges map is not attached yet, so
+// the size is only recorded here, for the forkserver to report it to the
+// fuzzer, and checked by __libafl_check_edges_map_size once the map is in use
+void __libafl_set_edges_map_size(size_t size) {
+  __afl_map_size = size;
+}
+
+// Abort if the target still uses the map of libafl_targets, but has more
+// edges than it holds. A shared map is sized by the fuzzer from the map size
+// the forkserver reports.
+void __libafl_check_edges_map_size(void) {
+  if (__afl_area_ptr == __afl_area_ptr_local &&
+      __afl_map_size > EDGES_MAP_SIZE) {
+    fprintf(stderr,
+            "The target needs an edges map of %zu entries, but it only has "
+            "%u. Use the LIBAFL_EDGES_MAP_SIZE env to increase it at compile "
+            "time, or pass a shared map in __AFL_SHM_ID.\n",
+            __afl_map_size, (unsigned)EDGES_MAP_SIZE);
+    abort();
+  }
+}
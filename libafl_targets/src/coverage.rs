@@ -25,6 +25,9 @@ extern "C" {
     /// The area pointer points to the accounting mem operations map.
     pub static mut __afl_acc_memop_ptr: *mut u32;
 
+    /// Aborts if the edges map is too small for the edges of the target.
+    fn __libafl_check_edges_map_size();
+
     /// Start of libafl token section
     #[cfg(any(target_os = "linux", target_vendor = "apple"))]
     pub static __token_start: *const u8;
@@ -54,11 +57,19 @@ pub fn autotokens() -> Result<Tokens, Error> {
 }
 
 /// The size of the map for edges.
+/// Binaries instrumented by the link time coverage pass of `libafl_cc` set it to the number of edges before `main`.
 #[no_mangle]
 pub static mut __afl_map_size: usize = EDGES_MAP_SIZE;
 pub use __afl_map_size as EDGES_MAP_PTR_SIZE;
 use libafl::bolts::ownedref::OwnedSliceMut;
 
+/// Aborts if the target still writes to the [`EDGES_MAP`], but has more edges than it holds.
+/// Binaries instrumented by the link time coverage pass of `libafl_cc` only record their number of edges
+/// before `main`, so call this once the edges map is attached, before running the target.
+pub fn check_edges_map_size() {
+    unsafe { __libafl_check_edges_map_size() }
+}
+
 /// Gets the edges map from the `EDGES_MAP_PTR` raw pointer.
 ///
 /// # Safety
@@ -72,6 +83,7 @@ pub unsafe fn edges_map_from_ptr<'a>() -> OwnedSliceMut<'a, u8> {
 }
 
 /// Gets the current maximum number of edges tracked.
+/// For binaries instrumented by the link time coverage pass of `libafl_cc`, this is the number of edges.
 #[must_use]
 pub fn edges_max_num() -> usize {
     unsafe {
@@ -84,7 +96,7 @@ pub fn edges_max_num() -> usize {
             }
             #[cfg(not(feature = "pointer_maps"))]
             {
-                EDGES_MAP.len()
+                EDGES_MAP.len().min(EDGES_MAP_PTR_SIZE)
             }
         }
     }
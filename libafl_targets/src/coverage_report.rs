@@ -0,0 +1,574 @@
+//! Source-level coverage reports of a corpus, in `lcov` and `HTML` format.
+//!
+//! The basic blocks a target executed, e.g. read from [`DrCov`](crate::drcov) files, the sancov `PC` table,
+//! or collected by block hooks, are collected in a [`BlockCoverage`]. The [`CoverageReport`] then symbolizes them
+//! using the `DWARF` debug info of the modules, and shows which lines and functions the corpus never reached.
+
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use addr2line::{
+    object::{self, Object, ObjectSegment, ObjectSymbol, SymbolKind},
+    Context,
+};
+use libafl::{
+    corpus::Corpus,
+    executors::{Executor, HasObservers},
+    observers::ObserversTuple,
+    state::{HasCorpus, UsesState},
+    Error,
+};
+use rangemap::RangeMap;
+
+use crate::drcov::{DrCovBasicBlock, DrCovReader};
+#[cfg(any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts"))]
+use crate::sancov_pcguard::sancov_pc_table;
+
+/// The basic blocks executed by the runs of a target, per module
+#[derive(Debug, Default, Clone)]
+pub struct BlockCoverage {
+    /// For each module path: the offset of each executed block from the module base,
+    /// its size, and the number of runs that executed it
+    modules: BTreeMap<String, BTreeMap<usize, (usize, u64)>>,
+}
+
+impl BlockCoverage {
+    /// Create a new, empty [`BlockCoverage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a block executed by one run, at the given offset from the base of the module
+    pub fn add_block(&mut self, module: &str, offset: usize, size: usize) {
+        let (block_size, hits) = self
+            .modules
+            .entry(module.to_string())
+            .or_default()
+            .entry(offset)
+            .or_insert((size, 0));
+        *block_size = size.max(*block_size);
+        *hits += 1;
+    }
+
+    /// Add a block executed by one run, at its address in memory, e.g. from a block hook.
+    /// The `module_mapping` maps addresses to the id and path of the modules, like for the [`crate::drcov::DrCovWriter`].
+    /// Blocks outside of all modules are ignored.
+    pub fn add_address_block(
+        &mut self,
+        module_mapping: &RangeMap<usize, (u16, String)>,
+        block: &DrCovBasicBlock,
+    ) {
+        if let Some((range, (_, path))) = module_mapping.get_key_value(&block.start) {
+            self.add_block(path, block.start - range.start, block.end - block.start);
+        }
+    }
+
+    /// Add the blocks of a `DrCov` file, the trace of one run.
+    /// Blocks listed more than once, as in full traces, are counted as a single hit.
+    pub fn add_drcov(&mut self, drcov: &DrCovReader) {
+        let module_mapping = drcov.module_mapping();
+        let blocks: BTreeSet<(usize, usize)> = drcov
+            .basic_blocks()
+            .iter()
+            .map(|block| (block.start, block.end))
+            .collect();
+        for (start, end) in blocks {
+            self.add_address_block(&module_mapping, &DrCovBasicBlock::new(start, end));
+        }
+    }
+
+    /// Add the blocks hit in an edges map by one run, given the address of the block of each map entry
+    pub fn add_pc_table_map<I>(
+        &mut self,
+        module_mapping: &RangeMap<usize, (u16, String)>,
+        pc_table: I,
+        map: &[u8],
+    ) where
+        I: IntoIterator<Item = usize>,
+    {
+        for (pc, hits) in pc_table.into_iter().zip(map) {
+            if *hits != 0 {
+                self.add_address_block(module_mapping, &DrCovBasicBlock::new_with_size(pc, 1));
+            }
+        }
+    }
+
+    /// Add the blocks hit in the sancov `pc_guard` edges map by one run.
+    /// Needs targets compiled with `-fsanitize-coverage=trace-pc-guard,pc-table`.
+    #[cfg(any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts"))]
+    pub fn add_sancov_map(&mut self, module_mapping: &RangeMap<usize, (u16, String)>, map: &[u8]) {
+        self.add_pc_table_map(module_mapping, sancov_pc_table().map(|entry| entry.pc), map);
+    }
+
+    /// The paths of the modules with executed blocks
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.modules.keys().map(String::as_str)
+    }
+
+    /// If no blocks were executed at all
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+}
+
+/// The mapping of the modules loaded into the current process, from `/proc/self/maps`,
+/// to pass to [`BlockCoverage::add_address_block`] for in-process targets.
+#[cfg(target_os = "linux")]
+pub fn current_module_mapping() -> Result<RangeMap<usize, (u16, String)>, Error> {
+    let maps = fs::read_to_string("/proc/self/maps")?;
+    // The first and last address of each file mapped into memory
+    let mut modules: Vec<(String, usize, usize)> = vec![];
+    for line in maps.lines() {
+        // `start-end perms offset dev inode path`
+        let mut fields = line.split_whitespace();
+        let range = fields.next().unwrap_or_default();
+        let path = match fields.nth(4) {
+            Some(path) if path.starts_with('/') => path,
+            _ => continue,
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (
+                usize::from_str_radix(start, 16).map_err(|_| Error::unknown("Invalid maps"))?,
+                usize::from_str_radix(end, 16).map_err(|_| Error::unknown("Invalid maps"))?,
+            ),
+            None => continue,
+        };
+        match modules.iter_mut().find(|(module, _, _)| module == path) {
+            Some((_, module_start, module_end)) => {
+                *module_start = start.min(*module_start);
+                *module_end = end.max(*module_end);
+            }
+            None => modules.push((path.to_string(), start, end)),
+        }
+    }
+
+    let mut module_mapping = RangeMap::new();
+    for (id, (path, start, end)) in modules.into_iter().enumerate() {
+        module_mapping.insert(start..end, (id as u16, path));
+    }
+    Ok(module_mapping)
+}
+
+/// Run all testcases of the corpus, and call `collect` with the observers after each run,
+/// to add the blocks the run executed to the `coverage`.
+pub fn replay_corpus<E, EM, F, Z>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    manager: &mut EM,
+    state: &mut E::State,
+    coverage: &mut BlockCoverage,
+    mut collect: F,
+) -> Result<(), Error>
+where
+    E: Executor<EM, Z> + HasObservers,
+    E::State: HasCorpus,
+    EM: UsesState<State = E::State>,
+    F: FnMut(&E::Observers, &mut BlockCoverage) -> Result<(), Error>,
+    Z: UsesState<State = E::State>,
+{
+    for idx in 0..state.corpus().count() {
+        let input = state.corpus().get(idx)?.borrow_mut().load_input()?.clone();
+
+        executor.observers_mut().pre_exec_all(state, &input)?;
+        let kind = executor.run_target(fuzzer, state, manager, &input)?;
+        executor
+            .observers_mut()
+            .post_exec_all(state, &input, &kind)?;
+
+        collect(executor.observers(), coverage)?;
+    }
+    Ok(())
+}
+
+/// The coverage of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// The demangled name
+    pub name: String,
+    /// The line the function starts at
+    pub line: u32,
+    /// The number of runs that executed the function
+    pub hits: u64,
+}
+
+/// The coverage of a source file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// The number of runs that executed each line with code
+    pub lines: BTreeMap<u32, u64>,
+    /// The functions defined in the file
+    pub functions: Vec<FunctionCoverage>,
+}
+
+impl FileCoverage {
+    /// The number of lines executed at least once
+    #[must_use]
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    /// The number of functions executed at least once
+    #[must_use]
+    pub fn functions_hit(&self) -> usize {
+        self.functions
+            .iter()
+            .filter(|function| function.hits > 0)
+            .count()
+    }
+}
+
+/// A source-level coverage report, for each source file the coverage of its lines and functions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    files: BTreeMap<String, FileCoverage>,
+}
+
+impl CoverageReport {
+    /// Symbolize the executed blocks, using the `DWARF` debug info and the symbol table of the module files
+    pub fn new(coverage: &BlockCoverage) -> Result<Self, Error> {
+        let mut report = Self::default();
+        for (module, blocks) in &coverage.modules {
+            report.add_module(module, blocks)?;
+        }
+        Ok(report)
+    }
+
+    fn add_module(
+        &mut self,
+        module: &str,
+        blocks: &BTreeMap<usize, (usize, u64)>,
+    ) -> Result<(), Error> {
+        let data = fs::read(module)?;
+        let file = object::File::parse(&*data)
+            .map_err(|e| Error::illegal_argument(format!("Failed to parse {module}: {e}")))?;
+        let context = Context::new(&file).map_err(|e| {
+            Error::illegal_argument(format!("Failed to read the debug info of {module}: {e}"))
+        })?;
+        // The link address the module base was loaded from, `0` for position independent code
+        let base = file
+            .segments()
+            .map(|segment| segment.address())
+            .min()
+            .unwrap_or(0)
+            & !0xfff;
+        let symbolize_error =
+            |e| Error::illegal_state(format!("Failed to symbolize {module}: {e}"));
+
+        // All lines of all functions, hit or not
+        let mut seen = BTreeSet::new();
+        for symbol in file.symbols() {
+            if symbol.kind() != SymbolKind::Text || symbol.size() == 0 {
+                continue;
+            }
+            let (start, end) = (symbol.address(), symbol.address() + symbol.size());
+            // Aliases share the address
+            if !seen.insert(start) {
+                continue;
+            }
+            let name = match symbol.name() {
+                Ok(name) => addr2line::demangle_auto(Cow::from(name), None).into_owned(),
+                Err(_) => continue,
+            };
+            let location = match context.find_location(start).map_err(symbolize_error)? {
+                Some(location) => location,
+                None => continue,
+            };
+            let (source, line) = match (location.file, location.line) {
+                (Some(source), Some(line)) => (source.to_string(), line),
+                _ => continue,
+            };
+
+            let offsets = (start - base) as usize..(end - base) as usize;
+            let hits = blocks
+                .range(offsets)
+                .map(|(_, (_, hits))| *hits)
+                .max()
+                .unwrap_or(0);
+            self.files
+                .entry(source)
+                .or_default()
+                .functions
+                .push(FunctionCoverage { name, line, hits });
+
+            for (_, _, location) in context
+                .find_location_range(start, end)
+                .map_err(symbolize_error)?
+            {
+                if let (Some(source), Some(line)) = (location.file, location.line) {
+                    self.file_mut(source).lines.entry(line).or_insert(0);
+                }
+            }
+        }
+
+        // The lines of the executed blocks
+        for (offset, (size, hits)) in blocks {
+            let start = base + *offset as u64;
+            let mut lines = BTreeSet::new();
+            for (_, _, location) in context
+                .find_location_range(start, start + *size as u64)
+                .map_err(symbolize_error)?
+            {
+                if let (Some(source), Some(line)) = (location.file, location.line) {
+                    lines.insert((source, line));
+                }
+            }
+            for (source, line) in lines {
+                let line_hits = self.file_mut(source).lines.entry(line).or_insert(0);
+                *line_hits = (*line_hits).max(*hits);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn file_mut(&mut self, source: &str) -> &mut FileCoverage {
+        if !self.files.contains_key(source) {
+            self.files
+                .insert(source.to_string(), FileCoverage::default());
+        }
+        self.files.get_mut(source).unwrap()
+    }
+
+    /// The coverage of each source file
+    #[must_use]
+    pub fn files(&self) -> &BTreeMap<String, FileCoverage> {
+        &self.files
+    }
+
+    /// The functions no run executed, with their source file
+    pub fn unreached_functions(&self) -> impl Iterator<Item = (&str, &FunctionCoverage)> {
+        self.files.iter().flat_map(|(source, coverage)| {
+            coverage
+                .functions
+                .iter()
+                .filter(|function| function.hits == 0)
+                .map(move |function| (source.as_str(), function))
+        })
+    }
+
+    /// Write the report as `lcov` tracefile, to be used with `genhtml` or IDE plugins
+    pub fn write_lcov<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for (source, coverage) in &self.files {
+            writeln!(writer, "TN:")?;
+            writeln!(writer, "SF:{source}")?;
+            for function in &coverage.functions {
+                writeln!(writer, "FN:{},{}", function.line, function.name)?;
+            }
+            for function in &coverage.functions {
+                writeln!(writer, "FNDA:{},{}", function.hits, function.name)?;
+            }
+            writeln!(writer, "FNF:{}", coverage.functions.len())?;
+            writeln!(writer, "FNH:{}", coverage.functions_hit())?;
+            for (line, hits) in &coverage.lines {
+                writeln!(writer, "DA:{line},{hits}")?;
+            }
+            writeln!(writer, "LF:{}", coverage.lines.len())?;
+            writeln!(writer, "LH:{}", coverage.lines_hit())?;
+            writeln!(writer, "end_of_record")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write a static `HTML` summary of the report, with the coverage of each file and the functions never reached
+    pub fn write_html<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let (lines, lines_hit, functions, functions_hit) = self.files.values().fold(
+            (0, 0, 0, 0),
+            |(lines, lines_hit, functions, functions_hit), coverage| {
+                (
+                    lines + coverage.lines.len(),
+                    lines_hit + coverage.lines_hit(),
+                    functions + coverage.functions.len(),
+                    functions_hit + coverage.functions_hit(),
+                )
+            },
+        );
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Coverage report</title>\n");
+        html.push_str("<style>body { font-family: sans-serif; } table { border-collapse: collapse; } td, th { padding: 2px 8px; border: 1px solid #ccc; } td.num { text-align: right; }</style>\n");
+        html.push_str("</head>\n<body>\n<h1>Coverage report</h1>\n");
+        writeln!(
+            html,
+            "<p>Lines: {lines_hit} / {lines} ({}), functions: {functions_hit} / {functions} ({})</p>",
+            percentage(lines_hit, lines),
+            percentage(functions_hit, functions)
+        )
+        .unwrap();
+
+        html.push_str("<table>\n<tr><th>File</th><th>Lines</th><th>Line coverage</th><th>Functions</th><th>Function coverage</th></tr>\n");
+        for (source, coverage) in &self.files {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{} / {}</td><td class=\"num\">{}</td><td class=\"num\">{} / {}</td><td class=\"num\">{}</td></tr>",
+                escape_html(source),
+                coverage.lines_hit(),
+                coverage.lines.len(),
+                percentage(coverage.lines_hit(), coverage.lines.len()),
+                coverage.functions_hit(),
+                coverage.functions.len(),
+                percentage(coverage.functions_hit(), coverage.functions.len()),
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Functions never reached</h2>\n<table>\n<tr><th>Function</th><th>Location</th></tr>\n");
+        for (source, function) in self.unreached_functions() {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{}:{}</td></tr>",
+                escape_html(&function.name),
+                escape_html(source),
+                function.line
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        fs::write(path, html)?;
+        Ok(())
+    }
+}
+
+/// `part` of `total` in percent, formatted
+#[allow(clippy::cast_precision_loss)]
+fn percentage(part: usize, total: usize) -> String {
+    if total == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", part as f64 * 100.0 / total as f64)
+    }
+}
+
+/// Escape the characters with a meaning in `HTML`
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use alloc::string::ToString;
+    use std::{env, fs};
+
+    use rangemap::RangeMap;
+
+    use super::{current_module_mapping, BlockCoverage, CoverageReport};
+    use crate::drcov::{DrCovBasicBlock, DrCovReader, DrCovWriter};
+
+    #[inline(never)]
+    fn covered_function() -> usize {
+        core::hint::black_box(1)
+    }
+
+    #[inline(never)]
+    fn uncovered_function() -> usize {
+        core::hint::black_box(2)
+    }
+
+    #[test]
+    fn test_drcov_duplicate_blocks() {
+        let mut module_mapping = RangeMap::new();
+        module_mapping.insert(0x1000..0x2000, (0, "/bin/target".to_string()));
+        let path = env::temp_dir().join(format!("libafl_report_{}.drcov", std::process::id()));
+        DrCovWriter::new(&module_mapping)
+            .write(
+                &path,
+                &[
+                    DrCovBasicBlock::new_with_size(0x1010, 8),
+                    DrCovBasicBlock::new_with_size(0x1020, 4),
+                    DrCovBasicBlock::new_with_size(0x1010, 8),
+                ],
+            )
+            .unwrap();
+        let drcov = DrCovReader::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // A full trace of one run counts as one hit per block
+        let mut coverage = BlockCoverage::new();
+        coverage.add_drcov(&drcov);
+        coverage.add_drcov(&drcov);
+        let blocks = &coverage.modules["/bin/target"];
+        assert_eq!(blocks[&0x10], (8, 2));
+        assert_eq!(blocks[&0x20], (4, 2));
+    }
+
+    #[test]
+    fn test_pc_table_report() {
+        covered_function();
+        uncovered_function();
+
+        // A pc-table with one block per function, and a map where only the first one was hit
+        let pc_table = [
+            covered_function as fn() -> usize as usize,
+            uncovered_function as fn() -> usize as usize,
+        ];
+        let mut coverage = BlockCoverage::new();
+        coverage.add_pc_table_map(&current_module_mapping().unwrap(), pc_table, &[3, 0]);
+        assert_eq!(coverage.modules().count(), 1);
+
+        let report = CoverageReport::new(&coverage).unwrap();
+        let (source, file) = report
+            .files()
+            .iter()
+            .find(|(source, _)| source.ends_with("coverage_report.rs"))
+            .unwrap();
+        let function = |name: &str| {
+            file.functions
+                .iter()
+                .find(|function| function.name.ends_with(name))
+                .unwrap()
+        };
+        assert_eq!(function("tests::covered_function").hits, 1);
+        assert_eq!(function("tests::uncovered_function").hits, 0);
+        assert!(file.lines_hit() > 0);
+        assert!(file.lines_hit() < file.lines.len());
+        assert!(report
+            .unreached_functions()
+            .any(|(_, function)| function.name.ends_with("tests::uncovered_function")));
+
+        let dir = env::temp_dir().join(format!("libafl_coverage_report_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        report.write_lcov(dir.join("lcov.info")).unwrap();
+        report.write_html(dir.join("index.html")).unwrap();
+        let lcov = fs::read_to_string(dir.join("lcov.info")).unwrap();
+        let html = fs::read_to_string(dir.join("index.html")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let record = lcov
+            .split("end_of_record\n")
+            .find(|record| record.contains(&format!("SF:{source}\n")))
+            .unwrap();
+        let covered = &function("tests::covered_function").name;
+        let uncovered = &function("tests::uncovered_function").name;
+        assert!(record.contains(&format!("FNDA:1,{covered}\n")));
+        assert!(record.contains(&format!("FNDA:0,{uncovered}\n")));
+        assert!(record.contains(&format!("LH:{}\n", file.lines_hit())));
+        assert!(record.contains(&format!("LF:{}\n", file.lines.len())));
+        assert!(html.contains(&format!("<tr><td>{uncovered}</td>")));
+        assert!(!html.contains(&format!("<tr><td>{covered}</td>")));
+    }
+}
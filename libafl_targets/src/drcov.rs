@@ -1,11 +1,15 @@
 //! [`DrCov`](https://dynamorio.org/page_drcov.html) support for `LibAFL` frida mode,
 //! writing basic-block trace files to be read by coverage analysis tools, such as [Lighthouse](https://github.com/gaasedelen/lighthouse),
-//! [bncov](https://github.com/ForAllSecure/bncov), [dragondance](https://github.com/0ffffffffh/dragondance), etc.
+//! [bncov](https://github.com/ForAllSecure/bncov), [dragondance](https://github.com/0ffffffffh/dragondance), etc.,
+//! and reading them back, e.g. for source-level coverage reports.
 
-use alloc::{string::String, vec::Vec};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::ptr::addr_of;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{BufWriter, Write},
     path::Path,
 };
@@ -36,6 +40,26 @@ pub struct DrCovWriter<'a> {
     module_mapping: &'a RangeMap<usize, (u16, String)>,
 }
 
+/// A module of the module table of a `DrCov` file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrCovModule {
+    /// The id the basic blocks refer to this module with
+    pub id: u16,
+    /// The address the module was loaded at
+    pub base: usize,
+    /// The end of the module in memory
+    pub end: usize,
+    /// The path of the module file
+    pub path: String,
+}
+
+/// A reader for `DrCov` files, as written by the [`DrCovWriter`] or the `drcov` tool of `DynamoRIO`
+#[derive(Debug)]
+pub struct DrCovReader {
+    modules: Vec<DrCovModule>,
+    basic_blocks: Vec<DrCovBasicBlock>,
+}
+
 impl DrCovBasicBlock {
     /// Create a new [`DrCovBasicBlock`] with the given `start` and `end` addresses.
     #[must_use]
@@ -109,3 +133,193 @@ impl<'a> DrCovWriter<'a> {
         Ok(())
     }
 }
+
+impl DrCovReader {
+    /// Read a `DrCov` file
+    pub fn read<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Parse the contents of a `DrCov` file
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self, Error> {
+        // Skip the version and flavor
+        let mut line = next_line(&mut data)?;
+        while !line.starts_with("Module Table:") {
+            line = next_line(&mut data)?;
+        }
+        // `Module Table: version 2, count 3` or `Module Table: 3` for version 1
+        let count = line
+            .rsplit([' ', ':'])
+            .next()
+            .and_then(|count| count.parse::<usize>().ok())
+            .ok_or_else(|| {
+                Error::illegal_argument(format!("Invalid DrCov module table: {line}"))
+            })?;
+
+        let mut line = next_line(&mut data)?;
+        let columns: Vec<&str> = if let Some(columns) = line.strip_prefix("Columns:") {
+            let columns = columns.split(',').map(str::trim).collect();
+            line = next_line(&mut data)?;
+            columns
+        } else {
+            vec!["id", "base", "end", "entry", "path"]
+        };
+        let column = |names: &[&str]| {
+            columns
+                .iter()
+                .position(|column| names.contains(column))
+                .ok_or_else(|| {
+                    Error::illegal_argument(format!("DrCov module table lacks a {names:?} column"))
+                })
+        };
+        let (id_col, base_col, end_col, path_col) = (
+            column(&["id"])?,
+            column(&["base", "start"])?,
+            column(&["end"])?,
+            column(&["path"])?,
+        );
+
+        let mut modules = Vec::with_capacity(count);
+        for _ in 0..count {
+            // The path is the last column, and may contain commas
+            let fields: Vec<&str> = line.splitn(columns.len(), ',').map(str::trim).collect();
+            if fields.len() != columns.len() {
+                return Err(Error::illegal_argument(format!(
+                    "Invalid DrCov module: {line}"
+                )));
+            }
+            modules.push(DrCovModule {
+                id: parse_number(fields[id_col])? as u16,
+                base: parse_number(fields[base_col])?,
+                end: parse_number(fields[end_col])?,
+                path: fields[path_col].to_string(),
+            });
+            line = next_line(&mut data)?;
+        }
+
+        // `BB Table: 42 bbs`, followed by the binary entries
+        let count = line
+            .strip_prefix("BB Table:")
+            .and_then(|count| count.trim().split(' ').next())
+            .and_then(|count| count.parse::<usize>().ok())
+            .ok_or_else(|| Error::illegal_argument(format!("Invalid DrCov BB table: {line}")))?;
+        if data.len() < count * 8 {
+            return Err(Error::illegal_argument("DrCov BB table is truncated"));
+        }
+        let mut basic_blocks = Vec::with_capacity(count);
+        for entry in data[..count * 8].chunks_exact(8) {
+            let start = u32::from_le_bytes(entry[0..4].try_into().unwrap()) as usize;
+            let size = u16::from_le_bytes(entry[4..6].try_into().unwrap()) as usize;
+            let mod_id = u16::from_le_bytes(entry[6..8].try_into().unwrap());
+            let module = modules
+                .iter()
+                .find(|module| module.id == mod_id)
+                .ok_or_else(|| {
+                    Error::illegal_argument(format!("DrCov BB refers to unknown module {mod_id}"))
+                })?;
+            basic_blocks.push(DrCovBasicBlock::new_with_size(module.base + start, size));
+        }
+
+        Ok(Self {
+            modules,
+            basic_blocks,
+        })
+    }
+
+    /// The modules of the module table
+    #[must_use]
+    pub fn modules(&self) -> &[DrCovModule] {
+        &self.modules
+    }
+
+    /// The basic blocks, at their addresses in memory
+    #[must_use]
+    pub fn basic_blocks(&self) -> &[DrCovBasicBlock] {
+        &self.basic_blocks
+    }
+
+    /// The module mapping, as passed to the [`DrCovWriter`]
+    #[must_use]
+    pub fn module_mapping(&self) -> RangeMap<usize, (u16, String)> {
+        let mut module_mapping = RangeMap::new();
+        for module in &self.modules {
+            if module.base < module.end {
+                module_mapping.insert(module.base..module.end, (module.id, module.path.clone()));
+            }
+        }
+        module_mapping
+    }
+}
+
+/// Split off the next line of a `DrCov` file
+fn next_line<'a>(data: &mut &'a [u8]) -> Result<&'a str, Error> {
+    let end = data
+        .iter()
+        .position(|b| *b == b'\n')
+        .ok_or_else(|| Error::illegal_argument("Unexpected end of DrCov file"))?;
+    let line = core::str::from_utf8(&data[..end])
+        .map_err(|_| Error::illegal_argument("Invalid UTF-8 in DrCov file"))?;
+    *data = &data[end + 1..];
+    Ok(line.trim_end_matches('\r'))
+}
+
+/// Parse a decimal or hexadecimal number of a `DrCov` module table
+fn parse_number(number: &str) -> Result<usize, Error> {
+    match number.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => number.parse(),
+    }
+    .map_err(|_| Error::illegal_argument(format!("Invalid number in DrCov file: {number}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use std::{env, fs};
+
+    use rangemap::RangeMap;
+
+    use super::{DrCovBasicBlock, DrCovModule, DrCovReader, DrCovWriter};
+
+    #[test]
+    fn test_drcov_roundtrip() {
+        let mut module_mapping = RangeMap::new();
+        module_mapping.insert(0x1000..0x3000, (0, "/bin/target".to_string()));
+        module_mapping.insert(0x7000..0x8000, (1, "/lib/lib, with comma.so".to_string()));
+        let blocks = [
+            DrCovBasicBlock::new_with_size(0x1010, 8),
+            DrCovBasicBlock::new_with_size(0x7004, 2),
+            DrCovBasicBlock::new_with_size(0x1010, 8),
+        ];
+
+        let path = env::temp_dir().join(format!("libafl_drcov_{}.drcov", std::process::id()));
+        DrCovWriter::new(&module_mapping)
+            .write(&path, &blocks)
+            .unwrap();
+        let drcov = DrCovReader::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            drcov.modules(),
+            &[
+                DrCovModule {
+                    id: 0,
+                    base: 0x1000,
+                    end: 0x3000,
+                    path: "/bin/target".to_string(),
+                },
+                DrCovModule {
+                    id: 1,
+                    base: 0x7000,
+                    end: 0x8000,
+                    path: "/lib/lib, with comma.so".to_string(),
+                },
+            ]
+        );
+        assert_eq!(drcov.basic_blocks(), &blocks);
+        assert_eq!(drcov.module_mapping(), module_mapping);
+    }
+}
@@ -18,6 +18,7 @@
 #define MAX_FILE (1024 * 1024)
 #define SHMEM_FUZZ_HDR_SIZE 4
 #define SHM_FUZZ_ENV_VAR "__AFL_SHM_FUZZ_ID"
+#define SHM_ENV_VAR "__AFL_SHM_ID"
 
 /* Reporting errors */
 #define FS_OPT_ERROR 0xf800008f
@@ -50,6 +51,7 @@
 int __afl_sharedmem_fuzzing __attribute__((weak));
 
 extern size_t __afl_map_size;
+extern uint8_t* __afl_area_ptr;
 extern uint8_t* __token_start;
 extern uint8_t* __token_stop;
 
@@ -59,6 +61,8 @@ uint32_t* __afl_fuzz_len = &__afl_fuzz_len_local;
 
 int already_initialized_forkserver;
 
+void __libafl_check_edges_map_size(void);
+
 static int child_pid;
 static void (*old_sigterm_handler)(int) = 0;
 
@@ -153,6 +157,54 @@ static void map_input_shared_memory() {
 
 }
 
+/* Attach the edges map shared by the fuzzer, if any. */
+
+static void map_edges_shared_memory() {
+
+  char *id_str = getenv(SHM_ENV_VAR);
+
+  if (id_str) {
+
+    uint8_t* map = NULL;
+
+#ifdef USEMMAP
+    const char *shm_file_path = id_str;
+    int         shm_fd = -1;
+
+    shm_fd = shm_open(shm_file_path, O_RDWR, DEFAULT_PERMISSION);
+    if (shm_fd == -1) {
+
+      fprintf(stderr, "shm_open() failed for the edges map\n");
+      send_forkserver_error(FS_ERROR_SHM_OPEN);
+      exit(1);
+
+    }
+
+    map = (uint8_t* )mmap(0, __afl_map_size, PROT_READ | PROT_WRITE, MAP_SHARED, shm_fd, 0);
+
+#else
+    uint32_t shm_id = atoi(id_str);
+    map = (uint8_t* )shmat(shm_id, NULL, 0);
+
+#endif
+
+    if (!map || map == (void *)-1) {
+
+      perror("Could not access the edges shared memory");
+      send_forkserver_error(FS_ERROR_SHMAT);
+      exit(1);
+
+    }
+
+    __afl_area_ptr = map;
+
+  }
+
+  /* Only now the map the target writes to is known */
+  __libafl_check_edges_map_size();
+
+}
+
 /* Fork server logic. */
 
 void __afl_start_forkserver(void) {
@@ -174,6 +226,8 @@ void __afl_start_forkserver(void) {
 
   void (*old_sigchld_handler)(int) = signal(SIGCHLD, SIG_DFL);
 
+  map_edges_shared_memory();
+
   if (__afl_map_size <= FS_OPT_MAX_MAPSIZE) {
 
     status_for_fsrv |= (FS_OPT_SET_MAPSIZE(__afl_map_size) | FS_OPT_MAPSIZE);
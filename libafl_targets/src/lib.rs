@@ -101,6 +101,9 @@ pub use cmplog::*;
 #[cfg(feature = "std")]
 pub mod drcov;
 
+#[cfg(feature = "coverage_report")]
+pub mod coverage_report;
+
 #[cfg(target_os = "linux")]
 pub mod forkserver;
 #[cfg(target_os = "linux")]
@@ -12,7 +12,7 @@ extern "C" {
     fn libafl_targets_libfuzzer_init(argc: *const i32, argv: *const *const *const u8) -> i32;
 }
 
-/// Calls the (native) libfuzzer initialize function.
+/// Calls the (native) libfuzzer initialize function, after checking that the edges map fits the target.
 /// Returns the value returned by the init function.
 /// # Safety
 /// Calls the libfuzzer-style init function which is native code.
@@ -24,6 +24,7 @@ pub fn libfuzzer_initialize(args: &[String]) -> i32 {
     assert!(argv.len() < i32::MAX as usize);
     #[allow(clippy::cast_possible_wrap)]
     let argc = argv.len() as i32;
+    crate::coverage::check_edges_map_size();
     unsafe {
         let argv_ptr = argv.as_ptr();
         libafl_targets_libfuzzer_init(core::ptr::addr_of!(argc), core::ptr::addr_of!(argv_ptr))
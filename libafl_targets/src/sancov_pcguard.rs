@@ -1,5 +1,7 @@
 //! [`LLVM` `PcGuard`](https://clang.llvm.org/docs/SanitizerCoverage.html#tracing-pcs-with-guards) runtime for `LibAFL`.
 
+use alloc::vec::Vec;
+
 use crate::coverage::{EDGES_MAP, MAX_EDGES_NUM};
 #[cfg(feature = "pointer_maps")]
 use crate::coverage::{EDGES_MAP_PTR, EDGES_MAP_PTR_SIZE};
@@ -76,3 +78,42 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard_init(mut start: *mut u32
         }
     }
 }
+
+/// An entry of the sancov `PC` table of a module, emitted with `-fsanitize-coverage=pc-table`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcTableEntry {
+    /// The address of the instrumented block
+    pub pc: usize,
+    /// `1` if the block is the entry of a function
+    pub flags: usize,
+}
+
+impl PcTableEntry {
+    /// If the block is the entry of a function
+    #[must_use]
+    pub fn is_function_entry(&self) -> bool {
+        self.flags & 1 == 1
+    }
+}
+
+/// The `PC` tables of the instrumented modules, in the order of their `pc_guard` initialization
+static mut PC_TABLES: Vec<&'static [PcTableEntry]> = Vec::new();
+
+/// Register the `PC` table of a module - usually called by `llvm`.
+///
+/// # Safety
+/// The table between `pcs_beg` and `pcs_end` has to live for the rest of the run.
+#[no_mangle]
+pub unsafe extern "C" fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *const usize) {
+    let len = (pcs_end as usize - pcs_beg as usize) / core::mem::size_of::<PcTableEntry>();
+    PC_TABLES.push(core::slice::from_raw_parts(
+        pcs_beg as *const PcTableEntry,
+        len,
+    ));
+}
+
+/// The entries of the sancov `PC` tables of all modules. The `n`th entry belongs to the `n`th entry of the edges map.
+pub fn sancov_pc_table() -> impl Iterator<Item = &'static PcTableEntry> {
+    unsafe { PC_TABLES.iter().flat_map(|table| table.iter()) }
+}
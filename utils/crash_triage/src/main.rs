@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use clap::{self, Parser};
+use libafl::feedbacks::triage::{
+    bucket_records_with_counts, load_bucket_counts, load_records, DEFAULT_BUCKET_DEPTH,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "crash_triage",
+    about = "Bucket the crash records written by a CrashTriageFeedback by their top frames"
+)]
+struct Opt {
+    #[arg(
+        short,
+        long,
+        name = "RECORDS",
+        help = "The directory containing the crash records"
+    )]
+    records: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        name = "DEPTH",
+        help = "The number of top frames used to bucket crashes",
+        default_value_t = DEFAULT_BUCKET_DEPTH
+    )]
+    depth: usize,
+}
+
+fn main() {
+    let opt = Opt::parse();
+
+    let records = load_records(&opt.records).expect("Failed to load the crash records");
+    let counts = load_bucket_counts(&opt.records).expect("Failed to load the bucket counts");
+    let buckets = bucket_records_with_counts(&records, opt.depth, counts.as_ref());
+
+    println!(
+        "{} crashes in {} buckets (depth {})",
+        buckets.iter().map(|bucket| bucket.count).sum::<usize>(),
+        buckets.len(),
+        opt.depth
+    );
+    for bucket in buckets {
+        let report = &bucket.representative.report;
+        let top_frame = report.frames.first().map_or_else(
+            || "<no frames>".to_string(),
+            |frame| match &frame.function {
+                Some(function) => function.clone(),
+                None => format!("{:#x}", frame.address),
+            },
+        );
+        println!(
+            "{:016x}  count: {:<6} class: {:<24} top frame: {:<32} input: {}",
+            bucket.hash,
+            bucket.count,
+            report.bug_class.as_deref().unwrap_or("unknown"),
+            top_frame,
+            bucket.representative.input_name
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{self, Parser};
+use libafl::{
+    bolts::{
+        current_nanos,
+        rands::StdRand,
+        shmem::{ShMem, ShMemProvider, StdShMemProvider},
+        tuples::tuple_list,
+        AsMutSlice,
+    },
+    corpus::{Corpus, CorpusMinimizer, InMemoryCorpus, StdGreedyCorpusMinimizer, Testcase},
+    events::NopEventManager,
+    executors::{command::CommandExecutor, ForkserverExecutor, TimeoutForkserverExecutor},
+    feedbacks::ConstFeedback,
+    inputs::BytesInput,
+    observers::{HitcountsMapObserver, StdMapObserver},
+    schedulers::QueueScheduler,
+    state::{HasCorpus, StdState},
+    Error, StdFuzzer,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "libafl_cmin",
+    about = "Minimize a corpus for an AFL-instrumented target, keeping its coverage. Inputs that crash or time out are dropped",
+    after_help = "Pass the target after `--`, with `@@` in place of the input file, e.g. `libafl_cmin -i in -o out -- ./target @@`"
+)]
+struct Opt {
+    #[arg(
+        short,
+        long,
+        name = "INPUT",
+        help = "The directory containing the inputs to minimize"
+    )]
+    input: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        name = "OUTPUT",
+        help = "The directory the kept inputs are copied to"
+    )]
+    output: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        name = "TIMEOUT",
+        help = "The timeout of each run in milliseconds, in forkserver mode",
+        default_value_t = 1000
+    )]
+    timeout: u64,
+
+    #[arg(
+        short,
+        long,
+        name = "MAP_SIZE",
+        help = "The size of the coverage map, the largest map the target may use",
+        default_value_t = 65536
+    )]
+    map_size: usize,
+
+    #[arg(
+        long,
+        help = "Run the target once per input instead of as a forkserver, with a timeout of 5 seconds"
+    )]
+    command: bool,
+
+    #[arg(long, help = "Show the output of the target")]
+    debug_child: bool,
+
+    #[arg(last = true, required = true, name = "TARGET")]
+    target: Vec<OsString>,
+}
+
+/// Load the inputs of the directory, sorted by their name
+fn load_inputs(dir: &Path) -> Result<InMemoryCorpus<BytesInput>, Error> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .map_or(true, |name| name.to_string_lossy().starts_with('.'));
+        if path.is_file() && !hidden {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut corpus = InMemoryCorpus::new();
+    for path in paths {
+        let bytes = fs::read(&path)?;
+        // Like afl-cmin, ignore empty files
+        if bytes.is_empty() {
+            continue;
+        }
+        corpus.add(Testcase::with_filename(
+            BytesInput::new(bytes),
+            path.to_string_lossy().into_owned(),
+        ))?;
+    }
+    Ok(corpus)
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::parse();
+
+    let corpus = load_inputs(&opt.input)?;
+    let count = corpus.count();
+    println!("Loaded {count} inputs from {}", opt.input.display());
+
+    let mut shmem_provider = StdShMemProvider::new()?;
+    let mut shmem = shmem_provider.new_shmem(opt.map_size)?;
+    shmem.write_to_env("__AFL_SHM_ID")?;
+    let edges_observer =
+        HitcountsMapObserver::new(StdMapObserver::new("edges", shmem.as_mut_slice()));
+
+    let mut feedback = ConstFeedback::new(false);
+    let mut objective = ConstFeedback::new(false);
+    let mut state = StdState::new(
+        StdRand::with_seed(current_nanos()),
+        corpus,
+        InMemoryCorpus::new(),
+        &mut feedback,
+        &mut objective,
+    )?;
+    let mut fuzzer = StdFuzzer::<_, _, _, ()>::new(QueueScheduler::new(), feedback, objective);
+    let mut mgr = NopEventManager::new();
+
+    if opt.command {
+        let minimizer = StdGreedyCorpusMinimizer::new(&edges_observer);
+        let mut executor = CommandExecutor::parse_afl_cmdline(
+            &opt.target,
+            tuple_list!(edges_observer),
+            opt.debug_child,
+        )?;
+        minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+    } else {
+        let minimizer = StdGreedyCorpusMinimizer::new(&edges_observer);
+        let executor = ForkserverExecutor::builder()
+            .program(&opt.target[0])
+            .debug_child(opt.debug_child)
+            .parse_afl_cmdline(&opt.target[1..])
+            .build_dynamic_map(edges_observer, tuple_list!())?;
+        let mut executor =
+            TimeoutForkserverExecutor::new(executor, Duration::from_millis(opt.timeout))?;
+        minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+    }
+
+    fs::create_dir_all(&opt.output)?;
+    for idx in 0..state.corpus().count() {
+        let testcase = state.corpus().get(idx)?.borrow();
+        let path = PathBuf::from(testcase.filename().as_ref().unwrap());
+        fs::copy(&path, opt.output.join(path.file_name().unwrap()))?;
+    }
+    println!(
+        "Kept {} of {count} inputs in {}",
+        state.corpus().count(),
+        opt.output.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+    use libafl::{bolts::AsSlice, corpus::Corpus, inputs::HasTargetBytes};
+
+    use super::{load_inputs, Opt};
+
+    #[test]
+    fn test_parse_opt() {
+        let opt = Opt::try_parse_from([
+            "libafl_cmin",
+            "-i",
+            "in",
+            "-o",
+            "out",
+            "--command",
+            "--",
+            "./target",
+            "-x",
+            "@@",
+        ])
+        .unwrap();
+        assert!(opt.command);
+        assert_eq!(opt.timeout, 1000);
+        assert_eq!(opt.target, ["./target", "-x", "@@"]);
+
+        // The target is required
+        assert!(Opt::try_parse_from(["libafl_cmin", "-i", "in", "-o", "out"]).is_err());
+    }
+
+    #[test]
+    fn test_load_inputs() {
+        let dir = std::env::temp_dir().join(format!("libafl_cmin_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("subdir")).unwrap();
+        fs::write(dir.join("b"), b"bb").unwrap();
+        fs::write(dir.join("a"), b"a").unwrap();
+        fs::write(dir.join("empty"), b"").unwrap();
+        fs::write(dir.join(".hidden"), b"hidden").unwrap();
+
+        let corpus = load_inputs(&dir).unwrap();
+        let loaded: Vec<(String, Vec<u8>)> = (0..corpus.count())
+            .map(|idx| {
+                let testcase = corpus.get(idx).unwrap().borrow();
+                (
+                    testcase.filename().clone().unwrap(),
+                    testcase
+                        .input()
+                        .as_ref()
+                        .unwrap()
+                        .target_bytes()
+                        .as_slice()
+                        .to_vec(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            loaded,
+            [
+                (dir.join("a").to_string_lossy().into_owned(), b"a".to_vec()),
+                (dir.join("b").to_string_lossy().into_owned(), b"bb".to_vec()),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}